@@ -0,0 +1,383 @@
+// Bakes the XML scenes listed in BUILTINS into Rust code, so the renderer
+// can construct them with no file access or parsing at run time. The code is
+// written to $OUT_DIR/builtin_scenes.rs and included by src/builtin.rs.
+// Scenes using anything the baker doesn't handle are parsed by xml_parser in
+// lib.rs at run time instead, with a build warning.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use roxmltree::Document;
+
+#[path = "src/spectrum.rs"]
+mod spectrum;
+
+use spectrum::Spectrum;
+
+// (builtin name, scene file relative to the crate root)
+const BUILTINS: &[(&str, &str)] = &[
+    ("three-spheres", "scene_01.xml"),
+    ("cover", "scenes/cover.xml"),
+    ("cornell", "scenes/cornell.xml"),
+    ("cornell-smoke", "scenes/cornell-smoke.xml"),
+    ("final-scene-2", "scenes/final-scene-2.xml"),
+    ("color-checker", "scenes/color-checker.xml")
+];
+
+// What can't be baked makes the builtin parse its file with xml_parser
+// instead, see main
+type Baked<T> = Result<T, String>;
+
+fn parts(attr: &str) -> Baked<Vec<f64>> {
+    attr.split_whitespace()
+        .map(|p| p.parse().map_err(|_| format!("can't parse the number '{}'", p)))
+        .collect()
+}
+
+fn vec3(attr: &str) -> Baked<String> {
+    match parts(attr)?[..] {
+        [x, y, z] => Ok(format!("Vec3::new({:?}, {:?}, {:?})", x, y, z)),
+        _ => Err(format!("'{}' isn't a vector", attr))
+    }
+}
+
+fn number(attr: &str) -> Baked<String> {
+    attr.trim().parse::<f64>()
+        .map(|value| format!("{:?}", value))
+        .map_err(|_| format!("can't parse the number '{}'", attr))
+}
+
+fn boolean(node: &roxmltree::Node, name: &str) -> Baked<bool> {
+    match node.attribute(name) {
+        Some("true") | Some("1") | None => Ok(true),
+        Some("false") | Some("0") => Ok(false),
+        Some(other) => Err(format!("'{}' isn't a boolean", other))
+    }
+}
+
+fn required<'a>(node: &roxmltree::Node<'a, '_>, name: &str) -> Baked<&'a str> {
+    node.attribute(name)
+        .ok_or_else(|| format!("<{}> is missing '{}'", node.tag_name().name(), name))
+}
+
+// Elements with attributes or children the baker doesn't handle are left to
+// xml_parser, which does
+fn supported(node: &roxmltree::Node, attributes: &[&str]) -> Baked<()> {
+    let name = node.tag_name().name();
+    if let Some(attr) = node.attributes().iter().find(|a| !attributes.contains(&a.name())) {
+        return Err(format!("can't bake the '{}' attribute of <{}>", attr.name(), name));
+    }
+    if let Some(child) = node.children().find(|n| n.is_element() && !(name == "environment" && n.has_tag_name("layer"))) {
+        return Err(format!("can't bake <{}> elements in <{}>", child.tag_name().name(), name));
+    }
+    Ok(())
+}
+
+// The color attribute scaled by the spectrum's color, as in spectrum_attr
+fn emission_color(node: &roxmltree::Node) -> Baked<String> {
+    let mut color = [1.0; 3];
+    if let Some(attr) = node.attribute("color") {
+        for (c, part) in color.iter_mut().zip(parts(attr)?) {
+            *c = part;
+        }
+    }
+
+    let temperature = match node.attribute("temperature") {
+        Some(t) => Some(t.trim().parse::<f64>().map_err(|_| format!("can't parse the number '{}'", t))?),
+        None => None
+    };
+    let spectrum = match (node.attribute("spectrum"), temperature) {
+        (Some(name), _) => Some(Spectrum::from_name(name, temperature)
+            .ok_or_else(|| format!("can't bake the spectrum '{}'", name))?),
+        (None, Some(kelvin)) => Some(Spectrum::Blackbody(kelvin)),
+        (None, None) => None
+    };
+    if let Some(spectrum) = spectrum {
+        for (c, s) in color.iter_mut().zip(spectrum.rgb()) {
+            *c *= s;
+        }
+    }
+
+    Ok(format!("Vec3::new({:?}, {:?}, {:?})", color[0], color[1], color[2]))
+}
+
+// Follows xml_parser in lib.rs, emitting constructor calls instead of values
+fn bake(name: &str, path: &str) -> Baked<String> {
+    let xml = fs::read_to_string(path).unwrap_or_else(|_| panic!("Unable to read {}", path));
+    let doc = Document::parse(&xml).map_err(|e| format!("can't parse the XML: {}", e))?;
+
+    let mut body = String::new();
+    let mut img_name = String::new();
+    let mut camera = None;
+    let mut camera_aspect = "None".to_string();
+    let mut film = ("None".to_string(), "None".to_string());
+    let mut flip = None;
+    let mut pixel_aspect = "1.0".to_string();
+    let mut environment = None;
+
+    writeln!(body, "    let mut world = World::new();").unwrap();
+    writeln!(body, "    let mut lights: Vec<Box<dyn Light>> = Vec::new();").unwrap();
+    writeln!(body, "    let mut light_objects: Vec<Option<usize>> = Vec::new();").unwrap();
+    writeln!(body, "    let mut last_mat: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.0, 0.0, 0.0)));").unwrap();
+
+    for node in doc.descendants().filter(|n| n.is_element()) {
+        match node.tag_name().name() {
+            "film" => {
+                supported(&node, &["filename", "width", "height", "flip", "pixel_aspect"])?;
+                img_name = node.attribute("filename").unwrap_or("default.ppm").to_string();
+
+                let size = |name: &str| match node.attribute(name) {
+                    Some(attr) => match attr.parse::<u64>() {
+                        Ok(value) if value > 0 => Ok(format!("Some({})", value)),
+                        _ => Err(format!("the film {} '{}' isn't a positive integer", name, attr))
+                    },
+                    None => Ok("None".to_string())
+                };
+                film = (size("width")?, size("height")?);
+
+                if let Some(value) = node.attribute("flip") {
+                    if !["none", "horizontal", "vertical", "both"].contains(&value) {
+                        return Err(format!("the film flip '{}' doesn't exist", value));
+                    }
+                    flip = Some(value);
+                }
+
+                if let Some(attr) = node.attribute("pixel_aspect") {
+                    let value = match attr.split_once('/') {
+                        Some((a, b)) => a.trim().parse::<f64>().ok().zip(b.trim().parse::<f64>().ok()).map(|(a, b)| a / b),
+                        None => attr.trim().parse().ok()
+                    };
+                    match value {
+                        Some(value) if value > 0.0 && value.is_finite() => pixel_aspect = format!("{:?}", value),
+                        _ => return Err(format!("the film pixel aspect '{}' isn't positive", attr))
+                    }
+                }
+            },
+            "camera" => {
+                supported(&node, &["look_from", "look_at", "up", "aperture", "shutter_open", "shutter_close", "aspect"])?;
+                let shutter_open = number(node.attribute("shutter_open").unwrap_or("0"))?;
+                let shutter_close = number(node.attribute("shutter_close").unwrap_or("1"))?;
+                if shutter_close.parse::<f64>().unwrap() < shutter_open.parse::<f64>().unwrap() {
+                    return Err("the camera shutter closes before it opens".to_string());
+                }
+                if let Some(attr) = node.attribute("aspect") {
+                    let aspect = number(attr)?;
+                    if aspect.parse::<f64>().unwrap() <= 0.0 {
+                        return Err(format!("the camera aspect '{}' isn't positive", attr));
+                    }
+                    camera_aspect = format!("Some({})", aspect);
+                }
+
+                camera = Some(format!(
+                    "Camera::new({}, {}, {}, DEFAULT_VFOV, aspect_ratio, {}, DEFAULT_FOCUS_DIST).with_shutter({}, {})",
+                    vec3(required(&node, "look_from")?)?,
+                    vec3(required(&node, "look_at")?)?,
+                    vec3(required(&node, "up")?)?,
+                    number(required(&node, "aperture")?)?,
+                    shutter_open,
+                    shutter_close
+                ));
+            },
+            "material" => {
+                supported(&node, &["type", "color", "fuzz", "refrect_idx"])?;
+                let color = match node.attribute("color") {
+                    Some(attr) => vec3(attr)?,
+                    None => "Vec3::new(0.0, 0.0, 0.0)".to_string()
+                };
+
+                let mat = match required(&node, "type")? {
+                    "lambertian" => format!("Lambertian::new({})", color),
+                    "metal" => format!("Metal::new({}, {})", color, number(required(&node, "fuzz")?)?),
+                    "dielectric" => format!("Dielectric::new({})", number(required(&node, "refrect_idx")?)?),
+                    "isotropic" => format!("Isotropic::new({})", color),
+                    other => return Err(format!("can't bake '{}' materials", other))
+                };
+                writeln!(body, "    last_mat = Arc::new({});", mat).unwrap();
+            },
+            "object" => {
+                let placement = ["rotate", "rotate_axis", "translate", "scale", "density"];
+                let object = match node.attribute("type") {
+                    None | Some("sphere") if node.has_attribute("center0") => {
+                        supported(&node, &[&["type", "center0", "center1", "time0", "time1", "radius"][..], &placement].concat())?;
+                        let time0 = number(node.attribute("time0").unwrap_or("0"))?;
+                        let time1 = number(node.attribute("time1").unwrap_or("1"))?;
+                        if time1.parse::<f64>().unwrap() < time0.parse::<f64>().unwrap() {
+                            return Err("the sphere time1 is before time0".to_string());
+                        }
+                        format!("MovingSphere::new({}, {}, {}, {}, {}, last_mat.clone())",
+                                vec3(required(&node, "center0")?)?,
+                                vec3(required(&node, "center1")?)?,
+                                time0, time1,
+                                number(node.attribute("radius").unwrap_or("1"))?)
+                    },
+                    None | Some("sphere") => {
+                        supported(&node, &[&["type", "center", "radius"][..], &placement].concat())?;
+                        format!("Sphere::new({}, {}, last_mat.clone())",
+                                vec3(required(&node, "center")?)?,
+                                number(required(&node, "radius")?)?)
+                    },
+                    Some("plane") => {
+                        supported(&node, &[&["type", "point", "normal"][..], &placement].concat())?;
+                        format!("Plane::new({}, {}, last_mat.clone())",
+                                vec3(required(&node, "point")?)?,
+                                vec3(required(&node, "normal")?)?)
+                    },
+                    Some("box") => {
+                        supported(&node, &[&["type", "min", "max"][..], &placement].concat())?;
+                        format!("Cuboid::new({}, {}, last_mat.clone())",
+                                vec3(required(&node, "min")?)?,
+                                vec3(required(&node, "max")?)?)
+                    },
+                    Some("quad") => {
+                        supported(&node, &[&["type", "corner", "u", "v"][..], &placement].concat())?;
+                        format!("Quad::new({}, {}, {}, last_mat.clone())",
+                                vec3(required(&node, "corner")?)?,
+                                vec3(required(&node, "u")?)?,
+                                vec3(required(&node, "v")?)?)
+                    },
+                    Some(other) => return Err(format!("can't bake objects of type '{}'", other))
+                };
+                // The wrappers placement_parser in lib.rs picks
+                let axis = vec3(node.attribute("rotate_axis").unwrap_or("0 1 0"))?;
+                let object = match node.attribute("scale") {
+                    Some(scale) => {
+                        let scale = if scale.split_whitespace().count() == 1 { [scale; 3].join(" ") } else { scale.to_string() };
+                        // Paths in full, since no builtin scales anything yet and its
+                        // imports would go unused
+                        format!("super::transform::Transform::new({}, super::matrix::Matrix::translation({}) \
+                                 * super::matrix::Matrix::rotation({}, {}) * super::matrix::Matrix::scale({}))",
+                                object, vec3(node.attribute("translate").unwrap_or("0 0 0"))?, axis,
+                                number(node.attribute("rotate").unwrap_or("0"))?, vec3(&scale)?)
+                    },
+                    None => {
+                        let object = match node.attribute("rotate") {
+                            Some(degrees) => format!("Rotate::new(Box::new({}), {}, {})", object, axis, number(degrees)?),
+                            None => object
+                        };
+                        match node.attribute("translate") {
+                            Some(offset) => format!("Translate::new(Box::new({}), {})", object, vec3(offset)?),
+                            None => object
+                        }
+                    }
+                };
+                let object = match node.attribute("density") {
+                    Some(density) => format!("ConstantMedium::new(Box::new({}), {}, last_mat.clone())", object, number(density)?),
+                    None => object
+                };
+                writeln!(body, "    world.push(Box::new({}));", object).unwrap();
+            },
+            "light" => {
+                supported(&node, &["type", "corner", "u", "v", "power", "units", "falloff", "color", "spectrum", "temperature"])?;
+                if node.attribute("type") != Some("area") {
+                    return Err("can't bake lights other than area lights".to_string());
+                }
+                let units = match node.attribute("units").unwrap_or("watt") {
+                    "watt" | "W" => "Watt",
+                    "lumen" | "lm" => "Lumen",
+                    "candela" | "cd" => "Candela",
+                    "nit" | "cd/m2" => "Nit",
+                    other => return Err(format!("the light units '{}' don't exist", other))
+                };
+                let falloff = match node.attribute("falloff").unwrap_or("inverse_square") {
+                    "inverse_square" => "InverseSquare",
+                    "none" => "None",
+                    other => return Err(format!("the light falloff '{}' doesn't exist", other))
+                };
+                let (corner, u, v) = (vec3(required(&node, "corner")?)?, vec3(required(&node, "u")?)?, vec3(required(&node, "v")?)?);
+
+                writeln!(body, "    let light = AreaLight::new({}, {}, {}, {}, {}, Units::{}, Falloff::{});",
+                         corner, u, v,
+                         emission_color(&node)?,
+                         number(required(&node, "power")?)?,
+                         units, falloff).unwrap();
+                writeln!(body, "    light_objects.push(Some(world.push(Box::new(Quad::new({}, {}, {}, Arc::new(Emissive::new(light.radiance(), light.emission())))))));", corner, u, v).unwrap();
+                writeln!(body, "    lights.push(Box::new(light));").unwrap();
+            },
+            "environment" => {
+                supported(&node, &[])?;
+                let mut layers = Vec::new();
+                for layer in node.children().filter(|n| n.is_element()) {
+                    supported(&layer, &["type", "color", "spectrum", "temperature", "camera", "diffuse", "specular"])?;
+                    if layer.attribute("type") != Some("constant") {
+                        return Err("can't bake environment layers other than constant ones".to_string());
+                    }
+                    layers.push((format!("Layer::Constant({})", emission_color(&layer)?),
+                                 format!("Visibility {{ camera: {}, diffuse: {}, specular: {} }}",
+                                         boolean(&layer, "camera")?, boolean(&layer, "diffuse")?, boolean(&layer, "specular")?)));
+                }
+                environment = Some(layers);
+            },
+            "layer" => { },
+            "RT" | "world" => { },
+            other => return Err(format!("can't bake <{}> elements", other))
+        }
+    }
+
+    let camera = camera.ok_or("missing <camera>")?;
+
+    let mut code = String::new();
+    writeln!(code, "// Baked from {}", path).unwrap();
+    writeln!(code, "#[allow(unused_assignments, unused_mut, clippy::approx_constant)]").unwrap();
+    writeln!(code, "fn {}() -> (String, Scene) {{", name.replace('-', "_")).unwrap();
+    code.push_str(&body);
+    writeln!(code, "    let (film_size, aspect_ratio) = super::film_shape(({}, {}), {}, {});", film.0, film.1, pixel_aspect, camera_aspect).unwrap();
+    writeln!(code, "    let cam = {};", camera).unwrap();
+    writeln!(code, "    let mut scene = Scene::new(world, lights, cam);").unwrap();
+    writeln!(code, "    scene.light_objects = light_objects;").unwrap();
+    if let Some(layers) = environment {
+        writeln!(code, "    scene.environment = Environment::new();").unwrap();
+        for (layer, visibility) in layers {
+            writeln!(code, "    scene.environment.push({}, {});", layer, visibility).unwrap();
+        }
+    }
+    writeln!(code, "    scene.film_size = film_size;").unwrap();
+    writeln!(code, "    scene.pixel_aspect = {};", pixel_aspect).unwrap();
+    if let Some(flip) = flip {
+        // In full, since few builtins flip their film
+        writeln!(code, "    scene.flip = super::render::Flip::from_name({:?}).unwrap();", flip).unwrap();
+    }
+    writeln!(code, "    ({:?}.to_string(), scene)", img_name).unwrap();
+    writeln!(code, "}}\n").unwrap();
+    Ok(code)
+}
+
+// Builtin parsing its file at run time, for the scenes bake can't handle
+fn parsed(name: &str, path: &str) -> String {
+    format!("// Parsed from {}\nfn {}() -> (String, Scene) {{\n    super::xml_parser(include_str!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/{}\")))\n}}\n\n",
+            path, name.replace('-', "_"), path)
+}
+
+fn main() {
+    let mut code = String::new();
+
+    for (name, path) in BUILTINS {
+        println!("cargo:rerun-if-changed={}", path);
+        let scene = bake(name, path).unwrap_or_else(|reason| {
+            println!("cargo:warning={}: {}, so the builtin '{}' is parsed at run time", path, reason, name);
+            parsed(name, path)
+        });
+        code.push_str(&scene);
+    }
+
+    writeln!(code, "pub const BUILTIN_NAMES: &[&str] = &[{}];",
+             BUILTINS.iter().map(|(n, _)| format!("{:?}", n)).collect::<Vec<_>>().join(", ")).unwrap();
+
+    // Their files, for the tests comparing them to xml_parser
+    writeln!(code, "#[cfg(test)]\npub const BUILTIN_FILES: &[(&str, &str)] = &[{}];",
+             BUILTINS.iter().map(|(n, p)| format!("({:?}, include_str!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/{}\")))", n, p)).collect::<Vec<_>>().join(", ")).unwrap();
+
+    writeln!(code, "\npub fn builtin(name: &str) -> Option<(String, Scene)> {{").unwrap();
+    writeln!(code, "    match name {{").unwrap();
+    for (name, _) in BUILTINS {
+        writeln!(code, "        {:?} => Some({}()),", name, name.replace('-', "_")).unwrap();
+    }
+    writeln!(code, "        _ => None").unwrap();
+    writeln!(code, "    }}\n}}").unwrap();
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("builtin_scenes.rs");
+    fs::write(out, code).expect("Unable to write baked scenes");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/spectrum.rs");
+}
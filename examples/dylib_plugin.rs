@@ -0,0 +1,181 @@
+// A plugin loaded by the renderer at runtime, built as a shared library with
+// `cargo build --release --example dylib_plugin`. It adds a coin object (a
+// flat disk), a striped texture and a tinted mirror material:
+//
+//     <plugin path="target/release/examples/libdylib_plugin.so" />
+//     <material type="lambertian">
+//         <texture type="stripes" color="0.9 0.9 0.9" odd="0.8 0.1 0.1" width="0.25" />
+//     </material>
+//     <object type="coin" center="0 0.01 0" normal="0 1 0" radius="2" />
+//     <material type="mirror" color="0.9 0.8 0.5" />
+//
+// Scenes loading it render with `rt --allow-plugins scene.xml`. Only the C ABI types of rt::plugin are used, so a plugin written in C
+// against include/rt_plugin.h works the same.
+
+use std::ffi::{c_char, c_void, CStr};
+
+use rt::plugin::{RtAttributes, RtHit, RtHost, RtMaterial, RtRay, RtShape, RtTexture, RtVec3, ABI_VERSION};
+
+fn attribute(attributes: *const RtAttributes, name: &str) -> Option<String> {
+    let attributes = unsafe { &*attributes };
+    (0..attributes.count).find_map(|i| unsafe {
+        let key = CStr::from_ptr(*attributes.names.add(i));
+        (key.to_str() == Ok(name)).then(|| CStr::from_ptr(*attributes.values.add(i)).to_string_lossy().into_owned())
+    })
+}
+
+fn number(attributes: *const RtAttributes, name: &str, default: f64) -> f64 {
+    attribute(attributes, name).and_then(|v| v.trim().parse().ok()).unwrap_or(default)
+}
+
+fn vector(attributes: *const RtAttributes, name: &str, default: [f64; 3]) -> [f64; 3] {
+    let Some(value) = attribute(attributes, name) else {
+        return default;
+    };
+    let v: Vec<f64> = value.split_whitespace().filter_map(|x| x.parse().ok()).collect();
+    if v.len() == 3 { [v[0], v[1], v[2]] } else { default }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn array(v: RtVec3) -> [f64; 3] {
+    [v.x, v.y, v.z]
+}
+
+fn vec3(v: [f64; 3]) -> RtVec3 {
+    RtVec3 { x: v[0], y: v[1], z: v[2] }
+}
+
+// Boxes the plugin side data of a shape, material or texture
+fn data<T>(value: T) -> *mut c_void {
+    Box::into_raw(Box::new(value)) as *mut c_void
+}
+
+extern "C" fn destroy<T>(data: *mut c_void) {
+    drop(unsafe { Box::from_raw(data as *mut T) });
+}
+
+struct Disk {
+    center: [f64; 3],
+    normal: [f64; 3],
+    radius: f64
+}
+
+extern "C" fn disk_hit(data: *mut c_void, ray: *const RtRay, t_min: f64, t_max: f64, out: *mut RtHit) -> bool {
+    let disk = unsafe { &*(data as *const Disk) };
+    let ray = unsafe { &*ray };
+    let (origin, direction) = (array(ray.origin), array(ray.direction));
+
+    let denom = dot(disk.normal, direction);
+    if denom.abs() < 1e-9 {
+        return false;
+    }
+    let to_center = [disk.center[0] - origin[0], disk.center[1] - origin[1], disk.center[2] - origin[2]];
+    let t = dot(disk.normal, to_center) / denom;
+    if t < t_min || t > t_max {
+        return false;
+    }
+
+    let offset: [f64; 3] = std::array::from_fn(|k| origin[k] + t * direction[k] - disk.center[k]);
+    if dot(offset, offset) > disk.radius * disk.radius {
+        return false;
+    }
+
+    unsafe {
+        (*out).t = t;
+        (*out).normal = vec3(disk.normal);
+    }
+    true
+}
+
+extern "C" fn disk_bounds(data: *mut c_void, min: *mut RtVec3, max: *mut RtVec3) -> bool {
+    let disk = unsafe { &*(data as *const Disk) };
+    let r = disk.radius;
+    unsafe {
+        *min = vec3([disk.center[0] - r, disk.center[1] - r, disk.center[2] - r]);
+        *max = vec3([disk.center[0] + r, disk.center[1] + r, disk.center[2] + r]);
+    }
+    true
+}
+
+extern "C" fn disk(attributes: *const RtAttributes, out: *mut RtShape) -> bool {
+    let n = vector(attributes, "normal", [0.0, 1.0, 0.0]);
+    let length = dot(n, n).sqrt();
+    if length == 0.0 {
+        return false;
+    }
+
+    let disk = Disk {
+        center: vector(attributes, "center", [0.0, 0.0, 0.0]),
+        normal: [n[0] / length, n[1] / length, n[2] / length],
+        radius: number(attributes, "radius", 1.0)
+    };
+    unsafe {
+        out.write(RtShape { data: data(disk), hit: disk_hit, bounds: Some(disk_bounds), destroy: Some(destroy::<Disk>) });
+    }
+    true
+}
+
+struct Stripes {
+    even: [f64; 3],
+    odd: [f64; 3],
+    width: f64
+}
+
+extern "C" fn stripes_value(data: *mut c_void, hit: *const RtHit, out: *mut RtVec3) {
+    let stripes = unsafe { &*(data as *const Stripes) };
+    let x = unsafe { (*hit).point.x };
+    let color = if ((x / stripes.width).floor() as i64).rem_euclid(2) == 0 { stripes.even } else { stripes.odd };
+    unsafe { *out = vec3(color) };
+}
+
+extern "C" fn stripes(attributes: *const RtAttributes, out: *mut RtTexture) -> bool {
+    let stripes = Stripes {
+        even: vector(attributes, "color", [0.9, 0.9, 0.9]),
+        odd: vector(attributes, "odd", [0.1, 0.1, 0.1]),
+        width: number(attributes, "width", 0.5)
+    };
+    unsafe {
+        out.write(RtTexture { data: data(stripes), value: stripes_value, destroy: Some(destroy::<Stripes>) });
+    }
+    true
+}
+
+extern "C" fn mirror_scatter(data: *mut c_void, ray: *const RtRay, hit: *const RtHit, attenuation: *mut RtVec3, scattered: *mut RtRay) -> bool {
+    let color = unsafe { *(data as *const [f64; 3]) };
+    let (ray, hit) = unsafe { (&*ray, &*hit) };
+    let (d, n) = (array(ray.direction), array(hit.normal));
+    let k = 2.0 * dot(d, n);
+
+    unsafe {
+        *attenuation = vec3(color);
+        *scattered = RtRay { origin: hit.point, direction: vec3([d[0] - k * n[0], d[1] - k * n[1], d[2] - k * n[2]]) };
+    }
+    true
+}
+
+extern "C" fn mirror(attributes: *const RtAttributes, out: *mut RtMaterial) -> bool {
+    let color = vector(attributes, "color", [0.9, 0.9, 0.9]);
+    unsafe {
+        out.write(RtMaterial { data: data(color), scatter: mirror_scatter, eval: None, emitted: None, destroy: Some(destroy::<[f64; 3]>) });
+    }
+    true
+}
+
+/// # Safety
+///
+/// Called by the renderer with a valid host.
+#[no_mangle]
+pub unsafe extern "C" fn rt_plugin_init(host: *const RtHost) -> bool {
+    let host = &*host;
+    if host.version != ABI_VERSION {
+        return false;
+    }
+
+    (host.register_shape)(c"coin".as_ptr() as *const c_char, disk);
+    (host.register_texture)(c"stripes".as_ptr() as *const c_char, stripes);
+    (host.register_material)(c"mirror".as_ptr() as *const c_char, mirror);
+    true
+}
@@ -0,0 +1,93 @@
+// Progressive rendering of a scene that is edited while it renders: the
+// render thread keeps adding passes to the image, and the main thread moves
+// the first object of the world up a little between passes. The image is
+// restarted after each edit, the way an interactive frontend would.
+//
+// Run with `cargo run --release --example editing -- scene.xml out.ppm`.
+
+use std::fs;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use rt::render::{self, Flip, Integrator, RenderSettings};
+use rt::tiles::TileOrder;
+use rt::vec::{Color, Vec3};
+
+const PASSES: u64 = 24;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let scene_file = args.next().expect("Missing scene file!");
+    let output = args.next().unwrap_or_else(|| "editing.ppm".to_string());
+
+    let scene = Arc::new(RwLock::new(rt::load_scene(&fs::read_to_string(scene_file).expect("Unable to read file."))));
+    let mut settings = RenderSettings {
+        image_width: 320,
+        image_height: 180,
+        overscan: 0,
+        bucket: None,
+        flip: Flip::default(),
+        pixel_aspect: 1.0,
+        samples_per_pixel: 1,
+        first_sample: 0,
+        max_depth: 8,
+        clamp: 0.0,
+        denoise: false,
+        integrator: Integrator::Path,
+        rr_start: None,
+        distance: 1.0,
+        packet_size: 0,
+        tile_order: TileOrder::Spiral,
+        seed: 0,
+        frame: 0,
+        progress: None
+    };
+
+    // Bumped by every edit
+    let generation = Arc::new(AtomicU64::new(0));
+
+    let renderer = {
+        let (scene, generation) = (scene.clone(), generation.clone());
+        thread::spawn(move || {
+            let mut image = vec![Color::new(0.0, 0.0, 0.0); (settings.image_width * settings.image_height) as usize];
+            let mut passes = 0;
+            let mut seen = 0;
+
+            for pass in 0..PASSES {
+                let scene = scene.read().unwrap();
+
+                // Passes from before the last edit are thrown away
+                let current = generation.load(Ordering::Acquire);
+                if current != seen {
+                    seen = current;
+                    image.fill(Color::new(0.0, 0.0, 0.0));
+                    passes = 0;
+                }
+
+                settings.seed = pass;
+                for (sum, sample) in image.iter_mut().zip(render::render(&scene, &settings)) {
+                    *sum += sample;
+                }
+                passes += 1;
+            }
+
+            (image, passes, settings)
+        })
+    };
+
+    // Object 1 is the second declared in the file, after the ground
+    for _ in 0..4 {
+        thread::sleep(Duration::from_millis(50));
+        scene.write().unwrap().move_object(1, Vec3::new(0.0, 0.1, 0.0));
+        generation.fetch_add(1, Ordering::Release);
+    }
+
+    let (image, passes, settings) = renderer.join().unwrap();
+    let mut file = fs::File::create(&output).expect("Unable to create file.");
+    write!(file, "P6\n{} {}\n255\n", settings.image_width, settings.image_height).unwrap();
+    let rgb: Vec<u8> = image.iter().flat_map(|p| p.to_rgb8(passes)).collect();
+    file.write_all(&rgb).unwrap();
+}
@@ -0,0 +1,56 @@
+// A renderer with two extra scene types, a checkered material and an object
+// that is a ball resting on the ground:
+//
+//     <material type="checker" color="0.9 0.9 0.9" odd="0.1 0.1 0.1" size="0.5" />
+//     <object type="ball" position="1 0" radius="0.5" />
+//
+// Run with `cargo run --release --example plugins -- scene.xml`.
+
+use std::sync::Arc;
+
+use rt::hit::{Hit, HitRecord};
+use rt::material::{Lambertian, Scatter};
+use rt::registry::{register_material, register_shape};
+use rt::sphere::Sphere;
+use rt::texture::Texture;
+use rt::vec::{Color, Point3};
+
+struct Checker {
+    even: Color,
+    odd: Color,
+    size: f64
+}
+
+impl Texture for Checker {
+    fn value(&self, rec: &HitRecord) -> Color {
+        let cell = |x: f64| (x / self.size).floor() as i64;
+        if (cell(rec.p.x()) + cell(rec.p.y()) + cell(rec.p.z())).rem_euclid(2) == 0 {
+            self.even
+        } else {
+            self.odd
+        }
+    }
+}
+
+fn checker(node: &rt::roxmltree::Node) -> Arc<dyn Scatter> {
+    let checker = Checker {
+        even: rt::color_attr(node, "color", Color::new(0.9, 0.9, 0.9)),
+        odd: rt::color_attr(node, "odd", Color::new(0.1, 0.1, 0.1)),
+        size: rt::f64_attr(node, "size", 1.0)
+    };
+
+    Arc::new(Lambertian::textured(Arc::new(checker)))
+}
+
+fn ball(node: &rt::roxmltree::Node, mat: Arc<dyn Scatter>) -> Box<dyn Hit> {
+    let radius = rt::f64_attr(node, "radius", 1.0);
+    let (x, z, _) = rt::value_parser(&format!("{} 0", node.attribute("position").expect("Missing ball position!")));
+
+    Box::new(Sphere::new(Point3::new(x, radius, z), radius, mat))
+}
+
+fn main() {
+    register_material("checker", checker);
+    register_shape("ball", ball);
+    rt::run();
+}
@@ -0,0 +1,80 @@
+/* C interface of rt runtime plugins, matching src/plugin.rs. A plugin is a
+ * shared library exporting rt_plugin_init, loaded by a scene file with
+ * <plugin path="libmyplugin.so" />. Its callbacks may be called from many
+ * render threads at once. */
+#ifndef RT_PLUGIN_H
+#define RT_PLUGIN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define RT_ABI_VERSION 1
+
+typedef struct RtVec3 {
+    double x, y, z;
+} RtVec3;
+
+typedef struct RtRay {
+    RtVec3 origin;
+    RtVec3 direction;
+} RtRay;
+
+/* Shapes fill in t and the outward normal; materials and textures get the
+ * point, the normal facing the ray and whether the ray hit the front. */
+typedef struct RtHit {
+    double t;
+    RtVec3 point;
+    RtVec3 normal;
+    bool front_face;
+} RtHit;
+
+/* Attributes of the scene element a factory builds from. */
+typedef struct RtAttributes {
+    const char *const *names;
+    const char *const *values;
+    size_t count;
+} RtAttributes;
+
+typedef struct RtShape {
+    void *data;
+    bool (*hit)(void *data, const RtRay *ray, double t_min, double t_max, RtHit *out);
+    /* Optional; unbounded shapes are tested against every ray. */
+    bool (*bounds)(void *data, RtVec3 *min, RtVec3 *max);
+    void (*destroy)(void *data);
+} RtShape;
+
+typedef struct RtMaterial {
+    void *data;
+    bool (*scatter)(void *data, const RtRay *ray, const RtHit *hit, RtVec3 *attenuation, RtRay *scattered);
+    /* Optional BRDF times cosine towards wi for direct lighting. */
+    bool (*eval)(void *data, const RtRay *ray, const RtHit *hit, const RtVec3 *wi, RtVec3 *out);
+    /* Optional radiance leaving the surface, which lights the others through
+       the rays they scatter onto it. */
+    void (*emitted)(void *data, const RtRay *ray, const RtHit *hit, RtVec3 *out);
+    void (*destroy)(void *data);
+} RtMaterial;
+
+typedef struct RtTexture {
+    void *data;
+    void (*value)(void *data, const RtHit *hit, RtVec3 *out);
+    void (*destroy)(void *data);
+} RtTexture;
+
+/* Factories return false if the element can't be built. */
+typedef bool (*RtShapeFactory)(const RtAttributes *attributes, RtShape *out);
+typedef bool (*RtMaterialFactory)(const RtAttributes *attributes, RtMaterial *out);
+typedef bool (*RtTextureFactory)(const RtAttributes *attributes, RtTexture *out);
+
+typedef struct RtHost {
+    uint32_t version;
+    /* Uniform number in [0, 1) from the sample's random stream. */
+    double (*random)(void);
+    void (*register_shape)(const char *name, RtShapeFactory factory);
+    void (*register_material)(const char *name, RtMaterialFactory factory);
+    void (*register_texture)(const char *name, RtTextureFactory factory);
+} RtHost;
+
+bool rt_plugin_init(const RtHost *host);
+
+#endif
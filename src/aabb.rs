@@ -0,0 +1,94 @@
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+// Axis-aligned bounding box
+#[derive(Clone, Copy)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3
+}
+
+impl Aabb {
+    pub fn new(a: Point3, b: Point3) -> Aabb {
+        Aabb {
+            min: Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z())),
+            max: Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()))
+        }
+    }
+
+    pub fn surrounding(self, other: Aabb) -> Aabb {
+        Aabb {
+            min: Point3::new(self.min.x().min(other.min.x()), self.min.y().min(other.min.y()), self.min.z().min(other.min.z())),
+            max: Point3::new(self.max.x().max(other.max.x()), self.max.y().max(other.max.y()), self.max.z().max(other.max.z()))
+        }
+    }
+
+    // Box of the space inside both; boxes that don't overlap give a flat box
+    // on the near side of the first one
+    pub fn overlap(self, other: Aabb) -> Aabb {
+        let min = Point3::new(self.min.x().max(other.min.x()), self.min.y().max(other.min.y()), self.min.z().max(other.min.z()));
+        let max = Point3::new(self.max.x().min(other.max.x()), self.max.y().min(other.max.y()), self.max.z().min(other.max.z()));
+        Aabb {
+            min,
+            max: Point3::new(max.x().max(min.x()), max.y().max(min.y()), max.z().max(min.z()))
+        }
+    }
+
+    // Grown to at least `delta` along every axis, so flat objects like
+    // axis-aligned quads still have a volume for the slab test
+    pub fn padded(self, delta: f64) -> Aabb {
+        let mut min = self.min;
+        let mut max = self.max;
+        for axis in 0..3 {
+            if max[axis] - min[axis] < delta {
+                min[axis] -= delta / 2.0;
+                max[axis] += delta / 2.0;
+            }
+        }
+
+        Aabb { min, max }
+    }
+
+    pub fn centroid(self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn extent(self) -> Vec3 {
+        self.max - self.min
+    }
+
+    // Slab test against the ray with precomputed inverse direction
+    pub fn hit(&self, origin: Point3, inv_dir: Vec3, t_min: f64, t_max: f64) -> bool {
+        self.interval(origin, inv_dir, t_min, t_max).is_some()
+    }
+
+    // Part of [t_min, t_max] the ray spends inside the box
+    pub fn interval(&self, origin: Point3, inv_dir: Vec3, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let mut t0 = t_min;
+        let mut t1 = t_max;
+
+        for axis in 0..3 {
+            let near = (self.min[axis] - origin[axis]) * inv_dir[axis];
+            let far = (self.max[axis] - origin[axis]) * inv_dir[axis];
+            let (near, far) = if near <= far { (near, far) } else { (far, near) };
+
+            // NaN from a zero direction on the slab's boundary is ignored
+            t0 = if near > t0 { near } else { t0 };
+            t1 = if far < t1 { far } else { t1 };
+            if t1 < t0 {
+                return None;
+            }
+        }
+
+        Some((t0, t1))
+    }
+
+    pub fn hit_ray(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        self.interval_ray(r, t_min, t_max).is_some()
+    }
+
+    pub fn interval_ray(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let d = r.direction();
+        self.interval(r.origin(), Vec3::new(1.0 / d.x(), 1.0 / d.y(), 1.0 / d.z()), t_min, t_max)
+    }
+}
@@ -0,0 +1,172 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rayon::prelude::*;
+
+use super::aabb::Aabb;
+use super::accel_cache;
+use super::bvh::Bvh;
+use super::hit::{Hit, HitRecord};
+use super::kdtree::KdTree;
+use super::packet::{RayPacket, MAX_LANES};
+use super::ray::Ray;
+
+// Structure answering the ray queries of a list of objects for their index
+// in the list, which hits keep as object_id. The objects are passed to
+// every call, so the lists own them and the structure only their indices.
+pub trait Accelerator<T: Hit>: Send + Sync {
+    fn hit(&self, objects: &[T], r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+
+    fn hit_packet(&self, objects: &[T], p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]);
+
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    // Edits of the list: objects[index] was added, is about to be removed,
+    // or changed shape or moved
+    fn insert(&mut self, objects: &[T], index: usize);
+    fn remove(&mut self, objects: &[T], index: usize);
+    fn refit(&mut self, objects: &[T], index: usize);
+}
+
+// Kind of structure built over the objects
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AccelKind {
+    // Bounding volume hierarchy, see bvh.rs
+    Bvh,
+    // Spatial subdivision, see kdtree.rs
+    KdTree
+}
+
+static KD_TREE: AtomicBool = AtomicBool::new(false);
+
+// Whether the command line chose, overruling the scenes
+static CHOSEN: AtomicBool = AtomicBool::new(false);
+
+impl AccelKind {
+    pub fn from_name(name: &str) -> Option<AccelKind> {
+        match name {
+            "bvh" => Some(AccelKind::Bvh),
+            "kdtree" => Some(AccelKind::KdTree),
+            _ => None
+        }
+    }
+
+    // Builds every structure made from now on this way, whatever the scenes
+    // ask for
+    pub fn set(self) {
+        KD_TREE.store(self == AccelKind::KdTree, Ordering::Relaxed);
+        CHOSEN.store(true, Ordering::Relaxed);
+    }
+
+    // The kind a scene asks for, the BVH if None, unless the command line
+    // already chose one
+    pub fn set_for_scene(kind: Option<AccelKind>) {
+        if !CHOSEN.load(Ordering::Relaxed) {
+            KD_TREE.store(kind == Some(AccelKind::KdTree), Ordering::Relaxed);
+        }
+    }
+
+    fn current() -> AccelKind {
+        if KD_TREE.load(Ordering::Relaxed) { AccelKind::KdTree } else { AccelKind::Bvh }
+    }
+}
+
+pub fn new<T: Hit>(objects: &[T]) -> Box<dyn Accelerator<T>> {
+    with_indices(objects, 0..objects.len())
+}
+
+// Structure of the current kind over only the objects at the given indices,
+// read from the acceleration cache when it has one, see accel_cache.rs
+pub fn with_indices<T: Hit>(objects: &[T], indices: impl Iterator<Item = usize>) -> Box<dyn Accelerator<T>> {
+    let boxes = boxes(objects, indices);
+    let n = objects.len();
+    match AccelKind::current() {
+        AccelKind::Bvh => Box::new(accel_cache::cached(n, boxes, |boxes| Bvh::from_boxes(n, boxes))),
+        AccelKind::KdTree => Box::new(accel_cache::cached(n, boxes, KdTree::from_boxes))
+    }
+}
+
+// Bounding boxes of the objects at the given indices, with their index
+pub fn boxes<T: Hit>(objects: &[T], indices: impl Iterator<Item = usize>) -> Vec<(usize, Option<Aabb>)> {
+    indices.collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|index| (index, objects[index].bounding_box()))
+        .collect()
+}
+
+// Random scenes and the hits a linear scan finds in them, which every
+// structure must find too
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec::{Color, Point3, Vec3};
+
+    pub fn rng(seed: u64) -> StdRng {
+        StdRng::seed_from_u64(seed)
+    }
+
+    fn point(rng: &mut StdRng, size: f64) -> Point3 {
+        Point3::new(rng.gen_range(-size..size), rng.gen_range(-size..size), rng.gen_range(-size..size))
+    }
+
+    // Spheres of every size in a cube growing with their count, so their
+    // density stays the same
+    pub fn spheres(rng: &mut StdRng, count: usize) -> Vec<Sphere> {
+        let size = (count as f64).cbrt();
+        let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        (0..count).map(|_| Sphere::new(point(rng, size), rng.gen_range(0.05..1.0), mat.clone())).collect()
+    }
+
+    // Rays from in and around the cube of spheres, in every direction
+    pub fn rays(rng: &mut StdRng, spheres: usize, count: usize) -> Vec<Ray> {
+        let size = 1.2 * (spheres as f64).cbrt();
+        (0..count).map(|_| {
+            let dir = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+            Ray::new(point(rng, size), dir)
+        }).collect()
+    }
+
+    fn closest(rec: Option<HitRecord>) -> Option<(f64, usize)> {
+        rec.map(|rec| (rec.t, rec.object_id))
+    }
+
+    // The hits of the structure, ray by ray and in packets of 4, are those
+    // of a scan of the objects `held` says it holds
+    pub fn assert_matches_scan(accel: &dyn Accelerator<Sphere>, objects: &[Sphere], held: &[bool], rays: &[Ray]) {
+        let mut hits = 0;
+        for (k, r) in rays.iter().enumerate() {
+            let mut scan = None;
+            for (index, object) in objects.iter().enumerate().filter(|&(index, _)| held[index]) {
+                let t_max = scan.map_or(f64::INFINITY, |(t, _)| t);
+                if let Some(rec) = object.hit(r, 0.001, t_max) {
+                    scan = Some((rec.t, index));
+                }
+            }
+            assert_eq!(closest(accel.hit(objects, r, 0.001, f64::INFINITY)), scan, "Ray {} finds another hit", k);
+            hits += scan.is_some() as usize;
+        }
+        assert!(hits > rays.len() / 4 && hits < rays.len(), "Only {} of {} rays hit", hits, rays.len());
+
+        for (k, chunk) in rays.chunks(4).enumerate() {
+            let packet = RayPacket::new(chunk);
+            let mut t_max = packet.initial_t_max(f64::INFINITY);
+            let mut recs: Vec<Option<HitRecord>> = chunk.iter().map(|_| None).collect();
+            accel.hit_packet(objects, &packet, 0.001, &mut t_max, &mut recs);
+            // Packet tests round their roots differently
+            for (lane, (r, rec)) in chunk.iter().zip(recs).enumerate() {
+                let (packet, single) = (closest(rec), closest(accel.hit(objects, r, 0.001, f64::INFINITY)));
+                let same = match (packet, single) {
+                    (Some((t, a)), Some((u, b))) => a == b && (t - u).abs() < 1e-9 * u,
+                    (a, b) => a == b
+                };
+                assert!(same, "Lane {} of packet {} finds {:?} instead of {:?}", lane, k, packet, single);
+            }
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::aabb::Aabb;
+use super::sampler;
+use super::vec::Point3;
+
+// First bytes of a cache file, to change with the layout of the entries
+const MAGIC: &[u8; 8] = b"rtaccel2";
+
+// Files written by other versions of the renderer are rebuilt, as their
+// builds may differ even with the same layout
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// Structures over fewer objects are built about as fast as they are read
+const MIN_OBJECTS: usize = 10000;
+
+// Acceleration structures built by earlier renders of a scene, kept in a
+// file next to it and found again by the boxes of the objects they were
+// built over. A structure only depends on those boxes and on how it is
+// built, so one built over the same boxes is the same as the one a build
+// would make, whatever changed inside them.
+struct Cache {
+    path: PathBuf,
+    entries: HashMap<u64, Vec<u8>>,
+    // Keys looked up or added since the file was opened; the others, left
+    // by geometry that changed since, are dropped when it is saved
+    used: HashSet<u64>,
+    changed: bool
+}
+
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+// Structure that can be written to the cache and read back
+pub trait Cached: Sized {
+    // Tells the kinds of structure and ways of building them apart in keys
+    fn tag() -> u64;
+
+    fn encode(&self, out: &mut Writer);
+
+    // The structure over `objects` objects, None if the entry doesn't
+    // describe one
+    fn decode(input: &mut Reader, objects: usize) -> Option<Self>;
+}
+
+// Little-endian encoding of an entry
+pub struct Writer {
+    bytes: Vec<u8>
+}
+
+impl Writer {
+    fn u64(&mut self, v: u64) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn usize(&mut self, v: usize) {
+        self.u64(v as u64);
+    }
+
+    pub fn f64(&mut self, v: f64) {
+        self.bytes.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn indices(&mut self, v: &[usize]) {
+        self.usize(v.len());
+        for &i in v {
+            self.usize(i);
+        }
+    }
+
+    pub fn aabb(&mut self, b: Option<Aabb>) {
+        match b {
+            Some(b) => {
+                self.bytes.push(1);
+                for v in [b.min.x(), b.min.y(), b.min.z(), b.max.x(), b.max.y(), b.max.z()] {
+                    self.f64(v);
+                }
+            },
+            None => self.bytes.push(0)
+        }
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Option<&[u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    pub fn usize(&mut self) -> Option<usize> {
+        usize::try_from(self.u64()?).ok()
+    }
+
+    pub fn f64(&mut self) -> Option<f64> {
+        Some(f64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    // Bytes left to read
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    // Indices, each less than `limit`
+    pub fn indices(&mut self, limit: usize) -> Option<Vec<usize>> {
+        let count = self.usize()?;
+        if count > self.remaining() / 8 {
+            return None;
+        }
+        (0..count).map(|_| self.usize().filter(|&i| i < limit)).collect()
+    }
+
+    pub fn aabb(&mut self) -> Option<Option<Aabb>> {
+        match self.take(1)?[0] {
+            0 => Some(None),
+            1 => {
+                let mut v = [0.0; 6];
+                for x in &mut v {
+                    *x = self.f64()?;
+                }
+                Some(Some(Aabb { min: Point3::new(v[0], v[1], v[2]), max: Point3::new(v[3], v[4], v[5]) }))
+            },
+            _ => None
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+}
+
+// Reads the cache file at path, if there is one, and caches the structures
+// built from now on until it is saved
+pub fn open(path: &Path) {
+    let mut entries = HashMap::new();
+    if let Ok(bytes) = std::fs::read(path) {
+        match parse(&bytes) {
+            Some(parsed) => entries = parsed,
+            None => eprintln!("Warning: the acceleration cache {} is invalid, it will be rebuilt.", path.display())
+        }
+    }
+
+    *CACHE.lock().unwrap() = Some(Cache { path: path.to_path_buf(), entries, used: HashSet::new(), changed: false });
+}
+
+fn parse(bytes: &[u8]) -> Option<HashMap<u64, Vec<u8>>> {
+    let mut input = Reader { bytes, pos: 0 };
+    if input.take(MAGIC.len())? != MAGIC {
+        return None;
+    }
+
+    let mut entries = HashMap::new();
+    while !input.done() {
+        let key = input.u64()?;
+        let len = input.usize()?;
+        entries.insert(key, input.take(len)?.to_vec());
+    }
+    Some(entries)
+}
+
+// Writes the structures used since the cache was opened, if any were added
+// or dropped
+pub fn save() {
+    let mut cache = CACHE.lock().unwrap();
+    let Some(cache) = cache.as_mut() else {
+        return;
+    };
+
+    let used = &cache.used;
+    let before = cache.entries.len();
+    cache.entries.retain(|key, _| used.contains(key));
+    if !cache.changed && cache.entries.len() == before {
+        return;
+    }
+
+    let mut out = Writer { bytes: MAGIC.to_vec() };
+    for (&key, bytes) in &cache.entries {
+        out.u64(key);
+        out.usize(bytes.len());
+        out.bytes.extend_from_slice(bytes);
+    }
+    match std::fs::write(&cache.path, out.bytes) {
+        Ok(()) => cache.changed = false,
+        Err(e) => eprintln!("Warning: failed to write the acceleration cache {}: {}.", cache.path.display(), e)
+    }
+}
+
+// The structure of kind S over the boxes of the objects at their indices,
+// from the cache if it holds one, otherwise made by `build` and added to it
+pub fn cached<S: Cached>(objects: usize, boxes: Vec<(usize, Option<Aabb>)>, build: impl FnOnce(Vec<(usize, Option<Aabb>)>) -> S) -> S {
+    if boxes.len() < MIN_OBJECTS || CACHE.lock().unwrap().is_none() {
+        return build(boxes);
+    }
+
+    let key = key(S::tag(), objects, &boxes);
+    let found = CACHE.lock().unwrap().as_mut().and_then(|cache| {
+        cache.used.insert(key);
+        decoded(cache.entries.get(&key)?, objects)
+    });
+    if let Some(structure) = found {
+        return structure;
+    }
+
+    let structure = build(boxes);
+    if let Some(cache) = CACHE.lock().unwrap().as_mut() {
+        cache.entries.insert(key, encoded(&structure));
+        cache.changed = true;
+    }
+    structure
+}
+
+fn encoded<S: Cached>(structure: &S) -> Vec<u8> {
+    let mut out = Writer { bytes: Vec::new() };
+    structure.encode(&mut out);
+    out.bytes
+}
+
+// The structure an entry holds, if all of it describes one
+fn decoded<S: Cached>(bytes: &[u8], objects: usize) -> Option<S> {
+    let mut input = Reader { bytes, pos: 0 };
+    S::decode(&mut input, objects).filter(|_| input.done())
+}
+
+// Hash of everything a build depends on
+fn key(tag: u64, objects: usize, boxes: &[(usize, Option<Aabb>)]) -> u64 {
+    let mut h = VERSION.bytes().fold(0, |h, b| sampler::hash(h ^ b as u64));
+    h = sampler::hash(h ^ tag ^ sampler::hash(objects as u64));
+    for (index, bbox) in boxes {
+        h = sampler::hash(h ^ *index as u64);
+        match bbox {
+            Some(b) => {
+                for v in [b.min.x(), b.min.y(), b.min.z(), b.max.x(), b.max.y(), b.max.z()] {
+                    h = sampler::hash(h ^ v.to_bits());
+                }
+            },
+            None => h = sampler::hash(!h)
+        }
+    }
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+    use crate::kdtree::KdTree;
+    use crate::vec::Vec3;
+
+    const OBJECTS: usize = 80;
+
+    // Small cubes scattered over a grid, with an unbounded object and a
+    // few indices left out of the structure
+    fn boxes(count: usize) -> Vec<(usize, Option<Aabb>)> {
+        let mut boxes = vec![(0, None)];
+        for k in 1..count {
+            let min = Point3::new((k * 7 % 10) as f64, (k / 10 % 4) as f64, 0.5 * (k % 3) as f64);
+            boxes.push((k + 2, Some(Aabb::new(min, min + Vec3::new(0.6, 0.6, 0.6)))));
+        }
+        boxes
+    }
+
+    fn unit_box() -> Option<Aabb> {
+        Some(Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)))
+    }
+
+    // BVH entry whose interior nodes form a chain `depth` long, each with
+    // the next on the left and a leaf on the right
+    fn bvh_chain(depth: usize) -> Vec<u8> {
+        let none = usize::MAX;
+        let count = 2 * depth + 1;
+        let mut out = Writer { bytes: Vec::new() };
+        out.usize(count);
+        for index in 0..count {
+            out.aabb(unit_box());
+            out.usize(match index {
+                0 => none,
+                i if i <= depth => i - 1,
+                i => i - depth - 1
+            });
+            if index < depth {
+                out.usize(0);
+                out.usize(index + 1);
+                out.usize(depth + 1 + index);
+            } else {
+                out.usize(none);
+                out.usize(0);
+                out.usize(0);
+            }
+        }
+        out.indices(&[]);
+        out.indices(&[]);
+        out.bytes
+    }
+
+    // kd-tree entry whose interior nodes form a chain `depth` long, each
+    // with the next below and a leaf above
+    fn kdtree_chain(depth: usize) -> Vec<u8> {
+        let count = 2 * depth + 1;
+        let mut out = Writer { bytes: Vec::new() };
+        out.usize(count);
+        for index in 0..count {
+            if index < depth {
+                out.usize(0);
+                out.f64(0.5);
+                out.usize(depth + 1 + index);
+            } else {
+                out.usize(3);
+                out.usize(0);
+                out.usize(0);
+            }
+        }
+        out.indices(&[]);
+        out.aabb(unit_box());
+        out.indices(&[]);
+        out.indices(&[]);
+        out.bytes
+    }
+
+    #[test]
+    fn bvh_round_trip() {
+        let bvh = Bvh::from_boxes(OBJECTS, boxes(60));
+        let bytes = encoded(&bvh);
+        let read: Bvh = decoded(&bytes, OBJECTS).expect("The BVH wasn't read back.");
+        assert_eq!(encoded(&read), bytes);
+    }
+
+    #[test]
+    fn kdtree_round_trip() {
+        let tree = KdTree::from_boxes(boxes(60));
+        let bytes = encoded(&tree);
+        let read: KdTree = decoded(&bytes, OBJECTS).expect("The kd-tree wasn't read back.");
+        assert_eq!(encoded(&read), bytes);
+    }
+
+    #[test]
+    fn truncated_entries_are_rejected() {
+        let bvh = encoded(&Bvh::from_boxes(OBJECTS, boxes(30)));
+        let tree = encoded(&KdTree::from_boxes(boxes(30)));
+        for len in 0..bvh.len() {
+            assert!(decoded::<Bvh>(&bvh[..len], OBJECTS).is_none(), "BVH cut at {} bytes was read.", len);
+        }
+        for len in 0..tree.len() {
+            assert!(decoded::<KdTree>(&tree[..len], OBJECTS).is_none(), "kd-tree cut at {} bytes was read.", len);
+        }
+
+        let mut longer = bvh.clone();
+        longer.push(0);
+        assert!(decoded::<Bvh>(&longer, OBJECTS).is_none());
+    }
+
+    #[test]
+    fn entries_over_other_objects_are_rejected() {
+        let bvh = encoded(&Bvh::from_boxes(OBJECTS, boxes(30)));
+        let tree = encoded(&KdTree::from_boxes(boxes(30)));
+        assert!(decoded::<Bvh>(&bvh, 20).is_none());
+        assert!(decoded::<KdTree>(&tree, 20).is_none());
+    }
+
+    #[test]
+    fn cyclic_trees_are_rejected() {
+        // The left child of the root, after the node count, its box, its
+        // parent and its axis
+        let mut bvh = encoded(&Bvh::from_boxes(OBJECTS, boxes(30)));
+        let left = 8 + 49 + 8 + 8;
+        bvh[left..left + 8].copy_from_slice(&0u64.to_le_bytes());
+        assert!(decoded::<Bvh>(&bvh, OBJECTS).is_none());
+
+        // The second interior node sharing the root's right leaf, after the
+        // root and its own box, parent, axis and left child
+        let mut bvh = bvh_chain(3);
+        let right = 8 + 81 + 49 + 8 + 8 + 8;
+        bvh[right..right + 8].copy_from_slice(&4u64.to_le_bytes());
+        assert!(decoded::<Bvh>(&bvh, OBJECTS).is_none());
+
+        // The same for the child above the second node of a kd-tree
+        let mut tree = kdtree_chain(3);
+        let above = 8 + 24 + 8 + 8;
+        tree[above..above + 8].copy_from_slice(&4u64.to_le_bytes());
+        assert!(decoded::<KdTree>(&tree, OBJECTS).is_none());
+    }
+
+    #[test]
+    fn deep_trees_are_rejected() {
+        assert!(decoded::<Bvh>(&bvh_chain(10), OBJECTS).is_some());
+        assert!(decoded::<Bvh>(&bvh_chain(70), OBJECTS).is_none());
+        assert!(decoded::<KdTree>(&kdtree_chain(10), OBJECTS).is_some());
+        assert!(decoded::<KdTree>(&kdtree_chain(70), OBJECTS).is_none());
+    }
+
+    #[test]
+    fn files_of_other_layouts_are_rejected() {
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&7u64.to_le_bytes());
+        file.extend_from_slice(&0u64.to_le_bytes());
+        assert_eq!(parse(&file).map(|entries| entries.len()), Some(1));
+
+        file[7] = b'1';
+        assert!(parse(&file).is_none());
+        assert!(parse(&file[..file.len() - 1]).is_none());
+    }
+}
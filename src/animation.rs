@@ -0,0 +1,79 @@
+use std::ops::RangeInclusive;
+
+use super::camera::{Camera, DEFAULT_FOCUS_DIST, DEFAULT_VFOV};
+use super::vec::{Point3, Vec3};
+
+// Camera placement at one frame of an animation
+#[derive(Clone, Copy)]
+pub struct CameraKey {
+    pub frame: u64,
+    pub look_from: Point3,
+    pub look_at: Point3,
+    pub up: Vec3,
+    pub aperture: f64
+}
+
+// Camera moving through keyframes, linearly interpolated between them
+pub struct CameraPath {
+    keys: Vec<CameraKey>,
+    // Shutter interval of every frame's camera
+    shutter: (f64, f64),
+    // Of the film every frame is rendered on
+    aspect_ratio: f64
+}
+
+impl CameraPath {
+    pub fn new(mut keys: Vec<CameraKey>, shutter: (f64, f64), aspect_ratio: f64) -> CameraPath {
+        if keys.is_empty() {
+            panic!("A camera path needs at least one key!");
+        }
+        keys.sort_by_key(|k| k.frame);
+
+        CameraPath {
+            keys,
+            shutter,
+            aspect_ratio
+        }
+    }
+
+    // Frames from the first key to the last one
+    pub fn frames(&self) -> RangeInclusive<u64> {
+        self.keys[0].frame..=self.keys[self.keys.len() - 1].frame
+    }
+
+    pub fn key(&self, frame: u64) -> CameraKey {
+        let next = self.keys.iter().position(|k| k.frame >= frame);
+
+        match next {
+            Some(0) => self.keys[0],
+            None => self.keys[self.keys.len() - 1],
+            Some(n) => {
+                let (a, b) = (&self.keys[n - 1], &self.keys[n]);
+                let t = (frame - a.frame) as f64 / (b.frame - a.frame) as f64;
+                let lerp = |x: Vec3, y: Vec3| (1.0 - t) * x + t * y;
+
+                CameraKey {
+                    frame,
+                    look_from: lerp(a.look_from, b.look_from),
+                    look_at: lerp(a.look_at, b.look_at),
+                    up: lerp(a.up, b.up),
+                    aperture: (1.0 - t) * a.aperture + t * b.aperture
+                }
+            }
+        }
+    }
+
+    pub fn camera(&self, frame: u64) -> Camera {
+        let key = self.key(frame);
+
+        Camera::new(
+            key.look_from,
+            key.look_at,
+            key.up,
+            DEFAULT_VFOV,
+            self.aspect_ratio,
+            key.aperture,
+            DEFAULT_FOCUS_DIST,
+        ).with_shutter(self.shutter.0, self.shutter.1)
+    }
+}
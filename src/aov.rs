@@ -0,0 +1,256 @@
+use rayon::prelude::*;
+
+use super::exr::ExrImage;
+use super::hit::Hit;
+use super::material;
+use super::metadata::Metadata;
+use super::ray::{Ray, RayKind};
+use super::render::{self, RenderSettings};
+use super::scene::Scene;
+use super::camera::Camera;
+use super::vec::{Color, Point3, Vec3};
+
+// Utility passes written next to the beauty image
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Aov {
+    // Index of the object seen in each pixel, -1 for the background
+    Id,
+    // Distance from the camera to the first hit, infinite for the background
+    Depth,
+    // Depth mapped from 0 at the near end of the depth range to 1 at its
+    // far end and beyond, for fog
+    Fog,
+    // World-space position of the first hit
+    Position,
+    // World-space shading normal of the first hit
+    Normal,
+    // Share of the direct light at the first hit found by light sampling
+    // (red) and by sampling the material (blue)
+    Mis,
+    // Pixels the first hit shifts by between the eyes of a stereo pair
+    Disparity
+}
+
+impl Aov {
+    pub fn from_name(name: &str) -> Option<Aov> {
+        match name {
+            "id" => Some(Aov::Id),
+            "depth" => Some(Aov::Depth),
+            "fog" => Some(Aov::Fog),
+            "position" => Some(Aov::Position),
+            "normal" => Some(Aov::Normal),
+            "mis" => Some(Aov::Mis),
+            "disparity" => Some(Aov::Disparity),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Id => "id",
+            Aov::Depth => "depth",
+            Aov::Fog => "fog",
+            Aov::Position => "position",
+            Aov::Normal => "normal",
+            Aov::Mis => "mis",
+            Aov::Disparity => "disparity"
+        }
+    }
+}
+
+// Depths the fog pass maps to 0 and 1; unset ends default to the camera
+// and the farthest surface in view
+#[derive(Clone, Copy, Default)]
+pub struct DepthRange {
+    pub near: Option<f64>,
+    pub far: Option<f64>
+}
+
+// Stereo pair the disparity pass is measured between; unset values default
+// to converging at the camera's focus distance with eyes a thirtieth of it
+// apart, the usual rule of thumb for comfortable depth
+#[derive(Clone, Copy, Default)]
+pub struct StereoRig {
+    pub interaxial: Option<f64>,
+    pub convergence: Option<f64>
+}
+
+// First surface seen through a pixel center
+pub struct PrimaryHit {
+    pub p: Point3,
+    pub object_id: usize,
+    // Distance from the camera
+    pub depth: f64,
+    pub normal: Vec3
+}
+
+// Direct lighting estimates of each strategy per pixel of the mis pass
+const MIS_SAMPLES: u64 = 16;
+
+// "scene.ppm" -> "scene.depth.exr"
+pub fn pass_filename(img_name: &str, pass: &str) -> String {
+    let stem = img_name.rsplit_once('.').map_or(img_name, |(stem, _)| stem);
+    format!("{}.{}.exr", stem, pass)
+}
+
+// One ray through each pixel center, top row first
+pub fn primary_hits(scene: &Scene, settings: &RenderSettings, cam: &Camera) -> Vec<Option<PrimaryHit>> {
+    let width = settings.image_width;
+    let height = settings.image_height;
+
+    (0..width * height)
+        .into_par_iter()
+        .map(|n| {
+            let i = n % width;
+            let j = height - 1 - n / width;
+            let (u, v) = settings.film_coords(i, j, 0.5, 0.5);
+            render::start_sample(settings, i, j, 0);
+            let r = cam.get_ray(u, v);
+
+            scene.world.hit(&r, 0.001, f64::INFINITY).map(|rec| PrimaryHit {
+                p: rec.p,
+                object_id: rec.object_id,
+                depth: rec.t * r.direction().length(),
+                normal: rec.normal
+            })
+        })
+        .collect()
+}
+
+// Direct light gathered at the first hit by each of the renderer's two
+// strategies, as (light sampling, material sampling) luminances. Every
+// emitter is left to one of them: lights are sampled at surfaces that can
+// be lit directly, while emissive objects, the environment and every light
+// seen from surfaces that can't are only found by the scattered rays.
+fn strategy_split(r: &Ray, scene: &Scene) -> (f64, f64) {
+    let luminance = |c: Color| 0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z();
+
+    let Some(mut rec) = scene.world.hit(r, 0.001, f64::INFINITY) else {
+        return (0.0, 0.0);
+    };
+    material::resolve(r, &mut rec);
+
+    let direct = render::direct_light(r, &rec, scene);
+    let kind = render::bounce_kind(r, &rec, RayKind::Camera, direct.is_some());
+    let light = direct.map_or(0.0, luminance);
+
+    let material = match render::scatter(r, &rec, scene) {
+        Some((attenuation, scattered)) => {
+            let found = match scene.world.hit(&scattered, 0.001, f64::INFINITY) {
+                Some(mut next) if render::counts_emission(kind, &next, scene) => {
+                    material::resolve(&scattered, &mut next);
+                    next.mat.emitted(&scattered, &next)
+                },
+                Some(_) => Color::new(0.0, 0.0, 0.0),
+                None => scene.background(&scattered, kind)
+            };
+            luminance(attenuation * found)
+        },
+        None => 0.0
+    };
+
+    (light, material)
+}
+
+// Red and blue shares of the direct light of every pixel for the mis pass,
+// black where there is none
+fn strategy_shares(scene: &Scene, settings: &RenderSettings, cam: &Camera) -> Vec<(f32, f32)> {
+    let width = settings.image_width;
+    let height = settings.image_height;
+
+    (0..width * height)
+        .into_par_iter()
+        .map(|n| {
+            let i = n % width;
+            let j = height - 1 - n / width;
+            let (u, v) = settings.film_coords(i, j, 0.5, 0.5);
+
+            let (mut light, mut material) = (0.0, 0.0);
+            for s in 0..MIS_SAMPLES {
+                render::start_sample(settings, i, j, s);
+                let (l, m) = strategy_split(&cam.get_ray(u, v), scene);
+                light += l;
+                material += m;
+            }
+
+            let total = light + material;
+            if total > 0.0 {
+                ((light / total) as f32, (material / total) as f32)
+            } else {
+                (0.0, 0.0)
+            }
+        })
+        .collect()
+}
+
+// The passes come from one ray through each pixel center, so ids and
+// depths are never blended across edges. With `pinhole` that ray is traced
+// from a pinhole version of the camera, keeping the passes sharp where the
+// beauty image is defocused.
+#[allow(clippy::too_many_arguments)]
+pub fn render_aovs(scene: &Scene, settings: &RenderSettings, aovs: &[Aov], pinhole: bool, z_range: DepthRange, rig: StereoRig, img_name: &str, metadata: &Metadata) {
+    let cam = if pinhole { scene.camera.pinhole() } else { scene.camera.clone() };
+    let hits = primary_hits(scene, settings, &cam);
+
+    let near = z_range.near.unwrap_or(0.0);
+    let far = z_range.far.unwrap_or_else(|| hits.iter().flatten().map(|h| h.depth).fold(near, f64::max));
+    if z_range.far.is_some() && far <= near {
+        panic!("--z-far must be beyond --z-near.");
+    }
+
+    for &aov in aovs {
+        let mut exr = ExrImage::new(settings.image_width as usize, settings.image_height as usize);
+        exr.overscan = settings.overscan as usize;
+        exr.flip = settings.flip;
+        exr.pixel_aspect = settings.pixel_aspect;
+        exr.text = metadata.entries().to_vec();
+        let channel = |f: &dyn Fn(&PrimaryHit) -> f32, background: f32| -> Vec<f32> {
+            hits.iter().map(|h| h.as_ref().map_or(background, f)).collect()
+        };
+
+        match aov {
+            Aov::Id => exr.add_channel("id", channel(&|h| h.object_id as f32, -1.0)),
+            Aov::Depth => exr.add_channel("Z", channel(&|h| h.depth as f32, f32::INFINITY)),
+            Aov::Fog => {
+                // An empty range puts everything beyond it
+                let fog = |depth: f64| if far > near { ((depth - near) / (far - near)).clamp(0.0, 1.0) } else { 1.0 };
+                exr.add_channel("Y", channel(&|h| fog(h.depth) as f32, 1.0));
+            },
+            Aov::Position => {
+                exr.add_channel("R", channel(&|h| h.p.x() as f32, 0.0));
+                exr.add_channel("G", channel(&|h| h.p.y() as f32, 0.0));
+                exr.add_channel("B", channel(&|h| h.p.z() as f32, 0.0));
+            },
+            Aov::Normal => {
+                exr.add_channel("R", channel(&|h| h.normal.x() as f32, 0.0));
+                exr.add_channel("G", channel(&|h| h.normal.y() as f32, 0.0));
+                exr.add_channel("B", channel(&|h| h.normal.z() as f32, 0.0));
+            },
+            Aov::Mis => {
+                let shares = strategy_shares(scene, settings, &cam);
+                exr.add_channel("R", shares.iter().map(|s| s.0).collect());
+                exr.add_channel("G", vec![0.0; shares.len()]);
+                exr.add_channel("B", shares.iter().map(|s| s.1).collect());
+            },
+            Aov::Disparity => {
+                let convergence = rig.convergence.unwrap_or(cam.focus_dist());
+                let interaxial = rig.interaxial.unwrap_or(convergence / 30.0);
+                if convergence <= 0.0 || interaxial <= 0.0 {
+                    panic!("The stereo convergence and interaxial distance must be positive.");
+                }
+
+                // Eyes side by side around the camera, their images shifted to
+                // line up at the convergence distance; nearer points are
+                // positive, seen more to the right by the left eye, and the
+                // background is as far as it gets
+                let pixels = (settings.display_size().0 - 1) as f64;
+                let disparity = |depth: f64| (pixels * interaxial / cam.film_width() * (1.0 / depth - 1.0 / convergence)) as f32;
+                exr.add_channel("Y", channel(&|h| disparity(cam.view_depth(h.p)), disparity(f64::INFINITY)));
+            }
+        }
+
+        let filename = pass_filename(img_name, aov.name());
+        exr.write(&filename).unwrap_or_else(|_| panic!("Failed to write {}.", filename));
+        eprintln!("Wrote {} pass to {}", aov.name(), filename);
+    }
+}
@@ -0,0 +1,216 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use super::hit::HitRecord;
+use super::material::Scatter;
+use super::ray::{Ray, RayType};
+use super::sampler;
+use super::vec::{Color, Point3, Vec3};
+
+// Earth's ground and atmosphere, in metres, that planets are scaled from
+const EARTH_RADIUS: f64 = 6360e3;
+const EARTH_ATMOSPHERE: f64 = 60e3;
+const RAYLEIGH_HEIGHT: f64 = 7994.0;
+const MIE_HEIGHT: f64 = 1200.0;
+const RAYLEIGH: [f64; 3] = [5.8e-6, 13.5e-6, 33.1e-6];
+const MIE: f64 = 21e-6;
+// Anisotropy of the haze, scattering forward
+const MIE_G: f64 = 0.76;
+
+// Steps along the viewing ray and towards the sun
+const VIEW_STEPS: usize = 16;
+const SUN_STEPS: usize = 8;
+
+// The air around a planet as a material of the sphere bounding it. Rays pass
+// through, dimmed by the air they cross, which glows with the sunlight it
+// scatters towards them once, off molecules (Rayleigh, blue) and haze (Mie,
+// white and forward), both thinning out exponentially with height. Sunlight
+// is sampled by the shader itself, so the sun needn't be a scene light, and
+// isn't dimmed on its way through the air to the ground.
+pub struct Atmosphere {
+    center: Point3,
+    ground: f64,
+    top: f64,
+    sun: Vec3,
+    irradiance: Color,
+    rayleigh: Color,
+    mie: f64,
+    rayleigh_height: f64,
+    mie_height: f64
+}
+
+impl Atmosphere {
+    // Earth's air over a planet of the given radius, its thickness and
+    // scattering scaled by the planet's size and by density
+    pub fn new(center: Point3, ground: f64, thickness: f64, density: f64, sun: Vec3, irradiance: Color) -> Atmosphere {
+        let scale = ground / EARTH_RADIUS;
+        let thickness_scale = thickness / (EARTH_ATMOSPHERE * scale);
+        Atmosphere {
+            center,
+            ground,
+            top: ground + thickness,
+            sun: sun.normalized(),
+            irradiance,
+            rayleigh: (density / scale) * Color::new(RAYLEIGH[0], RAYLEIGH[1], RAYLEIGH[2]),
+            mie: density * MIE / scale,
+            rayleigh_height: RAYLEIGH_HEIGHT * scale * thickness_scale,
+            mie_height: MIE_HEIGHT * scale * thickness_scale
+        }
+    }
+
+    // Distances along the unit direction d from o to where its line enters
+    // and leaves the sphere of the given radius
+    fn crossing(&self, o: Point3, d: Vec3, radius: f64) -> Option<(f64, f64)> {
+        let oc = o - self.center;
+        let half_b = oc.dot(d);
+        let discriminant = half_b * half_b - (oc.dot(oc) - radius * radius);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrtd = discriminant.sqrt();
+        Some((-half_b - sqrtd, -half_b + sqrtd))
+    }
+
+    // Where the air the ray sees starts, and how far it goes along the unit
+    // direction: from the shell it enters to the ground or the far side, or
+    // from an origin inside the air to the shell it leaves. Rays that entered
+    // the shell themselves have already been accounted for when they leave.
+    fn segment(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Point3, Vec3, f64)> {
+        let d = r_in.direction().normalized();
+        if rec.front_face {
+            let length = match self.crossing(rec.p, d, self.ground) {
+                Some((near, _)) if near > 0.0 => near,
+                _ => self.crossing(rec.p, d, self.top)?.1.max(0.0)
+            };
+            Some((rec.p, d, length))
+        } else if (r_in.origin() - self.center).length() < self.top * (1.0 - 1e-6) {
+            Some((r_in.origin(), d, (rec.p - r_in.origin()).length()))
+        } else {
+            None
+        }
+    }
+
+    // Densities of molecules and haze at p, relative to the ground's
+    fn density(&self, p: Point3) -> (f64, f64) {
+        let height = ((p - self.center).length() - self.ground).max(0.0);
+        ((-height / self.rayleigh_height).exp(), (-height / self.mie_height).exp())
+    }
+
+    fn transmittance(&self, rayleigh_depth: f64, mie_depth: f64) -> Color {
+        // Haze also absorbs a little, a tenth of what it scatters
+        let tau = rayleigh_depth * self.rayleigh + Color::new(1.0, 1.0, 1.0) * (1.1 * self.mie * mie_depth);
+        Color::new((-tau.x()).exp(), (-tau.y()).exp(), (-tau.z()).exp())
+    }
+
+    // Optical depths of molecules and haze from p to the top of the air
+    // towards the sun, or None where the planet shades p
+    fn sun_depth(&self, p: Point3) -> Option<(f64, f64)> {
+        if self.crossing(p, self.sun, self.ground).is_some_and(|(near, _)| near > 0.0) {
+            return None;
+        }
+
+        let length = self.crossing(p, self.sun, self.top)?.1.max(0.0);
+        let step = length / SUN_STEPS as f64;
+        let (mut rayleigh, mut mie) = (0.0, 0.0);
+        for k in 0..SUN_STEPS {
+            let (dr, dm) = self.density(p + (k as f64 + 0.5) * step * self.sun);
+            rayleigh += dr * step;
+            mie += dm * step;
+        }
+        Some((rayleigh, mie))
+    }
+}
+
+impl Scatter for Atmosphere {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let through = Ray::with_time(rec.p, r_in.direction(), r_in.time());
+        let (start, d, length) = match self.segment(r_in, rec) {
+            Some(segment) => segment,
+            None => return Some((Color::new(1.0, 1.0, 1.0), through))
+        };
+
+        let step = length / VIEW_STEPS as f64;
+        let (mut rayleigh, mut mie) = (0.0, 0.0);
+        for k in 0..VIEW_STEPS {
+            let (dr, dm) = self.density(start + (k as f64 + 0.5) * step * d);
+            rayleigh += dr * step;
+            mie += dm * step;
+        }
+        Some((self.transmittance(rayleigh, mie), through))
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        let (start, d, length) = match self.segment(r_in, rec) {
+            Some(segment) => segment,
+            None => return Color::new(0.0, 0.0, 0.0)
+        };
+
+        // Jittered steps, trading the banding of fixed ones for noise
+        let step = length / VIEW_STEPS as f64;
+        let jitter: f64 = sampler::rng().gen();
+        let (mut view_rayleigh, mut view_mie) = (0.0, 0.0);
+        let mut rayleigh_sum = Color::new(0.0, 0.0, 0.0);
+        let mut mie_sum = Color::new(0.0, 0.0, 0.0);
+        for k in 0..VIEW_STEPS {
+            let p = start + (k as f64 + jitter) * step * d;
+            let (dr, dm) = self.density(p);
+            view_rayleigh += dr * step;
+            view_mie += dm * step;
+
+            if let Some((sun_rayleigh, sun_mie)) = self.sun_depth(p) {
+                let t = self.transmittance(view_rayleigh + sun_rayleigh, view_mie + sun_mie);
+                rayleigh_sum += (dr * step) * t;
+                mie_sum += (dm * step) * t;
+            }
+        }
+
+        let mu = d.dot(self.sun);
+        let rayleigh_phase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+        let g2 = MIE_G * MIE_G;
+        let mie_phase = 3.0 / (8.0 * PI) * ((1.0 - g2) * (1.0 + mu * mu))
+            / ((2.0 + g2) * (1.0 + g2 - 2.0 * MIE_G * mu).powf(1.5));
+
+        self.irradiance * (rayleigh_phase * rayleigh_sum * self.rayleigh + (mie_phase * self.mie) * mie_sum)
+    }
+
+    fn ray_type(&self, r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> RayType {
+        r_in.ray_type()
+    }
+
+    fn transparent(&self, _r_in: &Ray, _rec: &HitRecord) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::Options;
+    use crate::ray::{Ray, RayKind};
+    use crate::render;
+    use crate::vec::{Color, Point3, Vec3};
+
+    // Planet under a black sky, lit by nothing but its air
+    const XML: &str = r#"<RT>
+        <camera look_from="0 20 0" look_at="0 0 0" up="0 0 1" aperture="0"/>
+        <environment><layer type="constant" color="0 0 0"/></environment>
+        <material type="lambertian" color="0.8 0.8 0.8"/>
+        <object type="planet" center="0 0 0" radius="10" atmosphere="1" air_density="4" sun="0 1 0"/>
+    </RT>"#;
+
+    #[test]
+    fn skylight_reaches_the_ground() {
+        let (_, scene) = crate::xml_parser(XML);
+        let args = ["--spp", "1"].map(str::to_string);
+        let settings = Options::parse(args.into_iter()).settings(&scene);
+
+        // Looking down from inside the air, so the view ray itself sees none
+        // of its glow before the ground
+        let r = Ray::new(Point3::new(0.0, 10.5, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for _ in 0..200 {
+            sum += render::trace(&r, &scene, &settings, settings.max_depth, RayKind::Camera);
+        }
+        assert!(sum.y() / 200.0 > 1e-3, "The ground gets no skylight: {:?}", sum / 200.0);
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use super::cli::Options;
+use super::exr::ExrImage;
+use super::hit::{Hit, HitRecord};
+use super::material::{Lambertian, Scatter};
+use super::png;
+use super::ray::{Ray, RayKind};
+use super::render::{self, RenderSettings};
+use super::sampler;
+use super::scene::Scene;
+use super::vec::{Color, Vec3};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BakeMode {
+    // Light leaving a white diffuse surface, i.e. irradiance / pi
+    Lightmap,
+    // Fraction of the cosine-weighted hemisphere left open within a distance
+    Ao
+}
+
+impl BakeMode {
+    fn from_name(name: &str) -> Option<BakeMode> {
+        match name {
+            "lightmap" => Some(BakeMode::Lightmap),
+            "ao" => Some(BakeMode::Ao),
+            _ => None
+        }
+    }
+}
+
+fn bake_sample(mode: BakeMode, rec: HitRecord, scene: &Scene, settings: &RenderSettings, ao_distance: f64) -> Color {
+    match mode {
+        BakeMode::Lightmap => {
+            let r = Ray::new(rec.p + rec.normal, (-1.0) * rec.normal);
+            render::clamp_sample(render::shade(&r, Some(rec), scene, settings, settings.max_depth, RayKind::Camera), settings.clamp)
+        },
+        BakeMode::Ao => {
+            let dir = (rec.normal + Vec3::random_in_unit_sphere().normalized()).normalized();
+            if dir.near_zero() || scene.world.hit(&Ray::new(rec.p, dir), 0.001, ao_distance).is_none() {
+                Color::new(1.0, 1.0, 1.0)
+            } else {
+                Color::new(0.0, 0.0, 0.0)
+            }
+        }
+    }
+}
+
+// Averages of every texel, top row (v = 1) first. Texels are sampled at
+// jittered texture coordinates, each sample with its own random stream.
+fn bake_texels(object: &dyn Hit, mode: BakeMode, scene: &Scene, settings: &RenderSettings, size: usize, ao_distance: f64) -> Vec<Color> {
+    let white: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(1.0, 1.0, 1.0)));
+
+    (0..size * size)
+        .into_par_iter()
+        .map(|n| {
+            let (x, y) = (n % size, n / size);
+            let mut total = Color::new(0.0, 0.0, 0.0);
+            let mut count = 0;
+
+            for s in 0..settings.samples_per_pixel {
+                sampler::start_sample(settings.seed, settings.frame, n as u64, s);
+                let u = (x as f64 + rand::Rng::gen::<f64>(&mut sampler::rng())) / size as f64;
+                let v = 1.0 - (y as f64 + rand::Rng::gen::<f64>(&mut sampler::rng())) / size as f64;
+
+                if let Some(mut rec) = object.surface_at(u, v) {
+                    rec.mat = white.clone();
+                    total += bake_sample(mode, rec, scene, settings, ao_distance);
+                    count += 1;
+                }
+            }
+
+            if count > 0 { total / count as f64 } else { total }
+        })
+        .collect()
+}
+
+// `rt bake scene.xml --object NAME [--mode lightmap|ao] [--size 512]
+// [--ao-distance 1] [-o texture.png] [render options]`
+pub fn bake(args: impl Iterator<Item = String>) {
+    let mut object_name = None;
+    let mut mode = BakeMode::Lightmap;
+    let mut size = 512;
+    let mut ao_distance = 1.0;
+    let mut output = None;
+    let mut render_args = vec!["--quality".to_string(), "medium".to_string()];
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("Missing value for {}.", arg));
+        match arg.as_str() {
+            "--object" => object_name = Some(value()),
+            "--mode" => {
+                let name = value();
+                mode = BakeMode::from_name(&name)
+                    .unwrap_or_else(|| panic!("Unknown bake mode '{}', expected lightmap or ao.", name));
+            },
+            "--size" => size = value().parse::<usize>().expect("Failed to parse value for --size.").max(1),
+            "--ao-distance" => ao_distance = value().parse().expect("Failed to parse value for --ao-distance."),
+            "-o" | "--output" => output = Some(value()),
+            _ => render_args.push(arg)
+        }
+    }
+
+    let opts = Options::parse(render_args.into_iter());
+    let path = opts.scene.clone().expect("Missing scene file to bake.");
+    let object_name = object_name.expect("Missing --object to bake.");
+    let (_, scene) = super::xml_parser(&super::read_xml(&path));
+    let settings = opts.settings(&scene);
+
+    let object = scene.object(&object_name)
+        .unwrap_or_else(|| panic!("No object named '{}' in {}.", object_name, path));
+    eprintln!("Baking {} at {}x{}", object_name, size, size);
+    let texels = bake_texels(object, mode, &scene, &settings, size, ao_distance);
+
+    let output = output.unwrap_or_else(|| {
+        let suffix = if mode == BakeMode::Ao { "ao" } else { "lightmap" };
+        format!("{}.{}.png", object_name, suffix)
+    });
+
+    // EXR keeps the linear values, PNG is gamma 2 encoded like renders
+    if output.ends_with(".exr") {
+        let mut exr = ExrImage::new(size, size);
+        exr.add_channel("R", texels.iter().map(|c| c.x() as f32).collect());
+        exr.add_channel("G", texels.iter().map(|c| c.y() as f32).collect());
+        exr.add_channel("B", texels.iter().map(|c| c.z() as f32).collect());
+        exr.write(&output).unwrap_or_else(|_| panic!("Failed to write {}.", output));
+    } else {
+        let rgb: Vec<u8> = texels.iter().flat_map(|c| c.to_rgb8(1)).collect();
+        png::write(&output, size, size, &rgb, &[]).unwrap_or_else(|_| panic!("Failed to write {}.", output));
+    }
+    eprintln!("Wrote {}", output);
+}
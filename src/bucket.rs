@@ -0,0 +1,226 @@
+use std::fs;
+
+use super::metadata::Metadata;
+use super::vec::Color;
+
+// Rectangle of the image rendered on its own with --bucket, in pixels of the
+// image without overscan, rows counted from the top. Every sample keeps the
+// random numbers it has in a render of the whole image, so buckets rendered
+// anywhere, at any time, fit together.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Bucket {
+    pub x: u64,
+    pub y: u64,
+    pub width: u64,
+    pub height: u64
+}
+
+impl Bucket {
+    // "x,y,width,height", as given to --bucket and written in the metadata
+    pub fn parse(value: &str) -> Option<Bucket> {
+        let parts: Vec<u64> = value.split(',').map(|p| p.trim().parse().ok()).collect::<Option<_>>()?;
+        match parts[..] {
+            [x, y, width, height] if width > 0 && height > 0 => Some(Bucket { x, y, width, height }),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+
+    pub fn contains(&self, x: u64, y: u64) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    // "scene.ppm" -> "scene.bucket_64_0.ppm"
+    pub fn filename(&self, img_name: &str) -> String {
+        match img_name.rsplit_once('.') {
+            Some((stem, ext)) => format!("{}.bucket_{}_{}.{}", stem, self.x, self.y, ext),
+            None => format!("{}.bucket_{}_{}", img_name, self.x, self.y)
+        }
+    }
+
+    // Weight of the bucket's pixel (x, y) where buckets overlap: its samples,
+    // the inverse of its variance, growing from the bucket's inner edges so
+    // that overlapping buckets fade into each other
+    fn weight(&self, x: u64, y: u64, samples: u64, width: u64, height: u64) -> f64 {
+        let mut edge = u64::MAX;
+        if self.x > 0 {
+            edge = edge.min(x - self.x);
+        }
+        if self.y > 0 {
+            edge = edge.min(y - self.y);
+        }
+        if self.x + self.width < width {
+            edge = edge.min(self.x + self.width - 1 - x);
+        }
+        if self.y + self.height < height {
+            edge = edge.min(self.y + self.height - 1 - y);
+        }
+
+        // Buckets spanning the whole image have no inner edge
+        let ramp = if edge == u64::MAX { 1.0 } else { (edge + 1) as f64 };
+        samples as f64 * ramp
+    }
+}
+
+// Entries that may differ between buckets of the same image
+const PER_BUCKET: [&str; 3] = ["Bucket", "Samples", "RenderTime"];
+
+// Bucket image read back with its metadata
+struct Part {
+    filename: String,
+    entries: Vec<(String, String)>,
+    bucket: Bucket,
+    samples: u64,
+    // Linear colors, top row first
+    pixels: Vec<Color>
+}
+
+impl Part {
+    fn entry(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+// PPM written by a --bucket render: "P3", "# Key: value" lines, the size,
+// 255 and the gamma 2 encoded pixels
+fn read_part(filename: &str) -> Part {
+    let text = fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Unable to read bucket {}.", filename));
+
+    let mut entries = Vec::new();
+    let mut values = Vec::new();
+    for line in text.lines() {
+        match line.strip_prefix('#') {
+            Some(comment) => {
+                if let Some((key, value)) = comment.split_once(':') {
+                    entries.push((key.trim().to_string(), value.trim().to_string()));
+                }
+            },
+            None => values.extend(line.split_whitespace())
+        }
+    }
+
+    let number = |k: usize| values.get(k).and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or_else(|| panic!("Failed to parse bucket {}, only PPM renders can be merged.", filename));
+    if values.first() != Some(&"P3") || number(3) != 255 {
+        panic!("Failed to parse bucket {}, only PPM renders can be merged.", filename);
+    }
+    let (width, height) = (number(1), number(2));
+
+    let mut part = Part {
+        filename: filename.to_string(),
+        entries,
+        bucket: Bucket { x: 0, y: 0, width, height },
+        samples: 0,
+        pixels: Vec::with_capacity((width * height) as usize)
+    };
+    part.bucket = part.entry("Bucket").and_then(Bucket::parse)
+        .unwrap_or_else(|| panic!("{} isn't a bucket render.", filename));
+    part.samples = part.entry("Samples").and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| panic!("Missing sample count in bucket {}!", filename));
+    if (part.bucket.width, part.bucket.height) != (width, height) {
+        panic!("The bucket {} is {}x{} pixels instead of the {}x{} it was rendered at.",
+               filename, width, height, part.bucket.width, part.bucket.height);
+    }
+
+    // The center of each 8-bit step, decoded back to linear
+    let decode = |k: usize| ((number(k) as f64 + 0.5) / 256.0).powi(2);
+    for k in 0..(width * height) as usize {
+        part.pixels.push(Color::new(decode(4 + 3 * k), decode(5 + 3 * k), decode(6 + 3 * k)));
+    }
+    part
+}
+
+// Every entry but the per-bucket ones must be those of the first bucket, or
+// the buckets would belong to different renders
+fn validate(parts: &[Part]) {
+    let shared = |part: &Part| -> Vec<(String, String)> {
+        part.entries.iter().filter(|(k, _)| !PER_BUCKET.contains(&k.as_str())).cloned().collect()
+    };
+
+    let first = &parts[0];
+    let expected = shared(first);
+    for part in &parts[1..] {
+        let entries = shared(part);
+        for (key, value) in &expected {
+            match part.entry(key) {
+                Some(other) if other == value => { },
+                Some(other) => panic!("The buckets {} and {} don't match: {} is {} instead of {}.",
+                                      first.filename, part.filename, key, other, value),
+                None => panic!("The buckets {} and {} don't match: {} is missing.", first.filename, part.filename, key)
+            }
+        }
+        if let Some((key, _)) = entries.iter().find(|(k, _)| first.entry(k).is_none()) {
+            panic!("The buckets {} and {} don't match: {} is missing.", part.filename, first.filename, key);
+        }
+    }
+}
+
+// rt merge out.ppm bucket.ppm...
+pub fn merge(args: impl Iterator<Item = String>) {
+    let args: Vec<String> = args.collect();
+    if args.len() < 2 {
+        panic!("Usage: rt merge out.ppm bucket.ppm...");
+    }
+
+    let parts: Vec<Part> = args[1..].iter().map(|name| read_part(name)).collect();
+    validate(&parts);
+
+    let resolution = parts[0].entry("Resolution").unwrap_or_else(|| panic!("Missing resolution in bucket {}!", parts[0].filename));
+    let (width, height) = resolution.split_once('x')
+        .and_then(|(w, h)| Some((w.parse::<u64>().ok()?, h.parse::<u64>().ok()?)))
+        .unwrap_or_else(|| panic!("Failed to parse bucket resolution '{}'.", resolution));
+    for part in &parts {
+        let b = part.bucket;
+        if b.x + b.width > width || b.y + b.height > height {
+            panic!("The bucket {} doesn't fit in the {}x{} image.", part.filename, width, height);
+        }
+    }
+
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            let mut total = 0.0;
+            for part in parts.iter().filter(|p| p.bucket.contains(x, y)) {
+                let b = part.bucket;
+                let w = b.weight(x, y, part.samples, width, height);
+                sum += w * part.pixels[((y - b.y) * b.width + x - b.x) as usize];
+                total += w;
+            }
+            if total == 0.0 {
+                panic!("No bucket covers the pixel ({}, {}).", x, y);
+            }
+            pixels.push((1.0 / total) * sum);
+        }
+    }
+
+    // The merged image records the render's settings, and the sample counts
+    // of its buckets when they differ
+    let mut metadata = Metadata::new();
+    for (key, value) in parts[0].entries.iter().filter(|(k, _)| k != "Software" && !PER_BUCKET.contains(&k.as_str())) {
+        metadata.add(key, value);
+    }
+    let (fewest, most) = parts.iter().fold((u64::MAX, 0), |(lo, hi), p| (lo.min(p.samples), hi.max(p.samples)));
+    if fewest == most {
+        metadata.add("Samples", most);
+    } else {
+        metadata.add("Samples", format!("{}-{}", fewest, most));
+    }
+    metadata.add("Buckets", parts.len());
+
+    let mut out = String::from("P3\n");
+    for (key, value) in metadata.entries() {
+        out.push_str(&format!("# {}: {}\n", key, value));
+    }
+    out.push_str(&format!("{} {}\n255\n", width, height));
+    for p in pixels {
+        out.push_str(&p.format_color(1));
+        out.push('\n');
+    }
+    fs::write(&args[0], out).unwrap_or_else(|_| panic!("Failed to write {}.", args[0]));
+    eprintln!("Merged {} buckets into {}", parts.len(), args[0]);
+}
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use super::camera::{Camera, DEFAULT_FOCUS_DIST, DEFAULT_VFOV};
+use super::cuboid::Cuboid;
+use super::environment::{Environment, Layer, Visibility};
+use super::hit::World;
+use super::light::{AreaLight, Falloff, Light, Units};
+use super::material::{Dielectric, Emissive, Isotropic, Lambertian, Metal, Scatter};
+use super::moving_sphere::MovingSphere;
+use super::plane::Plane;
+use super::quad::Quad;
+use super::scene::Scene;
+use super::sphere::Sphere;
+use super::transform::{Rotate, Translate};
+use super::vec::{Color, Vec3};
+use super::volume::ConstantMedium;
+
+// Scenes baked into the binary by build.rs
+include!(concat!(env!("OUT_DIR"), "/builtin_scenes.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Options;
+    use crate::render;
+    use crate::vec::Point3;
+
+    // sRGB encoding of a linear value, out of 255
+    fn srgb(c: f64) -> f64 {
+        let c = c.clamp(0.0, 1.0);
+        255.0 * if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+    }
+
+    // Each patch of the chart with its published sRGB value, from the
+    // comment before its material
+    fn patches() -> Vec<(Point3, [f64; 3])> {
+        let xml = include_str!("../scenes/color-checker.xml");
+        let doc = roxmltree::Document::parse(xml).unwrap();
+        let mut reference = None;
+        let mut patches = Vec::new();
+        for node in doc.descendants() {
+            if node.is_comment() {
+                if let Some((_, values)) = node.text().unwrap_or("").split_once(", sRGB ") {
+                    let values: Vec<f64> = values.split_whitespace().map(|v| v.parse().unwrap()).collect();
+                    reference = Some([values[0], values[1], values[2]]);
+                }
+            } else if node.has_tag_name("object") {
+                let (x, y, z) = crate::value_parser(node.attribute("corner").unwrap());
+                patches.push((Point3::new(x + 0.5, y + 0.5, z), reference.take().expect("Missing patch reference!")));
+            }
+        }
+        patches
+    }
+
+    #[test]
+    fn color_checker_matches_reference() {
+        let (_, scene) = builtin("color-checker").unwrap();
+        let args = ["--spp", "4", "--scale", "0.1", "--no-denoise"].map(str::to_string);
+        let settings = Options::parse(args.into_iter()).settings(&scene);
+        let pixels = render::render(&scene, &settings);
+        let (width, height) = (settings.image_width as usize, settings.image_height as usize);
+
+        let patches = patches();
+        assert_eq!(patches.len(), 24);
+        for (k, (center, reference)) in patches.into_iter().enumerate() {
+            // Averaged over the pixels around the middle of the patch
+            let (s, t) = scene.camera.project(center).unwrap();
+            let (x, y) = ((s * width as f64) as usize, ((1.0 - t) * height as f64) as usize);
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            for j in y - 1..=y + 1 {
+                for i in x - 1..=x + 1 {
+                    sum += pixels[j * width + i];
+                }
+            }
+            let average = sum / (9 * settings.samples_per_pixel) as f64;
+
+            for c in 0..3 {
+                let value = srgb(average[c]);
+                assert!((value - reference[c]).abs() <= 2.0,
+                        "Patch {} renders {:.1} in channel {}, expected {}.", k + 1, value, c, reference[c]);
+            }
+        }
+    }
+
+    #[test]
+    fn builtins_match_their_files() {
+        let args = ["--spp", "1", "--scale", "0.05", "--no-denoise"].map(str::to_string);
+        for (name, xml) in BUILTIN_FILES {
+            let (baked_name, baked) = builtin(name).unwrap();
+            let (parsed_name, parsed) = crate::xml_parser(xml);
+            assert_eq!(baked_name, parsed_name, "The builtin '{}' writes another file.", name);
+            assert_eq!(baked.film_size, parsed.film_size, "The builtin '{}' has another film size.", name);
+            assert_eq!(baked.pixel_aspect, parsed.pixel_aspect, "The builtin '{}' has other pixels.", name);
+
+            let settings = Options::parse(args.clone().into_iter()).settings(&parsed);
+            let (baked, parsed) = (render::render(&baked, &settings), render::render(&parsed, &settings));
+            assert!(baked.iter().zip(&parsed).all(|(a, b)| a == b), "The builtin '{}' renders differently from its file.", name);
+        }
+    }
+}
@@ -0,0 +1,725 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rayon::prelude::*;
+
+use super::aabb::Aabb;
+use super::accel::{self, Accelerator};
+use super::accel_cache::{Cached, Reader, Writer};
+use super::hit::{Hit, HitRecord};
+use super::packet::{RayPacket, MAX_LANES};
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+// Objects per leaf of median splits, and below which SAH builds always
+// make a leaf
+const LEAF_SIZE: usize = 2;
+
+// Most objects SAH builds put in a leaf when that is cheaper than splitting
+const MAX_LEAF_SIZE: usize = 8;
+
+// Slabs the centroids are sorted into along each axis by SAH builds
+const BINS: usize = 16;
+
+// Cost of visiting a node relative to testing an object, for the SAH
+const TRAVERSAL_COST: f64 = 1.0;
+
+// Objects of a node above which its box is found and its children are
+// built in parallel; smaller subtrees are left to a single thread
+const PARALLEL_SIZE: usize = 4096;
+
+// Levels forked beyond one per doubling of the threads, each subtree built
+// apart being copied into its parent's
+const EXTRA_FORKS: u32 = 2;
+
+// Deepest traversal stack. A traversal holds at most one entry per level
+// plus one, so trees grown deeper by insertions are rebuilt.
+const STACK_SIZE: usize = 64;
+
+const NONE: usize = usize::MAX;
+
+// How hierarchies are split while they are built
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BvhBuild {
+    // Where the surface area heuristic expects the fewest tests per ray:
+    // the chance of a ray that hits a node hitting each child is taken to
+    // be the ratio of their surface areas
+    Sah,
+    // At the median centroid along the axis the centroids spread the most
+    Median
+}
+
+static MEDIAN: AtomicBool = AtomicBool::new(false);
+
+impl BvhBuild {
+    pub fn from_name(name: &str) -> Option<BvhBuild> {
+        match name {
+            "sah" => Some(BvhBuild::Sah),
+            "median" => Some(BvhBuild::Median),
+            _ => None
+        }
+    }
+
+    // Builds every hierarchy made from now on, like those of meshes parsed
+    // later, this way
+    pub fn set(self) {
+        MEDIAN.store(self == BvhBuild::Median, Ordering::Relaxed);
+    }
+
+    fn current() -> BvhBuild {
+        if MEDIAN.load(Ordering::Relaxed) { BvhBuild::Median } else { BvhBuild::Sah }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    // The objects order[start..start + count]
+    Leaf { start: usize, count: usize },
+    // Children split along axis
+    Interior { left: usize, right: usize, axis: usize }
+}
+
+// Node of a bounding volume hierarchy; nodes whose objects were all
+// removed have no box
+#[derive(Clone, Copy)]
+pub struct BvhNode {
+    bbox: Option<Aabb>,
+    parent: usize,
+    kind: Kind
+}
+
+// Bounding volume hierarchy over a list of objects, answering ray queries
+// for their index in the list. Hits keep that index as object_id, the same
+// as a linear scan. Objects can be inserted, removed and refitted after
+// they moved, which only updates the nodes above them.
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    // Object indices, grouped by leaf
+    order: Vec<usize>,
+    // Leaf holding each object, by index, NONE for those outside the tree
+    leaf: Vec<usize>,
+    // Objects without a bounding box, tested against every ray
+    unbounded: Vec<usize>,
+    depth: usize
+}
+
+impl Bvh {
+    // Hierarchy over only the objects at the given indices
+    pub fn with_indices<T: Hit>(objects: &[T], indices: impl Iterator<Item = usize>) -> Bvh {
+        Bvh::from_boxes(objects.len(), accel::boxes(objects, indices))
+    }
+
+    // Hierarchy over a list of `objects` objects, given the boxes of those
+    // it holds
+    pub fn from_boxes(objects: usize, boxes: Vec<(usize, Option<Aabb>)>) -> Bvh {
+        Bvh::split_by(objects, boxes, BvhBuild::current())
+    }
+
+    // Same as from_boxes, split the given way
+    fn split_by(objects: usize, boxes: Vec<(usize, Option<Aabb>)>, split: BvhBuild) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            order: Vec::new(),
+            leaf: vec![NONE; objects],
+            unbounded: Vec::new(),
+            depth: 0
+        };
+
+        let mut items = Vec::with_capacity(boxes.len());
+        for (index, bbox) in boxes {
+            match bbox {
+                Some(bbox) => items.push((index, bbox)),
+                None => bvh.unbounded.push(index)
+            }
+        }
+
+        if !items.is_empty() {
+            bvh.build(&mut items, NONE, 0, split);
+        }
+        bvh.find_leaves();
+        bvh
+    }
+
+    fn find_leaves(&mut self) {
+        for (node, &BvhNode { kind, .. }) in self.nodes.iter().enumerate() {
+            if let Kind::Leaf { start, count } = kind {
+                for &object in &self.order[start..start + count] {
+                    self.leaf[object] = node;
+                }
+            }
+        }
+    }
+
+    // Nodes and order of the tree over items alone, for a thread of its own
+    fn subtree(items: &mut [(usize, Aabb)], depth: usize, kind: BvhBuild) -> Bvh {
+        let mut bvh = Bvh { nodes: Vec::new(), order: Vec::new(), leaf: Vec::new(), unbounded: Vec::new(), depth: 0 };
+        bvh.build(items, NONE, depth, kind);
+        bvh
+    }
+
+    // Puts a subtree's nodes after those of the tree, under parent, and
+    // returns the index of its root
+    fn append(&mut self, subtree: Bvh, parent: usize) -> usize {
+        let (offset, start_offset) = (self.nodes.len(), self.order.len());
+        self.nodes.extend(subtree.nodes.into_iter().map(|node| BvhNode {
+            bbox: node.bbox,
+            parent: if node.parent == NONE { parent } else { node.parent + offset },
+            kind: match node.kind {
+                Kind::Leaf { start, count } => Kind::Leaf { start: start + start_offset, count },
+                Kind::Interior { left, right, axis } => Kind::Interior { left: left + offset, right: right + offset, axis }
+            }
+        }));
+        self.order.extend(subtree.order);
+        self.depth = self.depth.max(subtree.depth);
+        offset
+    }
+
+    // Splits the items the given way and returns the index of the subtree's
+    // root. SAH splits fall back to the median when every centroid is in the
+    // same place, or once the tree grows deep enough to threaten the
+    // traversal stack.
+    fn build(&mut self, items: &mut [(usize, Aabb)], parent: usize, depth: usize, kind: BvhBuild) -> usize {
+        let index = self.nodes.len();
+        self.depth = self.depth.max(depth);
+
+        let parallel = items.len() > PARALLEL_SIZE && depth < fork_depth();
+        let first = items[0].1.centroid();
+        let (bbox, centroids) = if parallel {
+            items.par_iter()
+                .map(|(_, item)| (*item, Aabb::new(item.centroid(), item.centroid())))
+                .reduce(|| (items[0].1, Aabb::new(first, first)), |a, b| (a.0.surrounding(b.0), a.1.surrounding(b.1)))
+        } else {
+            items.iter().fold((items[0].1, Aabb::new(first, first)), |(b, c), (_, item)| {
+                (b.surrounding(*item), c.surrounding(Aabb::new(item.centroid(), item.centroid())))
+            })
+        };
+
+        let split = if items.len() <= LEAF_SIZE {
+            None
+        } else if kind == BvhBuild::Sah && depth + 2 < STACK_SIZE / 2 {
+            match sah_split(items, bbox, centroids) {
+                Some(split) => Some(split),
+                None if items.len() <= MAX_LEAF_SIZE => None,
+                None => Some(median_split(items, centroids))
+            }
+        } else {
+            Some(median_split(items, centroids))
+        };
+
+        let Some((axis, mid)) = split else {
+            self.nodes.push(BvhNode { bbox: Some(bbox), parent, kind: Kind::Leaf { start: self.order.len(), count: items.len() } });
+            self.order.extend(items.iter().map(|(object, _)| *object));
+            return index;
+        };
+
+        self.nodes.push(BvhNode { bbox: Some(bbox), parent, kind: Kind::Leaf { start: 0, count: 0 } });
+        let (left, right) = items.split_at_mut(mid);
+        let (left, right) = if parallel {
+            // Laid out as a single thread would, so the tree is the same
+            let (left, right) = rayon::join(|| Bvh::subtree(left, depth + 1, kind), || Bvh::subtree(right, depth + 1, kind));
+            (self.append(left, index), self.append(right, index))
+        } else {
+            (self.build(left, index, depth + 1, kind), self.build(right, index, depth + 1, kind))
+        };
+        self.nodes[index].kind = Kind::Interior { left, right, axis };
+
+        index
+    }
+
+    // Every object in the hierarchy
+    fn indices(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = self.unbounded.clone();
+        for node in &self.nodes {
+            if let Kind::Leaf { start, count } = node.kind {
+                indices.extend_from_slice(&self.order[start..start + count]);
+            }
+        }
+
+        indices
+    }
+
+    fn refit_from<T: Hit>(&mut self, objects: &[T], mut node: usize) {
+        while node != NONE {
+            self.nodes[node].bbox = match self.nodes[node].kind {
+                Kind::Leaf { start, count } => self.order[start..start + count].iter()
+                    .filter_map(|&i| objects[i].bounding_box())
+                    .reduce(|a, b| a.surrounding(b)),
+                Kind::Interior { left, right, .. } => match (self.nodes[left].bbox, self.nodes[right].bbox) {
+                    (Some(a), Some(b)) => Some(a.surrounding(b)),
+                    (a, b) => a.or(b)
+                }
+            };
+            node = self.nodes[node].parent;
+        }
+    }
+
+    // Children of an interior node, nearest first along the ray direction
+    fn children(&self, index: usize, dir: Vec3) -> Option<(usize, usize)> {
+        match self.nodes[index].kind {
+            Kind::Interior { left, right, axis } if dir[axis] < 0.0 => Some((right, left)),
+            Kind::Interior { left, right, .. } => Some((left, right)),
+            Kind::Leaf { .. } => None
+        }
+    }
+
+    fn leaf_objects(&self, index: usize) -> &[usize] {
+        match self.nodes[index].kind {
+            Kind::Leaf { start, count } => &self.order[start..start + count],
+            Kind::Interior { .. } => &[]
+        }
+    }
+
+    // Visits the nodes any lane of the packet can hit, near child first along
+    // the first lane. Lanes missing a node miss every node below it, so each
+    // node only tests the lanes from the first one that hit its parent. That
+    // one usually hits it too, and is tested alone; when it misses, the rest
+    // are tested N lanes at a time.
+    fn traverse_packet<const N: usize>(&self, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>], test: impl Fn(usize, &mut [f64; MAX_LANES], &mut [Option<HitRecord>])) {
+        let inv_dirs = p.inv_dirs();
+        let origin = |k: usize| Point3::new(p.ox[k], p.oy[k], p.oz[k]);
+        let inv_dir = |k: usize| Vec3::new(inv_dirs[0][k], inv_dirs[1][k], inv_dirs[2][k]);
+        let order_dir = Vec3::new(p.dx[0], p.dy[0], p.dz[0]);
+
+        // Nodes to visit, with the first lane that hit their parent
+        let mut stack = [(0, 0); STACK_SIZE];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let (index, first) = stack[top];
+
+            let Some(bbox) = self.nodes[index].bbox else {
+                continue;
+            };
+            let first = if bbox.hit(origin(first), inv_dir(first), t_min, t_max[first]) {
+                first
+            } else {
+                match p.first_hit_box::<N>(&inv_dirs, &bbox, t_min, t_max, first + 1) {
+                    Some(lane) => lane,
+                    None => continue
+                }
+            };
+
+            match self.children(index, order_dir) {
+                Some((near, far)) => {
+                    stack[top] = (far, first);
+                    stack[top + 1] = (near, first);
+                    top += 2;
+                },
+                None => {
+                    for &object in self.leaf_objects(index) {
+                        test(object, t_max, recs);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T: Hit> Accelerator<T> for Bvh {
+    // Adds objects[index], which must not be in the hierarchy yet, under the
+    // leaf whose box grows the least
+    fn insert(&mut self, objects: &[T], index: usize) {
+        if self.leaf.len() < objects.len() {
+            self.leaf.resize(objects.len(), NONE);
+        }
+
+        let Some(bbox) = objects[index].bounding_box() else {
+            self.unbounded.push(index);
+            return;
+        };
+
+        let leaf = self.nodes.len();
+        if self.nodes.is_empty() {
+            self.nodes.push(BvhNode { bbox: Some(bbox), parent: NONE, kind: Kind::Leaf { start: self.order.len(), count: 1 } });
+            self.order.push(index);
+            self.leaf[index] = leaf;
+            return;
+        }
+
+        let mut sibling = 0;
+        let mut depth = 1;
+        while let Kind::Interior { left, right, .. } = self.nodes[sibling].kind {
+            sibling = if growth(self.nodes[left].bbox, bbox) <= growth(self.nodes[right].bbox, bbox) { left } else { right };
+            depth += 1;
+        }
+
+        // The sibling leaf moves down under a new interior node taking its
+        // place, next to the leaf of the new object
+        let moved = leaf + 1;
+        let mut old = self.nodes[sibling];
+        old.parent = sibling;
+        if let Kind::Leaf { start, count } = old.kind {
+            for &object in &self.order[start..start + count] {
+                self.leaf[object] = moved;
+            }
+        }
+
+        let axis = old.bbox.map_or(0, |b| longest_axis(b.surrounding(bbox)));
+        self.nodes.push(BvhNode { bbox: Some(bbox), parent: sibling, kind: Kind::Leaf { start: self.order.len(), count: 1 } });
+        self.nodes.push(old);
+        self.nodes[sibling].kind = Kind::Interior { left: moved, right: leaf, axis };
+        self.order.push(index);
+        self.leaf[index] = leaf;
+        self.refit_from(objects, sibling);
+
+        self.depth = self.depth.max(depth);
+        if self.depth + 2 > STACK_SIZE {
+            *self = Bvh::with_indices(objects, self.indices().into_iter());
+        }
+    }
+
+    // Takes objects[index] out of the hierarchy
+    fn remove(&mut self, objects: &[T], index: usize) {
+        if let Some(k) = self.unbounded.iter().position(|&i| i == index) {
+            self.unbounded.swap_remove(k);
+            return;
+        }
+
+        let Some(&leaf) = self.leaf.get(index).filter(|&&leaf| leaf != NONE) else {
+            return;
+        };
+        if let Kind::Leaf { start, count } = self.nodes[leaf].kind {
+            let k = start + self.order[start..start + count].iter().position(|&i| i == index).unwrap();
+            self.order.swap(k, start + count - 1);
+            self.nodes[leaf].kind = Kind::Leaf { start, count: count - 1 };
+        }
+        self.leaf[index] = NONE;
+        self.refit_from(objects, leaf);
+    }
+
+    // Updates the boxes above objects[index] after it changed shape or
+    // moved
+    fn refit(&mut self, objects: &[T], index: usize) {
+        if let Some(&leaf) = self.leaf.get(index).filter(|&&leaf| leaf != NONE) {
+            self.refit_from(objects, leaf);
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.nodes.first().and_then(|root| root.bbox)
+    }
+
+    fn hit(&self, objects: &[T], r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = t_max;
+        let mut result = None;
+        let mut test = |index: usize, closest: &mut f64| {
+            if let Some(mut rec) = objects[index].hit(r, t_min, *closest) {
+                *closest = rec.t;
+                rec.object_id = index;
+                result = Some(rec);
+            }
+        };
+
+        for &index in &self.unbounded {
+            test(index, &mut closest);
+        }
+        if self.nodes.is_empty() {
+            return result;
+        }
+
+        let d = r.direction();
+        let origin = r.origin();
+        let inv_dir = Vec3::new(1.0 / d.x(), 1.0 / d.y(), 1.0 / d.z());
+
+        let mut stack = [0; STACK_SIZE];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let index = stack[top];
+            match self.nodes[index].bbox {
+                Some(bbox) if bbox.hit(origin, inv_dir, t_min, closest) => { },
+                _ => continue
+            }
+
+            match self.children(index, d) {
+                // The far child goes first on the stack so the near one is visited first
+                Some((near, far)) => {
+                    stack[top] = far;
+                    stack[top + 1] = near;
+                    top += 2;
+                },
+                None => {
+                    for &object in self.leaf_objects(index) {
+                        test(object, &mut closest);
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    fn hit_packet(&self, objects: &[T], p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+        let test = |index: usize, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]| {
+            let before = *t_max;
+            objects[index].hit_packet(p, t_min, t_max, recs);
+
+            for k in 0..p.lanes {
+                if t_max[k] != before[k] {
+                    if let Some(rec) = recs[k].as_mut() {
+                        rec.object_id = index;
+                    }
+                }
+            }
+        };
+
+        for &index in &self.unbounded {
+            test(index, t_max, recs);
+        }
+        if self.nodes.is_empty() || p.lanes == 0 {
+            return;
+        }
+
+        // Boxes are tested 4 lanes at a time for packets of up to 4 rays, so
+        // narrow packets don't pay for the lanes they leave unused
+        if p.lanes <= 4 {
+            self.traverse_packet::<4>(p, t_min, t_max, recs, test);
+        } else {
+            self.traverse_packet::<MAX_LANES>(p, t_min, t_max, recs, test);
+        }
+    }
+}
+
+impl Cached for Bvh {
+    fn tag() -> u64 {
+        match BvhBuild::current() {
+            BvhBuild::Sah => 1,
+            BvhBuild::Median => 2
+        }
+    }
+
+    fn encode(&self, out: &mut Writer) {
+        out.usize(self.nodes.len());
+        for node in &self.nodes {
+            out.aabb(node.bbox);
+            out.usize(node.parent);
+            match node.kind {
+                Kind::Leaf { start, count } => {
+                    out.usize(NONE);
+                    out.usize(start);
+                    out.usize(count);
+                },
+                Kind::Interior { left, right, axis } => {
+                    out.usize(axis);
+                    out.usize(left);
+                    out.usize(right);
+                }
+            }
+        }
+        out.indices(&self.order);
+        out.indices(&self.unbounded);
+    }
+
+    // Only trees as built are read back: children come after their parent,
+    // which they name, and no deeper than the traversal stack allows
+    fn decode(input: &mut Reader, objects: usize) -> Option<Bvh> {
+        let count = input.usize()?;
+        if count > input.remaining() {
+            return None;
+        }
+        let mut nodes = Vec::new();
+        let mut depths = vec![NONE; count];
+        let mut depth = 0;
+        for index in 0..count {
+            let bbox = input.aabb()?;
+            let parent = input.usize()?;
+            let (tag, a, b) = (input.usize()?, input.usize()?, input.usize()?);
+            let kind = match tag {
+                NONE => Kind::Leaf { start: a, count: b },
+                axis if axis < 3 && a > index && b > index && a != b && a < count && b < count => Kind::Interior { left: a, right: b, axis },
+                _ => return None
+            };
+
+            // The root's depth is 0, the others' set by their parent
+            let node_depth = if index == 0 { 0 } else { depths[index] };
+            if (index == 0) != (parent == NONE) || node_depth == NONE {
+                return None;
+            }
+            if let Kind::Interior { left, right, .. } = kind {
+                for child in [left, right] {
+                    if depths[child] != NONE {
+                        return None;
+                    }
+                    depths[child] = node_depth + 1;
+                }
+            }
+            depth = depth.max(node_depth);
+            nodes.push(BvhNode { bbox, parent, kind });
+        }
+        if depth + 2 > STACK_SIZE {
+            return None;
+        }
+        let parents_match = nodes.iter().enumerate().all(|(index, node)| match node.kind {
+            Kind::Leaf { .. } => true,
+            Kind::Interior { left, right, .. } => nodes[left].parent == index && nodes[right].parent == index
+        });
+        if !parents_match {
+            return None;
+        }
+
+        let order = input.indices(objects)?;
+        let leaves_fit = nodes.iter().all(|node| match node.kind {
+            Kind::Leaf { start, count } => start.checked_add(count).is_some_and(|end| end <= order.len()),
+            Kind::Interior { .. } => true
+        });
+        if !leaves_fit {
+            return None;
+        }
+
+        let mut bvh = Bvh { nodes, order, leaf: vec![NONE; objects], unbounded: input.indices(objects)?, depth };
+        bvh.find_leaves();
+        Some(bvh)
+    }
+}
+
+// Puts the half below the median centroid along the axis the centroids
+// spread the most first, returning the axis and the size of that half
+fn median_split(items: &mut [(usize, Aabb)], centroids: Aabb) -> (usize, usize) {
+    let axis = longest_axis(centroids);
+    let mid = items.len() / 2;
+    items.select_nth_unstable_by(mid, |a, b| a.1.centroid()[axis].total_cmp(&b.1.centroid()[axis]));
+    (axis, mid)
+}
+
+// Binned SAH: the centroids are sorted into BINS slabs along each axis, and
+// of the splits between slabs the one with the least surface area times
+// object count summed over both sides is made, putting the items of the
+// lower side first. Returns the axis and the size of that side, or None
+// if testing every object against the rays hitting the node is cheaper.
+fn sah_split(items: &mut [(usize, Aabb)], bbox: Aabb, centroids: Aabb) -> Option<(usize, usize)> {
+    let extent = centroids.extent();
+    let bin = |item: &Aabb, axis: usize| {
+        let offset = (item.centroid()[axis] - centroids.min[axis]) / extent[axis];
+        ((offset * BINS as f64) as usize).min(BINS - 1)
+    };
+
+    // Axis, last slab of the lower side and cost of the best split
+    let mut best: Option<(usize, usize, f64)> = None;
+    for axis in (0..3).filter(|&axis| extent[axis] > 0.0) {
+        let mut counts = [0usize; BINS];
+        let mut boxes: [Option<Aabb>; BINS] = [None; BINS];
+        for (_, item) in items.iter() {
+            let k = bin(item, axis);
+            counts[k] += 1;
+            boxes[k] = Some(boxes[k].map_or(*item, |b| b.surrounding(*item)));
+        }
+
+        // Area times count of the slabs above each split, swept from the top
+        let mut above = [0.0; BINS];
+        let (mut count, mut bounds) = (0, None::<Aabb>);
+        for k in (1..BINS).rev() {
+            count += counts[k];
+            bounds = boxes[k].map_or(bounds, |b| Some(bounds.map_or(b, |a| a.surrounding(b))));
+            above[k - 1] = bounds.map_or(0.0, |b| surface_area(b) * count as f64);
+        }
+
+        let (mut count, mut bounds) = (0, None::<Aabb>);
+        for k in 0..BINS - 1 {
+            count += counts[k];
+            bounds = boxes[k].map_or(bounds, |b| Some(bounds.map_or(b, |a| a.surrounding(b))));
+            if count == 0 || count == items.len() {
+                continue;
+            }
+
+            let cost = bounds.map_or(0.0, |b| surface_area(b) * count as f64) + above[k];
+            if best.is_none_or(|(_, _, c)| cost < c) {
+                best = Some((axis, k, cost));
+            }
+        }
+    }
+
+    let (axis, last, cost) = best?;
+    let area = surface_area(bbox);
+    if area > 0.0 && TRAVERSAL_COST + cost / area >= items.len() as f64 {
+        return None;
+    }
+
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if bin(&items[i].1, axis) <= last {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    Some((axis, mid))
+}
+
+// Levels of the tree whose children are built in parallel, none on a
+// single thread
+fn fork_depth() -> usize {
+    match rayon::current_num_threads() {
+        1 => 0,
+        threads => (threads.next_power_of_two().trailing_zeros() + EXTRA_FORKS) as usize
+    }
+}
+
+fn longest_axis(b: Aabb) -> usize {
+    let extent = b.extent();
+    if extent.x() >= extent.y() && extent.x() >= extent.z() {
+        0
+    } else if extent.y() >= extent.z() {
+        1
+    } else {
+        2
+    }
+}
+
+fn surface_area(b: Aabb) -> f64 {
+    let e = b.extent();
+    2.0 * (e.x() * e.y() + e.y() * e.z() + e.z() * e.x())
+}
+
+// Surface area a node's box gains by also enclosing bbox; empty nodes take
+// it for free
+fn growth(node: Option<Aabb>, bbox: Aabb) -> f64 {
+    match node {
+        Some(node) => surface_area(node.surrounding(bbox)) - surface_area(node),
+        None => 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::accel::tests::{assert_matches_scan, rays, rng, spheres};
+
+    // Above PARALLEL_SIZE objects, on 4 threads, the top of the tree is built
+    // in parallel
+    #[test]
+    fn splits_find_the_hits_of_a_scan() {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        for (seed, count) in [(1, 300), (2, PARALLEL_SIZE + 904)] {
+            let mut rng = rng(seed);
+            let objects = spheres(&mut rng, count);
+            let rays = rays(&mut rng, count, 400);
+            for kind in [BvhBuild::Sah, BvhBuild::Median] {
+                let bvh = pool.install(|| Bvh::split_by(count, accel::boxes(&objects, 0..count), kind));
+                assert_matches_scan(&bvh, &objects, &vec![true; count], &rays);
+            }
+        }
+    }
+
+    #[test]
+    fn edits_find_the_hits_of_a_scan() {
+        let mut rng = rng(3);
+        let objects = spheres(&mut rng, 600);
+        let rays = rays(&mut rng, 600, 400);
+
+        // Half the objects built, the others inserted, then a third of all
+        // taken out again
+        let mut bvh = Bvh::split_by(objects.len(), accel::boxes(&objects, 0..300), BvhBuild::Sah);
+        for index in 300..objects.len() {
+            bvh.insert(&objects, index);
+        }
+        let mut held = vec![true; objects.len()];
+        assert_matches_scan(&bvh, &objects, &held, &rays);
+
+        for _ in 0..200 {
+            let index = rng.gen_range(0..objects.len());
+            bvh.remove(&objects, index);
+            held[index] = false;
+        }
+        assert_matches_scan(&bvh, &objects, &held, &rays);
+    }
+}
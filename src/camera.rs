@@ -1,6 +1,15 @@
 use super::vec::{Vec3, Point3};
 use super::ray::Ray;
+use super::sampler;
+use super::scene::DEFAULT_FILM_SIZE;
 
+// Camera settings the scene format doesn't expose
+pub const DEFAULT_VFOV: f64 = 20.0;
+// Of the default film
+pub const DEFAULT_ASPECT_RATIO: f64 = DEFAULT_FILM_SIZE.0 as f64 / DEFAULT_FILM_SIZE.1 as f64;
+pub const DEFAULT_FOCUS_DIST: f64 = 10.0;
+
+#[derive(Clone)]
 pub struct Camera {
     origin: Point3,
     lower_left_corner: Point3,
@@ -8,7 +17,11 @@ pub struct Camera {
     vertical: Vec3,
     cu: Vec3,
     cv: Vec3,
-    lens_radius: f64
+    lens_radius: f64,
+    focus_dist: f64,
+    // Times the shutter opens and closes at; rays are spread evenly between
+    shutter_open: f64,
+    shutter_close: f64
 }
 
 impl Camera {
@@ -38,17 +51,107 @@ impl Camera {
             horizontal: h,
             vertical: v,
             lower_left_corner: llc,
-            cu: cu,
-            cv: cv,
-            lens_radius: aperture / 2.0
+            cu,
+            cv,
+            lens_radius: aperture / 2.0,
+            focus_dist,
+            shutter_open: 0.0,
+            shutter_close: 1.0
+        }
+    }
+
+    pub fn with_shutter(self, open: f64, close: f64) -> Camera {
+        Camera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
+
+    pub fn shutter(&self) -> (f64, f64) {
+        (self.shutter_open, self.shutter_close)
+    }
+
+    // The same camera with a pinhole lens, which has no depth of field
+    pub fn pinhole(&self) -> Camera {
+        Camera {
+            lens_radius: 0.0,
+            ..self.clone()
         }
     }
 
+    // The same camera focused at another distance, its field of view kept
+    pub fn refocused(&self, focus_dist: f64) -> Camera {
+        let scale = focus_dist / self.focus_dist;
+        let horizontal = scale * self.horizontal;
+        let vertical = scale * self.vertical;
+        let center = self.origin + scale * (self.lower_left_corner + 0.5 * self.horizontal + 0.5 * self.vertical - self.origin);
+
+        Camera {
+            lower_left_corner: center - 0.5 * horizontal - 0.5 * vertical,
+            horizontal,
+            vertical,
+            focus_dist,
+            ..self.clone()
+        }
+    }
+
+    pub fn aperture(&self) -> f64 {
+        2.0 * self.lens_radius
+    }
+
+    pub fn focus_dist(&self) -> f64 {
+        self.focus_dist
+    }
+
+    // Distance of p in front of the camera along its viewing direction
+    pub fn view_depth(&self, p: Point3) -> f64 {
+        (p - self.origin).dot(self.cv.cross(self.cu))
+    }
+
+    // Width the film spans at unit distance
+    pub fn film_width(&self) -> f64 {
+        self.horizontal.length() / self.focus_dist
+    }
+
+    // Film coordinates (s, t) of the ray, found where it crosses the plane of
+    // focus; every ray generated for (s, t) crosses it at the same point
+    pub fn film_coords(&self, r: &Ray) -> Option<(f64, f64)> {
+        let n = self.horizontal.cross(self.vertical);
+        let denom = r.direction().dot(n);
+        if denom.abs() < 1e-12 {
+            return None;
+        }
+
+        let d = (self.lower_left_corner - r.origin()).dot(n) / denom;
+        let p = r.at(d) - self.lower_left_corner;
+        Some((p.dot(self.horizontal) / self.horizontal.dot(self.horizontal),
+              p.dot(self.vertical) / self.vertical.dot(self.vertical)))
+    }
+
+    // Film coordinates (s, t) at which the camera sees the point p, or None
+    // if it is behind the camera
+    pub fn project(&self, p: Point3) -> Option<(f64, f64)> {
+        let n = self.horizontal.cross(self.vertical);
+        if (p - self.origin).dot(n) >= 0.0 {
+            return None;
+        }
+
+        self.film_coords(&Ray::new(self.origin, p - self.origin))
+    }
+
+    pub fn origin(&self) -> Point3 {
+        self.origin
+    }
+
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
         let rd = self.lens_radius * Vec3::random_in_unit_disk();
         let offset = self.cu * rd.x() + self.cv * rd.y();
 
-        Ray::new(self.origin + offset,
-                 self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset)
+        let time = self.shutter_open + sampler::shutter_time() * (self.shutter_close - self.shutter_open);
+
+        Ray::with_time(self.origin + offset,
+                       self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+                       time)
     }
 }
\ No newline at end of file
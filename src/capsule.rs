@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Points within radius of the segment from a to b: a cylinder between two
+// half spheres
+pub struct Capsule {
+    a: Point3,
+    b: Point3,
+    radius: f64,
+    mat: Arc<dyn Scatter>
+}
+
+impl Capsule {
+    pub fn new(a: Point3, b: Point3, radius: f64, m: Arc<dyn Scatter>) -> Capsule {
+        Capsule { a, b, radius, mat: m }
+    }
+
+    // Nearest point of the segment to p
+    fn closest(&self, p: Point3) -> Point3 {
+        let ba = self.b - self.a;
+        let length_squared = ba.dot(ba);
+        if length_squared == 0.0 {
+            return self.a;
+        }
+        self.a + ((p - self.a).dot(ba) / length_squared).clamp(0.0, 1.0) * ba
+    }
+}
+
+impl Hit for Capsule {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let ba = self.b - self.a;
+        let oa = r.origin() - self.a;
+        let r2 = self.radius * self.radius;
+
+        let mut closest: Option<f64> = None;
+        let mut consider = |t: f64| {
+            if t >= t_min && t <= t_max && closest.is_none_or(|c| t < c) {
+                closest = Some(t);
+            }
+        };
+
+        // Side: the cylinder around the segment, scaled by |ba|^2 so that
+        // the axis needn't be normalized, between the ends
+        let baba = ba.dot(ba);
+        let bard = ba.dot(r.direction());
+        let baoa = ba.dot(oa);
+        let a = baba * r.direction().dot(r.direction()) - bard * bard;
+        let half_b = baba * r.direction().dot(oa) - baoa * bard;
+        let c = baba * oa.dot(oa) - baoa * baoa - r2 * baba;
+        let discriminant = half_b * half_b - a * c;
+
+        if a > 1e-12 && discriminant >= 0.0 {
+            let sqrtd = discriminant.sqrt();
+            for t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                let along = baoa + t * bard;
+                if along > 0.0 && along < baba {
+                    consider(t);
+                }
+            }
+        }
+
+        // Ends: each sphere only beyond its end of the segment, where the
+        // side doesn't reach
+        for (center, outside) in [(self.a, -1.0), (self.b, 1.0)] {
+            let oc = r.origin() - center;
+            let a = r.direction().dot(r.direction());
+            let half_b = oc.dot(r.direction());
+            let c = oc.dot(oc) - r2;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                continue;
+            }
+
+            let sqrtd = discriminant.sqrt();
+            for t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                if outside * (oc + t * r.direction()).dot(ba) >= 0.0 {
+                    consider(t);
+                }
+            }
+        }
+
+        let t = closest?;
+        let outward_normal = (r.at(t) - self.closest(r.at(t))) / self.radius;
+        Some(HitRecord::new(r, t, outward_normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = Vec3::new(self.radius, self.radius, self.radius);
+        let ends = Aabb::new(self.a - extent, self.a + extent).surrounding(Aabb::new(self.b - extent, self.b + extent));
+        Some(ends.padded(1e-4))
+    }
+}
@@ -0,0 +1,381 @@
+use super::accel::AccelKind;
+use super::aov::{Aov, DepthRange, StereoRig};
+use super::bucket::Bucket;
+use super::bvh::BvhBuild;
+use super::focus::FocusStack;
+use super::render::{Integrator, RenderSettings};
+use super::scene::Scene;
+use super::tiles::TileOrder;
+
+#[derive(Clone, Copy)]
+pub enum Quality {
+    Draft,
+    Medium,
+    Final
+}
+
+// Values bundled by a --quality preset; each can still be overridden by its
+// own flag
+struct Preset {
+    spp: u64,
+    depth: u64,
+    clamp: f64,
+    denoise: bool,
+    scale: f64
+}
+
+const QUALITIES: [(&str, Quality); 3] = [("draft", Quality::Draft), ("medium", Quality::Medium), ("final", Quality::Final)];
+
+impl Quality {
+    fn from_name(name: &str) -> Option<Quality> {
+        QUALITIES.iter().find(|(n, _)| *n == name).map(|&(_, quality)| quality)
+    }
+
+    fn preset(self) -> Preset {
+        match self {
+            Quality::Draft => Preset { spp: 16, depth: 8, clamp: 10.0, denoise: true, scale: 0.5 },
+            Quality::Medium => Preset { spp: 100, depth: 20, clamp: 50.0, denoise: true, scale: 1.0 },
+            Quality::Final => Preset { spp: 500, depth: 50, clamp: 0.0, denoise: false, scale: 1.0 }
+        }
+    }
+}
+
+pub struct Options {
+    pub scene: Option<String>,
+    pub builtin: Option<String>,
+    pub integrator: Option<Integrator>,
+    pub packet_size: usize,
+    pub tile_order: TileOrder,
+    // Structure built over the objects, the scene's if None
+    pub accel: Option<AccelKind>,
+    // Keep the structures built for the scene in a file next to it
+    pub accel_cache: bool,
+    // Load the native plugins named by <plugin> elements of the scene
+    pub allow_plugins: bool,
+    // How the scene's hierarchies are split
+    pub bvh: BvhBuild,
+    pub quality: Quality,
+    pub spp: Option<u64>,
+    pub depth: Option<u64>,
+    pub clamp: Option<f64>,
+    pub denoise: Option<bool>,
+    pub scale: Option<f64>,
+    // Extra pixels rendered around the image, see RenderSettings
+    pub overscan: u64,
+    pub bucket: Option<Bucket>,
+    pub aovs: Vec<Aov>,
+    // Trace the AOV passes from a pinhole camera
+    pub aov_pinhole: bool,
+    // Depths the fog pass spans
+    pub z_range: DepthRange,
+    // Stereo pair the disparity pass is measured between
+    pub stereo: StereoRig,
+    pub deep: bool,
+    // Also write the even and odd samples as two images
+    pub half_buffers: bool,
+    // Also write the image at 1, 2, 4, ... samples per pixel
+    pub ladder: bool,
+    // Render the image at several focus distances and merge them
+    pub focus_stack: Option<FocusStack>,
+    // Exposure offsets, in stops, to also write the image at
+    pub brackets: Vec<f64>,
+    // Also write the luminance of the image in false color
+    pub false_color: bool,
+    pub seed: u64,
+    // Single frame to render, defaults to every frame of an animation
+    pub frame: Option<u64>,
+    // Number of frames to render from frame 0, defaults to the camera path's
+    pub frames: Option<u64>,
+    // Reuse the previous frames of an animation
+    pub temporal: bool,
+    pub script: Option<String>
+}
+
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> String {
+    args.next().unwrap_or_else(|| panic!("Missing value for {}.", flag))
+}
+
+fn parsed<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    value(args, flag).parse()
+        .unwrap_or_else(|_| panic!("Failed to parse value for {}.", flag))
+}
+
+// Printed by `rt --help` and for unknown options
+const OPTIONS: &str = "\
+Usage: rt [render] [options] <scene.xml|scene.gltf|scene.glb>
+       rt serve|contact-sheet|matpreview|bake|measure|merge|generate|tree ...
+
+Options:
+  --builtin NAME               render a scene baked into the binary
+  --quality draft|medium|final bundle of the settings below, final by default
+  --spp N                      samples per pixel
+  --depth N                    bounce limit
+  --clamp MAX                  largest sample value, 0 disables
+  --denoise, --no-denoise      denoise the image or not
+  --scale S                    factor multiplying the film resolution
+  --integrator NAME            path, wavefront, direct, ao, normals or depth
+  --packet 4|8                 trace the camera rays of a pixel in packets
+  --tile-order NAME            scanline, spiral or variance
+  --accel bvh|kdtree           structure built over the objects
+  --accel-cache                keep the structures in a file next to the scene
+  --bvh sah|median             how hierarchies are split
+  --allow-plugins              load the plugins the scene names
+  --seed N                     seed of the random sample streams
+  --overscan N                 extra pixels rendered around the image
+  --bucket x,y,width,height    render one region of the image
+  --aov LIST                   id, depth, fog, position, normal, mis, disparity
+  --aov-pinhole                trace the AOVs from a pinhole camera
+  --z-near D, --z-far D        depths the fog pass spans
+  --interaxial D               separation of the disparity pass' stereo pair
+  --convergence D              distance the stereo pair converges at
+  --deep                       also write a deep image
+  --half-buffers               also write the even and odd samples apart
+  --ladder                     also write the image at 1, 2, 4, ... spp
+  --focus-stack near,far,count render and merge several focus distances
+  --brackets EV,...            also write the image at these exposures
+  --false-color                also write the luminance in false color
+  --frame N                    render one frame of an animation
+  --frames N                   render frames 0 to N - 1
+  --temporal                   reuse the previous frames of an animation
+  --script FILE                run a script before every frame
+  -h, --help                   print this help
+";
+
+// The options and the settings of every --quality preset
+pub fn usage() -> String {
+    let mut out = format!("{}\nPresets:\n", OPTIONS);
+    for (name, quality) in QUALITIES {
+        let p = quality.preset();
+        let clamp = if p.clamp > 0.0 { p.clamp.to_string() } else { "off".to_string() };
+        let denoise = if p.denoise { "yes" } else { "no" };
+        out.push_str(&format!("  {:<8} spp {}, depth {}, clamp {}, denoise {}, scale {}\n", name, p.spp, p.depth, clamp, denoise, p.scale));
+    }
+    out
+}
+
+impl Options {
+    pub fn parse(args: impl Iterator<Item = String>) -> Options {
+        Options::parse_known(args).unwrap_or_else(|arg| panic!("Unknown option '{}'.", arg))
+    }
+
+    // Same as parse, returning the first unknown option as an error
+    pub fn parse_known(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+        let mut opts = Options {
+            scene: None,
+            builtin: None,
+            integrator: None,
+            packet_size: 0,
+            tile_order: TileOrder::Scanline,
+            accel: None,
+            accel_cache: false,
+            allow_plugins: false,
+            bvh: BvhBuild::Sah,
+            quality: Quality::Final,
+            spp: None,
+            depth: None,
+            clamp: None,
+            denoise: None,
+            scale: None,
+            overscan: 0,
+            bucket: None,
+            aovs: Vec::new(),
+            aov_pinhole: false,
+            z_range: DepthRange::default(),
+            stereo: StereoRig::default(),
+            deep: false,
+            half_buffers: false,
+            ladder: false,
+            focus_stack: None,
+            brackets: Vec::new(),
+            false_color: false,
+            seed: 0,
+            frame: None,
+            frames: None,
+            temporal: false,
+            script: None
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--integrator" => {
+                    let name = value(&mut args, "--integrator");
+                    opts.integrator = Some(Integrator::from_name(&name)
+                        .unwrap_or_else(|| panic!("Unknown integrator '{}', expected path, wavefront, direct, ao, normals or depth.", name)));
+                },
+                "--builtin" => opts.builtin = Some(value(&mut args, "--builtin")),
+                "--packet" => {
+                    opts.packet_size = parsed(&mut args, "--packet");
+                    if ![0, 1, 4, 8].contains(&opts.packet_size) {
+                        panic!("Packet size must be 4 or 8 (or 0 to disable).");
+                    }
+                },
+                "--tile-order" => {
+                    let name = value(&mut args, "--tile-order");
+                    opts.tile_order = TileOrder::from_name(&name)
+                        .unwrap_or_else(|| panic!("Unknown tile order '{}', expected scanline, spiral or variance.", name));
+                },
+                "--accel" => {
+                    let name = value(&mut args, "--accel");
+                    opts.accel = Some(AccelKind::from_name(&name)
+                        .unwrap_or_else(|| panic!("Unknown accelerator '{}', expected bvh or kdtree.", name)));
+                },
+                "--accel-cache" => opts.accel_cache = true,
+                "--allow-plugins" => opts.allow_plugins = true,
+                "--bvh" => {
+                    let name = value(&mut args, "--bvh");
+                    opts.bvh = BvhBuild::from_name(&name)
+                        .unwrap_or_else(|| panic!("Unknown BVH build '{}', expected sah or median.", name));
+                },
+                "--quality" => {
+                    let name = value(&mut args, "--quality");
+                    opts.quality = Quality::from_name(&name)
+                        .unwrap_or_else(|| panic!("Unknown quality '{}', expected draft, medium or final.", name));
+                },
+                "--spp" => opts.spp = Some(parsed(&mut args, "--spp")),
+                "--depth" => opts.depth = Some(parsed(&mut args, "--depth")),
+                "--clamp" => opts.clamp = Some(parsed(&mut args, "--clamp")),
+                "--denoise" => opts.denoise = Some(true),
+                "--no-denoise" => opts.denoise = Some(false),
+                "--scale" => {
+                    let scale: f64 = parsed(&mut args, "--scale");
+                    if scale <= 0.0 {
+                        panic!("--scale must be positive.");
+                    }
+                    opts.scale = Some(scale);
+                },
+                "--overscan" => opts.overscan = parsed(&mut args, "--overscan"),
+                "--bucket" => {
+                    let value = value(&mut args, "--bucket");
+                    opts.bucket = Some(Bucket::parse(&value)
+                        .unwrap_or_else(|| panic!("Failed to parse bucket '{}', expected x,y,width,height.", value)));
+                },
+                "--aov" => {
+                    for name in value(&mut args, "--aov").split(',') {
+                        let aov = Aov::from_name(name.trim())
+                            .unwrap_or_else(|| panic!("Unknown AOV '{}', expected id, depth, fog, position, normal, mis or disparity.", name));
+                        if !opts.aovs.contains(&aov) {
+                            opts.aovs.push(aov);
+                        }
+                    }
+                },
+                "--aov-pinhole" => opts.aov_pinhole = true,
+                "--z-near" => opts.z_range.near = Some(parsed(&mut args, "--z-near")),
+                "--z-far" => opts.z_range.far = Some(parsed(&mut args, "--z-far")),
+                "--interaxial" => opts.stereo.interaxial = Some(parsed(&mut args, "--interaxial")),
+                "--convergence" => opts.stereo.convergence = Some(parsed(&mut args, "--convergence")),
+                "--deep" => opts.deep = true,
+                "--half-buffers" => opts.half_buffers = true,
+                "--ladder" => opts.ladder = true,
+                "--focus-stack" => {
+                    let value = value(&mut args, "--focus-stack");
+                    let stack = FocusStack::parse(&value)
+                        .unwrap_or_else(|| panic!("Failed to parse focus stack '{}', expected near,far,count.", value));
+                    if stack.near <= 0.0 || stack.far <= stack.near {
+                        panic!("The focus stack must go from a positive near distance to a farther one.");
+                    }
+                    if stack.count < 2 {
+                        panic!("A focus stack needs at least 2 slices.");
+                    }
+                    opts.focus_stack = Some(stack);
+                },
+                "--brackets" => {
+                    for ev in value(&mut args, "--brackets").split(',') {
+                        let ev: f64 = ev.trim().parse()
+                            .unwrap_or_else(|_| panic!("Failed to parse exposure bracket '{}'.", ev));
+                        if !opts.brackets.contains(&ev) {
+                            opts.brackets.push(ev);
+                        }
+                    }
+                },
+                "--false-color" => opts.false_color = true,
+                "--seed" => opts.seed = parsed(&mut args, "--seed"),
+                "--frame" => opts.frame = Some(parsed(&mut args, "--frame")),
+                "--frames" => opts.frames = Some(parsed(&mut args, "--frames")),
+                "--temporal" => opts.temporal = true,
+                "--script" => opts.script = Some(value(&mut args, "--script")),
+                _ if arg.starts_with('-') => return Err(arg),
+                _ => opts.scene = Some(arg),
+            }
+        }
+
+        Ok(opts)
+    }
+
+    pub fn samples_per_pixel(&self) -> u64 {
+        self.spp.unwrap_or(self.quality.preset().spp).max(1)
+    }
+
+    // Factor the film size is scaled by
+    pub fn image_scale(&self) -> f64 {
+        self.scale.unwrap_or(self.quality.preset().scale)
+    }
+
+    // Settings for the scene's film, and the integrator its <integrator>
+    // element asks for unless overridden
+    pub fn settings(&self, scene: &Scene) -> RenderSettings {
+        let params = &scene.integrator;
+        let preset = self.quality.preset();
+        let integrator = self.integrator.or(params.integrator).unwrap_or(Integrator::Path);
+        let scale = self.image_scale();
+
+        let (film_width, film_height) = scene.film_size;
+        let image_width = (((film_width as f64) * scale) as u64).max(2);
+        let image_height = (image_width * film_height / film_width).max(2);
+
+        // Buckets are merged before denoising, which would see their edges
+        if let Some(b) = self.bucket {
+            if b.x + b.width > image_width || b.y + b.height > image_height {
+                panic!("The bucket {} doesn't fit in the {}x{} image.", b.name(), image_width, image_height);
+            }
+            if self.overscan > 0 {
+                panic!("--bucket can't be combined with --overscan.");
+            }
+            if self.denoise == Some(true) {
+                panic!("Buckets can't be denoised, only whole images.");
+            }
+            // Buckets are merged by their position in the unflipped image
+            if !scene.flip.is_none() {
+                panic!("--bucket can't be combined with a flipped film.");
+            }
+        }
+
+        RenderSettings {
+            image_width: image_width + 2 * self.overscan,
+            image_height: image_height + 2 * self.overscan,
+            overscan: self.overscan,
+            bucket: self.bucket,
+            flip: scene.flip,
+            pixel_aspect: scene.pixel_aspect,
+            samples_per_pixel: self.samples_per_pixel(),
+            first_sample: 0,
+            max_depth: self.depth.or(params.max_depth).unwrap_or(preset.depth),
+            clamp: self.clamp.unwrap_or(preset.clamp),
+            denoise: self.bucket.is_none() && self.denoise.unwrap_or(preset.denoise),
+            integrator,
+            rr_start: params.rr_start,
+            distance: params.distance(integrator),
+            packet_size: self.packet_size,
+            tile_order: self.tile_order,
+            seed: self.seed,
+            frame: self.frame.unwrap_or(0),
+            progress: None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_lists_the_presets() {
+        let usage = usage();
+        assert!(usage.contains("  draft    spp 16, depth 8, clamp 10, denoise yes, scale 0.5\n"), "{}", usage);
+        assert!(usage.contains("  final    spp 500, depth 50, clamp off, denoise no, scale 1\n"), "{}", usage);
+
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>().into_iter();
+        assert_eq!(Options::parse_known(args(&["--spp", "4", "--bogus"])).err().as_deref(), Some("--bogus"));
+        assert_eq!(Options::parse_known(args(&["-x", "scene.xml"])).err().as_deref(), Some("-x"));
+        assert_eq!(Options::parse_known(args(&["scene.xml"])).unwrap().scene.as_deref(), Some("scene.xml"));
+    }
+}
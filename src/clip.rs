@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Plane cutting away everything on the side its normal points to. Solids
+// cut open by it are closed with `cap` where it has one, and left hollow
+// otherwise.
+pub struct ClipPlane {
+    point: Point3,
+    normal: Vec3,
+    cap: Option<Arc<dyn Scatter>>
+}
+
+impl ClipPlane {
+    pub fn new(point: Point3, normal: Vec3, cap: Option<Arc<dyn Scatter>>) -> ClipPlane {
+        ClipPlane {
+            point,
+            normal: normal.normalized(),
+            cap
+        }
+    }
+
+    // The planes of the six faces of a box, facing out of it
+    pub fn around(bbox: Aabb, cap: Option<Arc<dyn Scatter>>) -> Vec<ClipPlane> {
+        let mut planes = Vec::new();
+        for axis in 0..3 {
+            for (point, side) in [(bbox.min, -1.0), (bbox.max, 1.0)] {
+                let mut normal = Vec3::new(0.0, 0.0, 0.0);
+                normal[axis] = side;
+                planes.push(ClipPlane::new(point, normal, cap.clone()));
+            }
+        }
+        planes
+    }
+
+    fn cuts(&self, p: Point3) -> bool {
+        (p - self.point).dot(self.normal) > 0.0
+    }
+}
+
+// Side of the planes a clip keeps
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    // What lies behind all of them
+    Inside,
+    // What lies in front of any of them, cutting the region behind them all
+    // out of the object
+    Outside
+}
+
+impl Keep {
+    pub fn from_name(name: &str) -> Option<Keep> {
+        match name {
+            "inside" => Some(Keep::Inside),
+            "outside" => Some(Keep::Outside),
+            _ => None
+        }
+    }
+}
+
+// Part of a ray between where it passes the last plane into the region
+// behind them all and the first plane out of it, with those planes; None
+// at either end of a ray that doesn't cross one there
+type Span<'a> = ((f64, Option<&'a ClipPlane>), (f64, Option<&'a ClipPlane>));
+
+// Object with the parts in front of any of the planes removed, or with
+// only those kept. Along a ray the region behind the planes lies between
+// where it passes the last one into it and the first one out of it; the
+// caps are hit at those two points when they are inside the object.
+pub struct Clipped {
+    object: Box<dyn Hit>,
+    planes: Arc<Vec<ClipPlane>>,
+    keep: Keep,
+    // Box the region is known to lie in
+    bounds: Option<Aabb>
+}
+
+impl Clipped {
+    pub fn new(object: Box<dyn Hit>, planes: Arc<Vec<ClipPlane>>) -> Clipped {
+        Clipped {
+            object,
+            planes,
+            keep: Keep::Inside,
+            bounds: None
+        }
+    }
+
+    pub fn with_keep(mut self, keep: Keep) -> Clipped {
+        self.keep = keep;
+        self
+    }
+
+    // Bounds the objects kept inside the region, like infinite planes
+    // trimmed by a box, so they can go in the hierarchy
+    pub fn with_bounds(mut self, bounds: Aabb) -> Clipped {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    fn span(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<Span<'_>> {
+        let (mut enter, mut exit) = ((t_min, None), (t_max, None));
+        for plane in self.planes.iter() {
+            let height = (r.origin() - plane.point).dot(plane.normal);
+            let rate = r.direction().dot(plane.normal);
+            if rate == 0.0 {
+                if height > 0.0 {
+                    return None;
+                }
+                continue;
+            }
+
+            let t = -height / rate;
+            if rate < 0.0 && t > enter.0 {
+                enter = (t, Some(plane));
+            } else if rate > 0.0 && t < exit.0 {
+                exit = (t, Some(plane));
+            }
+        }
+
+        if enter.0 > exit.0 {
+            return None;
+        }
+        Some((enter, exit))
+    }
+
+    // Cap of the plane at ray parameter t, if the object is solid there: the
+    // nearest crossing of its boundary ahead, or else behind, is then on the
+    // way out. Caps face out of the region, or into it for the objects kept
+    // outside it.
+    fn cap(&self, r: &Ray, t: f64, plane: &ClipPlane) -> Option<HitRecord> {
+        let mat = plane.cap.clone()?;
+        let back = Ray::with_time(r.at(t), (-1.0) * r.direction(), r.time()).with_type(r.ray_type());
+        let mut rec = self.object.hit(r, t, f64::INFINITY).or_else(|| self.object.hit(&back, 0.0, f64::INFINITY))?;
+        if rec.front_face {
+            return None;
+        }
+
+        // The rest of the record, like the light links, stays the object's
+        let side = if self.keep == Keep::Inside { 1.0 } else { -1.0 };
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, side * plane.normal);
+        rec.mat = mat;
+        Some(rec)
+    }
+
+    // The object before and after the region, and the caps between
+    fn hit_outside(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let Some((enter, exit)) = self.span(r, f64::NEG_INFINITY, f64::INFINITY) else {
+            return self.object.hit(r, t_min, t_max);
+        };
+
+        if enter.0 > t_min {
+            if let Some(rec) = self.object.hit(r, t_min, enter.0.min(t_max)) {
+                return Some(rec);
+            }
+        }
+        for (t, plane) in [enter, exit] {
+            if t >= t_min && t <= t_max {
+                if let Some(rec) = plane.and_then(|plane| self.cap(r, t, plane)) {
+                    return Some(rec);
+                }
+            }
+        }
+        if exit.0 < t_max {
+            return self.object.hit(r, exit.0.max(t_min), t_max);
+        }
+        None
+    }
+}
+
+impl Hit for Clipped {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if self.keep == Keep::Outside {
+            return self.hit_outside(r, t_min, t_max);
+        }
+
+        let (enter, exit) = self.span(r, t_min, t_max)?;
+        if let Some(rec) = enter.1.and_then(|plane| self.cap(r, enter.0, plane)) {
+            return Some(rec);
+        }
+        if let Some(rec) = self.object.hit(r, enter.0, exit.0) {
+            return Some(rec);
+        }
+        exit.1.and_then(|plane| self.cap(r, exit.0, plane))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match (self.object.bounding_box(), self.bounds) {
+            (Some(bbox), Some(bounds)) if self.keep == Keep::Inside => Some(bbox.overlap(bounds)),
+            (None, Some(bounds)) if self.keep == Keep::Inside => Some(bounds),
+            (bbox, _) => bbox
+        }
+    }
+
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let rec = self.object.surface_at(u, v)?;
+        let cut = self.planes.iter().any(|plane| plane.cuts(rec.p));
+        if cut == (self.keep == Keep::Inside) {
+            return None;
+        }
+        Some(rec)
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+// Spheres per leaf
+const LEAF_SIZE: usize = 4;
+
+// Deepest traversal stack; median splits over 4 spheres per leaf stay far
+// shallower than this for any cloud that fits in memory
+const STACK_SIZE: usize = 64;
+
+// Node of the cloud's hierarchy, stored depth first so that the left child
+// of an interior node is the node right after it
+struct Node {
+    bbox: Aabb,
+    // Leaves hold the spheres start..start + count, interior nodes have
+    // count 0 and the index of their right child in start
+    start: u32,
+    count: u32
+}
+
+// Many small spheres of one material, like particles or the points of a
+// scan, stored as arrays of single precision coordinates and radii rather
+// than as objects of their own, with a hierarchy over them whose leaves are
+// ranges of those arrays
+pub struct SphereCloud {
+    x: Vec<f32>,
+    y: Vec<f32>,
+    z: Vec<f32>,
+    radius: Vec<f32>,
+    nodes: Vec<Node>,
+    mat: Arc<dyn Scatter>
+}
+
+impl SphereCloud {
+    pub fn new(centers: &[Point3], radii: &[f64], m: Arc<dyn Scatter>) -> SphereCloud {
+        // Boxes are made from the stored values, so no sphere pokes out of
+        // its leaf
+        let single = |v: f64| v as f32 as f64;
+        let centers: Vec<Point3> = centers.iter().map(|c| Point3::new(single(c.x()), single(c.y()), single(c.z()))).collect();
+        let radii: Vec<f64> = radii.iter().map(|&r| single(r)).collect();
+
+        let mut order: Vec<usize> = (0..centers.len()).collect();
+        let mut nodes = Vec::new();
+        if !order.is_empty() {
+            build(&centers, &radii, &mut order, 0, &mut nodes);
+        }
+
+        // The spheres in the order of the leaves
+        SphereCloud {
+            x: order.iter().map(|&i| centers[i].x() as f32).collect(),
+            y: order.iter().map(|&i| centers[i].y() as f32).collect(),
+            z: order.iter().map(|&i| centers[i].z() as f32).collect(),
+            radius: order.iter().map(|&i| radii[i] as f32).collect(),
+            nodes,
+            mat: m
+        }
+    }
+
+    // Spheres of a point file: one "x y z" or "x y z radius" per line, with
+    // `radius` the size of those without one. Blank lines and lines starting
+    // with # are skipped.
+    pub fn load(filename: &str, radius: f64, m: Arc<dyn Scatter>) -> SphereCloud {
+        let text = std::fs::read_to_string(filename)
+            .unwrap_or_else(|_| panic!("Unable to read point file {}.", filename));
+
+        let mut centers = Vec::new();
+        let mut radii = Vec::new();
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            let values: Vec<f64> = line.split_whitespace()
+                .map(|v| v.parse().unwrap_or_else(|_| panic!("Failed to parse point file {}.", filename)))
+                .collect();
+            let (x, y, z, r) = match values[..] {
+                [x, y, z] => (x, y, z, radius),
+                [x, y, z, r] => (x, y, z, r),
+                _ => panic!("Failed to parse point file {}.", filename)
+            };
+            if r <= 0.0 {
+                panic!("The point radius must be positive.");
+            }
+            centers.push(Point3::new(x, y, z));
+            radii.push(r);
+        }
+        if centers.is_empty() {
+            panic!("No points found in point file {}.", filename);
+        }
+
+        SphereCloud::new(&centers, &radii, m)
+    }
+
+    fn center(&self, k: usize) -> Point3 {
+        Point3::new(self.x[k] as f64, self.y[k] as f64, self.z[k] as f64)
+    }
+}
+
+fn sphere_box(center: Point3, radius: f64) -> Aabb {
+    let r = Vec3::new(radius, radius, radius);
+    Aabb::new(center - r, center + r)
+}
+
+// Splits order at the median center along the axis the centers spread the
+// most, pushing the nodes of the subtree depth first
+fn build(centers: &[Point3], radii: &[f64], order: &mut [usize], start: usize, nodes: &mut Vec<Node>) {
+    let bbox = order.iter().map(|&i| sphere_box(centers[i], radii[i])).reduce(|a, b| a.surrounding(b)).unwrap();
+    let index = nodes.len();
+
+    if order.len() <= LEAF_SIZE {
+        nodes.push(Node { bbox, start: start as u32, count: order.len() as u32 });
+        return;
+    }
+
+    let spread = order.iter().fold(Aabb::new(centers[order[0]], centers[order[0]]), |b, &i| {
+        b.surrounding(Aabb::new(centers[i], centers[i]))
+    }).extent();
+    let axis = if spread.x() >= spread.y() && spread.x() >= spread.z() {
+        0
+    } else if spread.y() >= spread.z() {
+        1
+    } else {
+        2
+    };
+
+    let mid = order.len() / 2;
+    order.select_nth_unstable_by(mid, |&a, &b| centers[a][axis].total_cmp(&centers[b][axis]));
+
+    nodes.push(Node { bbox, start: 0, count: 0 });
+    let (left, right) = order.split_at_mut(mid);
+    build(centers, radii, left, start, nodes);
+    nodes[index].start = nodes.len() as u32;
+    build(centers, radii, right, start + mid, nodes);
+}
+
+impl Hit for SphereCloud {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let d = r.direction();
+        let origin = r.origin();
+        let inv_dir = Vec3::new(1.0 / d.x(), 1.0 / d.y(), 1.0 / d.z());
+        let a = d.dot(d);
+
+        let mut closest = t_max;
+        let mut hit = None;
+        let mut stack = [0; STACK_SIZE];
+        let mut top = 1;
+        while top > 0 {
+            top -= 1;
+            let node = &self.nodes[stack[top]];
+            if !node.bbox.hit(origin, inv_dir, t_min, closest) {
+                continue;
+            }
+
+            if node.count == 0 {
+                // The far child goes first on the stack so the near one is
+                // visited first, judged by the centers of their boxes
+                let (left, right) = (stack[top] + 1, node.start as usize);
+                let to_left = (self.nodes[left].bbox.centroid() - origin).dot(d);
+                let to_right = (self.nodes[right].bbox.centroid() - origin).dot(d);
+                let (near, far) = if to_left <= to_right { (left, right) } else { (right, left) };
+                stack[top] = far;
+                stack[top + 1] = near;
+                top += 2;
+                continue;
+            }
+
+            let start = node.start as usize;
+            for k in start..start + node.count as usize {
+                let oc = origin - self.center(k);
+                let half_b = oc.dot(d);
+                let radius = self.radius[k] as f64;
+                let discriminant = half_b * half_b - a * (oc.dot(oc) - radius * radius);
+                if discriminant < 0.0 {
+                    continue;
+                }
+
+                let sqrtd = discriminant.sqrt();
+                let near = (-half_b - sqrtd) / a;
+                let root = if near >= t_min { near } else { (-half_b + sqrtd) / a };
+                if root >= t_min && root < closest {
+                    closest = root;
+                    hit = Some(k);
+                }
+            }
+        }
+
+        let k = hit?;
+        let outward_normal = (r.at(closest) - self.center(k)) / self.radius[k] as f64;
+        Some(HitRecord::new(r, closest, outward_normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.nodes.first().map(|root| root.bbox)
+    }
+}
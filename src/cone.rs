@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Finite cone opening from the apex along the axis at half_angle (in
+// degrees), cut off height away from the apex where the base disk closes it
+// unless `cap` is off
+pub struct Cone {
+    apex: Point3,
+    axis: Vec3,
+    cos_angle: f64,
+    sin_angle: f64,
+    height: f64,
+    cap: bool,
+    mat: Arc<dyn Scatter>
+}
+
+impl Cone {
+    pub fn new(apex: Point3, axis: Vec3, half_angle: f64, height: f64, cap: bool, m: Arc<dyn Scatter>) -> Cone {
+        let angle = half_angle.to_radians();
+        Cone {
+            apex,
+            axis: axis.normalized(),
+            cos_angle: angle.cos(),
+            sin_angle: angle.sin(),
+            height,
+            cap,
+            mat: m
+        }
+    }
+
+    fn base_radius(&self) -> f64 {
+        self.height * self.sin_angle / self.cos_angle
+    }
+
+    // Outward normal of the side where p_perp is the offset of the hit from
+    // the axis. At the apex, where that offset vanishes and the side has no
+    // normal of its own, this is the average of the normals around it.
+    fn side_normal(&self, p_perp: Vec3) -> Vec3 {
+        let length = p_perp.length();
+        if length <= 1e-9 * self.height {
+            return (-1.0) * self.axis;
+        }
+        self.cos_angle / length * p_perp - self.sin_angle * self.axis
+    }
+}
+
+impl Hit for Cone {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let oc = r.origin() - self.apex;
+        let d_along = r.direction().dot(self.axis);
+        let oc_along = oc.dot(self.axis);
+
+        // Closest hit so far, with its outward normal
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut consider = |t: f64, normal: &dyn Fn(f64) -> Vec3| {
+            if t >= t_min && t <= t_max && closest.is_none_or(|(c, _)| t < c) {
+                closest = Some((t, normal(t)));
+            }
+        };
+
+        // Side: points whose angle to the axis at the apex is the half angle,
+        // (p . axis)^2 = cos^2 |p|^2, on the half of the double cone the axis
+        // points to
+        let k = self.cos_angle * self.cos_angle;
+        let a = d_along * d_along - k * r.direction().dot(r.direction());
+        let half_b = d_along * oc_along - k * r.direction().dot(oc);
+        let c = oc_along * oc_along - k * oc.dot(oc);
+
+        let roots = if a.abs() < 1e-12 {
+            // Rays parallel to the side cross it only once
+            if half_b.abs() < 1e-12 { vec![] } else { vec![-c / (2.0 * half_b)] }
+        } else {
+            // Rays through the apex graze it with a zero discriminant, which
+            // rounding can make slightly negative
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < -1e-9 * half_b * half_b {
+                vec![]
+            } else {
+                let sqrtd = discriminant.max(0.0).sqrt();
+                vec![(-half_b - sqrtd) / a, (-half_b + sqrtd) / a]
+            }
+        };
+
+        let d_perp = r.direction() - d_along * self.axis;
+        let oc_perp = oc - oc_along * self.axis;
+        for t in roots {
+            let along = oc_along + t * d_along;
+            if (-1e-9 * self.height..=self.height).contains(&along) {
+                consider(t, &|t| self.side_normal(oc_perp + t * d_perp));
+            }
+        }
+
+        // The base disk
+        if self.cap && d_along.abs() > 1e-12 {
+            let t = (self.height - oc_along) / d_along;
+            let offset = oc_perp + t * d_perp;
+            let radius = self.base_radius();
+            if offset.dot(offset) <= radius * radius {
+                consider(t, &|_| self.axis);
+            }
+        }
+
+        let (t, outward_normal) = closest?;
+        Some(HitRecord::new(r, t, outward_normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let base = self.apex + self.height * self.axis;
+        let radius = self.base_radius();
+
+        // The base disk reaches radius * sin of the angle to each axis
+        let extent = Vec3::new(
+            radius * (1.0 - self.axis.x() * self.axis.x()).max(0.0).sqrt(),
+            radius * (1.0 - self.axis.y() * self.axis.y()).max(0.0).sqrt(),
+            radius * (1.0 - self.axis.z() * self.axis.z()).max(0.0).sqrt()
+        );
+        Some(Aabb::new(base - extent, base + extent).surrounding(Aabb::new(self.apex, self.apex)).padded(1e-4))
+    }
+}
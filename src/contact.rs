@@ -0,0 +1,120 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use super::camera::DEFAULT_ASPECT_RATIO;
+use super::cli::Options;
+use super::font::{self, GLYPH_HEIGHT};
+use super::png;
+use super::render;
+
+const PADDING: usize = 8;
+const LABEL_HEIGHT: usize = GLYPH_HEIGHT + 6;
+const BACKGROUND: [u8; 3] = [32, 32, 32];
+const LABEL_COLOR: [u8; 3] = [230, 230, 230];
+const FAILED_COLOR: [u8; 3] = [220, 80, 80];
+
+// Thumbnail of one scene and its width and height, as large as fits in
+// width by height with the shape of the scene's film, or None if it failed
+// to load or render
+fn thumbnail(path: &str, opts: &Options, width: u64, height: u64) -> Option<(Vec<u8>, u64, u64)> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let (_, mut scene) = super::xml_parser(&super::read_xml(path));
+
+        // Thumbnails have square pixels, showing anamorphic films stretched
+        let (film_width, film_height) = scene.film_size;
+        let aspect = film_width as f64 * scene.pixel_aspect / film_height as f64;
+        let mut settings = opts.settings(&scene);
+        settings.image_width = width.min((height as f64 * aspect) as u64).max(2);
+        settings.image_height = height.min((width as f64 / aspect) as u64).max(2);
+        settings.pixel_aspect = 1.0;
+        if let Some(path) = &scene.camera_path {
+            scene.camera = path.camera(*path.frames().start());
+        }
+
+        let image = render::render(&scene, &settings)
+            .iter()
+            .flat_map(|p| p.to_rgb8(settings.samples_per_pixel))
+            .collect();
+        (image, settings.image_width, settings.image_height)
+    }));
+
+    result.ok()
+}
+
+// Label shortened with ".." to fit in max_width pixels
+fn fit_label(label: &str, max_width: usize) -> String {
+    if font::text_width(label, 1) <= max_width {
+        return label.to_string();
+    }
+
+    let mut chars: Vec<char> = label.chars().collect();
+    while !chars.is_empty() && font::text_width(&chars.iter().collect::<String>(), 1) + font::text_width("..", 1) + 1 > max_width {
+        chars.pop();
+    }
+    chars.iter().collect::<String>() + ".."
+}
+
+// `rt contact-sheet scenes/*.xml -o sheet.png [--columns N] [--width N] [--spp N]`
+pub fn contact_sheet(mut args: impl Iterator<Item = String>) {
+    let mut scenes = Vec::new();
+    let mut output = "contact-sheet.png".to_string();
+    let mut columns = None;
+    let mut width: u64 = 240;
+    let mut spp = "16".to_string();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("Missing value for {}.", arg));
+        match arg.as_str() {
+            "-o" | "--output" => output = value(),
+            "--columns" => columns = Some(value().parse::<usize>().expect("Failed to parse value for --columns.").max(1)),
+            "--width" => width = value().parse::<u64>().expect("Failed to parse value for --width.").max(2),
+            "--spp" => spp = value(),
+            _ if arg.starts_with('-') => panic!("Unknown option '{}' for contact-sheet.", arg),
+            _ => scenes.push(arg)
+        }
+    }
+
+    if scenes.is_empty() {
+        panic!("No scenes given to the contact sheet.");
+    }
+
+    let opts = Options::parse(["--quality", "draft", "--spp", &spp].iter().map(|s| s.to_string()));
+    let height = (((width as f64) / DEFAULT_ASPECT_RATIO) as u64).max(2);
+    let columns = columns.unwrap_or_else(|| (scenes.len() as f64).sqrt().ceil() as usize);
+    let rows = scenes.len().div_ceil(columns);
+
+    let cell_width = width as usize + PADDING;
+    let cell_height = height as usize + LABEL_HEIGHT + PADDING;
+    let sheet_width = columns * cell_width + PADDING;
+    let sheet_height = rows * cell_height + PADDING;
+
+    let mut sheet: Vec<u8> = BACKGROUND.iter().copied().cycle().take(sheet_width * sheet_height * 3).collect();
+
+    for (n, path) in scenes.iter().enumerate() {
+        eprintln!("Rendering {} ({}/{})", path, n + 1, scenes.len());
+
+        let left = PADDING + (n % columns) * cell_width;
+        let top = PADDING + (n / columns) * cell_height;
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let stem = name.strip_suffix(".xml").unwrap_or(name);
+
+        let (label, color) = match thumbnail(path, &opts, width, height) {
+            Some((image, w, h)) => {
+                // Centered in the cell
+                let (x, y) = ((width - w) as usize / 2, (height - h) as usize / 2);
+                for (row_index, row) in image.chunks(w as usize * 3).enumerate() {
+                    let k = 3 * ((top + y + row_index) * sheet_width + left + x);
+                    sheet[k..k + row.len()].copy_from_slice(row);
+                }
+                (stem.to_string(), LABEL_COLOR)
+            },
+            None => (format!("{} (failed)", stem), FAILED_COLOR)
+        };
+
+        let label = fit_label(&label, width as usize);
+        font::draw_text(&mut sheet, sheet_width, sheet_height, (left, top + height as usize + 4), &label, color, 1);
+    }
+
+    png::write(&output, sheet_width, sheet_height, &sheet, &[])
+        .unwrap_or_else(|_| panic!("Failed to write {}.", output));
+    eprintln!("Wrote {} scenes to {}", scenes.len(), output);
+}
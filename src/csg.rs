@@ -0,0 +1,132 @@
+use super::aabb::Aabb;
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+
+// Most boundary crossings gathered from a child along one ray
+const MAX_CROSSINGS: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    // The first object with the second carved out of it
+    Difference
+}
+
+impl CsgOp {
+    pub fn from_name(name: &str) -> Option<CsgOp> {
+        match name {
+            "union" => Some(CsgOp::Union),
+            "intersection" => Some(CsgOp::Intersection),
+            "difference" => Some(CsgOp::Difference),
+            _ => None
+        }
+    }
+
+    fn inside(self, a: bool, b: bool) -> bool {
+        match self {
+            CsgOp::Union => a || b,
+            CsgOp::Intersection => a && b,
+            CsgOp::Difference => a && !b
+        }
+    }
+}
+
+// Solid combining two closed objects. The surface is found from where the
+// whole line of the ray enters and leaves each child: walking through both
+// lists of crossings in order, the ray hits the combination wherever being
+// inside it changes.
+pub struct Csg {
+    op: CsgOp,
+    a: Box<dyn Hit>,
+    b: Box<dyn Hit>
+}
+
+// Crossings of the object's boundary along the whole line of r, nearest
+// first, and whether the line starts out inside it. A ray entering an
+// object sees its front face.
+fn crossings(object: &dyn Hit, r: &Ray) -> (bool, Vec<HitRecord>) {
+    let length = r.direction().length();
+    let mut list: Vec<HitRecord> = Vec::new();
+    let mut t = f64::NEG_INFINITY;
+
+    while list.len() < MAX_CROSSINGS {
+        match object.hit(r, t, f64::INFINITY) {
+            Some(rec) => {
+                t = rec.t + 1e-6 * (rec.t.abs() + 1.0 / length);
+                list.push(rec);
+            },
+            None => break
+        }
+    }
+
+    let starts_inside = list.first().is_some_and(|rec| !rec.front_face);
+    (starts_inside, list)
+}
+
+impl Csg {
+    pub fn new(op: CsgOp, a: Box<dyn Hit>, b: Box<dyn Hit>) -> Csg {
+        Csg {
+            op,
+            a,
+            b
+        }
+    }
+}
+
+impl Hit for Csg {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (mut in_a, list_a) = crossings(self.a.as_ref(), r);
+        let (mut in_b, list_b) = crossings(self.b.as_ref(), r);
+
+        let mut inside = self.op.inside(in_a, in_b);
+        let mut list_a = list_a.into_iter().peekable();
+        let mut list_b = list_b.into_iter().peekable();
+
+        loop {
+            let from_a = match (list_a.peek(), list_b.peek()) {
+                (Some(x), Some(y)) => x.t <= y.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None
+            };
+
+            let mut rec = if from_a {
+                let rec = list_a.next().unwrap();
+                in_a = rec.front_face;
+                rec
+            } else {
+                let rec = list_b.next().unwrap();
+                in_b = rec.front_face;
+                rec
+            };
+
+            if rec.t > t_max {
+                return None;
+            }
+
+            let now_inside = self.op.inside(in_a, in_b);
+            if now_inside != inside {
+                inside = now_inside;
+                if rec.t >= t_min {
+                    // The normal already faces the ray; the face is the front
+                    // one where the ray enters the combination, which for a
+                    // carved out surface is where it leaves the carving object
+                    rec.front_face = now_inside;
+                    return Some(rec);
+                }
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self.op {
+            CsgOp::Union => Some(self.a.bounding_box()?.surrounding(self.b.bounding_box()?)),
+            CsgOp::Intersection => match (self.a.bounding_box(), self.b.bounding_box()) {
+                (Some(a), Some(b)) => Some(a.overlap(b)),
+                (a, b) => a.or(b)
+            },
+            CsgOp::Difference => self.a.bounding_box()
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Axis-aligned box between the corners min and max, intersected as the
+// slabs of its three axes
+pub struct Cuboid {
+    bbox: Aabb,
+    mat: Arc<dyn Scatter>
+}
+
+impl Cuboid {
+    pub fn new(a: Point3, b: Point3, m: Arc<dyn Scatter>) -> Cuboid {
+        Cuboid {
+            bbox: Aabb::new(a, b),
+            mat: m
+        }
+    }
+}
+
+impl Hit for Cuboid {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let origin = r.origin();
+        let dir = r.direction();
+
+        // Latest entry and earliest exit over the slabs, with their axes
+        let (mut t_enter, mut enter_axis) = (f64::NEG_INFINITY, 0);
+        let (mut t_exit, mut exit_axis) = (f64::INFINITY, 0);
+
+        for axis in 0..3 {
+            let inv = 1.0 / dir[axis];
+            let near = (self.bbox.min[axis] - origin[axis]) * inv;
+            let far = (self.bbox.max[axis] - origin[axis]) * inv;
+            let (near, far) = if near <= far { (near, far) } else { (far, near) };
+
+            // Rays parallel to a slab and outside of it give NaN or infinite
+            // bounds that leave the box missed
+            if near > t_enter {
+                t_enter = near;
+                enter_axis = axis;
+            }
+            if far < t_exit {
+                t_exit = far;
+                exit_axis = axis;
+            }
+            if near.is_nan() || far.is_nan() {
+                return None;
+            }
+        }
+
+        if t_exit < t_enter {
+            return None;
+        }
+
+        // The exit is hit instead when the ray starts inside
+        let (t, axis, sign) = if (t_min..=t_max).contains(&t_enter) {
+            (t_enter, enter_axis, -1.0)
+        } else if (t_min..=t_max).contains(&t_exit) {
+            (t_exit, exit_axis, 1.0)
+        } else {
+            return None;
+        };
+
+        let mut outward_normal = Vec3::new(0.0, 0.0, 0.0);
+        outward_normal[axis] = if dir[axis] > 0.0 { sign } else { -sign };
+
+        Some(HitRecord::new(r, t, outward_normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox.padded(1e-4))
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::accel::{self, Accelerator};
+use super::capsule::Capsule;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::ray::Ray;
+use super::vec::Point3;
+
+// Most straight pieces a curve is split into
+const MAX_PIECES: usize = 64;
+
+// Cubic Bezier curve from p[0] to p[3], pulled towards p[1] and p[2], swept
+// by a circle whose radius goes from `radius.0` at the root to `radius.1` at
+// the tip. The curve is split into enough straight pieces, each a capsule
+// of the radius at its middle, to stay within a tenth of the average radius
+// of the true curve, so hair shades like the round fibre it is.
+pub struct Curve {
+    pieces: Vec<Capsule>,
+    bbox: Aabb
+}
+
+impl Curve {
+    pub fn new(p: [Point3; 4], radius: (f64, f64), m: Arc<dyn Scatter>) -> Curve {
+        let at = |t: f64| {
+            let s = 1.0 - t;
+            s * s * s * p[0] + 3.0 * s * s * t * p[1] + 3.0 * s * t * t * p[2] + t * t * t * p[3]
+        };
+
+        // A curve split in n pieces strays from them by at most 3/4 of the
+        // largest second difference of its control points over n^2
+        let bend = (p[0] - 2.0 * p[1] + p[2]).length().max((p[1] - 2.0 * p[2] + p[3]).length());
+        let tolerance = 0.05 * (radius.0 + radius.1);
+        let count = ((0.75 * bend / tolerance).sqrt().ceil() as usize).clamp(1, MAX_PIECES);
+
+        let pieces: Vec<Capsule> = (0..count).map(|k| {
+            let (t0, t1) = (k as f64 / count as f64, (k + 1) as f64 / count as f64);
+            let middle = 0.5 * (t0 + t1);
+            let r = (1.0 - middle) * radius.0 + middle * radius.1;
+            Capsule::new(at(t0), at(t1), r, m.clone())
+        }).collect();
+
+        let bbox = pieces.iter()
+            .filter_map(|piece| piece.bounding_box())
+            .reduce(|a, b| a.surrounding(b))
+            .unwrap();
+        Curve { pieces, bbox }
+    }
+}
+
+impl Hit for Curve {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = t_max;
+        let mut result = None;
+        for piece in &self.pieces {
+            if let Some(rec) = piece.hit(r, t_min, closest) {
+                closest = rec.t;
+                result = Some(rec);
+            }
+        }
+        result
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+// Strand of hair or other fibre, a chain of cubic Bezier curves through
+// points[0], points[3], points[6], ... each pulled towards the two points in
+// between, with the radius at each of those ends if the file gives them
+pub struct Strand {
+    pub points: Vec<Point3>,
+    pub radii: Option<Vec<f64>>
+}
+
+// Curves of many strands, like the hair of a head or the fur of an animal,
+// with an accelerator of their own over the curves
+pub struct Curves {
+    curves: Vec<Curve>,
+    accel: Box<dyn Accelerator<Curve>>
+}
+
+impl Curves {
+    // Strands tapering from `root` to `tip` along their length, unless they
+    // have radii of their own
+    pub fn new(strands: &[Strand], root: f64, tip: f64, m: Arc<dyn Scatter>) -> Curves {
+        let mut curves = Vec::new();
+        for strand in strands {
+            let count = (strand.points.len() - 1) / 3;
+            let radius = |k: usize| match &strand.radii {
+                Some(radii) => radii[k],
+                None => root + (tip - root) * k as f64 / count as f64
+            };
+
+            for k in 0..count {
+                // Hair files can give whole curves no thickness
+                if radius(k) <= 0.0 && radius(k + 1) <= 0.0 {
+                    continue;
+                }
+                let p = [strand.points[3 * k], strand.points[3 * k + 1], strand.points[3 * k + 2], strand.points[3 * k + 3]];
+                curves.push(Curve::new(p, (radius(k), radius(k + 1)), m.clone()));
+            }
+        }
+
+        let accel = accel::new(&curves);
+        Curves { curves, accel }
+    }
+}
+
+impl Hit for Curves {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.accel.hit(&self.curves, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.accel.bounding_box()
+    }
+}
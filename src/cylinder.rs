@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Finite cylinder around the axis through center, reaching height / 2 to
+// either side of it; without caps it is an open tube
+pub struct Cylinder {
+    center: Point3,
+    axis: Vec3,
+    radius: f64,
+    half_height: f64,
+    caps: bool,
+    mat: Arc<dyn Scatter>
+}
+
+impl Cylinder {
+    pub fn new(center: Point3, axis: Vec3, radius: f64, height: f64, caps: bool, m: Arc<dyn Scatter>) -> Cylinder {
+        Cylinder {
+            center,
+            axis: axis.normalized(),
+            radius,
+            half_height: height / 2.0,
+            caps,
+            mat: m
+        }
+    }
+}
+
+impl Hit for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let oc = r.origin() - self.center;
+        let d_along = r.direction().dot(self.axis);
+        let oc_along = oc.dot(self.axis);
+
+        // Closest hit so far, with its outward normal
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut consider = |t: f64, normal: &dyn Fn(f64) -> Vec3| {
+            if t >= t_min && t <= t_max && closest.is_none_or(|(c, _)| t < c) {
+                closest = Some((t, normal(t)));
+            }
+        };
+
+        // Side: the quadratic of the distance to the axis, perpendicular to it
+        let d_perp = r.direction() - d_along * self.axis;
+        let oc_perp = oc - oc_along * self.axis;
+        let a = d_perp.dot(d_perp);
+        let half_b = d_perp.dot(oc_perp);
+        let c = oc_perp.dot(oc_perp) - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+
+        if a > 1e-12 && discriminant >= 0.0 {
+            let sqrtd = discriminant.sqrt();
+            for t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                if (oc_along + t * d_along).abs() <= self.half_height {
+                    consider(t, &|t| (oc_perp + t * d_perp) / self.radius);
+                }
+            }
+        }
+
+        // Caps, the disks at either end
+        if self.caps && d_along.abs() > 1e-12 {
+            for side in [-1.0, 1.0] {
+                let t = (side * self.half_height - oc_along) / d_along;
+                let offset = oc_perp + t * d_perp;
+                if offset.dot(offset) <= self.radius * self.radius {
+                    consider(t, &|_| side * self.axis);
+                }
+            }
+        }
+
+        let (t, outward_normal) = closest?;
+        Some(HitRecord::new(r, t, outward_normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let a = self.center - self.half_height * self.axis;
+        let b = self.center + self.half_height * self.axis;
+
+        // The end disks reach radius * sin of the angle to each axis
+        let extent = Vec3::new(
+            self.radius * (1.0 - self.axis.x() * self.axis.x()).max(0.0).sqrt(),
+            self.radius * (1.0 - self.axis.y() * self.axis.y()).max(0.0).sqrt(),
+            self.radius * (1.0 - self.axis.z() * self.axis.z()).max(0.0).sqrt()
+        );
+        Some(Aabb::new(a - extent, a + extent).surrounding(Aabb::new(b - extent, b + extent)).padded(1e-4))
+    }
+}
@@ -0,0 +1,145 @@
+use rayon::prelude::*;
+
+use super::aov::pass_filename;
+use super::exr::DeepExrImage;
+use super::hit::Hit;
+use super::material;
+use super::metadata::Metadata;
+use super::render::{self, RenderSettings};
+use super::ray::{Ray, RayKind};
+use super::scene::Scene;
+use super::vec::Color;
+
+// Neighbouring samples of one object are merged into a single deep sample
+// unless their depths differ by more than this fraction
+const MERGE_TOLERANCE: f64 = 0.05;
+
+// A camera sample with the depth its radiance is attributed to
+struct DepthSample {
+    color: Color,
+    z: f64,
+    object_id: usize
+}
+
+// Follows a camera sample through see-through surfaces and returns its
+// radiance at the first surface it doesn't refract through. Samples that
+// leave the scene aren't recorded, the background being composited
+// separately.
+fn trace_sample(r: &Ray, scene: &Scene, settings: &RenderSettings) -> Option<DepthSample> {
+    let mut ray = Ray::with_time(r.origin(), r.direction(), r.time());
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut z = 0.0;
+    let mut kind = RayKind::Camera;
+
+    for depth in (1..=settings.max_depth).rev() {
+        let mut rec = scene.world.hit(&ray, 0.001, f64::INFINITY)?;
+        material::resolve(&ray, &mut rec);
+        z += rec.t * ray.direction().length();
+        let object_id = rec.object_id;
+
+        // Holdouts cover what is behind them with black
+        if rec.holdout && kind == RayKind::Camera {
+            return Some(DepthSample { color: Color::new(0.0, 0.0, 0.0), z, object_id });
+        }
+
+        // Surfaces lit directly are always opaque
+        if rec.mat.eval(&ray, &rec, rec.normal).is_some() {
+            let color = throughput * render::shade(&ray, Some(rec), scene, settings, depth, kind);
+            return Some(DepthSample { color, z, object_id });
+        }
+
+        let emitted = if render::counts_emission(kind, &rec, scene) {
+            rec.mat.emitted(&ray, &rec)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+
+        match rec.mat.scatter(&ray, &rec) {
+            Some((attenuation, scattered)) if scattered.direction().dot(rec.geometric_normal) < 0.0 => {
+                throughput *= attenuation;
+                let ty = rec.mat.ray_type(&ray, &rec, &scattered);
+                kind = render::bounce_kind(&ray, &rec, kind, false);
+                ray = scattered.with_type(ty);
+            },
+            Some((attenuation, scattered)) => {
+                let ty = rec.mat.ray_type(&ray, &rec, &scattered);
+                let scattered = scattered.with_type(ty);
+                let color = throughput * (emitted + attenuation * render::trace(&scattered, scene, settings, depth - 1, RayKind::Specular));
+                return Some(DepthSample { color, z, object_id });
+            },
+            None => return Some(DepthSample { color: throughput * emitted, z, object_id })
+        }
+    }
+
+    None
+}
+
+// Groups the camera samples of a pixel into deep samples of premultiplied
+// color, coverage alpha and the depth range they span, front to back
+fn merge(mut samples: Vec<DepthSample>, spp: u64) -> Vec<Vec<f32>> {
+    samples.sort_by(|a, b| a.object_id.cmp(&b.object_id).then(a.z.total_cmp(&b.z)));
+
+    let mut deep: Vec<(Color, u64, f64, f64)> = Vec::new();
+    let mut last: Option<(usize, f64)> = None;
+    for s in samples {
+        let same_group = last.is_some_and(|(id, z)| id == s.object_id && s.z <= z * (1.0 + MERGE_TOLERANCE));
+        if same_group {
+            let group = deep.last_mut().unwrap();
+            group.0 += s.color;
+            group.1 += 1;
+            group.3 = s.z;
+        } else {
+            deep.push((s.color, 1, s.z, s.z));
+        }
+        last = Some((s.object_id, s.z));
+    }
+
+    deep.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+    let scale = 1.0 / (spp as f64);
+    deep.into_iter()
+        .map(|(color, count, z, z_back)| {
+            let c = scale * color;
+            vec![c.x() as f32, c.y() as f32, c.z() as f32, (count as f64 * scale) as f32, z as f32, z_back as f32]
+        })
+        .collect()
+}
+
+// Renders the scene again, keeping the samples of every pixel apart by
+// depth, and writes them as a deep EXR next to the image
+pub fn render_deep(scene: &Scene, settings: &RenderSettings, img_name: &str, metadata: &Metadata) {
+    let width = settings.image_width;
+    let height = settings.image_height;
+
+    let pixels: Vec<Vec<Vec<f32>>> = (0..width * height)
+        .into_par_iter()
+        .map(|n| {
+            let i = n % width;
+            let j = height - 1 - n / width;
+
+            let samples = (0..settings.samples_per_pixel)
+                .filter_map(|s| {
+                    render::start_sample(settings, i, j, s);
+                    let r = render::sample_ray(&scene.camera, settings, i, j);
+                    trace_sample(&r, scene, settings)
+                })
+                .map(|s| DepthSample { color: render::clamp_sample(s.color, settings.clamp), ..s })
+                .collect();
+
+            merge(samples, settings.samples_per_pixel)
+        })
+        .collect();
+
+    let mut exr = DeepExrImage::new(width as usize, height as usize, &["R", "G", "B", "A", "Z", "ZBack"]);
+    exr.overscan = settings.overscan as usize;
+    exr.flip = settings.flip;
+    exr.pixel_aspect = settings.pixel_aspect;
+    exr.text = metadata.entries().to_vec();
+    for samples in pixels {
+        exr.push_pixel(samples);
+    }
+
+    let filename = pass_filename(img_name, "deep");
+    exr.write(&filename).unwrap_or_else(|_| panic!("Failed to write {}.", filename));
+    eprintln!("Wrote deep samples to {}", filename);
+}
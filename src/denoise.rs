@@ -0,0 +1,54 @@
+use rayon::prelude::*;
+
+use super::vec::Color;
+
+const RADIUS: i64 = 2;
+const SIGMA_SPATIAL: f64 = 1.5;
+const SIGMA_COLOR: f64 = 0.1;
+
+// Gamma-2 encoded color, matching how format_color displays it, so the
+// range weight compares colors the way they will be seen
+fn encoded(c: Color) -> Color {
+    Color::new(c.x().max(0.0).sqrt(), c.y().max(0.0).sqrt(), c.z().max(0.0).sqrt())
+}
+
+// Edge-preserving bilateral filter over summed pixel samples; returns sums
+// over the same number of samples so it can be written like a raw render
+pub fn denoise(pixels: &[Color], width: u64, height: u64, samples_per_pixel: u64) -> Vec<Color> {
+    let spp = samples_per_pixel as f64;
+    let w = width as i64;
+    let h = height as i64;
+
+    (0..pixels.len())
+        .into_par_iter()
+        .map(|n| {
+            let x = n as i64 % w;
+            let y = n as i64 / w;
+            let center = encoded(pixels[n] / spp);
+
+            let mut sum = Color::new(0.0, 0.0, 0.0);
+            let mut weight_sum = 0.0;
+
+            for dy in -RADIUS..=RADIUS {
+                for dx in -RADIUS..=RADIUS {
+                    let qx = x + dx;
+                    let qy = y + dy;
+                    if qx < 0 || qy < 0 || qx >= w || qy >= h {
+                        continue;
+                    }
+
+                    let q = pixels[(qy * w + qx) as usize];
+                    let diff = encoded(q / spp) - center;
+                    let spatial = ((dx * dx + dy * dy) as f64) / (2.0 * SIGMA_SPATIAL * SIGMA_SPATIAL);
+                    let range = diff.dot(diff) / (2.0 * SIGMA_COLOR * SIGMA_COLOR);
+                    let weight = (-spatial - range).exp();
+
+                    sum += weight * q;
+                    weight_sum += weight;
+                }
+            }
+
+            sum / weight_sum
+        })
+        .collect()
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Flat disk around center, facing the side its normal points to. With an
+// inner radius it is a ring, open in the middle
+pub struct Disk {
+    center: Point3,
+    normal: Vec3,
+    radius: f64,
+    inner_radius: f64,
+    mat: Arc<dyn Scatter>
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, inner_radius: f64, m: Arc<dyn Scatter>) -> Disk {
+        Disk {
+            center,
+            normal: normal.normalized(),
+            radius,
+            inner_radius,
+            mat: m
+        }
+    }
+}
+
+impl Hit for Disk {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = self.normal.dot(r.direction());
+
+        // No hit if the ray is parallel to the disk
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = self.normal.dot(self.center - r.origin()) / denom;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let offset = r.at(t) - self.center;
+        let distance_squared = offset.dot(offset);
+        if distance_squared > self.radius * self.radius || distance_squared < self.inner_radius * self.inner_radius {
+            return None;
+        }
+
+        Some(HitRecord::new(r, t, self.normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // The disk reaches radius * sin of the angle to each axis
+        let n = self.normal;
+        let extent = Vec3::new(
+            self.radius * (1.0 - n.x() * n.x()).max(0.0).sqrt(),
+            self.radius * (1.0 - n.y() * n.y()).max(0.0).sqrt(),
+            self.radius * (1.0 - n.z() * n.z()).max(0.0).sqrt()
+        );
+        Some(Aabb::new(self.center - extent, self.center + extent).padded(1e-4))
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::image::Image;
+use super::material::Scatter;
+use super::mesh::{Mesh, MeshData};
+use super::ray::Ray;
+use super::sampler;
+use super::sphere;
+use super::texture::Texture;
+use super::triangle::Triangle;
+use super::vec::{Point3, Vec3};
+
+// Where the heights of a displaced surface come from, each in [0, 1] for
+// maps of that range
+#[derive(Clone)]
+pub enum Height {
+    // Latitude-longitude image around a sphere, in the texture coordinates
+    // of Sphere::surface_at
+    Map(Arc<Image>),
+    // Texture looked up on the undisplaced surface
+    Texture(Arc<dyn Texture>),
+    // Fractal value noise in space, `frequency` features per scene unit in
+    // the first octave and every further octave twice as fine and half as
+    // strong
+    Noise { frequency: f64, octaves: u32, seed: u64 }
+}
+
+impl Height {
+    // Height over the point p of a surface with unit normal n, averaged
+    // over the color channels
+    fn at(&self, p: Point3, n: Vec3, m: &Arc<dyn Scatter>) -> f64 {
+        let c = match self {
+            Height::Map(image) => {
+                let (u, v) = sphere::uv(n);
+                // The top row is the north pole
+                image.sample(u, 1.0 - v)
+            },
+            Height::Texture(texture) => texture.value(&HitRecord::facing(p, n, m.clone())),
+            Height::Noise { frequency, octaves, seed } => return noise(*frequency * p, *octaves, *seed)
+        };
+        (c.x() + c.y() + c.z()) / 3.0
+    }
+}
+
+// Value noise in [0, 1], smoothly interpolated between random values at the
+// points of the integer lattice
+pub fn noise(p: Point3, octaves: u32, seed: u64) -> f64 {
+    let lattice = |x: i64, y: i64, z: i64, octave: u32| {
+        let h = sampler::hash(sampler::hash(sampler::hash(sampler::hash(seed ^ octave as u64) ^ x as u64) ^ y as u64) ^ z as u64);
+        (h >> 11) as f64 / (1u64 << 53) as f64
+    };
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+
+    let mut total = 0.0;
+    let mut sum = 0.0;
+    for octave in 0..octaves {
+        let weight = 0.5_f64.powi(octave as i32);
+        let q = 2.0_f64.powi(octave as i32) * p;
+        let (x0, y0, z0) = (q.x().floor(), q.y().floor(), q.z().floor());
+        let (fx, fy, fz) = (smooth(q.x() - x0), smooth(q.y() - y0), smooth(q.z() - z0));
+        let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+
+        let mut value = 0.0;
+        for corner in 0..8 {
+            let (dx, dy, dz) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let w = if dx == 1 { fx } else { 1.0 - fx }
+                * if dy == 1 { fy } else { 1.0 - fy }
+                * if dz == 1 { fz } else { 1.0 - fz };
+            value += w * lattice(x0 + dx, y0 + dy, z0 + dz, octave);
+        }
+        sum += weight * value;
+        total += weight;
+    }
+    sum / total
+}
+
+// Displacement of the objects of a material: `amount` times the height
+// along their normals, on meshes split `subdivisions` times first
+pub struct Displacement {
+    pub amount: f64,
+    pub subdivisions: u32,
+    pub height: Height
+}
+
+// Splits every face in four at the midpoints of its edges, which `midpoint`
+// places, keeping the winding
+fn subdivide(vertices: &mut Vec<Vec3>, faces: &[[usize; 3]], midpoint: impl Fn(Vec3, Vec3) -> Vec3) -> Vec<[usize; 3]> {
+    // Edge midpoints are shared by the faces on both sides
+    let mut midpoints = HashMap::new();
+    let mut split = |a: usize, b: usize, vertices: &mut Vec<Vec3>| {
+        *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+            vertices.push(midpoint(vertices[a], vertices[b]));
+            vertices.len() - 1
+        })
+    };
+
+    faces.iter().flat_map(|&[a, b, c]| {
+        let ab = split(a, b, vertices);
+        let bc = split(b, c, vertices);
+        let ca = split(c, a, vertices);
+        [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+    }).collect()
+}
+
+// Unit sphere as an icosahedron whose faces are split in four `subdivisions`
+// times, 20 * 4^subdivisions triangles of nearly the same size, wound
+// counter-clockwise seen from outside
+fn icosphere(subdivisions: u32) -> (Vec<Vec3>, Vec<[usize; 3]>) {
+    let t = (1.0 + 5.0_f64.sqrt()) / 2.0;
+    let mut vertices: Vec<Vec3> = [
+        (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+        (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+        (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0)
+    ].iter().map(|&(x, y, z)| Vec3::new(x, y, z).normalized()).collect();
+
+    let mut faces = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1]
+    ];
+
+    for _ in 0..subdivisions {
+        faces = subdivide(&mut vertices, &faces, |a, b| (a + b).normalized());
+    }
+
+    (vertices, faces)
+}
+
+// Sphere tessellated at load time and pushed out along its normals by
+// `amount` times the height, so the map changes its silhouette and shadows
+// as well as its shading
+pub fn sphere(center: Point3, radius: f64, amount: f64, subdivisions: u32, height: &Height, m: Arc<dyn Scatter>) -> Mesh {
+    let (directions, faces) = icosphere(subdivisions);
+    let points: Vec<Point3> = directions.iter()
+        .map(|&n| center + (radius + amount * height.at(center + radius * n, n, &m)) * n)
+        .collect();
+
+    let triangles = faces.iter()
+        .map(|&[a, b, c]| Triangle::new(points[a], points[b], points[c], m.clone()))
+        .collect();
+    Mesh::new(triangles)
+}
+
+// Mesh split into finer triangles and displaced along its smoothed vertex
+// normals. Vertices at the same position are welded first, so faces that
+// don't share vertices in the file, like those of STL files or along the
+// seams of texture coordinates, don't tear apart.
+pub fn mesh(data: &MeshData, displacement: &Displacement, m: Arc<dyn Scatter>) -> Mesh {
+    // Positions a millionth of the mesh's size apart are the same
+    let extent = data.positions.iter().fold(0.0_f64, |e, p| e.max(p.x().abs()).max(p.y().abs()).max(p.z().abs()));
+    let tolerance = 1e-6 * extent.max(f64::MIN_POSITIVE);
+
+    let mut welded = HashMap::new();
+    let mut vertices = Vec::new();
+    let mut index = |p: Point3, vertices: &mut Vec<Vec3>| {
+        let key = [p.x(), p.y(), p.z()].map(|x| (x / tolerance).round() as i64);
+        *welded.entry(key).or_insert_with(|| {
+            vertices.push(p);
+            vertices.len() - 1
+        })
+    };
+    let mut faces: Vec<[usize; 3]> = data.oriented_faces()
+        .map(|(face, _)| face.map(|v| index(data.positions[v], &mut vertices)))
+        .collect();
+
+    for _ in 0..displacement.subdivisions {
+        faces = subdivide(&mut vertices, &faces, |a, b| 0.5 * (a + b));
+    }
+
+    // Face normals weighted by area
+    let mut normals = vec![Vec3::new(0.0, 0.0, 0.0); vertices.len()];
+    for &[a, b, c] in &faces {
+        let n = (vertices[b] - vertices[a]).cross(vertices[c] - vertices[a]);
+        for v in [a, b, c] {
+            normals[v] += n;
+        }
+    }
+
+    let points: Vec<Point3> = vertices.iter().zip(&normals).map(|(&p, &n)| {
+        // Isolated or degenerate vertices stay put
+        if n.length() == 0.0 {
+            return p;
+        }
+        let n = n.normalized();
+        p + displacement.amount * displacement.height.at(p, n, &m) * n
+    }).collect();
+
+    let triangles = faces.iter()
+        .map(|&[a, b, c]| Triangle::new(points[a], points[b], points[c], m.clone()))
+        .collect();
+    Mesh::new(triangles)
+}
+
+// Steps of the march through the shell the displaced surface lies in
+const MAX_STEPS: usize = 256;
+
+// Sphere whose radius in each direction grows by `amount` times the height
+// there, found by marching rays through the shell it lies in instead of
+// tessellating it, so the detail is as fine as the heights
+pub struct DisplacedSphere {
+    center: Point3,
+    radius: f64,
+    amount: f64,
+    height: Height,
+    mat: Arc<dyn Scatter>
+}
+
+impl DisplacedSphere {
+    pub fn new(center: Point3, radius: f64, amount: f64, height: Height, m: Arc<dyn Scatter>) -> DisplacedSphere {
+        DisplacedSphere {
+            center,
+            radius,
+            amount,
+            height,
+            mat: m
+        }
+    }
+
+    // Positive outside the surface; not a true distance, but close to one
+    // where the heights change slowly
+    fn field(&self, p: Point3) -> f64 {
+        let offset = p - self.center;
+        let distance = offset.length();
+        if distance == 0.0 {
+            return -self.radius;
+        }
+        let n = offset / distance;
+        distance - (self.radius + self.amount * self.height.at(self.center + self.radius * n, n, &self.mat))
+    }
+
+    fn normal(&self, p: Point3) -> Vec3 {
+        let e = 1e-4 * self.radius;
+        let dx = Vec3::new(e, 0.0, 0.0);
+        let dy = Vec3::new(0.0, e, 0.0);
+        let dz = Vec3::new(0.0, 0.0, e);
+        Vec3::new(
+            self.field(p + dx) - self.field(p - dx),
+            self.field(p + dy) - self.field(p - dy),
+            self.field(p + dz) - self.field(p - dz)
+        ).normalized()
+    }
+}
+
+impl Hit for DisplacedSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // The part of the ray inside the sphere bounding every height
+        let outer = self.radius + self.amount.max(0.0);
+        let oc = r.origin() - self.center;
+        let a = r.direction().dot(r.direction());
+        let half_b = oc.dot(r.direction());
+        let c = oc.dot(oc) - outer * outer;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let sqrtd = discriminant.sqrt();
+        let mut t = ((-half_b - sqrtd) / a).max(t_min);
+        let end = ((-half_b + sqrtd) / a).min(t_max);
+        if t >= end {
+            return None;
+        }
+
+        // Steps of half the field, but at least a thousandth of the shell's
+        // thickness so that grazing rays get through, until the ray crosses
+        // to the other side
+        let speed = a.sqrt();
+        let min_step = 1e-3 * self.amount.abs().max(1e-3 * self.radius) / speed;
+        let mut f = self.field(r.at(t));
+        let side = f.signum();
+        for _ in 0..MAX_STEPS {
+            let next = (t + (0.5 * f.abs() / speed).max(min_step)).min(end);
+            let g = self.field(r.at(next));
+            if g.signum() != side {
+                // Bisection between the last two points
+                let (mut lo, mut hi) = (t, next);
+                for _ in 0..16 {
+                    let mid = 0.5 * (lo + hi);
+                    if self.field(r.at(mid)).signum() == side {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                return Some(HitRecord::new(r, hi, self.normal(r.at(hi)), self.mat.clone()));
+            }
+            if next >= end {
+                return None;
+            }
+            t = next;
+            f = g;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let outer = self.radius + self.amount.max(0.0);
+        let r = Vec3::new(outer, outer, outer);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+}
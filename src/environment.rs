@@ -0,0 +1,202 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::image::Image;
+use super::light::{Light, LightSample};
+use super::ray::{Ray, RayKind};
+use super::sampler;
+use super::vec::{Color, Point3, Vec3};
+
+// Which kinds of rays see an environment layer when they escape the scene
+#[derive(Clone, Copy)]
+pub struct Visibility {
+    pub camera: bool,
+    pub diffuse: bool,
+    pub specular: bool
+}
+
+impl Visibility {
+    pub const ALL: Visibility = Visibility { camera: true, diffuse: true, specular: true };
+
+    pub fn sees(self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Diffuse => self.diffuse,
+            RayKind::Specular => self.specular
+        }
+    }
+}
+
+pub enum Layer {
+    // Vertical blend from the horizon color to the zenith color
+    Gradient { horizon: Color, zenith: Color },
+    Constant(Color),
+    // Latitude-longitude environment map, rotated about +y by `rotate` radians
+    Hdri { image: Arc<Image>, intensity: f64, rotate: f64 },
+    // Sun disk; its diffuse lighting comes from the matching SunLight
+    Sun { direction: Vec3, cos_radius: f64, radiance: Color }
+}
+
+impl Layer {
+    fn radiance(&self, dir: Vec3) -> Color {
+        match self {
+            Layer::Gradient { horizon, zenith } => {
+                let t = 0.5 * (dir.y() + 1.0);
+                (1.0 - t) * *horizon + t * *zenith
+            },
+            Layer::Constant(c) => *c,
+            Layer::Hdri { image, intensity, rotate } => {
+                let phi = dir.x().atan2(-dir.z()) + rotate;
+                let theta = dir.y().clamp(-1.0, 1.0).acos();
+                *intensity * image.sample(0.5 + phi / (2.0 * PI), theta / PI)
+            },
+            Layer::Sun { direction, cos_radius, radiance } => {
+                if dir.dot(*direction) >= *cos_radius {
+                    *radiance
+                } else {
+                    Color::new(0.0, 0.0, 0.0)
+                }
+            }
+        }
+    }
+}
+
+// Radiance arriving from outside the scene, as a stack of added layers
+pub struct Environment {
+    layers: Vec<(Layer, Visibility)>
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment {
+            layers: Vec::new()
+        }
+    }
+
+    pub fn push(&mut self, layer: Layer, visibility: Visibility) {
+        self.layers.push((layer, visibility));
+    }
+
+    pub fn radiance(&self, r: &Ray, kind: RayKind) -> Color {
+        let dir = r.direction().normalized();
+
+        let mut total = Color::new(0.0, 0.0, 0.0);
+        for (layer, visibility) in &self.layers {
+            if visibility.sees(kind) {
+                total += layer.radiance(dir);
+            }
+        }
+
+        total
+    }
+}
+
+impl Default for Environment {
+    // The original white-to-blue sky
+    fn default() -> Environment {
+        let mut env = Environment::new();
+        env.push(Layer::Gradient {
+            horizon: Color::new(1.0, 1.0, 1.0),
+            zenith: Color::new(0.5, 0.7, 1.0)
+        }, Visibility::ALL);
+        env
+    }
+}
+
+// Softboxes of the studio environment: azimuth and elevation of the center
+// and half extents in degrees, with their radiance. Azimuth 0 looks down -z,
+// so the key and fill lights face a camera placed on +z.
+const SOFTBOXES: [(f64, f64, f64, f64, f64); 3] = [
+    (-135.0, 35.0, 22.0, 16.0, 6.0),
+    (120.0, 20.0, 15.0, 12.0, 1.5),
+    (0.0, 50.0, 30.0, 10.0, 4.0)
+];
+
+// Procedural latitude-longitude map of a photo studio: dim walls, a darker
+// floor and three softboxes (key, fill and rim)
+pub fn studio_hdri() -> Image {
+    let (width, height) = (512, 256);
+    let mut data = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        let elevation = 90.0 - 180.0 * (y as f64 + 0.5) / (height as f64);
+
+        for x in 0..width {
+            let azimuth = 360.0 * (x as f64 + 0.5) / (width as f64) - 180.0;
+
+            let mut radiance = if elevation < 0.0 {
+                0.03
+            } else {
+                0.08 + 0.06 * elevation / 90.0
+            };
+
+            for (center_azimuth, center_elevation, half_width, half_height, power) in SOFTBOXES {
+                let d_azimuth = (azimuth - center_azimuth + 540.0).rem_euclid(360.0) - 180.0;
+                if d_azimuth.abs() < half_width && (elevation - center_elevation).abs() < half_height {
+                    radiance += power;
+                }
+            }
+
+            data.push(Color::new(radiance, radiance, radiance));
+        }
+    }
+
+    Image { width, height, data }
+}
+
+// Directional light from a sun disk of the given angular radius (degrees),
+// whose irradiance at normal incidence is `irradiance`
+pub struct SunLight {
+    direction: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    cos_radius: f64,
+    irradiance: Color
+}
+
+impl SunLight {
+    pub fn new(direction: Vec3, angular_radius: f64, irradiance: Color) -> SunLight {
+        let direction = direction.normalized();
+        let (tangent, bitangent) = direction.orthonormal_basis();
+
+        SunLight {
+            direction,
+            tangent,
+            bitangent,
+            cos_radius: angular_radius.to_radians().cos(),
+            irradiance
+        }
+    }
+
+    // Sky layer showing the disk with the radiance matching this light
+    pub fn layer(&self) -> Layer {
+        let solid_angle = 2.0 * PI * (1.0 - self.cos_radius);
+        Layer::Sun {
+            direction: self.direction,
+            cos_radius: self.cos_radius,
+            radiance: self.irradiance / solid_angle
+        }
+    }
+}
+
+impl Light for SunLight {
+    fn sample(&self, _p: Point3) -> Option<LightSample> {
+        // Uniform direction within the cone of the disk
+        let mut rng = sampler::rng();
+        let cos_theta = 1.0 - rng.gen::<f64>() * (1.0 - self.cos_radius);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+
+        let wi = cos_theta * self.direction
+            + sin_theta * phi.cos() * self.tangent
+            + sin_theta * phi.sin() * self.bitangent;
+
+        Some(LightSample {
+            wi,
+            dist: f64::INFINITY,
+            radiance: self.irradiance
+        })
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::render::Flip;
+
+// Uncompressed single-part scanline OpenEXR file with 32-bit float channels
+pub struct ExrImage {
+    pub width: usize,
+    pub height: usize,
+    // Pixels on every side of the data that lie outside the image
+    pub overscan: usize,
+    // Mirroring of the pixels as they are written
+    pub flip: Flip,
+    // Width of a pixel over its height
+    pub pixel_aspect: f64,
+    // Extra string attributes, such as the render's metadata
+    pub text: Vec<(String, String)>,
+    // Channel name and its values, top row first
+    channels: Vec<(String, Vec<f32>)>
+}
+
+fn attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(kind.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+fn box2i(x_min: i32, y_min: i32, x_max: i32, y_max: i32) -> Vec<u8> {
+    [x_min, y_min, x_max, y_max].iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+// Channel list of FLOAT channels, which must already be sorted
+fn channel_list(names: &[&str]) -> Vec<u8> {
+    let mut chlist = Vec::new();
+    for name in names {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        // FLOAT pixels, not linear, reserved, x and y sampling
+        chlist.extend_from_slice(&2i32.to_le_bytes());
+        chlist.extend_from_slice(&[0, 0, 0, 0]);
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+    }
+    chlist.push(0);
+    chlist
+}
+
+// Attributes every scanline part needs, then the extra text ones. The data
+// window holds all width x height pixels, the display window leaves out the
+// overscan border around it.
+fn common_attributes(header: &mut Vec<u8>, width: usize, height: usize, overscan: usize, pixel_aspect: f64, names: &[&str], text: &[(String, String)]) {
+    let n = overscan as i32;
+    let data = box2i(-n, -n, width as i32 - n - 1, height as i32 - n - 1);
+    let display = box2i(0, 0, width as i32 - 2 * n - 1, height as i32 - 2 * n - 1);
+    attribute(header, "channels", "chlist", &channel_list(names));
+    attribute(header, "compression", "compression", &[0]);
+    attribute(header, "dataWindow", "box2i", &data);
+    attribute(header, "displayWindow", "box2i", &display);
+    attribute(header, "lineOrder", "lineOrder", &[0]);
+    attribute(header, "pixelAspectRatio", "float", &(pixel_aspect as f32).to_le_bytes());
+    attribute(header, "screenWindowCenter", "v2f", &[0u8; 8]);
+    attribute(header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    for (name, value) in text {
+        attribute(header, name, "string", value.as_bytes());
+    }
+}
+
+impl ExrImage {
+    pub fn new(width: usize, height: usize) -> ExrImage {
+        ExrImage {
+            width,
+            height,
+            overscan: 0,
+            flip: Flip::default(),
+            pixel_aspect: 1.0,
+            text: Vec::new(),
+            channels: Vec::new()
+        }
+    }
+
+    pub fn add_channel(&mut self, name: &str, values: Vec<f32>) {
+        assert_eq!(values.len(), self.width * self.height, "EXR channel {} has the wrong size", name);
+        self.channels.push((name.to_string(), values));
+    }
+
+    pub fn write(&mut self, path: &str) -> std::io::Result<()> {
+        // Readers expect channels in alphabetical order
+        self.channels.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+        let names: Vec<&str> = self.channels.iter().map(|(name, _)| name.as_str()).collect();
+        common_attributes(&mut header, self.width, self.height, self.overscan, self.pixel_aspect, &names, &self.text);
+        header.push(0);
+
+        // One scanline per block: y, byte count, then each channel's row
+        let line_bytes = self.channels.len() * self.width * 4;
+        let block_bytes = 8 + line_bytes;
+        let table_start = header.len() + 8 * self.height;
+
+        let channels: Vec<Vec<f32>> = self.channels.iter()
+            .map(|(_, values)| self.flip.apply(values, self.width))
+            .collect();
+
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&header)?;
+        for y in 0..self.height {
+            out.write_all(&((table_start + y * block_bytes) as u64).to_le_bytes())?;
+        }
+
+        for y in 0..self.height {
+            out.write_all(&(y as i32 - self.overscan as i32).to_le_bytes())?;
+            out.write_all(&(line_bytes as i32).to_le_bytes())?;
+            for values in &channels {
+                for v in &values[y * self.width..(y + 1) * self.width] {
+                    out.write_all(&v.to_le_bytes())?;
+                }
+            }
+        }
+
+        out.flush()
+    }
+}
+
+// Deep scanline OpenEXR file: every pixel holds its own list of samples, each
+// with a value for all of the FLOAT channels
+pub struct DeepExrImage {
+    pub width: usize,
+    pub height: usize,
+    pub overscan: usize,
+    pub flip: Flip,
+    pub pixel_aspect: f64,
+    pub text: Vec<(String, String)>,
+    channels: Vec<String>,
+    // Samples of each pixel, top row first, as rows of channel values
+    pixels: Vec<Vec<Vec<f32>>>
+}
+
+impl DeepExrImage {
+    pub fn new(width: usize, height: usize, channels: &[&str]) -> DeepExrImage {
+        DeepExrImage {
+            width,
+            height,
+            overscan: 0,
+            flip: Flip::default(),
+            pixel_aspect: 1.0,
+            text: Vec::new(),
+            channels: channels.iter().map(|c| c.to_string()).collect(),
+            pixels: Vec::with_capacity(width * height)
+        }
+    }
+
+    // Appends the next pixel; each sample lists its values in the order the
+    // channels were given
+    pub fn push_pixel(&mut self, samples: Vec<Vec<f32>>) {
+        assert!(samples.iter().all(|s| s.len() == self.channels.len()), "Deep EXR sample has the wrong size");
+        self.pixels.push(samples);
+    }
+
+    pub fn write(&self, path: &str) -> std::io::Result<()> {
+        assert_eq!(self.pixels.len(), self.width * self.height, "Deep EXR image is incomplete");
+
+        // Sample values are stored in alphabetical channel order
+        let mut order: Vec<usize> = (0..self.channels.len()).collect();
+        order.sort_by(|&a, &b| self.channels[a].cmp(&self.channels[b]));
+        let names: Vec<&str> = order.iter().map(|&c| self.channels[c].as_str()).collect();
+
+        let max_samples = self.pixels.iter().map(|p| p.len()).max().unwrap_or(0);
+
+        // Version 2 with the non-image (deep data) flag set
+        let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0x08, 0, 0];
+        common_attributes(&mut header, self.width, self.height, self.overscan, self.pixel_aspect, &names, &self.text);
+        attribute(&mut header, "chunkCount", "int", &(self.height as i32).to_le_bytes());
+        attribute(&mut header, "maxSamplesPerPixel", "int", &(max_samples as i32).to_le_bytes());
+        attribute(&mut header, "name", "string", b"deep");
+        attribute(&mut header, "type", "string", b"deepscanline");
+        attribute(&mut header, "version", "int", &1i32.to_le_bytes());
+        header.push(0);
+
+        // One scanline per chunk: y, the sizes of the offset table and the
+        // sample data (twice, as it isn't compressed), the cumulative sample
+        // counts of the row, then every channel's samples pixel by pixel
+        let pixels = self.flip.apply(&self.pixels.iter().collect::<Vec<_>>(), self.width);
+        let chunks: Vec<Vec<u8>> = pixels.chunks(self.width).enumerate().map(|(y, row)| {
+            let mut table = Vec::with_capacity(4 * self.width);
+            let mut total = 0;
+            for pixel in row {
+                total += pixel.len();
+                table.extend_from_slice(&(total as i32).to_le_bytes());
+            }
+
+            let mut data = Vec::with_capacity(4 * total * order.len());
+            for &c in &order {
+                for sample in row.iter().flat_map(|pixel| pixel.iter()) {
+                    data.extend_from_slice(&sample[c].to_le_bytes());
+                }
+            }
+
+            let mut chunk = Vec::with_capacity(28 + table.len() + data.len());
+            chunk.extend_from_slice(&(y as i32 - self.overscan as i32).to_le_bytes());
+            chunk.extend_from_slice(&(table.len() as u64).to_le_bytes());
+            chunk.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            chunk.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            chunk.extend_from_slice(&table);
+            chunk.extend_from_slice(&data);
+            chunk
+        }).collect();
+
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&header)?;
+        let mut offset = header.len() + 8 * self.height;
+        for chunk in &chunks {
+            out.write_all(&(offset as u64).to_le_bytes())?;
+            offset += chunk.len();
+        }
+        for chunk in &chunks {
+            out.write_all(chunk)?;
+        }
+
+        out.flush()
+    }
+}
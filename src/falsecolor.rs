@@ -0,0 +1,61 @@
+use super::metadata::Metadata;
+use super::render::RenderSettings;
+use super::vec::Color;
+
+// Middle gray, the exposure the scale is centred on
+const MIDDLE_GRAY: f64 = 0.18;
+
+// Bands of the scale in stops from middle gray, with the display color
+// they are painted in and their name in the summary. Luminances outside
+// every band are shown in gray at their own brightness.
+const BANDS: [(f64, f64, [f64; 3], &str); 6] = [
+    (f64::NEG_INFINITY, -6.0, [0.5, 0.0, 0.6], "crushed"),
+    (-6.0, -5.0, [0.0, 0.3, 1.0], "near black"),
+    (-1.0 / 6.0, 1.0 / 6.0, [0.1, 0.8, 0.1], "middle gray"),
+    (0.75, 1.25, [1.0, 0.5, 0.7], "one stop over"),
+    (2.0, 2.474, [1.0, 0.9, 0.0], "near clipping"),
+    // Luminance 1, what the image clips at
+    (2.474, f64::INFINITY, [1.0, 0.0, 0.0], "clipped")
+];
+
+fn band(luminance: f64) -> Option<usize> {
+    let stops = (luminance / MIDDLE_GRAY).log2();
+    BANDS.iter().position(|&(low, high, _, _)| stops >= low && stops < high)
+}
+
+// Writes the luminance of every pixel on a false color scale like those of
+// cinema cameras, scene.falsecolor.ppm, and prints how much of the image
+// falls in each band
+pub fn write_false_color(img_name: &str, pixels: &[Color], settings: &RenderSettings, metadata: &Metadata) {
+    let samples = settings.samples_per_pixel as f64;
+    let visible = settings.crop(pixels);
+    let mut counts = [0usize; BANDS.len()];
+
+    let colors: Vec<Color> = pixels.iter().map(|p| {
+        let c = *p / samples;
+        let luminance = 0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z();
+        let display = match band(luminance) {
+            Some(k) => Color::new(BANDS[k].2[0], BANDS[k].2[1], BANDS[k].2[2]),
+            None => {
+                let gray = luminance.clamp(0.0, 1.0).sqrt();
+                Color::new(gray, gray, gray)
+            }
+        };
+        // Back to linear sums, which the image is encoded from
+        samples * (display * display)
+    }).collect();
+
+    for p in visible.iter() {
+        let c = *p / samples;
+        if let Some(k) = band(0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()) {
+            counts[k] += 1;
+        }
+    }
+
+    let filename = super::display_filename(img_name, "falsecolor");
+    super::write_image(&filename, &colors, None, settings, metadata);
+    eprintln!("Wrote false color to {}", filename);
+    for (k, &(_, _, _, name)) in BANDS.iter().enumerate() {
+        eprintln!("  {:>13}: {:5.1}%", name, 100.0 * counts[k] as f64 / visible.len().max(1) as f64);
+    }
+}
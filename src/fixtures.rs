@@ -0,0 +1,14 @@
+// Files read by the tests of the loaders: small ones checked in under
+// tests/fixtures, and binary ones the tests write themselves
+
+pub fn path(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+// Writes bytes to a file of the temporary directory, named after the test
+// process so runs don't overwrite each other, and returns its path
+pub fn write(name: &str, bytes: &[u8]) -> String {
+    let path = std::env::temp_dir().join(format!("rt-{}-{}", std::process::id(), name));
+    std::fs::write(&path, bytes).unwrap_or_else(|_| panic!("Unable to write {}.", path.display()));
+    path.to_string_lossy().into_owned()
+}
@@ -0,0 +1,123 @@
+use std::time::Instant;
+
+use super::aov;
+use super::metadata::Metadata;
+use super::render::{self, RenderSettings, SplitColor};
+use super::scene::Scene;
+use super::vec::Color;
+
+// Pixels on each side of the window the sharpness of a pixel is summed over
+const WINDOW: i64 = 3;
+
+// Focus distances from near to far, evenly spaced in their inverse the way
+// a focus rail is stepped, so that the depths of field of neighbouring
+// slices overlap about as much close up as far away
+#[derive(Clone, Copy)]
+pub struct FocusStack {
+    pub near: f64,
+    pub far: f64,
+    pub count: u64
+}
+
+impl FocusStack {
+    // "near,far,count"
+    pub fn parse(value: &str) -> Option<FocusStack> {
+        let parts: Vec<&str> = value.split(',').map(str::trim).collect();
+        match parts[..] {
+            [near, far, count] => Some(FocusStack {
+                near: near.parse().ok()?,
+                far: far.parse().ok()?,
+                count: count.parse().ok()?
+            }),
+            _ => None
+        }
+    }
+
+    pub fn distances(&self) -> Vec<f64> {
+        (0..self.count).map(|k| {
+            let t = k as f64 / (self.count - 1) as f64;
+            1.0 / ((1.0 - t) / self.near + t / self.far)
+        }).collect()
+    }
+}
+
+// Sharpness of every pixel: the Laplacians of the even and odd samples'
+// luminance multiplied and summed over a window around it. Detail shows in
+// both halves and adds up, while their noise is independent and cancels
+// out. The luminance is compressed so that bright highlights don't outweigh
+// the detail of everything else.
+fn sharpness(split: &[SplitColor], width: usize, height: usize, samples: u64) -> Vec<f64> {
+    let laplacian = |half: &dyn Fn(&SplitColor) -> Color, n: u64| -> Vec<f64> {
+        let lum: Vec<f64> = split.iter().map(|p| {
+            let c = half(p);
+            let l = (0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()) / n as f64;
+            l / (1.0 + l)
+        }).collect();
+        let at = |x: i64, y: i64| lum[(y.clamp(0, height as i64 - 1) as usize) * width + x.clamp(0, width as i64 - 1) as usize];
+
+        (0..width * height).map(|i| {
+            let (x, y) = ((i % width) as i64, (i / width) as i64);
+            4.0 * at(x, y) - at(x - 1, y) - at(x + 1, y) - at(x, y - 1) - at(x, y + 1)
+        }).collect()
+    };
+    let even = laplacian(&|p| p.even, samples.div_ceil(2));
+    let odd = laplacian(&|p| p.odd, samples / 2);
+
+    (0..width * height).map(|i| {
+        let (x, y) = ((i % width) as i64, (i / width) as i64);
+        let mut sum = 0.0;
+        for dy in -WINDOW..=WINDOW {
+            for dx in -WINDOW..=WINDOW {
+                let (sx, sy) = ((x + dx).clamp(0, width as i64 - 1), (y + dy).clamp(0, height as i64 - 1));
+                let k = sy as usize * width + sx as usize;
+                sum += even[k] * odd[k];
+            }
+        }
+        sum
+    }).collect()
+}
+
+// Renders the image once for every distance of the stack, writes each slice
+// as a linear EXR next to the image, scene.focus1.exr to scene.focusN.exr
+// from near to far, and returns the samples of the slice that is sharpest
+// around each pixel, like render::render_split. The slices keep the
+// defocused highlights at their full brightness for merging elsewhere.
+pub fn render_focus_stack(scene: &mut Scene, settings: &RenderSettings, stack: FocusStack, img_name: &str, source: &Metadata) -> Vec<SplitColor> {
+    let camera = scene.camera.clone();
+    let digits = stack.count.to_string().len();
+    let (width, height) = (settings.image_width as usize, settings.image_height as usize);
+
+    let mut merged = Vec::new();
+    let mut best = Vec::new();
+    for (k, distance) in stack.distances().into_iter().enumerate() {
+        let start = Instant::now();
+        scene.camera = camera.refocused(distance);
+        let split = render::render_split(scene, settings);
+        let pixels: Vec<Color> = split.iter().map(|p| p.total()).collect();
+        let coverage = scene.has_holdouts.then(|| render::coverage(&split));
+
+        let mut metadata = source.clone();
+        metadata.add_settings(settings);
+        metadata.add("FocusDistance", format!("{:.3}", distance));
+        metadata.add("RenderTime", format!("{:.3} s", start.elapsed().as_secs_f64()));
+        let filename = aov::pass_filename(img_name, &format!("focus{:0digits$}", k + 1));
+        super::write_exr(&filename, &pixels, coverage.as_deref(), settings.samples_per_pixel, settings, &metadata);
+        eprintln!("Wrote focus distance {:.3} to {}", distance, filename);
+
+        let sharp = sharpness(&split, width, height, settings.samples_per_pixel);
+        if merged.is_empty() {
+            merged = split;
+            best = sharp;
+            continue;
+        }
+        for ((m, b), (p, s)) in merged.iter_mut().zip(best.iter_mut()).zip(split.into_iter().zip(sharp)) {
+            if s > *b {
+                *m = p;
+                *b = s;
+            }
+        }
+    }
+
+    scene.camera = camera;
+    merged
+}
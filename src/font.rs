@@ -0,0 +1,100 @@
+// 5x7 bitmap font for labels. Letters are drawn in upper case; characters
+// without a glyph are drawn as '?'.
+
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 7;
+
+const GLYPHS: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    (' ', [".....", ".....", ".....", ".....", ".....", ".....", "....."]),
+    ('A', [".###.", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('B', ["####.", "#...#", "#...#", "####.", "#...#", "#...#", "####."]),
+    ('C', [".###.", "#...#", "#....", "#....", "#....", "#...#", ".###."]),
+    ('D', ["####.", "#...#", "#...#", "#...#", "#...#", "#...#", "####."]),
+    ('E', ["#####", "#....", "#....", "####.", "#....", "#....", "#####"]),
+    ('F', ["#####", "#....", "#....", "####.", "#....", "#....", "#...."]),
+    ('G', [".###.", "#...#", "#....", "#.###", "#...#", "#...#", ".####"]),
+    ('H', ["#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#"]),
+    ('I', [".###.", "..#..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('J', ["..###", "...#.", "...#.", "...#.", "...#.", "#..#.", ".##.."]),
+    ('K', ["#...#", "#..#.", "#.#..", "##...", "#.#..", "#..#.", "#...#"]),
+    ('L', ["#....", "#....", "#....", "#....", "#....", "#....", "#####"]),
+    ('M', ["#...#", "##.##", "#.#.#", "#.#.#", "#...#", "#...#", "#...#"]),
+    ('N', ["#...#", "##..#", "#.#.#", "#..##", "#...#", "#...#", "#...#"]),
+    ('O', [".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('P', ["####.", "#...#", "#...#", "####.", "#....", "#....", "#...."]),
+    ('Q', [".###.", "#...#", "#...#", "#...#", "#.#.#", "#..#.", ".##.#"]),
+    ('R', ["####.", "#...#", "#...#", "####.", "#.#..", "#..#.", "#...#"]),
+    ('S', [".####", "#....", "#....", ".###.", "....#", "....#", "####."]),
+    ('T', ["#####", "..#..", "..#..", "..#..", "..#..", "..#..", "..#.."]),
+    ('U', ["#...#", "#...#", "#...#", "#...#", "#...#", "#...#", ".###."]),
+    ('V', ["#...#", "#...#", "#...#", "#...#", "#...#", ".#.#.", "..#.."]),
+    ('W', ["#...#", "#...#", "#...#", "#.#.#", "#.#.#", "#.#.#", ".#.#."]),
+    ('X', ["#...#", "#...#", ".#.#.", "..#..", ".#.#.", "#...#", "#...#"]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#..", "..#.."]),
+    ('Z', ["#####", "....#", "...#.", "..#..", ".#...", "#....", "#####"]),
+    ('0', [".###.", "#...#", "#..##", "#.#.#", "##..#", "#...#", ".###."]),
+    ('1', ["..#..", ".##..", "..#..", "..#..", "..#..", "..#..", ".###."]),
+    ('2', [".###.", "#...#", "....#", "...#.", "..#..", ".#...", "#####"]),
+    ('3', ["#####", "...#.", "..#..", "...#.", "....#", "#...#", ".###."]),
+    ('4', ["...#.", "..##.", ".#.#.", "#..#.", "#####", "...#.", "...#."]),
+    ('5', ["#####", "#....", "####.", "....#", "....#", "#...#", ".###."]),
+    ('6', ["..##.", ".#...", "#....", "####.", "#...#", "#...#", ".###."]),
+    ('7', ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."]),
+    ('8', [".###.", "#...#", "#...#", ".###.", "#...#", "#...#", ".###."]),
+    ('9', [".###.", "#...#", "#...#", ".####", "....#", "...#.", ".##.."]),
+    ('.', [".....", ".....", ".....", ".....", ".....", ".##..", ".##.."]),
+    (',', [".....", ".....", ".....", ".....", ".##..", "..#..", ".#..."]),
+    ('-', [".....", ".....", ".....", "#####", ".....", ".....", "....."]),
+    ('_', [".....", ".....", ".....", ".....", ".....", ".....", "#####"]),
+    ('/', [".....", "....#", "...#.", "..#..", ".#...", "#....", "....."]),
+    (':', [".....", ".##..", ".##..", ".....", ".##..", ".##..", "....."]),
+    ('(', ["...#.", "..#..", ".#...", ".#...", ".#...", "..#..", "...#."]),
+    (')', [".#...", "..#..", "...#.", "...#.", "...#.", "..#..", ".#..."]),
+    ('=', [".....", ".....", "#####", ".....", "#####", ".....", "....."]),
+    ('+', [".....", "..#..", "..#..", "#####", "..#..", "..#..", "....."]),
+    ('%', ["##...", "##..#", "...#.", "..#..", ".#...", "#..##", "...##"]),
+    ('?', [".###.", "#...#", "....#", "...#.", "..#..", ".....", "..#.."]),
+];
+
+fn glyph(c: char) -> &'static [&'static str; GLYPH_HEIGHT] {
+    let c = c.to_ascii_uppercase();
+    GLYPHS.iter()
+        .find(|(g, _)| *g == c)
+        .or_else(|| GLYPHS.iter().find(|(g, _)| *g == '?'))
+        .map(|(_, rows)| rows)
+        .unwrap()
+}
+
+// Width in pixels of text drawn at the given scale, with one pixel of
+// spacing between glyphs
+pub fn text_width(text: &str, scale: usize) -> usize {
+    let n = text.chars().count();
+    if n == 0 { 0 } else { (n * (GLYPH_WIDTH + 1) - 1) * scale }
+}
+
+// Draws text with its top left corner at (x, y) into an RGB image, clipping
+// what falls outside
+pub fn draw_text(rgb: &mut [u8], width: usize, height: usize, (x, y): (usize, usize), text: &str, color: [u8; 3], scale: usize) {
+    for (n, c) in text.chars().enumerate() {
+        let left = x + n * (GLYPH_WIDTH + 1) * scale;
+
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for (col, bit) in bits.chars().enumerate() {
+                if bit != '#' {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let px = left + col * scale + dx;
+                        let py = y + row * scale + dy;
+                        if px < width && py < height {
+                            let k = 3 * (py * width + px);
+                            rgb[k..k + 3].copy_from_slice(&color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt::Write;
+use std::fs;
+
+use super::heightfield;
+use super::sampler;
+use super::vec::{Point3, Vec3};
+
+// Terrain of the landscape preset, its fractal noise written into the scene
+// as heightfield attributes and evaluated here to place things on it
+struct Terrain {
+    samples: Vec<f64>,
+    n: usize,
+    size: f64,
+    height: f64
+}
+
+impl Terrain {
+    const RESOLUTION: usize = 256;
+    const OCTAVES: u32 = 6;
+    const FREQUENCY: f64 = 3.0;
+
+    fn new(size: f64, seed: u64) -> Terrain {
+        Terrain {
+            samples: heightfield::noise(Terrain::RESOLUTION, Terrain::OCTAVES, Terrain::FREQUENCY, seed),
+            n: Terrain::RESOLUTION,
+            size,
+            height: 0.15 * size
+        }
+    }
+
+    // Height over (x, z) on the same two triangles per cell the heightfield
+    // is made of
+    fn height_at(&self, x: f64, z: f64) -> f64 {
+        let cell = self.size / (self.n - 1) as f64;
+        let u = ((x + 0.5 * self.size) / cell).clamp(0.0, (self.n - 1) as f64 - 1e-9);
+        let v = ((z + 0.5 * self.size) / cell).clamp(0.0, (self.n - 1) as f64 - 1e-9);
+        let (i, k) = (u.floor() as usize, v.floor() as usize);
+        let (fx, fz) = (u - i as f64, v - k as f64);
+
+        let h = |i: usize, k: usize| self.height * self.samples[k * self.n + i];
+        if fx > fz {
+            (1.0 - fx) * h(i, k) + (fx - fz) * h(i + 1, k) + fz * h(i + 1, k + 1)
+        } else {
+            (1.0 - fz) * h(i, k) + (fz - fx) * h(i, k + 1) + fx * h(i + 1, k + 1)
+        }
+    }
+
+    // Upward normal, from the heights a cell away on either side
+    fn normal_at(&self, x: f64, z: f64) -> Vec3 {
+        let e = self.size / (self.n - 1) as f64;
+        let slope_x = (self.height_at(x + e, z) - self.height_at(x - e, z)) / (2.0 * e);
+        let slope_z = (self.height_at(x, z + e) - self.height_at(x, z - e)) / (2.0 * e);
+        Vec3::new(-slope_x, 1.0, -slope_z).normalized()
+    }
+}
+
+fn xml_vec(v: Vec3) -> String {
+    format!("{:.3} {:.3} {:.3}", v.x(), v.y(), v.z())
+}
+
+// Scene file of an outdoor landscape: fractal terrain under a gradient sky
+// and a low sun from the side, with forests grown along random walks, so
+// that trees come in clumps and strips, and rocks strewn everywhere, both
+// instances of a single definition
+fn landscape(seed: u64, size: f64, trees: u64, rocks: u64, image: &str) -> String {
+    let terrain = Terrain::new(size, seed);
+    let mut state = sampler::hash(seed ^ 0x6c61_6e64);
+    let mut random = || {
+        state = sampler::hash(state);
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    };
+
+    // Looking down across the terrain from above its near edge
+    let half = 0.5 * size;
+    let eye_z = 0.48 * size;
+    let look_from = Point3::new(0.0, terrain.height_at(0.0, eye_z).max(0.5 * terrain.height) + 0.15 * size, eye_z);
+    let look_at = Point3::new(0.0, 0.3 * terrain.height, -0.05 * size);
+
+    let mut xml = String::new();
+    writeln!(xml, "<RT>").unwrap();
+    writeln!(xml, "    <film filename=\"{}\" />", image).unwrap();
+    writeln!(xml, "    <camera look_from=\"{}\" look_at=\"{}\" up=\"0 1 0\" aperture=\"0\"/>", xml_vec(look_from), xml_vec(look_at)).unwrap();
+    writeln!(xml, "    <environment>").unwrap();
+    writeln!(xml, "        <layer type=\"gradient\" horizon=\"0.85 0.9 1\" zenith=\"0.3 0.5 0.9\" />").unwrap();
+    writeln!(xml, "        <layer type=\"sun\" direction=\"0.8 0.35 0.15\" irradiance=\"3\" color=\"1 0.9 0.75\" />").unwrap();
+    writeln!(xml, "    </environment>").unwrap();
+    writeln!(xml, "    <world>").unwrap();
+
+    writeln!(xml, "        <define name=\"tree\">").unwrap();
+    writeln!(xml, "            <material type=\"lambertian\" color=\"0.3 0.2 0.1\" />").unwrap();
+    writeln!(xml, "            <object type=\"cylinder\" center=\"0 0.75 0\" radius=\"0.15\" height=\"1.5\" />").unwrap();
+    writeln!(xml, "            <material type=\"lambertian\" color=\"0.08 0.25 0.08\" />").unwrap();
+    writeln!(xml, "            <object type=\"cone\" apex=\"0 4.5 0\" half_angle=\"22\" height=\"3.5\" />").unwrap();
+    writeln!(xml, "            <object type=\"cone\" apex=\"0 3.2 0\" half_angle=\"28\" height=\"2.4\" />").unwrap();
+    writeln!(xml, "        </define>").unwrap();
+    writeln!(xml, "        <define name=\"rock\">").unwrap();
+    writeln!(xml, "            <material type=\"lambertian\" color=\"0.4 0.38 0.35\">").unwrap();
+    writeln!(xml, "                <displacement amount=\"0.35\" frequency=\"1.5\" octaves=\"4\" seed=\"{}\" />", seed).unwrap();
+    writeln!(xml, "            </material>").unwrap();
+    writeln!(xml, "            <object center=\"0 0 0\" radius=\"0.8\" />").unwrap();
+    writeln!(xml, "        </define>").unwrap();
+
+    writeln!(xml, "        <material type=\"lambertian\" color=\"0.3 0.35 0.18\" />").unwrap();
+    writeln!(xml, "        <object type=\"heightfield\" min=\"{} 0 {}\" max=\"{} {} {}\" resolution=\"{}\" octaves=\"{}\" frequency=\"{}\" seed=\"{}\" />",
+             -half, -half, half, terrain.height, half, Terrain::RESOLUTION, Terrain::OCTAVES, Terrain::FREQUENCY, seed).unwrap();
+
+    // Each walk plants a clump of trees, turning a little at every step,
+    // but not above the tree line or on steep slopes
+    let clumps = trees.div_ceil(25).max(1);
+    let mut planted = 0;
+    for clump in 0..clumps {
+        let mut x = (random() - 0.5) * 0.9 * size;
+        let mut z = (random() - 0.5) * 0.9 * size;
+        let mut heading = random() * 2.0 * std::f64::consts::PI;
+        let quota = (trees * (clump + 1)) / clumps;
+        for _ in 0..200 {
+            if planted >= quota {
+                break;
+            }
+            heading += (random() - 0.5) * 1.2;
+            let step = 0.01 * size * (1.0 + random());
+            let (next_x, next_z) = (x + step * heading.cos(), z + step * heading.sin());
+            if next_x.abs() > 0.48 * size || next_z.abs() > 0.48 * size {
+                // Turned back at the edge of the terrain
+                heading += std::f64::consts::PI;
+                continue;
+            }
+            (x, z) = (next_x, next_z);
+
+            let y = terrain.height_at(x, z);
+            if y > 0.6 * terrain.height || terrain.normal_at(x, z).y() < 0.85 {
+                continue;
+            }
+            let scale = 1.0 + 0.8 * random();
+            writeln!(xml, "        <use ref=\"tree\" translate=\"{}\" rotate=\"{:.1}\" scale=\"{:.2}\" />",
+                     xml_vec(Point3::new(x, y - 0.1, z)), 360.0 * random(), scale).unwrap();
+            planted += 1;
+        }
+    }
+
+    for _ in 0..rocks {
+        let x = (random() - 0.5) * 0.95 * size;
+        let z = (random() - 0.5) * 0.95 * size;
+        let scale = Vec3::new(1.0 + 1.5 * random(), 0.6 + 0.6 * random(), 1.0 + 1.5 * random());
+        // Half sunk into the ground
+        let y = terrain.height_at(x, z) - 0.3 * scale.y();
+        writeln!(xml, "        <use ref=\"rock\" translate=\"{}\" rotate=\"{:.1}\" scale=\"{:.2} {:.2} {:.2}\" />",
+                 xml_vec(Point3::new(x, y, z)), 360.0 * random(), scale.x(), scale.y(), scale.z()).unwrap();
+    }
+
+    writeln!(xml, "    </world>").unwrap();
+    writeln!(xml, "</RT>").unwrap();
+    xml
+}
+
+// `rt generate --preset landscape -o landscape.xml` writes a procedural
+// scene file to render like any other
+pub fn generate(args: impl Iterator<Item = String>) {
+    let mut preset = None;
+    let mut output = String::from("landscape.xml");
+    let mut seed = 0;
+    let mut size = 200.0;
+    let mut trees = 150;
+    let mut rocks = 40;
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("Missing value for {}.", arg));
+        match arg.as_str() {
+            "--preset" => preset = Some(value()),
+            "-o" | "--output" => output = value(),
+            "--seed" => seed = value().parse().expect("Failed to parse --seed."),
+            "--size" => size = value().parse().expect("Failed to parse --size."),
+            "--trees" => trees = value().parse().expect("Failed to parse --trees."),
+            "--rocks" => rocks = value().parse().expect("Failed to parse --rocks."),
+            other => panic!("Unknown option '{}'.", other)
+        }
+    }
+    if size <= 0.0 {
+        panic!("The --size must be positive.");
+    }
+
+    // The image is named after the scene file
+    let stem = output.strip_suffix(".xml").unwrap_or(&output);
+    let image = format!("{}.ppm", stem.rsplit('/').next().unwrap_or(stem));
+
+    let xml = match preset.as_deref() {
+        Some("landscape") => landscape(seed, size, trees, rocks, &image),
+        Some(other) => panic!("The preset '{}' doesn't exists!", other),
+        None => panic!("Missing --preset, expected landscape.")
+    };
+    fs::write(&output, xml).unwrap_or_else(|_| panic!("Failed to write {}.", output));
+    eprintln!("Wrote {}", output);
+}
@@ -0,0 +1,90 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use super::cuboid::Cuboid;
+use super::hit::Hit;
+use super::material::Scatter;
+use super::sampler;
+use super::sphere::Sphere;
+use super::triangle::Triangle;
+use super::vec::{Point3, Vec3};
+
+// Procedural stress scenes for benchmarking the acceleration structures,
+// whose object counts grow quickly with their parameters
+
+// Sphere with nine spheres a third its size on it, each carrying nine of
+// its own in turn, `depth` levels deep: six around its equator and three
+// on top, facing away from the parent. (9^(depth + 1) - 1) / 8 spheres.
+pub fn sphereflake(center: Point3, radius: f64, depth: u32, m: Arc<dyn Scatter>) -> Vec<Box<dyn Hit>> {
+    let mut objects: Vec<Box<dyn Hit>> = Vec::new();
+    flake(&mut objects, center, radius, Vec3::new(0.0, 1.0, 0.0), depth, &m);
+    objects
+}
+
+fn flake(objects: &mut Vec<Box<dyn Hit>>, center: Point3, radius: f64, axis: Vec3, depth: u32, m: &Arc<dyn Scatter>) {
+    objects.push(Box::new(Sphere::new(center, radius, m.clone())));
+    if depth == 0 {
+        return;
+    }
+
+    let (t, b) = axis.orthonormal_basis();
+    let child = radius / 3.0;
+    let ring = |count: u32, offset: f64, elevation: f64| (0..count).map(move |k| {
+        let azimuth = offset + 2.0 * PI * k as f64 / count as f64;
+        elevation.cos() * (azimuth.cos() * t + azimuth.sin() * b) + elevation.sin() * axis
+    });
+
+    for dir in ring(6, 0.0, 0.0).chain(ring(3, PI / 6.0, PI / 3.0)) {
+        flake(objects, center + (radius + child) * dir, child, dir, depth - 1, m);
+    }
+}
+
+// Cube from min to max split into 27 and with the center and the middles
+// of the faces taken out, the remaining 20 split again `depth` times.
+// 20^depth boxes.
+pub fn menger(min: Point3, max: Point3, depth: u32, m: Arc<dyn Scatter>) -> Vec<Box<dyn Hit>> {
+    let mut objects: Vec<Box<dyn Hit>> = Vec::new();
+    sponge(&mut objects, min, max, depth, &m);
+    objects
+}
+
+fn sponge(objects: &mut Vec<Box<dyn Hit>>, min: Point3, max: Point3, depth: u32, m: &Arc<dyn Scatter>) {
+    if depth == 0 {
+        objects.push(Box::new(Cuboid::new(min, max, m.clone())));
+        return;
+    }
+
+    let third = (max - min) / 3.0;
+    for x in 0..3 {
+        for y in 0..3 {
+            for z in 0..3 {
+                // Cells in the middle of two or three axes are removed
+                if [x, y, z].iter().filter(|&&c| c == 1).count() >= 2 {
+                    continue;
+                }
+
+                let corner = min + Vec3::new(x as f64 * third.x(), y as f64 * third.y(), z as f64 * third.z());
+                sponge(objects, corner, corner + third, depth - 1, m);
+            }
+        }
+    }
+}
+
+// `count` triangles with corners within `size` of random points between
+// min and max, the same ones for the same seed
+pub fn triangle_soup(min: Point3, max: Point3, count: u64, size: f64, seed: u64, m: Arc<dyn Scatter>) -> Vec<Box<dyn Hit>> {
+    // SplitMix64 over the seed
+    let mut state = seed;
+    let mut random = || {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        (sampler::hash(state) >> 11) as f64 / (1u64 << 53) as f64
+    };
+
+    (0..count).map(|_| {
+        let extent = max - min;
+        let center = min + Vec3::new(random() * extent.x(), random() * extent.y(), random() * extent.z());
+        let mut corner = || center + size * Vec3::new(2.0 * random() - 1.0, 2.0 * random() - 1.0, 2.0 * random() - 1.0);
+        let (a, b, c) = (corner(), corner(), corner());
+        Box::new(Triangle::new(a, b, c, m.clone())) as Box<dyn Hit>
+    }).collect()
+}
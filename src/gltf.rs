@@ -0,0 +1,464 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use super::camera::{Camera, DEFAULT_ASPECT_RATIO, DEFAULT_FOCUS_DIST, DEFAULT_VFOV};
+use super::hit::World;
+use super::json::Json;
+use super::light::Emission;
+use super::matrix::Matrix;
+use super::material::{Emissive, FresnelSwitch, Glossy, Lambertian, Mix, Scatter};
+use super::mesh::{Face, Mesh, MeshData};
+use super::scene::{Scene, DEFAULT_FILM_SIZE};
+use super::texture::{SolidColor, Texture};
+use super::vec::{Color, Point3, Vec3};
+
+// glTF 2.0 reader, of .gltf files with their buffers in other files or data
+// URIs and of binary .glb files. The triangle meshes of the default scene's
+// nodes become objects named after their nodes, their metallic-roughness
+// materials a mix of a glossy metal and a diffuse base under a glossy coat,
+// and the first perspective camera the scene's camera. Scenes without one
+// are framed from the front. Textures, lights and animations are skipped.
+// Returns the scene with the name of its image, the file's with .ppm.
+pub fn load(filename: &str) -> (String, Scene) {
+    let bytes = std::fs::read(filename)
+        .unwrap_or_else(|_| panic!("Unable to read glTF file {}.", filename));
+    let fail = || -> ! { panic!("Failed to parse glTF file {}.", filename) };
+
+    let (text, bin) = if bytes.starts_with(b"glTF") { glb(&bytes).unwrap_or_else(|| fail()) } else { (&bytes[..], None) };
+    let doc = std::str::from_utf8(text).ok().and_then(Json::parse).unwrap_or_else(|| fail());
+
+    let dir = Path::new(filename).parent().unwrap_or(Path::new(""));
+    let buffers = doc.get("buffers").and_then(Json::array).unwrap_or(&[]).iter().map(|buffer| {
+        match buffer.get("uri").and_then(Json::str) {
+            Some(uri) if uri.starts_with("data:") => {
+                uri.split_once(',').and_then(|(_, data)| base64(data)).unwrap_or_else(|| fail())
+            },
+            Some(uri) => {
+                let path = dir.join(percent_decode(uri));
+                std::fs::read(&path).unwrap_or_else(|_| panic!("Unable to read glTF buffer {}.", path.display()))
+            },
+            None => bin.map(<[u8]>::to_vec).unwrap_or_else(|| fail())
+        }
+    }).collect();
+
+    let gltf = Gltf { doc: &doc, buffers };
+    let mut content = Content {
+        world: World::new(),
+        object_names: Vec::new(),
+        camera: None,
+        materials: doc.get("materials").and_then(Json::array).unwrap_or(&[]).iter().map(|m| material(Some(m))).collect(),
+        default_material: material(None),
+        bounds: None,
+        visited: Vec::new()
+    };
+
+    // The default scene, or else the first, or else every node without a
+    // parent
+    let scene = doc.get("scene").and_then(Json::index).unwrap_or(0);
+    let roots: Vec<usize> = match doc.get("scenes").and_then(|s| s.at(scene)) {
+        Some(scene) => scene.get("nodes").and_then(Json::array).unwrap_or(&[]).iter().filter_map(Json::index).collect(),
+        None => {
+            let nodes = doc.get("nodes").and_then(Json::array).unwrap_or(&[]);
+            let children: Vec<usize> = nodes.iter()
+                .filter_map(|n| n.get("children").and_then(Json::array))
+                .flat_map(|c| c.iter().filter_map(Json::index))
+                .collect();
+            (0..nodes.len()).filter(|k| !children.contains(k)).collect()
+        }
+    };
+    for root in roots {
+        gltf.node(root, &Matrix::identity(), &mut content).unwrap_or_else(|| fail());
+    }
+
+    if content.world.is_empty() {
+        panic!("No triangle meshes found in glTF file {}.", filename);
+    }
+
+    let camera = content.camera.unwrap_or_else(|| {
+        // Far enough along +z to see the whole bounding sphere
+        let (min, max) = content.bounds.unwrap_or((Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, 0.0)));
+        let center = 0.5 * (min + max);
+        let radius = (0.5 * (max - min).length()).max(1e-3);
+        let distance = radius / (DEFAULT_VFOV.to_radians() / 2.0).sin();
+        (center + Vec3::new(0.0, 0.0, distance), center, Vec3::new(0.0, 1.0, 0.0), DEFAULT_VFOV, None)
+    });
+
+    // Cameras with an aspect ratio get a film of that shape
+    let (look_from, look_at, up, vfov, aspect) = camera;
+    let aspect = aspect.unwrap_or(DEFAULT_ASPECT_RATIO);
+    let cam = Camera::new(look_from, look_at, up, vfov, aspect, 0.0, DEFAULT_FOCUS_DIST);
+
+    let mut scene = Scene::new(content.world, Vec::new(), cam);
+    scene.film_size = (DEFAULT_FILM_SIZE.0, ((DEFAULT_FILM_SIZE.0 as f64 / aspect).round() as u64).max(1));
+    scene.object_names = content.object_names;
+
+    let img_name = match filename.rsplit_once('.') {
+        Some((stem, _)) => format!("{}.ppm", stem),
+        None => format!("{}.ppm", filename)
+    };
+    (img_name, scene)
+}
+
+// JSON and binary chunks of a .glb file
+fn glb(bytes: &[u8]) -> Option<(&[u8], Option<&[u8]>)> {
+    let word = |pos: usize| bytes.get(pos..pos + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    if word(4)? != 2 {
+        return None;
+    }
+
+    let (mut json, mut bin) = (None, None);
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let length = word(pos)?;
+        let chunk = bytes.get(pos + 8..pos + 8 + length)?;
+        match word(pos + 4)? {
+            0x4e4f534a => json = Some(chunk),
+            0x004e4942 => bin = Some(chunk),
+            _ => { }
+        }
+        pos += 8 + length;
+    }
+    Some((json?, bin))
+}
+
+fn base64(data: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None
+    };
+
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in data.bytes().filter(|&c| c != b'=' && !c.is_ascii_whitespace()) {
+        bits = (bits << 6) | value(c)? as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+// "my%20model.bin" -> "my model.bin"
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut k = 0;
+    while k < bytes.len() {
+        let escaped = bytes.get(k + 1..k + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[k], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                k += 3;
+            },
+            (byte, _) => {
+                decoded.push(byte);
+                k += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Base color, metallic and roughness factors, and emission for emissive
+// materials. Metals reflect in their color with a rough GGX lobe, and
+// non-metals are diffuse under a white one of index 1.5; partly metallic
+// surfaces mix the two.
+fn material(m: Option<&Json>) -> Arc<dyn Scatter> {
+    let pbr = m.and_then(|m| m.get("pbrMetallicRoughness"));
+    let factor = |name: &str| pbr.and_then(|p| p.get(name)).and_then(Json::number).unwrap_or(1.0);
+    let color = pbr.and_then(|p| p.get("baseColorFactor"))
+        .and_then(Json::numbers)
+        .filter(|c| c.len() >= 3)
+        .map_or(Color::new(1.0, 1.0, 1.0), |c| Color::new(c[0], c[1], c[2]));
+    let metallic = factor("metallicFactor").clamp(0.0, 1.0);
+    let roughness = factor("roughnessFactor");
+
+    let strength = m.and_then(|m| m.get("extensions"))
+        .and_then(|e| e.get("KHR_materials_emissive_strength"))
+        .and_then(|e| e.get("emissiveStrength"))
+        .and_then(Json::number)
+        .unwrap_or(1.0);
+    let emission = m.and_then(|m| m.get("emissiveFactor"))
+        .and_then(Json::numbers)
+        .filter(|e| e.len() >= 3)
+        .map(|e| strength * Color::new(e[0], e[1], e[2]));
+    if let Some(emission) = emission.filter(|e| !e.near_zero()) {
+        return Arc::new(Emissive::new(emission, Emission::diffuse()));
+    }
+
+    let solid = |c: Color| -> Arc<dyn Texture> { Arc::new(SolidColor::new(c)) };
+    let glossy = |albedo: Color| -> Arc<dyn Scatter> {
+        Arc::new(Glossy::new(solid(albedo),
+                             solid(Color::new(roughness, roughness, roughness)),
+                             solid(Color::new(0.0, 0.0, 0.0)),
+                             solid(Color::new(0.0, 0.0, 0.0)),
+                             Vec3::new(1.0, 0.0, 0.0)))
+    };
+    let dielectric: Arc<dyn Scatter> = Arc::new(FresnelSwitch::new(Arc::new(Lambertian::new(color)), glossy(Color::new(1.0, 1.0, 1.0)), 1.5));
+
+    if metallic == 0.0 {
+        dielectric
+    } else if metallic == 1.0 {
+        glossy(color)
+    } else {
+        Arc::new(Mix::new(dielectric, glossy(color), solid(Color::new(metallic, metallic, metallic))))
+    }
+}
+
+struct Gltf<'a> {
+    doc: &'a Json,
+    buffers: Vec<Vec<u8>>
+}
+
+// What the nodes add to the scene
+struct Content {
+    world: World,
+    object_names: Vec<Option<String>>,
+    // Look from, look at, up and vertical field of view
+    camera: Option<(Point3, Point3, Vec3, f64, Option<f64>)>,
+    materials: Vec<Arc<dyn Scatter>>,
+    default_material: Arc<dyn Scatter>,
+    bounds: Option<(Point3, Point3)>,
+    // Nodes reached so far. The nodes of a file form trees, so one reached
+    // twice is part of a cycle, or of a scene listing it again
+    visited: Vec<usize>
+}
+
+impl Gltf<'_> {
+    fn node(&self, index: usize, parent: &Matrix, content: &mut Content) -> Option<()> {
+        let node = self.doc.get("nodes")?.at(index)?;
+        if content.visited.contains(&index) {
+            return None;
+        }
+        content.visited.push(index);
+        let matrix = *parent * local_matrix(node)?;
+        let name = node.get("name").and_then(Json::str).map(str::to_string);
+
+        if let Some(mesh) = node.get("mesh").and_then(Json::index) {
+            let mesh = self.doc.get("meshes")?.at(mesh)?;
+            for primitive in mesh.get("primitives")?.array()? {
+                if let Some(data) = self.primitive(primitive, &matrix)? {
+                    for &p in &data.positions {
+                        content.bounds = Some(match content.bounds {
+                            Some((min, max)) => (
+                                Point3::new(min.x().min(p.x()), min.y().min(p.y()), min.z().min(p.z())),
+                                Point3::new(max.x().max(p.x()), max.y().max(p.y()), max.z().max(p.z()))
+                            ),
+                            None => (p, p)
+                        });
+                    }
+
+                    let mat = match primitive.get("material").and_then(Json::index) {
+                        Some(m) => content.materials.get(m)?.clone(),
+                        None => content.default_material.clone()
+                    };
+                    let mesh = Mesh::transformed(&data, Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0), mat);
+                    content.world.push(Box::new(mesh));
+                    content.object_names.push(name.clone());
+                }
+            }
+        }
+
+        // Cameras look down their -z with +y up
+        if let Some(camera) = node.get("camera").and_then(Json::index) {
+            let camera = self.doc.get("cameras")?.at(camera)?;
+            if content.camera.is_none() && camera.get("type").and_then(Json::str) == Some("perspective") {
+                let perspective = camera.get("perspective")?;
+                let yfov = perspective.get("yfov")?.number()?;
+                let origin = matrix.point(Point3::new(0.0, 0.0, 0.0));
+                content.camera = Some((origin,
+                                       origin + matrix.direction(Vec3::new(0.0, 0.0, -1.0)),
+                                       matrix.direction(Vec3::new(0.0, 1.0, 0.0)),
+                                       yfov.to_degrees(),
+                                       perspective.get("aspectRatio").and_then(Json::number).filter(|&a| a > 0.0)));
+            }
+        }
+
+        for child in node.get("children").and_then(Json::array).unwrap_or(&[]) {
+            self.node(child.index()?, &matrix, content)?;
+        }
+        Some(())
+    }
+
+    // Geometry of a triangle primitive in world space, or None inside for
+    // points and lines
+    fn primitive(&self, primitive: &Json, matrix: &Matrix) -> Option<Option<MeshData>> {
+        let attributes = primitive.get("attributes")?;
+        let positions = self.accessor(attributes.get("POSITION")?.index()?)?;
+        let normals = match attributes.get("NORMAL") {
+            Some(accessor) => Some(self.accessor(accessor.index()?)?),
+            None => None
+        };
+        let count = positions.len() / 3;
+
+        let indices: Vec<usize> = match primitive.get("indices") {
+            Some(accessor) => self.accessor(accessor.index()?)?.iter().map(|&i| i as usize).collect(),
+            None => (0..count).collect()
+        };
+        if indices.iter().any(|&i| i >= count) {
+            return None;
+        }
+
+        let triangles: Vec<[usize; 3]> = match primitive.get("mode").and_then(Json::index).unwrap_or(4) {
+            4 => indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            // Every other triangle of a strip winds the other way
+            5 => (2..indices.len()).map(|k| {
+                if k % 2 == 0 { [indices[k - 2], indices[k - 1], indices[k]] } else { [indices[k - 1], indices[k - 2], indices[k]] }
+            }).collect(),
+            6 => (2..indices.len()).map(|k| [indices[0], indices[k - 1], indices[k]]).collect(),
+            _ => return Some(None)
+        };
+        if triangles.is_empty() {
+            return Some(None);
+        }
+
+        // Normals go through the inverse transpose, and mirroring transforms
+        // reverse the winding
+        let normal_matrix = matrix.normal_matrix().unwrap_or(*matrix);
+        let mirrored = matrix.determinant() < 0.0;
+
+        Some(Some(MeshData {
+            positions: positions.chunks_exact(3).map(|p| matrix.point(Point3::new(p[0], p[1], p[2]))).collect(),
+            normals: normals.iter().flat_map(|n| n.chunks_exact(3)).map(|n| normal_matrix.direction(Vec3::new(n[0], n[1], n[2]))).collect(),
+            faces: triangles.into_iter().map(|[a, b, c]| {
+                let vertices = if mirrored && normals.is_none() { [a, c, b] } else { [a, b, c] };
+                Face { vertices, normals: normals.as_ref().map(|_| vertices) }
+            }).collect()
+        }))
+    }
+
+    // Components of every element of an accessor, one after the other,
+    // zeros for accessors without a buffer view
+    fn accessor(&self, index: usize) -> Option<Vec<f64>> {
+        let accessor = self.doc.get("accessors")?.at(index)?;
+        let count = accessor.get("count")?.index()?;
+        let components = match accessor.get("type")?.str()? {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            "MAT2" => 4,
+            "MAT3" => 9,
+            "MAT4" => 16,
+            _ => return None
+        };
+        let component_type = accessor.get("componentType")?.index()?;
+        let size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return None
+        };
+        let normalized = matches!(accessor.get("normalized"), Some(Json::Bool(true)));
+
+        let view = match accessor.get("bufferView") {
+            Some(view) => self.doc.get("bufferViews")?.at(view.index()?)?,
+            None => return Some(vec![0.0; count * components])
+        };
+        let buffer = self.buffers.get(view.get("buffer")?.index()?)?;
+        let offset = view.get("byteOffset").and_then(Json::index).unwrap_or(0)
+            + accessor.get("byteOffset").and_then(Json::index).unwrap_or(0);
+        let stride = view.get("byteStride").and_then(Json::index).unwrap_or(components * size);
+
+        let mut values = Vec::with_capacity(count * components);
+        for element in 0..count {
+            for component in 0..components {
+                let start = offset + element * stride + component * size;
+                let b = buffer.get(start..start + size)?;
+                let value = match component_type {
+                    5120 => b[0] as i8 as f64,
+                    5121 => b[0] as f64,
+                    5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64
+                };
+
+                // Normalized integers map their range to [0, 1] or [-1, 1]
+                let value = match (normalized, component_type) {
+                    (true, 5120) => (value / 127.0).max(-1.0),
+                    (true, 5121) => value / 255.0,
+                    (true, 5122) => (value / 32767.0).max(-1.0),
+                    (true, 5123) => value / 65535.0,
+                    _ => value
+                };
+                values.push(value);
+            }
+        }
+        Some(values)
+    }
+}
+
+// The node's matrix, or its translation, rotation and scale in that order
+fn local_matrix(node: &Json) -> Option<Matrix> {
+    if let Some(m) = node.get("matrix") {
+        // Stored column by column
+        let m = m.numbers().filter(|m| m.len() == 16)?;
+        let mut matrix = Matrix::identity();
+        for (row, values) in matrix.m.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                *value = m[col * 4 + row];
+            }
+        }
+        return Some(matrix);
+    }
+
+    let vector = |name: &str, len: usize, default: &[f64]| match node.get(name) {
+        Some(v) => v.numbers().filter(|v| v.len() == len),
+        None => Some(default.to_vec())
+    };
+    let t = vector("translation", 3, &[0.0, 0.0, 0.0])?;
+    let q = vector("rotation", 4, &[0.0, 0.0, 0.0, 1.0])?;
+    let s = vector("scale", 3, &[1.0, 1.0, 1.0])?;
+
+    Some(Matrix::translation(Vec3::new(t[0], t[1], t[2]))
+        * Matrix::quaternion(q[0], q[1], q[2], q[3])
+        * Matrix::scale(Vec3::new(s[0], s[1], s[2])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::ray::Ray;
+
+    #[test]
+    fn loads_node_hierarchies() {
+        let (img_name, scene) = load(&fixtures::path("triangle.gltf"));
+        assert!(img_name.ends_with("triangle.ppm"));
+        assert_eq!(scene.object_names, [Some("triangle".to_string())]);
+
+        // Scaled by the child node, then moved by the root
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        let hit = scene.intersect(&Ray::new(Point3::new(2.5, 0.5, 5.0), down)).expect("The triangle wasn't hit.");
+        assert!((hit.t - 5.0).abs() < 1e-9);
+        assert!(scene.intersect(&Ray::new(Point3::new(3.9, 1.9, 5.0), down)).is_none());
+        assert!(scene.intersect(&Ray::new(Point3::new(0.5, 0.5, 5.0), down)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to parse glTF file")]
+    fn rejects_node_cycles() {
+        // The triangle node made a parent of the root
+        let text = std::fs::read_to_string(fixtures::path("triangle.gltf")).unwrap();
+        let cyclic = text.replacen("\"mesh\": 0,", "\"mesh\": 0, \"children\": [0],", 1);
+        load(&fixtures::write("cycle.gltf", cyclic.as_bytes()));
+    }
+
+    #[test]
+    fn decodes_buffers() {
+        assert_eq!(base64("AAEC/w==").unwrap(), [0, 1, 2, 255]);
+        assert_eq!(base64("_-8").unwrap(), [255, 239]);
+        assert!(base64("AA*").is_none());
+        assert_eq!(percent_decode("my%20model.bin"), "my model.bin");
+    }
+}
@@ -0,0 +1,30 @@
+use super::aabb::Aabb;
+use super::accel::{self, Accelerator};
+use super::hit::{Hit, HitRecord};
+use super::ray::Ray;
+
+// Objects hit as one, with an accelerator of their own
+pub struct Group {
+    objects: Vec<Box<dyn Hit>>,
+    accel: Box<dyn Accelerator<Box<dyn Hit>>>
+}
+
+impl Group {
+    pub fn new(objects: Vec<Box<dyn Hit>>) -> Group {
+        let accel = accel::new(&objects);
+        Group {
+            objects,
+            accel
+        }
+    }
+}
+
+impl Hit for Group {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.accel.hit(&self.objects, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.accel.bounding_box()
+    }
+}
@@ -0,0 +1,107 @@
+use super::curve::Strand;
+use super::vec::Point3;
+
+// Reader of curve files, either text files with one strand per line, the
+// x y z of its 3n + 1 Bezier control points, skipping blank lines and lines
+// starting with #, or the binary .hair files of Cem Yuksel's hair models,
+// whose strands are polylines turned into Catmull-Rom splines through their
+// points, with the thickness of each point if the file has them
+pub fn load(filename: &str) -> Vec<Strand> {
+    let bytes = std::fs::read(filename)
+        .unwrap_or_else(|_| panic!("Unable to read curve file {}.", filename));
+
+    let strands = if bytes.starts_with(b"HAIR") { parse_hair(&bytes) } else { parse_text(&bytes) };
+    let strands = strands.unwrap_or_else(|| panic!("Failed to parse curve file {}.", filename));
+    if strands.is_empty() {
+        panic!("No strands found in curve file {}.", filename);
+    }
+    strands
+}
+
+fn parse_text(bytes: &[u8]) -> Option<Vec<Strand>> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    let mut strands = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let values = line.split_whitespace().map(|v| v.parse().ok()).collect::<Option<Vec<f64>>>()?;
+        if values.len() % 3 != 0 || values.len() < 12 || (values.len() / 3 - 1) % 3 != 0 {
+            return None;
+        }
+
+        let points = values.chunks(3).map(|c| Point3::new(c[0], c[1], c[2])).collect();
+        strands.push(Strand { points, radii: None });
+    }
+    Some(strands)
+}
+
+// Bits of the .hair header telling which arrays follow it, in this order;
+// the transparency and color arrays after them are skipped
+const HAS_SEGMENTS: u32 = 1;
+const HAS_POINTS: u32 = 2;
+const HAS_THICKNESS: u32 = 4;
+
+fn parse_hair(bytes: &[u8]) -> Option<Vec<Strand>> {
+    let word = |pos: usize| Some(u32::from_le_bytes(bytes.get(pos..pos + 4)?.try_into().ok()?));
+    let float = |pos: usize| word(pos).map(f32::from_bits).map(|f| f as f64);
+
+    // 128 byte header: counts, flags, defaults and a description
+    let hairs = word(4)? as usize;
+    let point_count = word(8)? as usize;
+    let flags = word(12)?;
+    let default_segments = word(16)? as usize;
+    if flags & HAS_POINTS == 0 {
+        return None;
+    }
+
+    let mut pos = 128;
+    let segments: Vec<usize> = if flags & HAS_SEGMENTS != 0 {
+        let segments = (0..hairs)
+            .map(|k| Some(u16::from_le_bytes(bytes.get(pos + 2 * k..pos + 2 * k + 2)?.try_into().ok()?) as usize))
+            .collect::<Option<Vec<usize>>>()?;
+        pos += 2 * hairs;
+        segments
+    } else {
+        vec![default_segments; hairs]
+    };
+
+    let points = (0..point_count)
+        .map(|k| Some(Point3::new(float(pos + 12 * k)?, float(pos + 12 * k + 4)?, float(pos + 12 * k + 8)?)))
+        .collect::<Option<Vec<Point3>>>()?;
+    pos += 12 * point_count;
+
+    // Thickness is a diameter
+    let radii = if flags & HAS_THICKNESS != 0 {
+        Some((0..point_count).map(|k| float(pos + 4 * k).map(|t| 0.5 * t)).collect::<Option<Vec<f64>>>()?)
+    } else {
+        None
+    };
+
+    let mut strands = Vec::new();
+    let mut first = 0;
+    for count in segments {
+        let last = first + count;
+        if last >= point_count {
+            return None;
+        }
+        if count > 0 {
+            strands.push(catmull_rom(&points[first..=last], radii.as_ref().map(|r| &r[first..=last])));
+        }
+        first = last + 1;
+    }
+    Some(strands)
+}
+
+// Bezier control points of the Catmull-Rom spline through the points of a
+// polyline, its end points repeated for the tangents at the ends
+fn catmull_rom(points: &[Point3], radii: Option<&[f64]>) -> Strand {
+    let n = points.len();
+    let mut bezier = vec![points[0]];
+    for k in 0..n - 1 {
+        let before = points[k.saturating_sub(1)];
+        let after = points[(k + 2).min(n - 1)];
+        bezier.push(points[k] + (points[k + 1] - before) / 6.0);
+        bezier.push(points[k + 1] - (after - points[k]) / 6.0);
+        bezier.push(points[k + 1]);
+    }
+
+    Strand { points: bezier, radii: radii.map(|r| r.to_vec()) }
+}
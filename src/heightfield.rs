@@ -0,0 +1,219 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::sampler;
+use super::triangle;
+
+// Terrain over the rectangle from min to max in x and z, sampled on an nx by
+// nz grid whose heights in [0, 1] are raised from min.y to max.y. Every cell
+// between four samples is split into two triangles along its diagonal, and
+// normals are interpolated between the samples for smooth shading.
+pub struct Heightfield {
+    // World heights, one row of nx samples per step in z
+    heights: Vec<f64>,
+    normals: Vec<Vec3>,
+    nx: usize,
+    nz: usize,
+    min: Point3,
+    dx: f64,
+    dz: f64,
+    bbox: Aabb,
+    mat: Arc<dyn Scatter>
+}
+
+impl Heightfield {
+    pub fn new(samples: Vec<f64>, nx: usize, nz: usize, min: Point3, max: Point3, m: Arc<dyn Scatter>) -> Heightfield {
+        let heights: Vec<f64> = samples.iter().map(|s| min.y() + s * (max.y() - min.y())).collect();
+        let dx = (max.x() - min.x()) / (nx - 1) as f64;
+        let dz = (max.z() - min.z()) / (nz - 1) as f64;
+
+        // Central differences, one-sided along the edges
+        let mut normals = Vec::with_capacity(nx * nz);
+        for k in 0..nz {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (k0, k1) = (k.saturating_sub(1), (k + 1).min(nz - 1));
+                let slope_x = (heights[k * nx + i1] - heights[k * nx + i0]) / ((i1 - i0) as f64 * dx);
+                let slope_z = (heights[k1 * nx + i] - heights[k0 * nx + i]) / ((k1 - k0) as f64 * dz);
+                normals.push(Vec3::new(-slope_x, 1.0, -slope_z).normalized());
+            }
+        }
+
+        let low = heights.iter().copied().fold(f64::INFINITY, f64::min);
+        let high = heights.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let bbox = Aabb::new(Point3::new(min.x(), low, min.z()), Point3::new(max.x(), high, max.z())).padded(1e-4);
+
+        Heightfield {
+            heights,
+            normals,
+            nx,
+            nz,
+            min,
+            dx,
+            dz,
+            bbox,
+            mat: m
+        }
+    }
+
+    fn point(&self, i: usize, k: usize) -> Point3 {
+        Point3::new(self.min.x() + i as f64 * self.dx, self.heights[k * self.nx + i], self.min.z() + k as f64 * self.dz)
+    }
+
+    fn normal(&self, i: usize, k: usize) -> Vec3 {
+        self.normals[k * self.nx + i]
+    }
+
+    // Nearest hit on the two triangles of cell (i, k)
+    fn hit_cell(&self, r: &Ray, i: usize, k: usize, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let corners = [(i, k), (i + 1, k), (i, k + 1), (i + 1, k + 1)];
+        let mut closest: Option<(f64, Vec3, Vec3)> = None;
+
+        for [a, b, c] in [[corners[0], corners[2], corners[3]], [corners[0], corners[3], corners[1]]] {
+            let t_max = closest.map_or(t_max, |(t, _, _)| t);
+            let (pa, pb, pc) = (self.point(a.0, a.1), self.point(b.0, b.1), self.point(c.0, c.1));
+            if let Some((t, u, v)) = triangle::intersect(r, pa, pb, pc, t_min, t_max) {
+                let n = (1.0 - u - v) * self.normal(a.0, a.1) + u * self.normal(b.0, b.1) + v * self.normal(c.0, c.1);
+                closest = Some((t, (pb - pa).cross(pc - pa).normalized(), n.normalized()));
+            }
+        }
+
+        closest.map(|(t, face, n)| {
+            let mut rec = HitRecord::new(r, t, face, self.mat.clone());
+            rec.set_shading_normal(n);
+            rec
+        })
+    }
+}
+
+impl Hit for Heightfield {
+    // Walks the cells under the ray in order with a 2D DDA over the grid,
+    // skipping cells whose highest corner is below the ray
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (start, end) = self.bbox.interval_ray(r, t_min, t_max)?;
+        let o = r.origin();
+        let d = r.direction();
+        let p = r.at(start);
+
+        let cell = |offset: f64, size: f64, n: usize| ((offset / size).floor().max(0.0) as usize).min(n - 2);
+        let mut i = cell(p.x() - self.min.x(), self.dx, self.nx);
+        let mut k = cell(p.z() - self.min.z(), self.dz, self.nz);
+
+        // Ray parameters of the next cell boundaries in x and z, and the
+        // spacing between them
+        let boundary = |index: usize, origin: f64, dir: f64, min: f64, size: f64| {
+            if dir > 0.0 {
+                (min + (index + 1) as f64 * size - origin) / dir
+            } else if dir < 0.0 {
+                (min + index as f64 * size - origin) / dir
+            } else {
+                f64::INFINITY
+            }
+        };
+        let mut next_x = boundary(i, o.x(), d.x(), self.min.x(), self.dx);
+        let mut next_z = boundary(k, o.z(), d.z(), self.min.z(), self.dz);
+        let delta_x = self.dx / d.x().abs();
+        let delta_z = self.dz / d.z().abs();
+
+        let mut enter = start;
+        loop {
+            let exit = next_x.min(next_z).min(end);
+
+            let lowest = r.at(enter).y().min(r.at(exit).y());
+            let highest = [(i, k), (i + 1, k), (i, k + 1), (i + 1, k + 1)]
+                .iter()
+                .map(|&(a, b)| self.heights[b * self.nx + a])
+                .fold(f64::NEG_INFINITY, f64::max);
+            if lowest <= highest {
+                if let Some(rec) = self.hit_cell(r, i, k, t_min, t_max) {
+                    return Some(rec);
+                }
+            }
+
+            if exit >= end {
+                return None;
+            }
+
+            if next_x < next_z {
+                if (d.x() > 0.0 && i + 2 >= self.nx) || (d.x() < 0.0 && i == 0) {
+                    return None;
+                }
+                i = if d.x() > 0.0 { i + 1 } else { i - 1 };
+                enter = next_x;
+                next_x += delta_x;
+            } else {
+                if (d.z() > 0.0 && k + 2 >= self.nz) || (d.z() < 0.0 && k == 0) {
+                    return None;
+                }
+                k = if d.z() > 0.0 { k + 1 } else { k - 1 };
+                enter = next_z;
+                next_z += delta_z;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+
+    // u along x and v along z over the whole extent
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let x = u.clamp(0.0, 1.0) * (self.nx - 1) as f64;
+        let z = v.clamp(0.0, 1.0) * (self.nz - 1) as f64;
+        let i = (x.floor() as usize).min(self.nx - 2);
+        let k = (z.floor() as usize).min(self.nz - 2);
+        let (fx, fz) = (x - i as f64, z - k as f64);
+
+        // Weights of the corners of the triangle on the point's side of the
+        // diagonal
+        let corners = if fx >= fz {
+            [((i, k), 1.0 - fx), ((i + 1, k), fx - fz), ((i + 1, k + 1), fz)]
+        } else {
+            [((i, k), 1.0 - fz), ((i, k + 1), fz - fx), ((i + 1, k + 1), fx)]
+        };
+
+        let mut p = Vec3::new(0.0, 0.0, 0.0);
+        let mut n = Vec3::new(0.0, 0.0, 0.0);
+        for ((a, b), w) in corners {
+            p += w * self.point(a, b);
+            n += w * self.normal(a, b);
+        }
+        Some(HitRecord::facing(p, n.normalized(), self.mat.clone()))
+    }
+}
+
+// Fractal value noise in [0, 1] on an n by n grid, `frequency` features
+// across it in the first octave and every further octave twice as fine and
+// half as strong
+pub fn noise(n: usize, octaves: u32, frequency: f64, seed: u64) -> Vec<f64> {
+    let lattice = |x: i64, z: i64, octave: u32| {
+        let h = sampler::hash(sampler::hash(sampler::hash(seed ^ octave as u64) ^ x as u64) ^ z as u64);
+        (h >> 11) as f64 / (1u64 << 53) as f64
+    };
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+
+    let total: f64 = (0..octaves).map(|octave| 0.5_f64.powi(octave as i32)).sum();
+    let mut heights = Vec::with_capacity(n * n);
+    for k in 0..n {
+        for i in 0..n {
+            let mut height = 0.0;
+            for octave in 0..octaves {
+                let scale = frequency * 2.0_f64.powi(octave as i32) / (n - 1).max(1) as f64;
+                let (x, z) = (i as f64 * scale, k as f64 * scale);
+                let (x0, z0) = (x.floor(), z.floor());
+                let (fx, fz) = (smooth(x - x0), smooth(z - z0));
+                let (x0, z0) = (x0 as i64, z0 as i64);
+
+                let near = (1.0 - fx) * lattice(x0, z0, octave) + fx * lattice(x0 + 1, z0, octave);
+                let far = (1.0 - fx) * lattice(x0, z0 + 1, octave) + fx * lattice(x0 + 1, z0 + 1, octave);
+                height += 0.5_f64.powi(octave as i32) * ((1.0 - fz) * near + fz * far);
+            }
+            heights.push(height / total);
+        }
+    }
+    heights
+}
@@ -1,46 +1,502 @@
 use std::sync::Arc;
 
+use super::aabb::Aabb;
+use super::accel::{self, Accelerator};
+use super::instance::Variation;
 use super::vec::{Point3, Vec3};
 use super::ray::Ray;
 use super::material::Scatter;
+use super::packet::{RayPacket, MAX_LANES};
+
+// Bit i set when light i illuminates the hit object, see LightLinked
+pub type LightMask = u64;
+
+pub const ALL_LIGHTS: LightMask = LightMask::MAX;
 
 pub struct HitRecord {
     pub p: Point3,
+    // Normal materials shade with, interpolated between vertex normals on
+    // smooth meshes
     pub normal: Vec3,
+    // Normal of the surface actually hit, on the same side as `normal`
+    pub geometric_normal: Vec3,
     pub mat: Arc<dyn Scatter>,
     pub t: f64,
-    pub front_face: bool
+    pub front_face: bool,
+    pub light_mask: LightMask,
+    // Whether the object is a holdout, see Holdout
+    pub holdout: bool,
+    // Index of the hit object in the world
+    pub object_id: usize,
+    // Of the instance hit, see instance.rs
+    pub variation: Option<Variation>
 }
 
 impl HitRecord {
-    pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) -> () {
+    pub fn new(r: &Ray, t: f64, outward_normal: Vec3, mat: Arc<dyn Scatter>) -> HitRecord {
+        let mut rec = HitRecord {
+            t,
+            p: r.at(t),
+            mat,
+            normal: outward_normal,
+            geometric_normal: outward_normal,
+            front_face: true,
+            light_mask: ALL_LIGHTS,
+            holdout: false,
+            object_id: 0,
+            variation: None
+        };
+        rec.set_face_normal(r, outward_normal);
+
+        rec
+    }
+
+    // Whether light number `index` of the scene may light this hit; only the
+    // first 64 lights can be linked, later ones light everything
+    pub fn lit_by(&self, index: usize) -> bool {
+        index >= 64 || self.light_mask & (1 << index) != 0
+    }
+
+    // Record of a ray arriving head-on at p, the way a surface point is seen
+    // when it isn't found by tracing
+    pub fn facing(p: Point3, outward_normal: Vec3, mat: Arc<dyn Scatter>) -> HitRecord {
+        HitRecord::new(&Ray::new(p + outward_normal, (-1.0) * outward_normal), 1.0, outward_normal, mat)
+    }
+
+    // Sets both normals from the surface's outward normal
+    pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
         self.front_face = r.direction().dot(outward_normal) < 0.0;
-        self.normal = if self.front_face {
+        self.geometric_normal = if self.front_face {
             outward_normal
         } else {
             (-1.0) * outward_normal
         };
+        self.normal = self.geometric_normal;
+    }
+
+    // Shades with another outward normal, turned to the front of the surface
+    // if it points behind it, as badly interpolated normals can
+    pub fn set_shading_normal(&mut self, outward_normal: Vec3) {
+        let side = if self.front_face { 1.0 } else { -1.0 };
+        let n = side * outward_normal;
+        self.normal = if n.dot(self.geometric_normal) < 0.0 { (-1.0) * n } else { n };
+    }
+}
+
+// Object of a world with the edits made to it since it was added
+struct Slot {
+    // None once removed, so the indices of later objects stay the same
+    object: Option<Box<dyn Hit>>,
+    offset: Option<Vec3>,
+    mat: Option<Arc<dyn Scatter>>
+}
+
+impl Slot {
+    fn edited(&self) -> bool {
+        self.offset.is_some() || self.mat.is_some()
+    }
+
+    fn apply(&self, mut rec: HitRecord) -> HitRecord {
+        if let Some(offset) = self.offset {
+            rec.p += offset;
+        }
+        if let Some(mat) = &self.mat {
+            rec.mat = mat.clone();
+        }
+
+        rec
     }
 }
 
-pub type World = Vec<Box<dyn Hit>>;
+impl Hit for Slot {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let object = self.object.as_ref()?;
+        let rec = match self.offset {
+            Some(offset) => object.hit(&Ray::with_time(r.origin() - offset, r.direction(), r.time()), t_min, t_max)?,
+            None => object.hit(r, t_min, t_max)?
+        };
+
+        Some(self.apply(rec))
+    }
+
+    fn hit_packet(&self, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+        let Some(object) = &self.object else {
+            return;
+        };
+        if !self.edited() {
+            return object.hit_packet(p, t_min, t_max, recs);
+        }
+
+        for k in 0..p.lanes {
+            if let Some(rec) = self.hit(&p.ray(k), t_min, t_max[k]) {
+                t_max[k] = rec.t;
+                recs[k] = Some(rec);
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bbox = self.object.as_ref()?.bounding_box()?;
+        let offset = self.offset.unwrap_or(Vec3::new(0.0, 0.0, 0.0));
+        Some(Aabb::new(bbox.min + offset, bbox.max + offset))
+    }
+
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        self.object.as_ref()?.surface_at(u, v).map(|rec| self.apply(rec))
+    }
+}
+
+// Top-level objects of a scene, by index. Once built, an accelerator over
+// them answers the ray queries and is kept up to date by the edits below;
+// a BVH only touches the nodes above the edited object.
+#[derive(Default)]
+pub struct World {
+    objects: Vec<Slot>,
+    accel: Option<Box<dyn Accelerator<Slot>>>
+}
+
+impl World {
+    pub fn new() -> World {
+        World::default()
+    }
+
+    // Adds an object and returns its index
+    pub fn push(&mut self, object: Box<dyn Hit>) -> usize {
+        self.objects.push(Slot { object: Some(object), offset: None, mat: None });
+
+        let index = self.objects.len() - 1;
+        if let Some(accel) = &mut self.accel {
+            accel.insert(&self.objects, index);
+        }
+        index
+    }
+
+    // Removes the object at index, returning whether there was one; the
+    // index isn't reused
+    pub fn remove(&mut self, index: usize) -> bool {
+        if self.get(index).is_none() {
+            return false;
+        }
+
+        if let Some(accel) = &mut self.accel {
+            accel.remove(&self.objects, index);
+        }
+        self.objects[index].object = None;
+        true
+    }
+
+    // Moves the object at index by offset
+    pub fn translate(&mut self, index: usize, offset: Vec3) -> bool {
+        let Some(slot) = self.objects.get_mut(index).filter(|slot| slot.object.is_some()) else {
+            return false;
+        };
+        slot.offset = Some(slot.offset.map_or(offset, |o| o + offset));
+
+        if let Some(accel) = &mut self.accel {
+            accel.refit(&self.objects, index);
+        }
+        true
+    }
+
+    // Makes the object at index out of mat, whatever materials it had
+    pub fn set_material(&mut self, index: usize, mat: Arc<dyn Scatter>) -> bool {
+        match self.objects.get_mut(index).filter(|slot| slot.object.is_some()) {
+            Some(slot) => {
+                slot.mat = Some(mat);
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&dyn Hit> {
+        self.objects.get(index)
+            .filter(|slot| slot.object.is_some())
+            .map(|slot| slot as &dyn Hit)
+    }
+
+    // Number of indices handed out, including those of removed objects
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    // Replaces every object by what f makes of it
+    pub fn wrap(&mut self, f: impl Fn(Box<dyn Hit>) -> Box<dyn Hit>) {
+        for slot in &mut self.objects {
+            slot.object = slot.object.take().map(&f);
+        }
+
+        if self.accel.is_some() {
+            self.build_accel();
+        }
+    }
+
+    pub fn build_accel(&mut self) {
+        let live = (0..self.objects.len()).filter(|&i| self.objects[i].object.is_some());
+        self.accel = Some(accel::with_indices(&self.objects, live));
+    }
+}
 
 impl Hit for World {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if let Some(accel) = &self.accel {
+            return accel.hit(&self.objects, r, t_min, t_max);
+        }
+
         let mut tmp_rec = None;
         let mut closest_so_far = t_max;
 
-        for object in self {
-            if let Some(rec) = object.hit(r, t_min, closest_so_far) {
+        for (index, object) in self.objects.iter().enumerate() {
+            if let Some(mut rec) = object.hit(r, t_min, closest_so_far) {
                 closest_so_far = rec.t;
+                rec.object_id = index;
                 tmp_rec = Some(rec);
             }
         }
         
         tmp_rec
     }
+
+    fn hit_packet(&self, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+        if let Some(accel) = &self.accel {
+            return accel.hit_packet(&self.objects, p, t_min, t_max, recs);
+        }
+
+        for (index, object) in self.objects.iter().enumerate() {
+            let before = *t_max;
+            object.hit_packet(p, t_min, t_max, recs);
+
+            for k in 0..p.lanes {
+                if t_max[k] != before[k] {
+                    if let Some(rec) = recs[k].as_mut() {
+                        rec.object_id = index;
+                    }
+                }
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.objects.iter().filter(|slot| slot.object.is_some()).map(|slot| slot.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |b, other| Some(b.surrounding(other?)))
+    }
 }
 
 pub trait Hit : Send + Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
-}
\ No newline at end of file
+
+    // Intersects every lane of the packet, replacing recs[k] and shrinking
+    // t_max[k] for lanes where this object is closer than the current hit.
+    // Objects without a packet test fall back to one ray at a time.
+    fn hit_packet(&self, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+        for k in 0..p.lanes {
+            if let Some(rec) = self.hit(&p.ray(k), t_min, t_max[k]) {
+                t_max[k] = rec.t;
+                recs[k] = Some(rec);
+            }
+        }
+    }
+
+    // Box enclosing the object, or None if it is unbounded
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    // Record of the surface at texture coordinates (u, v), as seen by a ray
+    // arriving along the normal, for objects with a UV parameterization
+    fn surface_at(&self, _u: f64, _v: f64) -> Option<HitRecord> {
+        None
+    }
+}
+
+impl Hit for Box<dyn Hit> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn hit_packet(&self, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+        (**self).hit_packet(p, t_min, t_max, recs)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        (**self).surface_at(u, v)
+    }
+}
+
+impl Hit for Arc<dyn Hit> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        (**self).hit(r, t_min, t_max)
+    }
+
+    fn hit_packet(&self, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+        (**self).hit_packet(p, t_min, t_max, recs)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        (**self).surface_at(u, v)
+    }
+}
+
+// Packet test of a wrapped object, with edit applied to the records of the
+// lanes it hit
+fn wrapped_packet(object: &dyn Hit, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>], edit: impl Fn(&mut HitRecord)) {
+    let before = *t_max;
+    object.hit_packet(p, t_min, t_max, recs);
+
+    for k in 0..p.lanes {
+        if t_max[k] != before[k] {
+            if let Some(rec) = recs[k].as_mut() {
+                edit(rec);
+            }
+        }
+    }
+}
+
+// Restricts which lights illuminate the wrapped object
+pub struct LightLinked {
+    object: Box<dyn Hit>,
+    mask: LightMask
+}
+
+impl LightLinked {
+    pub fn new(object: Box<dyn Hit>, mask: LightMask) -> LightLinked {
+        LightLinked {
+            object,
+            mask
+        }
+    }
+}
+
+impl Hit for LightLinked {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut rec = self.object.hit(r, t_min, t_max)?;
+        rec.light_mask &= self.mask;
+        Some(rec)
+    }
+
+    fn hit_packet(&self, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+        wrapped_packet(&*self.object, p, t_min, t_max, recs, |rec| rec.light_mask &= self.mask);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let mut rec = self.object.surface_at(u, v)?;
+        rec.light_mask &= self.mask;
+        Some(rec)
+    }
+}
+
+// Object standing in for something real the image is composited with. The
+// camera sees it as a hole, black with no alpha, while it still casts
+// shadows, is lit and shows in reflections like any other object.
+pub struct Holdout {
+    object: Box<dyn Hit>
+}
+
+impl Holdout {
+    pub fn new(object: Box<dyn Hit>) -> Holdout {
+        Holdout {
+            object
+        }
+    }
+}
+
+impl Hit for Holdout {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut rec = self.object.hit(r, t_min, t_max)?;
+        rec.holdout = true;
+        Some(rec)
+    }
+
+    fn hit_packet(&self, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+        wrapped_packet(&*self.object, p, t_min, t_max, recs, |rec| rec.holdout = true);
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let mut rec = self.object.surface_at(u, v)?;
+        rec.holdout = true;
+        Some(rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec::Color;
+
+    // Sphere counting the single rays and the packets tested against it
+    struct Counted {
+        sphere: Sphere,
+        calls: Arc<[AtomicUsize; 2]>
+    }
+
+    impl Hit for Counted {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            self.calls[0].fetch_add(1, Ordering::Relaxed);
+            self.sphere.hit(r, t_min, t_max)
+        }
+
+        fn hit_packet(&self, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+            self.calls[1].fetch_add(1, Ordering::Relaxed);
+            self.sphere.hit_packet(p, t_min, t_max, recs)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            self.sphere.bounding_box()
+        }
+    }
+
+    // Rays down at the unit sphere: two hit it, one misses and one already
+    // hit something closer. The wrapped sphere gets the packet whole, and
+    // only the records of the lanes it hit are edited.
+    fn check_packets(wrap: impl Fn(Box<dyn Hit>) -> Box<dyn Hit>, edited: impl Fn(&HitRecord) -> bool) {
+        let mat: Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let rays: Vec<Ray> = [0.0, 0.5, 2.0, 0.0].iter().map(|&x| Ray::new(Point3::new(x, 5.0, 0.0), down)).collect();
+        let packet = RayPacket::new(&rays);
+
+        let calls = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+        let object = wrap(Box::new(Counted { sphere: Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, mat.clone()), calls: calls.clone() }));
+
+        let mut t_max = packet.initial_t_max(f64::INFINITY);
+        let mut recs: Vec<Option<HitRecord>> = rays.iter().map(|_| None).collect();
+        t_max[3] = 1.0;
+        recs[3] = Some(HitRecord::new(&rays[3], 1.0, (-1.0) * down, mat.clone()));
+        object.hit_packet(&packet, 0.001, &mut t_max, &mut recs);
+
+        assert_eq!((calls[0].load(Ordering::Relaxed), calls[1].load(Ordering::Relaxed)), (0, 1));
+        assert!(recs[0].as_ref().is_some_and(&edited) && recs[1].as_ref().is_some_and(&edited));
+        assert!(recs[2].is_none());
+        assert!(recs[3].as_ref().is_some_and(|rec| rec.t == 1.0 && rec.light_mask == ALL_LIGHTS && !rec.holdout));
+    }
+
+    #[test]
+    fn wrappers_forward_packets() {
+        check_packets(|object| Box::new(LightLinked::new(object, 0b10)), |rec| rec.light_mask == 0b10);
+        check_packets(|object| Box::new(Holdout::new(object)), |rec| rec.holdout);
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs;
+
+// Photometric web of a luminaire read from an IES LM-63 file (type C
+// photometry): candela values over vertical angles measured from the nadir
+// and horizontal angles around it
+pub struct IesProfile {
+    vertical: Vec<f64>,
+    horizontal: Vec<f64>,
+    // candela[h][v], already scaled by the file's candela multiplier
+    candela: Vec<Vec<f64>>,
+    max_candela: f64
+}
+
+// Index of the segment [angles[i], angles[i + 1]] containing a, and the
+// interpolation weight within it
+fn segment(angles: &[f64], a: f64) -> (usize, f64) {
+    if angles.len() == 1 {
+        return (0, 0.0);
+    }
+
+    let i = match angles.iter().position(|&x| x > a) {
+        Some(0) => 0,
+        Some(i) => i - 1,
+        None => angles.len() - 2
+    };
+    let span = angles[i + 1] - angles[i];
+    let w = if span > 0.0 { ((a - angles[i]) / span).clamp(0.0, 1.0) } else { 0.0 };
+
+    (i.min(angles.len() - 2), w)
+}
+
+impl IesProfile {
+    pub fn load(path: &str) -> IesProfile {
+        let contents = fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("Unable to read IES file {}.", path));
+        IesProfile::parse(&contents)
+            .unwrap_or_else(|| panic!("Failed to parse IES file {}.", path))
+    }
+
+    pub fn parse(contents: &str) -> Option<IesProfile> {
+        // Keywords come first, up to the TILT line
+        let tilt_at = contents.find("TILT=")?;
+        let after_tilt = &contents[tilt_at..];
+        let line_end = after_tilt.find('\n').unwrap_or(after_tilt.len());
+        let tilt = after_tilt[5..line_end].trim();
+
+        let mut numbers = after_tilt[line_end..]
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty())
+            .map(|t| t.parse::<f64>());
+        let mut next = || numbers.next()?.ok();
+
+        if tilt == "INCLUDE" {
+            // Lamp-to-luminaire geometry, then pairs of angles and factors
+            next()?;
+            let pairs = next()? as usize;
+            for _ in 0..2 * pairs {
+                next()?;
+            }
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let n_vertical = next()? as usize;
+        let n_horizontal = next()? as usize;
+        let photometric_type = next()?;
+        // Units type, width, length, height
+        for _ in 0..4 {
+            next()?;
+        }
+        let ballast = next()?;
+        let ballast_lamp = next()?;
+        let _input_watts = next()?;
+
+        if photometric_type != 1.0 {
+            eprintln!("Warning: only type C IES photometry is supported, treating profile as type C");
+        }
+
+        let scale = multiplier * ballast * ballast_lamp;
+        let vertical = (0..n_vertical).map(|_| next()).collect::<Option<Vec<f64>>>()?;
+        let horizontal = (0..n_horizontal).map(|_| next()).collect::<Option<Vec<f64>>>()?;
+        let candela = (0..n_horizontal)
+            .map(|_| (0..n_vertical).map(|_| next().map(|c| c * scale)).collect::<Option<Vec<f64>>>())
+            .collect::<Option<Vec<Vec<f64>>>>()?;
+
+        if vertical.is_empty() || horizontal.is_empty() {
+            return None;
+        }
+
+        let max_candela = candela.iter().flatten().fold(0.0_f64, |m, &c| m.max(c));
+
+        Some(IesProfile {
+            vertical,
+            horizontal,
+            candela,
+            max_candela
+        })
+    }
+
+    pub fn max_candela(&self) -> f64 {
+        self.max_candela
+    }
+
+    // Candela emitted at vertical angle theta (from the nadir) and horizontal
+    // angle phi, both in degrees, honouring the file's horizontal symmetry
+    pub fn candela(&self, theta: f64, phi: f64) -> f64 {
+        let v_first = self.vertical[0];
+        let v_last = self.vertical[self.vertical.len() - 1];
+        if theta < v_first || theta > v_last {
+            return 0.0;
+        }
+
+        let h_last = self.horizontal[self.horizontal.len() - 1];
+        let mut phi = phi.rem_euclid(360.0);
+        if h_last <= 180.0 && phi > 180.0 {
+            // Bilateral symmetry about the 0-180 plane
+            phi = 360.0 - phi;
+        }
+        if h_last <= 90.0 && phi > 90.0 {
+            // Quadrant symmetry
+            phi = 180.0 - phi;
+        }
+
+        let (v, wv) = segment(&self.vertical, theta);
+        let (h, wh) = segment(&self.horizontal, phi);
+        let v1 = (v + 1).min(self.vertical.len() - 1);
+        let h1 = (h + 1).min(self.horizontal.len() - 1);
+
+        let lerp = |row: &Vec<f64>| row[v] * (1.0 - wv) + row[v1] * wv;
+        lerp(&self.candela[h]) * (1.0 - wh) + lerp(&self.candela[h1]) * wh
+    }
+}
@@ -0,0 +1,179 @@
+use std::fs;
+
+use super::vec::Color;
+
+// Linear RGB raster, top row first
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<Color>
+}
+
+impl Image {
+    // Loads .hdr (Radiance RGBE) files as they are and 8-bit .ppm files
+    // converted from gamma 2 to linear, matching how images are written
+    pub fn load(path: &str) -> Image {
+        let bytes = fs::read(path).unwrap_or_else(|_| panic!("Unable to read image {}.", path));
+
+        let image = if path.ends_with(".hdr") {
+            Image::parse_hdr(&bytes)
+        } else if path.ends_with(".ppm") {
+            Image::parse_ppm(&bytes)
+        } else {
+            panic!("Unsupported image format {}, expected .hdr or .ppm.", path);
+        };
+
+        image.unwrap_or_else(|| panic!("Failed to parse image {}.", path))
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.data[y * self.width + x]
+    }
+
+    // Bilinear lookup at texture coordinates in [0, 1], v = 0 being the top
+    // row; u wraps around and v is clamped
+    pub fn sample(&self, u: f64, v: f64) -> Color {
+        let x = u.rem_euclid(1.0) * (self.width as f64) - 0.5;
+        let y = (v.clamp(0.0, 1.0) * (self.height as f64) - 0.5).clamp(0.0, (self.height - 1) as f64);
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let xi = |x: f64| (x as i64).rem_euclid(self.width as i64) as usize;
+        let yi = |y: f64| (y as usize).min(self.height - 1);
+
+        let top = (1.0 - fx) * self.get(xi(x0), yi(y0)) + fx * self.get(xi(x0 + 1.0), yi(y0));
+        let bottom = (1.0 - fx) * self.get(xi(x0), yi(y0 + 1.0)) + fx * self.get(xi(x0 + 1.0), yi(y0 + 1.0));
+        (1.0 - fy) * top + fy * bottom
+    }
+
+    fn parse_ppm(bytes: &[u8]) -> Option<Image> {
+        // Header tokens: magic, width, height, max value, skipping comments
+        let mut tokens = Vec::new();
+        let mut pos = 0;
+        while tokens.len() < 4 && pos < bytes.len() {
+            if bytes[pos] == b'#' {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else if bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            } else {
+                let start = pos;
+                while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+                tokens.push(String::from_utf8_lossy(&bytes[start..pos]).to_string());
+            }
+        }
+
+        if tokens.len() < 4 {
+            return None;
+        }
+
+        let width: usize = tokens[1].parse().ok()?;
+        let height: usize = tokens[2].parse().ok()?;
+        let max: f64 = tokens[3].parse().ok()?;
+
+        let values: Vec<f64> = match tokens[0].as_str() {
+            "P3" => String::from_utf8_lossy(&bytes[pos..])
+                .split_whitespace()
+                .map(|t| t.parse::<f64>().ok())
+                .collect::<Option<Vec<f64>>>()?,
+            "P6" if max < 256.0 => bytes.get(pos + 1..)?.iter().map(|&b| b as f64).collect(),
+            _ => return None
+        };
+
+        if values.len() < width * height * 3 {
+            return None;
+        }
+
+        let linear = |c: f64| (c / max).powi(2);
+        let data = values.chunks(3)
+            .take(width * height)
+            .map(|c| Color::new(linear(c[0]), linear(c[1]), linear(c[2])))
+            .collect();
+
+        Some(Image { width, height, data })
+    }
+
+    fn parse_hdr(bytes: &[u8]) -> Option<Image> {
+        // Header lines end at an empty line, followed by the resolution line
+        let mut pos = 0;
+        let mut line = || -> Option<String> {
+            let start = pos;
+            while pos < bytes.len() && bytes[pos] != b'\n' {
+                pos += 1;
+            }
+            let l = String::from_utf8_lossy(&bytes[start..pos]).to_string();
+            pos += 1;
+            if start >= bytes.len() { None } else { Some(l) }
+        };
+
+        if !line()?.starts_with("#?") {
+            return None;
+        }
+        while !line()?.trim().is_empty() { }
+
+        let resolution = line()?;
+        let parts: Vec<&str> = resolution.split_whitespace().collect();
+        if parts.len() != 4 || parts[0] != "-Y" || parts[2] != "+X" {
+            return None;
+        }
+        let height: usize = parts[1].parse().ok()?;
+        let width: usize = parts[3].parse().ok()?;
+
+        let mut data = Vec::with_capacity(width * height);
+        let mut scanline = vec![[0u8; 4]; width];
+
+        for _ in 0..height {
+            let rle = (8..32768).contains(&width)
+                && bytes.get(pos..pos + 2)? == [2, 2]
+                && bytes.get(pos + 2)? & 0x80 == 0;
+
+            if rle {
+                // New-style run length encoding, one channel at a time
+                pos += 4;
+                for channel in 0..4 {
+                    let mut x = 0;
+                    while x < width {
+                        let count = *bytes.get(pos)? as usize;
+                        pos += 1;
+                        if count > 128 {
+                            let value = *bytes.get(pos)?;
+                            pos += 1;
+                            for _ in 0..count - 128 {
+                                scanline.get_mut(x)?[channel] = value;
+                                x += 1;
+                            }
+                        } else {
+                            for _ in 0..count {
+                                scanline.get_mut(x)?[channel] = *bytes.get(pos)?;
+                                pos += 1;
+                                x += 1;
+                            }
+                        }
+                    }
+                }
+            } else {
+                for pixel in scanline.iter_mut() {
+                    pixel.copy_from_slice(bytes.get(pos..pos + 4)?);
+                    pos += 4;
+                }
+            }
+
+            for rgbe in &scanline {
+                if rgbe[3] == 0 {
+                    data.push(Color::new(0.0, 0.0, 0.0));
+                } else {
+                    let f = 2.0_f64.powi(rgbe[3] as i32 - 136);
+                    data.push(Color::new(rgbe[0] as f64 * f, rgbe[1] as f64 * f, rgbe[2] as f64 * f));
+                }
+            }
+        }
+
+        Some(Image { width, height, data })
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::ray::Ray;
+use super::sampler;
+use super::transform::Transform;
+use super::vec::Color;
+
+// Shared object placed by a transform of its own, so one copy of its
+// geometry can stand in many places
+pub type Instance = Transform<Arc<dyn Hit>>;
+
+// How far the materials of an instance stray from those it shares with the
+// others, the same way everywhere on it: random amounts drawn from the
+// instance's seed, up to `color` and `roughness`
+#[derive(Clone, Copy)]
+pub struct Variation {
+    pub seed: u64,
+    pub color: f64,
+    pub roughness: f64
+}
+
+impl Variation {
+    // Uniform number in [-1, 1) for the k-th amount
+    fn amount(self, k: u64) -> f64 {
+        let u = (sampler::hash(sampler::hash(self.seed) ^ k) >> 11) as f64 / (1u64 << 53) as f64;
+        2.0 * u - 1.0
+    }
+
+    // Base color scaled by up to `color` overall, and by up to half of it
+    // per channel to shift its hue, kept from reflecting more than it gets
+    pub fn color(self, c: Color) -> Color {
+        let scale = |k: u64| (1.0 + self.color * (self.amount(0) + 0.5 * self.amount(k))).max(0.0);
+        let channel = |v: f64, k: u64| (v * scale(k)).min(v.max(1.0));
+        Color::new(channel(c.x(), 1), channel(c.y(), 2), channel(c.z(), 3))
+    }
+
+    // Roughness moved by up to `roughness`
+    pub fn roughness(self, r: f64) -> f64 {
+        (r + self.roughness * self.amount(4)).clamp(0.0, 1.0)
+    }
+}
+
+// Object whose hits vary the materials of its surface, see Variation.
+// Variations of instances nested in it stay theirs.
+pub struct Varied {
+    object: Box<dyn Hit>,
+    variation: Variation
+}
+
+impl Varied {
+    pub fn new(object: Box<dyn Hit>, variation: Variation) -> Varied {
+        Varied {
+            object,
+            variation
+        }
+    }
+}
+
+impl Hit for Varied {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut rec = self.object.hit(r, t_min, t_max)?;
+        rec.variation = rec.variation.or(Some(self.variation));
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let mut rec = self.object.surface_at(u, v)?;
+        rec.variation = rec.variation.or(Some(self.variation));
+        Some(rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::hit::World;
+    use crate::material::{Lambertian, Scatter};
+    use crate::matrix::Matrix;
+    use crate::sphere::Sphere;
+    use crate::vec::{Point3, Vec3};
+
+    // Sphere counting the rays tested against it
+    struct Counted {
+        sphere: Sphere,
+        rays: AtomicUsize
+    }
+
+    impl Hit for Counted {
+        fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            self.rays.fetch_add(1, Ordering::Relaxed);
+            self.sphere.hit(r, t_min, t_max)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            self.sphere.bounding_box()
+        }
+    }
+
+    fn gray() -> Arc<dyn Scatter> {
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn uses_share_their_definition() {
+        const USES: usize = 10_000;
+        let uses: String = (0..USES).map(|k| format!(r#"<use ref="tree" translate="{} 0 {}" rotate="{}"/>"#, 3 * (k % 100), 3 * (k / 100), k % 360)).collect();
+        let xml = format!(r#"<RT>
+            <define name="tree">
+                <object type="sphere" center="0 1 0" radius="0.5"/>
+                <object type="box" min="-0.1 0 -0.1" max="0.1 0.6 0.1"/>
+            </define>
+            {}
+        </RT>"#, uses);
+        let doc = roxmltree::Document::parse(&xml).unwrap();
+
+        let camera = (Point3::new(0.0, 0.0, 1.0), Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let mut defines = HashMap::new();
+        let mut world = World::new();
+        for node in doc.root_element().children().filter(|n| n.is_element()) {
+            if node.has_tag_name("define") {
+                let object = crate::define_parser(&node, gray(), camera, &defines);
+                defines.insert("tree".to_string(), object);
+            } else {
+                world.push(crate::element_parser(&node, gray(), camera, &defines));
+            }
+        }
+
+        // One copy of the tree and its hierarchy, and one object per use
+        assert_eq!(Arc::strong_count(&defines["tree"]), USES + 1);
+        assert_eq!(world.len(), USES);
+    }
+
+    #[test]
+    fn worlds_cull_instances_by_their_boxes() {
+        let tree = Arc::new(Counted { sphere: Sphere::new(Point3::new(0.0, 0.0, 0.0), 0.5, gray()), rays: AtomicUsize::new(0) });
+        let shared: Arc<dyn Hit> = tree.clone();
+
+        let mut world = World::new();
+        for k in 0..100 {
+            world.push(Box::new(Instance::new(shared.clone(), Matrix::translation(Vec3::new(3.0 * k as f64, 0.0, 0.0)))));
+        }
+        world.build_accel();
+        drop(shared);
+        assert_eq!(Arc::strong_count(&tree), 101);
+
+        // The world's hierarchy is over the boxes of the instances
+        let bbox = world.get(10).unwrap().bounding_box().unwrap();
+        assert_eq!((bbox.min, bbox.max), (Point3::new(29.5, -0.5, -0.5), Point3::new(30.5, 0.5, 0.5)));
+
+        // So a ray only reaches the tree through the instances of the leaf
+        // it crosses,
+        let rec = world.hit(&Ray::new(Point3::new(30.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::INFINITY).unwrap();
+        assert_eq!(rec.object_id, 10);
+        assert!((rec.t - 4.5).abs() < 1e-9);
+        assert!(tree.rays.load(Ordering::Relaxed) <= 2, "{} of the instances were tested.", tree.rays.load(Ordering::Relaxed));
+
+        // and none at all for rays missing every box
+        tree.rays.store(0, Ordering::Relaxed);
+        assert!(world.hit(&Ray::new(Point3::new(30.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0)), 0.001, f64::INFINITY).is_none());
+        assert_eq!(tree.rays.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,221 @@
+// Minimal JSON reader, enough for the scene description of a glTF file
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut parser = Parser { bytes: text.as_bytes(), pos: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.bytes.len() {
+            return None;
+        }
+        Some(value)
+    }
+
+    // Member of an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    // Element of an array
+    pub fn at(&self, index: usize) -> Option<&Json> {
+        self.array()?.get(index)
+    }
+
+    pub fn array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None
+        }
+    }
+
+    pub fn number(&self) -> Option<f64> {
+        match self {
+            Json::Number(x) => Some(*x),
+            _ => None
+        }
+    }
+
+    pub fn index(&self) -> Option<usize> {
+        self.number().filter(|x| *x >= 0.0 && x.fract() == 0.0).map(|x| x as usize)
+    }
+
+    pub fn str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    // Array of numbers
+    pub fn numbers(&self) -> Option<Vec<f64>> {
+        self.array()?.iter().map(Json::number).collect()
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.bytes.get(self.pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn expect(&mut self, literal: &str) -> Option<()> {
+        let end = self.pos + literal.len();
+        if self.bytes.get(self.pos..end)? != literal.as_bytes() {
+            return None;
+        }
+        self.pos = end;
+        Some(())
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.skip_whitespace();
+        match *self.bytes.get(self.pos)? {
+            b'n' => self.expect("null").map(|_| Json::Null),
+            b't' => self.expect("true").map(|_| Json::Bool(true)),
+            b'f' => self.expect("false").map(|_| Json::Bool(false)),
+            b'"' => self.string().map(Json::String),
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.pos) == Some(&b']') {
+                    self.pos += 1;
+                    return Some(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next()? {
+                        b',' => continue,
+                        b']' => return Some(Json::Array(items)),
+                        _ => return None
+                    }
+                }
+            },
+            b'{' => {
+                self.pos += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.pos) == Some(&b'}') {
+                    self.pos += 1;
+                    return Some(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    if self.next()? != b':' {
+                        return None;
+                    }
+                    members.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.next()? {
+                        b',' => continue,
+                        b'}' => return Some(Json::Object(members)),
+                        _ => return None
+                    }
+                }
+            },
+            _ => {
+                let start = self.pos;
+                while self.bytes.get(self.pos).is_some_and(|b| b.is_ascii_digit() || b"+-.eE".contains(b)) {
+                    self.pos += 1;
+                }
+                std::str::from_utf8(&self.bytes[start..self.pos]).ok()?.parse().ok().map(Json::Number)
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.next()? != b'"' {
+            return None;
+        }
+
+        let mut bytes = Vec::new();
+        loop {
+            match self.next()? {
+                b'"' => return String::from_utf8(bytes).ok(),
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let high = self.hex()?;
+                            // Characters outside the basic plane come as a
+                            // surrogate pair
+                            if (0xd800..0xdc00).contains(&high) {
+                                self.expect("\\u")?;
+                                let low = self.hex()?;
+                                char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low.checked_sub(0xdc00)?))?
+                            } else {
+                                char::from_u32(high)?
+                            }
+                        },
+                        _ => return None
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                },
+                b => bytes.push(b)
+            }
+        }
+    }
+
+    fn hex(&mut self) -> Option<u32> {
+        let digits = std::str::from_utf8(self.bytes.get(self.pos..self.pos + 4)?).ok()?;
+        self.pos += 4;
+        u32::from_str_radix(digits, 16).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values() {
+        let doc = Json::parse(r#" {"a": [1, -2.5e1, true, null], "b": {"c": "x\"\u00e9\n"}} "#).unwrap();
+        let a = doc.get("a").and_then(Json::array).unwrap();
+        assert_eq!(a[0].index(), Some(1));
+        assert_eq!(a[1].number(), Some(-25.0));
+        assert!(matches!(a[2], Json::Bool(true)));
+        assert!(matches!(a[3], Json::Null));
+        assert_eq!(doc.get("b").and_then(|b| b.get("c")).and_then(Json::str), Some("x\"\u{e9}\n"));
+        assert!(doc.get("missing").is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_documents() {
+        for text in ["", "[1, 2", "{\"a\" 1}", "[1,]", "\"open", "1 2", "[1e]"] {
+            assert!(Json::parse(text).is_none(), "{:?} was parsed.", text);
+        }
+    }
+}
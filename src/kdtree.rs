@@ -0,0 +1,407 @@
+use super::aabb::Aabb;
+use super::accel::{self, Accelerator};
+use super::accel_cache::{Cached, Reader, Writer};
+use super::hit::{Hit, HitRecord};
+use super::packet::{RayPacket, MAX_LANES};
+use super::ray::Ray;
+use super::vec::Vec3;
+
+// Cost of testing an object relative to stepping through a node, for the
+// SAH
+const INTERSECT_COST: f64 = 20.0;
+
+// Share of the cost taken off splits that leave one side empty, which rays
+// cross for free
+const EMPTY_BONUS: f64 = 0.5;
+
+// Splits costing more than a leaf allowed on the way down a branch, as
+// the splits below them can still pay off
+const BAD_REFINES: usize = 3;
+
+// Deepest traversal stack, holding at most one entry per level
+const STACK_SIZE: usize = 64;
+
+#[derive(Clone, Copy)]
+enum Node {
+    // The objects order[start..start + count]
+    Leaf { start: usize, count: usize },
+    // Split by the plane at `split` along `axis`; the child below it is the
+    // next node, the one above it is node `above`
+    Interior { axis: usize, split: f64, above: usize }
+}
+
+// Plane an edge of an object's box lies in, sorted along an axis
+#[derive(Clone, Copy)]
+struct Edge {
+    t: f64,
+    start: bool
+}
+
+// kd-tree over a list of objects, splitting space by planes chosen with the
+// surface area heuristic rather than splitting the objects themselves.
+// Objects crossing a plane are in the leaves on both sides, and a ray
+// visits the leaves it crosses front to back, stopping at the first one
+// with a hit closer than its far side, so thin cells tightly fit around
+// large objects. Edits rebuild the whole tree.
+pub struct KdTree {
+    nodes: Vec<Node>,
+    // Object indices, grouped by leaf, with objects in several leaves
+    // listed in each of them
+    order: Vec<usize>,
+    bounds: Option<Aabb>,
+    // Every object in the tree, by index
+    indices: Vec<usize>,
+    // Objects without a bounding box, tested against every ray
+    unbounded: Vec<usize>
+}
+
+impl KdTree {
+    // Tree over only the objects at the given indices
+    pub fn with_indices<T: Hit>(objects: &[T], indices: impl Iterator<Item = usize>) -> KdTree {
+        KdTree::from_boxes(accel::boxes(objects, indices))
+    }
+
+    // Tree over the objects of the given boxes
+    pub fn from_boxes(boxes: Vec<(usize, Option<Aabb>)>) -> KdTree {
+        let mut tree = KdTree {
+            nodes: Vec::new(),
+            order: Vec::new(),
+            bounds: None,
+            indices: Vec::new(),
+            unbounded: Vec::new()
+        };
+
+        let mut items = Vec::new();
+        for (index, bbox) in boxes {
+            tree.indices.push(index);
+            match bbox {
+                Some(bbox) => items.push((index, bbox)),
+                None => tree.unbounded.push(index)
+            }
+        }
+
+        if let Some(bounds) = items.iter().map(|(_, bbox)| *bbox).reduce(|a, b| a.surrounding(b)) {
+            // Deep enough for an object per leaf, as in PBRT
+            let max_depth = (8.0 + 1.3 * (items.len() as f64).log2()).round() as usize;
+            tree.bounds = Some(bounds);
+            tree.build(&items, bounds, max_depth.min(STACK_SIZE - 1), 0);
+        }
+        tree
+    }
+
+    fn build(&mut self, items: &[(usize, Aabb)], bounds: Aabb, depth: usize, bad_refines: usize) {
+        let leaf_cost = INTERSECT_COST * items.len() as f64;
+        let split = if items.len() <= 1 || depth == 0 { None } else { best_split(items, bounds) };
+
+        // Splits worse than the leaf are tried a few times, unless they are
+        // far worse on a small node
+        let mut bad_refines = bad_refines;
+        let split = split.filter(|&(_, _, cost)| {
+            if cost <= leaf_cost {
+                return true;
+            }
+            bad_refines += 1;
+            !(cost > 4.0 * leaf_cost && items.len() < 16) && bad_refines < BAD_REFINES
+        });
+
+        let Some((axis, split, _)) = split else {
+            self.nodes.push(Node::Leaf { start: self.order.len(), count: items.len() });
+            self.order.extend(items.iter().map(|(index, _)| *index));
+            return;
+        };
+
+        // Objects lying in the plane go on both sides
+        let clipped = |bbox: &Aabb| bbox.overlap(bounds);
+        let below: Vec<(usize, Aabb)> = items.iter().copied()
+            .filter(|(_, bbox)| clipped(bbox).min[axis] < split || clipped(bbox).max[axis] <= split)
+            .collect();
+        let above: Vec<(usize, Aabb)> = items.iter().copied()
+            .filter(|(_, bbox)| clipped(bbox).max[axis] > split || clipped(bbox).min[axis] >= split)
+            .collect();
+
+        let (mut below_bounds, mut above_bounds) = (bounds, bounds);
+        below_bounds.max[axis] = split;
+        above_bounds.min[axis] = split;
+
+        let index = self.nodes.len();
+        self.nodes.push(Node::Leaf { start: 0, count: 0 });
+        self.build(&below, below_bounds, depth - 1, bad_refines);
+        self.nodes[index] = Node::Interior { axis, split, above: self.nodes.len() };
+        self.build(&above, above_bounds, depth - 1, bad_refines);
+    }
+}
+
+// Of the planes through the edges of the objects' boxes, clipped to the
+// node, the one with the least expected cost: the chance of a ray crossing
+// the node crossing each side is the ratio of their surface areas. Returns
+// the axis, the plane and the cost.
+fn best_split(items: &[(usize, Aabb)], bounds: Aabb) -> Option<(usize, f64, f64)> {
+    let extent = bounds.extent();
+    let area = surface_area(extent);
+    if area <= 0.0 {
+        return None;
+    }
+
+    let mut best: Option<(usize, f64, f64)> = None;
+    for axis in (0..3).filter(|&axis| extent[axis] > 0.0) {
+        let mut edges: Vec<Edge> = items.iter().flat_map(|(_, bbox)| {
+            let bbox = bbox.overlap(bounds);
+            [Edge { t: bbox.min[axis], start: true }, Edge { t: bbox.max[axis], start: false }]
+        }).collect();
+        // Starts before ends where edges share a plane
+        edges.sort_by(|a, b| a.t.total_cmp(&b.t).then(b.start.cmp(&a.start)));
+
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let (mut below, mut above) = (0, items.len());
+        for edge in &edges {
+            if !edge.start {
+                above -= 1;
+            }
+
+            if edge.t > bounds.min[axis] && edge.t < bounds.max[axis] {
+                let side = |length: f64| 2.0 * (extent[u] * extent[v] + length * (extent[u] + extent[v]));
+                let below_area = side(edge.t - bounds.min[axis]);
+                let above_area = side(bounds.max[axis] - edge.t);
+                let bonus = if below == 0 || above == 0 { EMPTY_BONUS } else { 0.0 };
+                let cost = 1.0 + INTERSECT_COST * (1.0 - bonus) * (below_area * below as f64 + above_area * above as f64) / area;
+                if best.is_none_or(|(_, _, c)| cost < c) {
+                    best = Some((axis, edge.t, cost));
+                }
+            }
+
+            if edge.start {
+                below += 1;
+            }
+        }
+    }
+
+    best
+}
+
+fn surface_area(e: Vec3) -> f64 {
+    2.0 * (e.x() * e.y() + e.y() * e.z() + e.z() * e.x())
+}
+
+impl Cached for KdTree {
+    fn tag() -> u64 {
+        3
+    }
+
+    fn encode(&self, out: &mut Writer) {
+        out.usize(self.nodes.len());
+        for node in &self.nodes {
+            match *node {
+                Node::Leaf { start, count } => {
+                    out.usize(3);
+                    out.usize(start);
+                    out.usize(count);
+                },
+                Node::Interior { axis, split, above } => {
+                    out.usize(axis);
+                    out.f64(split);
+                    out.usize(above);
+                }
+            }
+        }
+        out.indices(&self.order);
+        out.aabb(self.bounds);
+        out.indices(&self.indices);
+        out.indices(&self.unbounded);
+    }
+
+    // Only trees as built are read back: every node but the root is the
+    // child of a single node before it, and none is deeper than the
+    // traversal stack allows
+    fn decode(input: &mut Reader, objects: usize) -> Option<KdTree> {
+        let count = input.usize()?;
+        if count > input.remaining() {
+            return None;
+        }
+        let mut nodes = Vec::new();
+        let mut depths = vec![usize::MAX; count];
+        for index in 0..count {
+            let depth = if index == 0 { 0 } else { depths[index] };
+            if depth >= STACK_SIZE {
+                return None;
+            }
+
+            nodes.push(match input.usize()? {
+                3 => Node::Leaf { start: input.usize()?, count: input.usize()? },
+                // The child below is the next node, so both must follow it
+                axis if axis < 3 => {
+                    let split = input.f64()?;
+                    let above = input.usize()?;
+                    if index + 1 >= count || above <= index + 1 || above >= count {
+                        return None;
+                    }
+                    for child in [index + 1, above] {
+                        if depths[child] != usize::MAX {
+                            return None;
+                        }
+                        depths[child] = depth + 1;
+                    }
+                    Node::Interior { axis, split, above }
+                },
+                _ => return None
+            });
+        }
+
+        let order = input.indices(objects)?;
+        let leaves_fit = nodes.iter().all(|node| match *node {
+            Node::Leaf { start, count } => start.checked_add(count).is_some_and(|end| end <= order.len()),
+            Node::Interior { .. } => true
+        });
+        if !leaves_fit {
+            return None;
+        }
+
+        let bounds = input.aabb()?;
+        if bounds.is_some() && nodes.is_empty() {
+            return None;
+        }
+        Some(KdTree { nodes, order, bounds, indices: input.indices(objects)?, unbounded: input.indices(objects)? })
+    }
+}
+
+impl<T: Hit> Accelerator<T> for KdTree {
+    fn hit(&self, objects: &[T], r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = t_max;
+        let mut result = None;
+        let test = |index: usize, closest: &mut f64, result: &mut Option<HitRecord>| {
+            if let Some(mut rec) = objects[index].hit(r, t_min, *closest) {
+                *closest = rec.t;
+                rec.object_id = index;
+                *result = Some(rec);
+            }
+        };
+
+        for &index in &self.unbounded {
+            test(index, &mut closest, &mut result);
+        }
+
+        let d = r.direction();
+        let origin = r.origin();
+        let inv_dir = Vec3::new(1.0 / d.x(), 1.0 / d.y(), 1.0 / d.z());
+        let Some((mut t0, mut t1)) = self.bounds.and_then(|b| b.interval(origin, inv_dir, t_min, closest)) else {
+            return result;
+        };
+
+        // Far children still to visit, with the part of the ray inside them
+        let mut stack = [(0, 0.0, 0.0); STACK_SIZE];
+        let mut top = 0;
+        let mut node = 0;
+        loop {
+            match self.nodes[node] {
+                Node::Interior { axis, split, above } => {
+                    // The side of the origin is crossed first; rays in the
+                    // plane count as below it, like the objects in it
+                    let below_first = origin[axis] < split || (origin[axis] == split && d[axis] <= 0.0);
+                    let (near, far) = if below_first { (node + 1, above) } else { (above, node + 1) };
+
+                    // NaN for rays in the plane, which stay on one side
+                    let t_plane = (split - origin[axis]) * inv_dir[axis];
+                    if t_plane.is_nan() || t_plane > t1 || t_plane <= 0.0 {
+                        node = near;
+                    } else if t_plane < t0 {
+                        node = far;
+                    } else {
+                        stack[top] = (far, t_plane, t1);
+                        top += 1;
+                        node = near;
+                        t1 = t_plane;
+                    }
+                },
+                Node::Leaf { start, count } => {
+                    for &object in &self.order[start..start + count] {
+                        test(object, &mut closest, &mut result);
+                    }
+
+                    // Hits closer than the next cell can't be beaten
+                    loop {
+                        if top == 0 {
+                            return result;
+                        }
+                        top -= 1;
+                        (node, t0, t1) = stack[top];
+                        if t0 <= closest {
+                            break;
+                        }
+                    }
+                    t1 = t1.min(closest);
+                }
+            }
+        }
+    }
+
+    fn hit_packet(&self, objects: &[T], p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+        // Lanes part ways at the first plane between them, so they are
+        // traced one by one
+        for k in 0..p.lanes {
+            if let Some(rec) = self.hit(objects, &p.ray(k), t_min, t_max[k]) {
+                t_max[k] = rec.t;
+                recs[k] = Some(rec);
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    fn insert(&mut self, objects: &[T], index: usize) {
+        let indices: Vec<usize> = self.indices.iter().copied().chain([index]).collect();
+        *self = KdTree::with_indices(objects, indices.into_iter());
+    }
+
+    fn remove(&mut self, objects: &[T], index: usize) {
+        let indices: Vec<usize> = self.indices.iter().copied().filter(|&i| i != index).collect();
+        *self = KdTree::with_indices(objects, indices.into_iter());
+    }
+
+    fn refit(&mut self, objects: &[T], _index: usize) {
+        let indices = std::mem::take(&mut self.indices);
+        *self = KdTree::with_indices(objects, indices.into_iter());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::accel::tests::{assert_matches_scan, rays, rng, spheres};
+
+    #[test]
+    fn trees_find_the_hits_of_a_scan() {
+        for (seed, count) in [(4, 300), (5, 3000)] {
+            let mut rng = rng(seed);
+            let objects = spheres(&mut rng, count);
+            let rays = rays(&mut rng, count, 400);
+            let tree = KdTree::from_boxes(accel::boxes(&objects, 0..count));
+            assert_matches_scan(&tree, &objects, &vec![true; count], &rays);
+        }
+    }
+
+    // Edits rebuild the tree, over the objects it held and the one added or
+    // without the one taken out
+    #[test]
+    fn edits_find_the_hits_of_a_scan() {
+        let mut rng = rng(6);
+        let objects = spheres(&mut rng, 300);
+        let rays = rays(&mut rng, 300, 400);
+
+        let mut tree = KdTree::from_boxes(accel::boxes(&objects, 0..280));
+        for index in 280..objects.len() {
+            tree.insert(&objects, index);
+        }
+        let mut held = vec![true; objects.len()];
+        assert_matches_scan(&tree, &objects, &held, &rays);
+
+        for _ in 0..20 {
+            let index = rng.gen_range(0..objects.len());
+            tree.remove(&objects, index);
+            held[index] = false;
+        }
+        assert_matches_scan(&tree, &objects, &held, &rays);
+    }
+}
@@ -0,0 +1,57 @@
+use std::time::Instant;
+
+use super::denoise;
+use super::metadata::Metadata;
+use super::render::{self, RenderSettings, SplitColor};
+use super::scene::Scene;
+use super::vec::Color;
+
+// "scene.ppm" -> "scene.spp016.ppm", padded to the digits of the largest count
+fn rung_filename(img_name: &str, samples: u64, digits: usize) -> String {
+    match img_name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}.spp{:0digits$}.{}", stem, samples, ext),
+        None => format!("{}.spp{:0digits$}", img_name, samples)
+    }
+}
+
+// Renders the image in passes that each double the samples per pixel, 1, 2,
+// 4, ... up to samples_per_pixel, and writes it after every pass. Samples
+// keep their random numbers from pass to pass, so each image is the one a
+// render with that many samples per pixel gives. Returns the samples of all
+// passes, like render::render_split.
+pub fn render_ladder(scene: &Scene, settings: &RenderSettings, img_name: &str, source: &Metadata) -> Vec<SplitColor> {
+    let start = Instant::now();
+    let total = settings.samples_per_pixel;
+    let digits = total.to_string().len();
+
+    let mut sums = vec![SplitColor::default(); (settings.image_width * settings.image_height) as usize];
+    let mut done = 0;
+    while done < total {
+        let rung = (2 * done).clamp(1, total);
+        let pass = RenderSettings { first_sample: done, samples_per_pixel: rung - done, ..settings.clone() };
+        for (sum, p) in sums.iter_mut().zip(render::render_split(scene, &pass)) {
+            sum.even += p.even;
+            sum.odd += p.odd;
+            sum.coverage += p.coverage;
+        }
+        done = rung;
+
+        let rung_settings = RenderSettings { samples_per_pixel: rung, ..settings.clone() };
+        let pixels: Vec<Color> = sums.iter().map(|p| p.total()).collect();
+        let pixels = if settings.denoise {
+            denoise::denoise(&pixels, settings.image_width, settings.image_height, rung)
+        } else {
+            pixels
+        };
+
+        let mut metadata = source.clone();
+        metadata.add_settings(&rung_settings);
+        metadata.add("RenderTime", format!("{:.3} s", start.elapsed().as_secs_f64()));
+
+        let filename = rung_filename(img_name, rung, digits);
+        super::write_image(&filename, &pixels, scene.has_holdouts.then(|| render::coverage(&sums)).as_deref(), &rung_settings, &metadata);
+        eprintln!("Wrote {} samples per pixel to {}", rung, filename);
+    }
+
+    sums
+}
@@ -0,0 +1,1976 @@
+pub mod aabb;
+pub mod accel;
+pub mod accel_cache;
+mod animation;
+pub mod atmosphere;
+mod aov;
+mod bake;
+pub mod bucket;
+mod builtin;
+pub mod bvh;
+pub mod camera;
+pub mod capsule;
+mod cli;
+pub mod clip;
+pub mod cloud;
+pub mod cone;
+pub mod csg;
+mod contact;
+pub mod cuboid;
+pub mod curve;
+pub mod cylinder;
+mod deep;
+mod denoise;
+mod displace;
+pub mod disk;
+mod environment;
+mod exr;
+mod falsecolor;
+#[cfg(test)]
+mod fixtures;
+mod focus;
+mod font;
+mod generate;
+mod generator;
+mod gltf;
+pub mod group;
+mod hair;
+pub mod heightfield;
+pub mod hit;
+pub mod ies;
+pub mod image;
+pub mod instance;
+mod json;
+pub mod kdtree;
+mod ladder;
+pub mod light;
+pub mod material;
+mod matpreview;
+pub mod matrix;
+mod measure;
+mod metadata;
+pub mod moving_sphere;
+pub mod mesh;
+pub mod metaball;
+pub mod microfacet;
+mod obj;
+pub mod packet;
+pub mod plane;
+pub mod plugin;
+mod ply;
+mod png;
+pub mod quad;
+pub mod quadric;
+pub mod ray;
+pub mod registry;
+pub mod render;
+pub mod rounded_box;
+pub mod sampler;
+pub mod scene;
+mod script;
+pub mod sdf;
+mod server;
+mod spectrum;
+pub mod sphere;
+mod stl;
+pub mod subsurface;
+mod temporal;
+pub mod tiles;
+pub mod texture;
+pub mod torus;
+pub mod transform;
+mod tree;
+pub mod triangle;
+pub mod vec;
+pub mod volume;
+mod wavefront;
+pub mod waves;
+
+use std::collections::HashMap;
+use std::io::{Read, Write, BufWriter};
+use std::fs::File;
+use std::sync::Arc;
+use std::time::Instant;
+
+use roxmltree::Document;
+
+use aabb::Aabb;
+use accel::AccelKind;
+use animation::{CameraKey, CameraPath};
+use atmosphere::Atmosphere;
+use camera::{Camera, DEFAULT_ASPECT_RATIO, DEFAULT_FOCUS_DIST, DEFAULT_VFOV};
+use capsule::Capsule;
+use clip::{ClipPlane, Clipped, Keep};
+use cloud::SphereCloud;
+use cone::Cone;
+use csg::{Csg, CsgOp};
+use cuboid::Cuboid;
+use curve::{Curve, Curves};
+use cylinder::Cylinder;
+use disk::Disk;
+use displace::{DisplacedSphere, Displacement, Height};
+use exr::ExrImage;
+use environment::{Environment, Layer, SunLight, Visibility};
+use group::Group;
+use heightfield::Heightfield;
+use hit::{Hit, Holdout, LightLinked, LightMask, World};
+use ies::IesProfile;
+use image::Image;
+use instance::{Instance, Varied, Variation};
+use light::{AreaLight, Emission, Falloff, Light, PointLight, SphereLight, SpotLight, Units};
+use material::{Dielectric, Displaced, Emissive, FresnelSwitch, Glossy, Iridescent, Isotropic, Lambertian, Metal, Mix, RaySwitch, Subsurface, ThinDielectric, Transparent, Water};
+use matrix::Matrix;
+use mesh::Mesh;
+use metaball::{Charge, Metaballs};
+use metadata::Metadata;
+use moving_sphere::MovingSphere;
+use cli::Options;
+use plane::Plane;
+use render::{Flip, Integrator, IntegratorParams, RenderSettings, SplitColor};
+use quad::Quad;
+use quadric::Quadric;
+use rounded_box::RoundedBox;
+use ray::RayType;
+use scene::{Scene, DEFAULT_FILM_SIZE};
+use script::Script;
+use sdf::{Sdf, SdfShape};
+use spectrum::Spectrum;
+use sphere::Sphere;
+use texture::{Blend, LatLong, Projected, SolidColor, Texture};
+use torus::Torus;
+use transform::{Rotate, Transform, Translate};
+use triangle::Triangle;
+use vec::{Color, Point3, Vec3};
+use volume::ConstantMedium;
+use waves::Waves;
+
+use crate::material::Scatter;
+
+pub use roxmltree;
+
+pub fn value_parser(values: &str) -> (f64, f64, f64) {
+    let parts: Vec<&str> = values.split_whitespace().collect();
+
+    // Parse each part into an f64 variable
+    (
+        parts[0].parse::<f64>().expect("Failed to parse number 1"),
+        parts[1].parse::<f64>().expect("Failed to parse number 2"),
+        parts[2].parse::<f64>().expect("Failed to parse number 3"),
+    )
+}
+
+pub fn vec_attr(node: &roxmltree::Node, name: &str, what: &str) -> Vec3 {
+    if let Some(attr) = node.attribute(name) {
+        let value = value_parser(attr);
+        Vec3::new(value.0, value.1, value.2)
+    } else {
+        panic!("Missing {} {}!", what, name);
+    }
+}
+
+// Returns the light and, for lights with a shape, the emitting geometry
+fn light_parser(node: &roxmltree::Node) -> (Box<dyn Light>, Option<Box<dyn Hit>>) {
+    let mut color = Color::new(1.0, 1.0, 1.0);
+    let mut units = Units::Watt;
+    let mut falloff = Falloff::InverseSquare;
+
+    // Parsing light color
+    if let Some(attr) = node.attribute("color") {
+        let value = value_parser(attr);
+        color = Color::new(value.0, value.1, value.2);
+    }
+    color *= spectrum_attr(node, "light");
+
+    // Parsing IES profile; without a power its candelas are used as-is
+    let ies = node.attribute("ies").map(|path| Arc::new(IesProfile::load(path)));
+    let mut ies_rotate = 0.0;
+    if let Some(attr) = node.attribute("ies_rotate") {
+        ies_rotate = attr.parse()
+            .expect("Failed to parse light IES rotation.");
+    }
+
+    // Parsing light power and its units
+    let power: f64 = if let Some(attr) = node.attribute("power") {
+        attr.parse()
+            .expect("Failed to parse light power.")
+    } else if ies.is_some() {
+        1.0
+    } else {
+        panic!("Missing light power!");
+    };
+    let absolute = node.attribute("power").is_none();
+
+    if let Some(attr) = node.attribute("units") {
+        units = Units::from_name(attr)
+            .unwrap_or_else(|| panic!("Unknown light units '{}'.", attr));
+    }
+
+    // Parsing distance falloff
+    if let Some(attr) = node.attribute("falloff") {
+        falloff = Falloff::from_name(attr)
+            .unwrap_or_else(|| panic!("Unknown light falloff '{}'.", attr));
+    }
+
+    match node.attribute("type") {
+        Some("point") => {
+            let position = vec_attr(node, "position", "light");
+            let mut light = PointLight::new(position, color, power, units, falloff);
+
+            if let Some(profile) = ies {
+                // The profile's nadir points down unless a direction is given
+                let mut nadir = Vec3::new(0.0, -1.0, 0.0);
+                if node.attribute("direction").is_some() {
+                    nadir = vec_attr(node, "direction", "light");
+                }
+                light = light.with_ies(profile, nadir, ies_rotate, absolute);
+            }
+
+            (Box::new(light), None)
+        },
+        Some("spot") => {
+            let position = vec_attr(node, "position", "light");
+            let direction = vec_attr(node, "direction", "light");
+
+            // Parsing cone angles
+            let outer: f64 = if let Some(attr) = node.attribute("angle") {
+                attr.parse()
+                    .expect("Failed to parse spot light angle.")
+            } else {
+                panic!("Missing spot light angle!");
+            };
+            let inner = if let Some(attr) = node.attribute("inner_angle") {
+                attr.parse()
+                    .expect("Failed to parse spot light inner angle.")
+            } else {
+                outer
+            };
+
+            let mut light = SpotLight::new(position, direction, inner, outer, color, power, units, falloff);
+            if let Some(profile) = ies {
+                light = light.with_ies(profile, ies_rotate, absolute);
+            }
+
+            (Box::new(light), None)
+        },
+        Some("area") => {
+            if ies.is_some() {
+                panic!("IES profiles are only supported on point and spot lights!");
+            }
+
+            let corner = vec_attr(node, "corner", "light");
+            let u = vec_attr(node, "u", "light");
+            let v = vec_attr(node, "v", "light");
+
+            let emission = Emission {
+                two_sided: bool_attr(node, "two_sided", false),
+                spread: f64_attr(node, "spread", 0.0).max(0.0)
+            };
+
+            let light = AreaLight::new(corner, u, v, color, power, units, falloff).with_emission(emission);
+            let shape = Quad::new(corner, u, v, light_material(node, Emissive::new(light.radiance(), light.emission())));
+
+            (Box::new(light), Some(Box::new(shape)))
+        },
+        Some("sphere") => {
+            if ies.is_some() {
+                panic!("IES profiles are only supported on point and spot lights!");
+            }
+
+            let center = vec_attr(node, "center", "light");
+            let radius = f64_attr(node, "radius", 0.1);
+            if radius <= 0.0 {
+                panic!("The light radius must be positive.");
+            }
+
+            let light = SphereLight::new(center, radius, color, power, units, falloff);
+            let shape = Sphere::new(center, radius, light_material(node, Emissive::new(light.radiance(), Emission::diffuse())));
+
+            (Box::new(light), Some(Box::new(shape)))
+        },
+        Some(other) => panic!("The light type '{}' doesn't exists!", other),
+        None => panic!("Missing light type!")
+    }
+}
+
+// Material of the shape of a light, which camera rays go through when the
+// light is hidden from the camera
+fn light_material(node: &roxmltree::Node, emissive: Emissive) -> Arc<dyn Scatter> {
+    let emissive: Arc<dyn Scatter> = Arc::new(emissive);
+    if bool_attr(node, "camera", true) {
+        emissive
+    } else {
+        Arc::new(RaySwitch::new(emissive, Arc::new(Transparent), vec![RayType::Camera]))
+    }
+}
+
+pub fn bool_attr(node: &roxmltree::Node, name: &str, default: bool) -> bool {
+    match node.attribute(name) {
+        Some("true") | Some("1") => true,
+        Some("false") | Some("0") => false,
+        Some(other) => panic!("Failed to parse '{}' as a boolean for {}.", other, name),
+        None => default
+    }
+}
+
+pub fn f64_attr(node: &roxmltree::Node, name: &str, default: f64) -> f64 {
+    if let Some(attr) = node.attribute(name) {
+        attr.parse()
+            .unwrap_or_else(|_| panic!("Failed to parse {}.", name))
+    } else {
+        default
+    }
+}
+
+pub fn color_attr(node: &roxmltree::Node, name: &str, default: Color) -> Color {
+    if node.attribute(name).is_some() {
+        vec_attr(node, name, "color")
+    } else {
+        default
+    }
+}
+
+// Color of an emission spectrum given by name, scaling the color set next to
+// it; white without one. A temperature alone means a blackbody.
+fn spectrum_attr(node: &roxmltree::Node, what: &str) -> Color {
+    let temperature = node.attribute("temperature").map(|attr| {
+        let kelvin: f64 = attr.parse()
+            .unwrap_or_else(|_| panic!("Failed to parse {} temperature.", what));
+        if kelvin <= 0.0 {
+            panic!("The {} temperature must be positive.", what);
+        }
+        kelvin
+    });
+
+    let spectrum = match (node.attribute("spectrum"), temperature) {
+        (Some(name), _) => Spectrum::from_name(name, temperature).unwrap_or_else(|| match name {
+            "blackbody" => panic!("Missing {} blackbody temperature!", what),
+            _ => panic!("The spectrum '{}' doesn't exists!", name)
+        }),
+        (None, Some(kelvin)) => Spectrum::Blackbody(kelvin),
+        (None, None) => return Color::new(1.0, 1.0, 1.0)
+    };
+
+    let [r, g, b] = spectrum.rgb();
+    Color::new(r, g, b)
+}
+
+// The material color, or the registered texture of a <texture> child, with
+// its <projection> children layered over it in order; projectors default to
+// the scene camera
+fn texture_parser(node: &roxmltree::Node, color: Color, camera: (Point3, Point3, Vec3)) -> Arc<dyn Texture> {
+    let mut texture: Arc<dyn Texture> = match node.children().find(|n| n.has_tag_name("texture")) {
+        Some(child) => {
+            let name = child.attribute("type").expect("Missing texture type!");
+            match registry::texture(name) {
+                Some(parser) => parser(&child),
+                None => panic!("The texture type '{}' doesn't exists!", name)
+            }
+        },
+        None => Arc::new(SolidColor::new(color))
+    };
+
+    for projection in node.children().filter(|n| n.has_tag_name("projection")) {
+        let image = Arc::new(Image::load(projection.attribute("image").expect("Missing projection image!")));
+        let look_from = projection.attribute("look_from").map_or(camera.0, |_| vec_attr(&projection, "look_from", "projection"));
+        let look_at = projection.attribute("look_at").map_or(camera.1, |_| vec_attr(&projection, "look_at", "projection"));
+        let up = projection.attribute("up").map_or(camera.2, |_| vec_attr(&projection, "up", "projection"));
+        let aspect = f64_attr(&projection, "aspect", image.width as f64 / image.height as f64);
+        let projector = Camera::new(look_from, look_at, up, f64_attr(&projection, "fov", DEFAULT_VFOV), aspect, 0.0, DEFAULT_FOCUS_DIST);
+
+        let blend = match projection.attribute("blend") {
+            None | Some("over") => Blend::Over,
+            Some("multiply") => Blend::Multiply,
+            Some(other) => panic!("Unknown projection blend '{}'.", other)
+        };
+
+        texture = Arc::new(Projected::new(texture, image, projector, f64_attr(&projection, "opacity", 1.0), blend));
+    }
+
+    texture
+}
+
+// Single-valued texture of a material parameter: the attribute `name`, or
+// the texture and projections of the child element `name`, over that value
+fn map_parser(node: &roxmltree::Node, name: &str, default: f64, camera: (Point3, Point3, Vec3)) -> Arc<dyn Texture> {
+    let value = f64_attr(node, name, default);
+    let color = Color::new(value, value, value);
+
+    match node.children().find(|n| n.has_tag_name(name)) {
+        Some(child) => texture_parser(&child, color, camera),
+        None => Arc::new(SolidColor::new(color))
+    }
+}
+
+// Gerstner waves of a <waves> element, blown along the wind direction in xz
+fn waves_parser(node: &roxmltree::Node) -> Waves {
+    let u64_attr = |name: &str, default: u64| node.attribute(name).map_or(default, |attr| {
+        attr.parse::<u64>().unwrap_or_else(|_| panic!("Failed to parse waves {}.", name))
+    });
+
+    let count = u64_attr("count", 8);
+    if count == 0 {
+        panic!("The waves count can't be zero.");
+    }
+    let scale = f64_attr(node, "scale", 4.0);
+    if scale <= 0.0 {
+        panic!("The waves scale must be positive.");
+    }
+    let wind = node.attribute("wind").map_or(Vec3::new(1.0, 0.0, 0.0), |_| vec_attr(node, "wind", "waves"));
+    if wind.x() == 0.0 && wind.z() == 0.0 {
+        panic!("The waves wind must have a horizontal direction.");
+    }
+
+    Waves::new(count as u32, scale, f64_attr(node, "amplitude", 0.1), f64_attr(node, "choppiness", 0.5), wind, u64_attr("seed", 0))
+}
+
+fn integrator_parser(node: &roxmltree::Node) -> IntegratorParams {
+    let u64_attr = |name: &str| node.attribute(name).map(|attr| {
+        attr.parse::<u64>().unwrap_or_else(|_| panic!("Failed to parse integrator {}.", name))
+    });
+
+    let integrator = match node.attribute("type") {
+        Some(name) => Integrator::from_name(name)
+            .unwrap_or_else(|| panic!("The integrator '{}' doesn't exists!", name)),
+        None => panic!("Missing integrator type!")
+    };
+
+    IntegratorParams {
+        integrator: Some(integrator),
+        max_depth: u64_attr("max_depth"),
+        rr_start: u64_attr("rr_start"),
+        distance: node.attribute("distance").map(|_| f64_attr(node, "distance", 0.0))
+    }
+}
+
+// The two materials nested in a combinator material
+fn child_materials(node: &roxmltree::Node, camera: (Point3, Point3, Vec3)) -> (Arc<dyn Scatter>, Arc<dyn Scatter>) {
+    let children: Vec<Arc<dyn Scatter>> = node.children()
+        .filter(|n| n.has_tag_name("material"))
+        .map(|n| material_parser(&n, camera))
+        .collect();
+
+    match <[Arc<dyn Scatter>; 2]>::try_from(children) {
+        Ok([a, b]) => (a, b),
+        Err(_) => panic!("A {} material needs exactly two child materials.", node.attribute("type").unwrap_or(""))
+    }
+}
+
+// Material of a <material> element, with the projector defaults of
+// texture_parser
+fn material_parser(node: &roxmltree::Node, camera: (Point3, Point3, Vec3)) -> Arc<dyn Scatter> {
+    let mut color = Color::new(0.0, 0.0, 0.0);
+
+    // Parsing material type 
+    let mat_type = if let Some(attr) = node.attribute("type") {
+        attr.to_string()
+    } else {
+        panic!("Missing material type!");
+    };
+
+    // Parsing material color 
+    if let Some(attr) = node.attribute("color") {
+        let value = value_parser(attr);
+        color = Color::new(value.0, value.1, value.2);
+    }
+
+    let albedo = texture_parser(node, color, camera);
+
+    let material: Arc<dyn Scatter> = match mat_type.as_str() {
+        "lambertian" => Arc::new(Lambertian::textured(albedo)),
+        "isotropic" => Arc::new(Isotropic::new(color)),
+        "metal" => {
+            // Parsing fuzziness 
+            let fuzz = if let Some(attr) = node.attribute("fuzz") {
+                attr.parse()
+                    .expect("Failed to parse material fuzziness.")
+            } else {
+                panic!("Missing material fuzziness.");
+            };
+
+            Arc::new(Metal::textured(albedo, fuzz))
+        },
+        "dielectric" => {
+            // Parsing fuzziness 
+            let refrect = if let Some(attr) = node.attribute("refrect_idx") {
+                attr.parse()
+                    .expect("Failed to parse material refrective index.")
+            } else {
+                panic!("Missing material refrective index.");
+            };
+
+            // Sheets without a volume inside, like window panes
+            if bool_attr(node, "thin", false) {
+                Arc::new(ThinDielectric::new(refrect))
+            } else {
+                Arc::new(Dielectric::new(refrect))
+            }
+        },
+        "water" => {
+            // Pure water absorbs about this much per metre
+            let absorption = color_attr(node, "absorption", Color::new(0.45, 0.06, 0.015));
+            let waves = node.children().find(|n| n.has_tag_name("waves")).map(|child| Arc::new(waves_parser(&child)));
+            Arc::new(Water::new(f64_attr(node, "refrect_idx", 1.33), absorption, waves))
+        },
+        "iridescent" => {
+            Arc::new(Iridescent::new(color,
+                                     f64_attr(node, "roughness", 0.2),
+                                     f64_attr(node, "period", 1.0),
+                                     f64_attr(node, "phase", 0.0),
+                                     f64_attr(node, "intensity", 1.0)))
+        },
+        "glossy" => {
+            let tangent = node.attribute("tangent").map_or(Vec3::new(1.0, 0.0, 0.0), |_| vec_attr(node, "tangent", "material"));
+            Arc::new(Glossy::new(texture_parser(node, color, camera),
+                                 map_parser(node, "roughness", 0.2, camera),
+                                 map_parser(node, "anisotropy", 0.0, camera),
+                                 map_parser(node, "rotation", 0.0, camera),
+                                 tangent))
+        },
+        "mix" => {
+            let children = child_materials(node, camera);
+
+            // The factor is the probability of the second material, and
+            // projections over it paint the mask
+            let factor = f64_attr(node, "factor", 0.5);
+            let mask = texture_parser(node, Color::new(factor, factor, factor), camera);
+            Arc::new(Mix::new(children.0, children.1, mask))
+        },
+        "fresnel" => {
+            let children = child_materials(node, camera);
+            Arc::new(FresnelSwitch::new(children.0, children.1, f64_attr(node, "refrect_idx", 1.5)))
+        },
+        "ray_switch" => {
+            let children = child_materials(node, camera);
+            let types = node.attribute("rays")
+                .expect("Missing ray switch rays!")
+                .split_whitespace()
+                .map(|name| RayType::from_name(name).unwrap_or_else(|| panic!("The ray type '{}' doesn't exists!", name)))
+                .collect();
+            Arc::new(RaySwitch::new(children.0, children.1, types))
+        },
+        "transparent" => Arc::new(Transparent),
+        "subsurface" => {
+            // Parsing the mean free path, the average distance light travels
+            // between scattering events in each channel
+            let mfp = vec_attr(node, "mfp", "material");
+            if mfp.x() <= 0.0 || mfp.y() <= 0.0 || mfp.z() <= 0.0 {
+                panic!("The material mean free path must be positive.");
+            }
+
+            Arc::new(Subsurface::new(f64_attr(node, "refrect_idx", 1.4), mfp, color))
+        },
+        other => match registry::material(other) {
+            Some(parser) => parser(node),
+            None => panic!("The material doesn't exists!.")
+        },
+    };
+
+    match node.children().find(|n| n.has_tag_name("displacement")) {
+        Some(child) => Arc::new(Displaced::new(material, displacement_parser(&child, camera))),
+        None => material
+    }
+}
+
+// Displacement of a <displacement> element, by the heights of its texture or
+// of noise
+fn displacement_parser(node: &roxmltree::Node, camera: (Point3, Point3, Vec3)) -> Displacement {
+    let u64_attr = |name: &str, default: u64| node.attribute(name).map_or(default, |attr| {
+        attr.parse::<u64>().unwrap_or_else(|_| panic!("Failed to parse displacement {}.", name))
+    });
+
+    let subdivisions = u64_attr("subdivisions", 2);
+    if subdivisions > 6 {
+        panic!("The displacement subdivisions can't be over 6.");
+    }
+
+    let height = if node.children().any(|n| n.has_tag_name("texture") || n.has_tag_name("projection")) {
+        Height::Texture(texture_parser(node, Color::new(0.0, 0.0, 0.0), camera))
+    } else {
+        let octaves = u64_attr("octaves", 4);
+        if octaves == 0 {
+            panic!("The displacement octaves can't be zero.");
+        }
+        Height::Noise { frequency: f64_attr(node, "frequency", 1.0), octaves: octaves as u32, seed: u64_attr("seed", 0) }
+    };
+
+    Displacement {
+        amount: f64_attr(node, "amount", 0.1),
+        subdivisions: subdivisions as u32,
+        height
+    }
+}
+
+// Solid of the two objects or nested CSG elements of the node, made of the
+// materials defined before them like objects in the world
+fn csg_parser(node: &roxmltree::Node, mut mat: Arc<dyn Scatter>, camera: (Point3, Point3, Vec3)) -> Box<dyn Hit> {
+    let op = match node.attribute("op") {
+        Some(name) => CsgOp::from_name(name).unwrap_or_else(|| panic!("The CSG operation '{}' doesn't exists!", name)),
+        None => panic!("Missing CSG operation!")
+    };
+
+    let mut children = Vec::new();
+    for child in node.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "material" => mat = material_parser(&child, camera),
+            "object" => children.push(clips_parser(&child, object_parser(&child, mat.clone(), camera), camera)),
+            "csg" => children.push(clips_parser(&child, csg_parser(&child, mat.clone(), camera), camera)),
+            // Its own clips cut the result
+            "clip" => { },
+            other => panic!("The CSG element can't hold a '{}' element!", other)
+        }
+    }
+
+    if children.len() != 2 {
+        panic!("A CSG element needs exactly two objects!");
+    }
+    let b = children.pop().unwrap();
+    let a = children.pop().unwrap();
+
+    Box::new(Csg::new(op, a, b))
+}
+
+// Distance function of an element of an SDF object and its children
+fn sdf_parser(node: &roxmltree::Node) -> SdfShape {
+    let children = || -> Vec<SdfShape> {
+        let shapes: Vec<SdfShape> = node.children().filter(|n| n.is_element()).map(|n| sdf_parser(&n)).collect();
+        if shapes.is_empty() {
+            panic!("The SDF {} needs at least one shape!", node.tag_name().name());
+        }
+        shapes
+    };
+    let smooth = f64_attr(node, "smooth", 0.0);
+    let fold = |combine: fn(Box<SdfShape>, Box<SdfShape>, f64) -> SdfShape| {
+        children().into_iter().reduce(|a, b| combine(Box::new(a), Box::new(b), smooth)).unwrap()
+    };
+
+    match node.tag_name().name() {
+        "sphere" => SdfShape::Sphere {
+            center: vec_attr(node, "center", "SDF sphere"),
+            radius: f64_attr(node, "radius", 1.0)
+        },
+        "box" => {
+            let min = vec_attr(node, "min", "SDF box");
+            let max = vec_attr(node, "max", "SDF box");
+            let half = 0.5 * (max - min);
+            let rounding = f64_attr(node, "rounding", 0.0);
+            if rounding < 0.0 || rounding > half.x().abs().min(half.y().abs()).min(half.z().abs()) {
+                panic!("The SDF box rounding must be between 0 and half its smallest side.");
+            }
+
+            SdfShape::Box {
+                center: 0.5 * (min + max),
+                half: Vec3::new(half.x().abs(), half.y().abs(), half.z().abs()),
+                rounding
+            }
+        },
+        "torus" => SdfShape::Torus {
+            center: vec_attr(node, "center", "SDF torus"),
+            major_radius: f64_attr(node, "major_radius", 1.0),
+            minor_radius: f64_attr(node, "minor_radius", 0.25)
+        },
+        "capsule" => SdfShape::Capsule {
+            a: vec_attr(node, "a", "SDF capsule"),
+            b: vec_attr(node, "b", "SDF capsule"),
+            radius: f64_attr(node, "radius", 0.5)
+        },
+        "union" => fold(SdfShape::Union),
+        "intersection" => fold(SdfShape::Intersection),
+        "difference" => fold(SdfShape::Difference),
+        other => panic!("The SDF shape '{}' doesn't exists!", other)
+    }
+}
+
+// Scale factors of the `scale` attribute, where a single one scales every
+// axis
+fn scale_attr(node: &roxmltree::Node, what: &str) -> Vec3 {
+    match node.attribute("scale").map(|attr| attr.split_whitespace().count()) {
+        None => Vec3::new(1.0, 1.0, 1.0),
+        Some(1) => {
+            let factor = f64_attr(node, "scale", 1.0);
+            Vec3::new(factor, factor, factor)
+        },
+        Some(_) => vec_attr(node, "scale", what)
+    }
+}
+
+// Rotation axis of the attribute `name`, the y axis by default
+fn axis_attr(node: &roxmltree::Node, name: &str, what: &str) -> Vec3 {
+    let axis = node.attribute(name).map_or(Vec3::new(0.0, 1.0, 0.0), |_| vec_attr(node, name, what));
+    if axis.near_zero() {
+        panic!("The {} rotation axis can't be zero.", what);
+    }
+    axis
+}
+
+// Scales by `scale`, then turns by `rotate` degrees around the axis of the
+// attribute `axis`, then moves by `translate`
+fn transform_attr(node: &roxmltree::Node, what: &str, axis: &str) -> Matrix {
+    let scale = scale_attr(node, what);
+    if scale.x() * scale.y() * scale.z() == 0.0 {
+        panic!("The {} scale can't be zero.", what);
+    }
+
+    let translate = node.attribute("translate").map_or(Vec3::new(0.0, 0.0, 0.0), |_| vec_attr(node, "translate", what));
+    Matrix::translation(translate) * Matrix::rotation(axis_attr(node, axis, what), f64_attr(node, "rotate", 0.0)) * Matrix::scale(scale)
+}
+
+// Object scaled by `scale`, turned by `rotate` degrees about `rotate_axis`
+// through the origin, then moved by `translate`. Scaled objects get a full
+// transform, the others only the wrappers they need.
+fn placement_parser(node: &roxmltree::Node, mut object: Box<dyn Hit>) -> Box<dyn Hit> {
+    if node.has_attribute("scale") {
+        return Box::new(Transform::new(object, transform_attr(node, "object", "rotate_axis")));
+    }
+
+    if node.has_attribute("rotate") {
+        object = Box::new(Rotate::new(object, axis_attr(node, "rotate_axis", "object"), f64_attr(node, "rotate", 0.0)));
+    }
+    if node.has_attribute("translate") {
+        object = Box::new(Translate::new(object, vec_attr(node, "translate", "object")));
+    }
+    object
+}
+
+// Object of an <object>, <csg>, <use> or <group> element, made of the
+// material mat and filled with a medium scattering by it instead if it has a
+// density
+fn element_parser(node: &roxmltree::Node, mat: Arc<dyn Scatter>, camera: (Point3, Point3, Vec3), defines: &HashMap<String, Arc<dyn Hit>>) -> Box<dyn Hit> {
+    let object: Box<dyn Hit> = match node.tag_name().name() {
+        "csg" => placement_parser(node, csg_parser(node, mat.clone(), camera)),
+        "group" => {
+            // Its children are placed inside it, so they inherit its
+            // transform on top of their own
+            let objects = children_parser(node, mat.clone(), camera, defines, "grouped");
+            if objects.is_empty() {
+                panic!("Empty groups aren't allowed!");
+            }
+            placement_parser(node, Box::new(Group::new(objects)))
+        },
+        "use" => {
+            let name = node.attribute("ref").expect("Missing use ref!");
+            let object = defines.get(name).unwrap_or_else(|| panic!("The definition '{}' doesn't exists!", name));
+            let instance = Box::new(Instance::new(object.clone(), transform_attr(node, "use", "axis")));
+            match variation_parser(node) {
+                Some(variation) => Box::new(Varied::new(instance, variation)),
+                None => instance
+            }
+        },
+        _ => placement_parser(node, object_parser(node, mat.clone(), camera))
+    };
+
+    if !node.has_attribute("density") {
+        return clips_parser(node, object, camera);
+    }
+    let density = f64_attr(node, "density", 0.0);
+    if density <= 0.0 {
+        panic!("The medium density must be positive.");
+    }
+    clips_parser(node, Box::new(ConstantMedium::new(object, density, mat)), camera)
+}
+
+// Object cut by the <clip> elements among its children, one after the
+// other. Each keeps what is inside or outside its region: the box between
+// `min` and `max`, or what lies behind its own plane or those of its
+// <plane> children.
+fn clips_parser(node: &roxmltree::Node, mut object: Box<dyn Hit>, camera: (Point3, Point3, Vec3)) -> Box<dyn Hit> {
+    for clip in node.children().filter(|n| n.has_tag_name("clip")) {
+        let cap = clip.children()
+            .find(|n| n.has_tag_name("material"))
+            .map(|n| material_parser(&n, camera));
+        let keep = match clip.attribute("keep") {
+            Some(name) => Keep::from_name(name).unwrap_or_else(|| panic!("The clip keep '{}' doesn't exists!", name)),
+            None => Keep::Inside
+        };
+
+        let clipped = if clip.has_attribute("min") || clip.has_attribute("max") {
+            let bbox = Aabb::new(vec_attr(&clip, "min", "clip box"), vec_attr(&clip, "max", "clip box"));
+            Clipped::new(object, Arc::new(ClipPlane::around(bbox, cap))).with_bounds(bbox)
+        } else {
+            let planes: Vec<ClipPlane> = if clip.has_attribute("normal") {
+                vec![clip_plane_parser(&clip, cap)]
+            } else {
+                clip.children()
+                    .filter(|n| n.has_tag_name("plane"))
+                    .map(|n| clip_plane_parser(&n, cap.clone()))
+                    .collect()
+            };
+            if planes.is_empty() {
+                panic!("A clip needs a box, a plane or <plane> children!");
+            }
+            Clipped::new(object, Arc::new(planes))
+        };
+        object = Box::new(clipped.with_keep(keep));
+    }
+    object
+}
+
+fn clip_plane_parser(node: &roxmltree::Node, cap: Option<Arc<dyn Scatter>>) -> ClipPlane {
+    let normal = vec_attr(node, "normal", "clip plane");
+    if normal.near_zero() {
+        panic!("The clip plane normal can't be zero.");
+    }
+    ClipPlane::new(vec_attr(node, "point", "clip plane"), normal, cap)
+}
+
+// Variation of the materials of a <use>, if it asks for one, drawn from its
+// `seed`, by default the element's place in the file
+fn variation_parser(node: &roxmltree::Node) -> Option<Variation> {
+    let color = f64_attr(node, "color_variation", 0.0);
+    let roughness = f64_attr(node, "roughness_variation", 0.0);
+    if !(0.0..=1.0).contains(&color) {
+        panic!("The color variation must be between 0 and 1.");
+    }
+    if !(0.0..=1.0).contains(&roughness) {
+        panic!("The roughness variation must be between 0 and 1.");
+    }
+    if color == 0.0 && roughness == 0.0 {
+        return None;
+    }
+
+    let seed = node.attribute("seed").map_or(node.id().get() as u64, |seed| {
+        seed.parse().expect("Failed to parse instance seed.")
+    });
+    Some(Variation { seed, color, roughness })
+}
+
+// Objects among the children of a <define> or <group>, made of the material
+// mat until one of their own; `what` is done to them, for errors
+fn children_parser(node: &roxmltree::Node, mut mat: Arc<dyn Scatter>, camera: (Point3, Point3, Vec3), defines: &HashMap<String, Arc<dyn Hit>>, what: &str) -> Vec<Box<dyn Hit>> {
+    let mut objects = Vec::new();
+    for child in node.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "material" => mat = material_parser(&child, camera),
+            "object" | "csg" | "use" | "group" => objects.push(element_parser(&child, mat.clone(), camera, defines)),
+            "generator" => objects.extend(generator_parser(&child, mat.clone())),
+            // Clips of a group cut it as a whole
+            "clip" if node.has_tag_name("group") => { },
+            other => panic!("<{}> elements can't be {}.", other, what)
+        }
+    }
+    objects
+}
+
+// Objects of a <define>, for <use> elements to share
+fn define_parser(node: &roxmltree::Node, mat: Arc<dyn Scatter>, camera: (Point3, Point3, Vec3), defines: &HashMap<String, Arc<dyn Hit>>) -> Arc<dyn Hit> {
+    let mut objects = children_parser(node, mat, camera, defines, "defined");
+
+    match objects.len() {
+        0 => panic!("Empty definitions can't be used!"),
+        1 => Arc::from(objects.pop().unwrap()),
+        _ => Arc::new(Group::new(objects))
+    }
+}
+
+// Sphere pushed out along its normals by `displacement` times the heights of
+// a lat-long heightmap image or of the texture of a <height> child, and
+// tessellated into a mesh of 20 * 4^subdivisions triangles
+fn displaced_sphere_parser(node: &roxmltree::Node, center: Point3, radius: f64, mat: Arc<dyn Scatter>, camera: (Point3, Point3, Vec3)) -> Box<dyn Hit> {
+    let amount = f64_attr(node, "displacement", 0.0);
+    let subdivisions = node.attribute("subdivisions").map_or(5, |attr| {
+        attr.parse::<u32>().expect("Failed to parse sphere subdivisions.")
+    });
+    if subdivisions > 8 {
+        panic!("The sphere subdivisions can't be over 8.");
+    }
+
+    let height = match (node.attribute("heightmap"), node.children().find(|n| n.has_tag_name("height"))) {
+        (Some(filename), _) => {
+            // Heights are the gray levels stored in the file, like those of
+            // heightfields
+            let mut image = Image::load(filename);
+            if filename.ends_with(".ppm") {
+                for c in image.data.iter_mut() {
+                    *c = Color::new(c.x().sqrt(), c.y().sqrt(), c.z().sqrt());
+                }
+            }
+            Height::Map(Arc::new(image))
+        },
+        (None, Some(child)) => Height::Texture(texture_parser(&child, Color::new(0.0, 0.0, 0.0), camera)),
+        (None, None) => panic!("A displaced sphere needs a heightmap or a <height> texture!")
+    };
+
+    Box::new(displace::sphere(center, radius, amount, subdivisions, &height, mat))
+}
+
+// Object of an <object> element, made of the material mat; spheres unless
+// the type says otherwise
+fn object_parser(node: &roxmltree::Node, mat: Arc<dyn Scatter>, camera: (Point3, Point3, Vec3)) -> Box<dyn Hit> {
+    match node.attribute("type") {
+        None | Some("sphere") if node.has_attribute("center0") => {
+            // Moving from center0 to center1 between time0 and time1
+            let center0 = vec_attr(node, "center0", "sphere");
+            let center1 = vec_attr(node, "center1", "sphere");
+            let time0 = f64_attr(node, "time0", 0.0);
+            let time1 = f64_attr(node, "time1", 1.0);
+            let radius = f64_attr(node, "radius", 1.0);
+            if time1 < time0 {
+                panic!("The sphere time1 can't be before time0.");
+            }
+
+            Box::new(MovingSphere::new(center0, center1, time0, time1, radius, mat))
+        },
+        None | Some("sphere") => {
+            // Parsing object center 
+            let center = if let Some(attr) = node.attribute("center") {
+                let value = value_parser(attr);
+                Point3::new(value.0, value.1, value.2)
+            } else {
+                panic!("Missing object center!");
+            };
+
+            // Parsing object radius 
+            let rad = if let Some(attr) = node.attribute("radius") {
+                attr.parse()
+                    .expect("Failed to parse object radius.")
+            } else {
+                panic!("Missing object radius.");
+            };
+
+            if node.has_attribute("displacement") {
+                return displaced_sphere_parser(node, center, rad, mat, camera);
+            }
+            if let Some(displacement) = mat.displacement() {
+                // Marched, since spheres are smooth enough to step through
+                return Box::new(DisplacedSphere::new(center, rad, displacement.amount, displacement.height.clone(), mat.clone()));
+            }
+            Box::new(Sphere::new(center, rad, mat))
+        },
+        Some("box") => {
+            let min = vec_attr(node, "min", "box");
+            let max = vec_attr(node, "max", "box");
+
+            Box::new(Cuboid::new(min, max, mat))
+        },
+        Some("rounded_box") => {
+            let min = vec_attr(node, "min", "rounded box");
+            let max = vec_attr(node, "max", "rounded box");
+
+            // A tenth of the shortest side by default
+            let extent = Aabb::new(min, max).extent();
+            let shortest = extent.x().min(extent.y()).min(extent.z());
+            let radius = f64_attr(node, "radius", 0.1 * shortest);
+            if radius <= 0.0 {
+                panic!("The rounded box radius must be positive.");
+            }
+            if radius > 0.5 * shortest {
+                panic!("The rounded box radius can't be more than half its shortest side.");
+            }
+
+            Box::new(RoundedBox::new(min, max, radius, mat))
+        },
+        Some("plane") => {
+            let point = vec_attr(node, "point", "plane");
+            let normal = vec_attr(node, "normal", "plane");
+            if normal.near_zero() {
+                panic!("The plane normal can't be zero.");
+            }
+
+            Box::new(Plane::new(point, normal, mat))
+        },
+        Some("planet") => {
+            // A ground sphere of the material, or of a lat-long map, inside
+            // an atmosphere shell lit by the sun
+            let center = vec_attr(node, "center", "planet");
+            let radius = f64_attr(node, "radius", 1.0);
+            if radius <= 0.0 {
+                panic!("The planet radius must be positive.");
+            }
+            let thickness = f64_attr(node, "atmosphere", radius * 0.01);
+            if thickness <= 0.0 {
+                panic!("The planet atmosphere must be positive.");
+            }
+
+            let sun = vec_attr(node, "sun", "planet");
+            if sun.near_zero() {
+                panic!("The planet sun can't be zero.");
+            }
+            let irradiance = f64_attr(node, "sun_intensity", 20.0) * color_attr(node, "sun_color", Color::new(1.0, 1.0, 1.0));
+            let air = Atmosphere::new(center, radius, thickness, f64_attr(node, "air_density", 1.0), sun, irradiance);
+
+            let ground: Arc<dyn Scatter> = match node.attribute("map") {
+                Some(filename) => Arc::new(Lambertian::textured(Arc::new(LatLong::new(Arc::new(Image::load(filename)), center)))),
+                None => mat
+            };
+            Box::new(Group::new(vec![
+                Box::new(Sphere::new(center, radius, ground)),
+                Box::new(Sphere::new(center, radius + thickness, Arc::new(air)))
+            ]))
+        },
+        Some("quad") => {
+            // Parallelogram from corner along the edges u and v, like area
+            // lights; its front is the side of u x v
+            let corner = vec_attr(node, "corner", "quad");
+            let u = vec_attr(node, "u", "quad");
+            let v = vec_attr(node, "v", "quad");
+            if u.cross(v).near_zero() {
+                panic!("The quad edges u and v can't be parallel.");
+            }
+
+            Box::new(Quad::new(corner, u, v, mat))
+        },
+        Some("triangle") => {
+            // Corners in counter-clockwise order seen from the front
+            let v0 = vec_attr(node, "v0", "triangle");
+            let v1 = vec_attr(node, "v1", "triangle");
+            let v2 = vec_attr(node, "v2", "triangle");
+
+            Box::new(Triangle::new(v0, v1, v2, mat))
+        },
+        Some("capsule") => {
+            let a = vec_attr(node, "a", "capsule");
+            let b = vec_attr(node, "b", "capsule");
+            let radius = f64_attr(node, "radius", 0.5);
+            if radius <= 0.0 {
+                panic!("The capsule radius must be positive.");
+            }
+            Box::new(Capsule::new(a, b, radius, mat))
+        },
+        Some("curve") => {
+            let p = ["p0", "p1", "p2", "p3"].map(|name| vec_attr(node, name, "curve"));
+            let radius = f64_attr(node, "radius", 0.05);
+            let tip = f64_attr(node, "tip_radius", radius);
+            if radius <= 0.0 || tip <= 0.0 {
+                panic!("The curve radius must be positive.");
+            }
+            Box::new(Curve::new(p, (radius, tip), mat))
+        },
+        Some("curves") => {
+            let filename = node.attribute("filename").expect("Missing curves filename!");
+            let radius = f64_attr(node, "radius", 0.01);
+            let tip = f64_attr(node, "tip_radius", radius);
+            if radius <= 0.0 || tip <= 0.0 {
+                panic!("The curves radius must be positive.");
+            }
+            Box::new(Curves::new(&hair::load(filename), radius, tip, mat))
+        },
+        Some("cone") => {
+            // Opens from the apex along the axis, which defaults to down so
+            // the cone stands on its base
+            let apex = vec_attr(node, "apex", "cone");
+            let axis = node.attribute("axis").map_or(Vec3::new(0.0, -1.0, 0.0), |_| vec_attr(node, "axis", "cone"));
+            if axis.near_zero() {
+                panic!("The cone axis can't be zero.");
+            }
+
+            let half_angle = f64_attr(node, "half_angle", 30.0);
+            if half_angle <= 0.0 || half_angle >= 90.0 {
+                panic!("The cone half_angle must be between 0 and 90 degrees.");
+            }
+            let height = f64_attr(node, "height", 1.0);
+            Box::new(Cone::new(apex, axis, half_angle, height, bool_attr(node, "cap", true), mat))
+        },
+        Some("cylinder") => {
+            // Centered on the center, around the axis, which defaults to up
+            let center = vec_attr(node, "center", "cylinder");
+            let axis = node.attribute("axis").map_or(Vec3::new(0.0, 1.0, 0.0), |_| vec_attr(node, "axis", "cylinder"));
+            if axis.near_zero() {
+                panic!("The cylinder axis can't be zero.");
+            }
+
+            let radius = f64_attr(node, "radius", 1.0);
+            let height = f64_attr(node, "height", 1.0);
+            Box::new(Cylinder::new(center, axis, radius, height, bool_attr(node, "caps", true), mat))
+        },
+        Some("disk") => {
+            let center = vec_attr(node, "center", "disk");
+            let normal = vec_attr(node, "normal", "disk");
+            if normal.near_zero() {
+                panic!("The disk normal can't be zero.");
+            }
+
+            // A positive inner radius cuts a hole out of the middle
+            let radius = f64_attr(node, "radius", 1.0);
+            let inner_radius = f64_attr(node, "inner_radius", 0.0);
+            if inner_radius >= radius {
+                panic!("The disk inner_radius must be smaller than its radius.");
+            }
+            Box::new(Disk::new(center, normal, radius, inner_radius, mat))
+        },
+        Some("torus") => {
+            // Lies flat around the axis, which defaults to up
+            let center = vec_attr(node, "center", "torus");
+            let axis = node.attribute("axis").map_or(Vec3::new(0.0, 1.0, 0.0), |_| vec_attr(node, "axis", "torus"));
+            if axis.near_zero() {
+                panic!("The torus axis can't be zero.");
+            }
+
+            let major_radius = f64_attr(node, "major_radius", 1.0);
+            let minor_radius = f64_attr(node, "minor_radius", 0.25);
+            if minor_radius <= 0.0 || minor_radius >= major_radius {
+                panic!("The torus minor_radius must be positive and smaller than its major_radius.");
+            }
+            Box::new(Torus::new(center, axis, major_radius, minor_radius, mat))
+        },
+        Some("ellipsoid") => {
+            let center = vec_attr(node, "center", "ellipsoid");
+            let radii = vec_attr(node, "radii", "ellipsoid");
+            if radii.x() <= 0.0 || radii.y() <= 0.0 || radii.z() <= 0.0 {
+                panic!("The ellipsoid radii must be positive.");
+            }
+            Box::new(Quadric::ellipsoid(center, radii, mat))
+        },
+        Some("quadric") => {
+            // Row by row, clipped to the box from min to max
+            let values: Vec<f64> = node.attribute("matrix")
+                .expect("Missing quadric matrix!")
+                .split_whitespace()
+                .map(|v| v.parse().expect("Failed to parse quadric matrix."))
+                .collect();
+            if values.len() != 16 {
+                panic!("The quadric matrix must have 16 values.");
+            }
+            let mut q = [[0.0; 4]; 4];
+            for (row, chunk) in q.iter_mut().zip(values.chunks(4)) {
+                row.copy_from_slice(chunk);
+            }
+
+            let bounds = Aabb::new(vec_attr(node, "min", "quadric"), vec_attr(node, "max", "quadric"));
+            Box::new(Quadric::new(q, bounds, mat))
+        },
+        Some("sdf") => {
+            // Several shapes are joined into one
+            let shapes: Vec<SdfShape> = node.children().filter(|n| n.is_element() && !n.has_tag_name("clip")).map(|n| sdf_parser(&n)).collect();
+            let shape = shapes.into_iter()
+                .reduce(|a, b| SdfShape::Union(Box::new(a), Box::new(b), 0.0))
+                .expect("Missing SDF shape!");
+
+            Box::new(Sdf::new(shape, mat))
+        },
+        Some("metaballs") => {
+            let charges: Vec<Charge> = node.children()
+                .filter(|n| n.has_tag_name("ball"))
+                .map(|ball| {
+                    let radius = f64_attr(&ball, "radius", 1.0);
+                    if radius <= 0.0 {
+                        panic!("The metaball radius must be positive.");
+                    }
+                    Charge { center: vec_attr(&ball, "center", "metaball"), radius, weight: f64_attr(&ball, "weight", 1.0) }
+                })
+                .collect();
+            if charges.is_empty() {
+                panic!("Missing metaballs!");
+            }
+
+            let threshold = f64_attr(node, "threshold", 0.5);
+            if threshold <= 0.0 {
+                panic!("The metaball threshold must be positive.");
+            }
+
+            Box::new(Metaballs::new(charges, threshold, mat))
+        },
+        Some("heightfield") => {
+            let min = vec_attr(node, "min", "heightfield");
+            let max = vec_attr(node, "max", "heightfield");
+            let count = |name: &str, default: u64| node.attribute(name).map_or(default, |attr| {
+                attr.parse::<u64>().unwrap_or_else(|_| panic!("Failed to parse heightfield {}.", name))
+            });
+
+            let waves = node.children().find(|n| n.has_tag_name("waves"));
+            let (samples, nx, nz) = if let Some(child) = waves {
+                // The wave crests reach max.y and their troughs min.y
+                let waves = waves_parser(&child);
+                let n = count("resolution", 256) as usize;
+                let extent = waves.extent();
+                let step_x = (max.x() - min.x()) / (n - 1).max(1) as f64;
+                let step_z = (max.z() - min.z()) / (n - 1).max(1) as f64;
+                let samples = (0..n * n).map(|k| {
+                    let (x, z) = (min.x() + (k % n) as f64 * step_x, min.z() + (k / n) as f64 * step_z);
+                    0.5 + 0.5 * waves.height(x, z) / extent
+                }).collect();
+                (samples, n, n)
+            } else if let Some(filename) = node.attribute("image") {
+                // Heights are the gray levels stored in the file, which for
+                // PPM images were decoded from gamma 2
+                let image = Image::load(filename);
+                let level = |c: f64| if filename.ends_with(".ppm") { c.sqrt() } else { c };
+                let samples = image.data.iter().map(|c| (level(c.x()) + level(c.y()) + level(c.z())) / 3.0).collect();
+                (samples, image.width, image.height)
+            } else {
+                let n = count("resolution", 256) as usize;
+                let octaves = count("octaves", 6) as u32;
+                if octaves == 0 {
+                    panic!("The heightfield octaves can't be zero.");
+                }
+                (heightfield::noise(n, octaves, f64_attr(node, "frequency", 4.0), count("seed", 0)), n, n)
+            };
+
+            if nx < 2 || nz < 2 {
+                panic!("A heightfield needs at least 2 by 2 samples!");
+            }
+            if min.x() >= max.x() || min.z() >= max.z() {
+                panic!("The heightfield extent can't be empty.");
+            }
+
+            Box::new(Heightfield::new(samples, nx, nz, min, max, mat))
+        },
+        Some("points") => {
+            let filename = node.attribute("filename").expect("Missing points filename!");
+            let radius = f64_attr(node, "radius", 0.01);
+            if radius <= 0.0 {
+                panic!("The points radius must be positive.");
+            }
+            Box::new(SphereCloud::load(filename, radius, mat))
+        },
+        Some("mesh") => {
+            let filename = node.attribute("filename").expect("Missing mesh filename!");
+
+            let data = if filename.ends_with(".ply") {
+                ply::load(filename)
+            } else if filename.ends_with(".stl") {
+                stl::load(filename)
+            } else {
+                obj::load(filename)
+            };
+            match mat.displacement() {
+                Some(displacement) => Box::new(displace::mesh(&data, displacement, mat.clone())),
+                None => Box::new(Mesh::transformed(&data, Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0), mat))
+            }
+        },
+        Some(other) => match registry::shape(other) {
+            Some(parser) => parser(node, mat),
+            None => panic!("The object type '{}' doesn't exists!", other)
+        }
+    }
+}
+
+// Objects of a stress scene generator, each added to the world on its own
+fn generator_parser(node: &roxmltree::Node, mat: Arc<dyn Scatter>) -> Vec<Box<dyn Hit>> {
+    let count = |name: &str, default: u64| node.attribute(name).map_or(default, |attr| {
+        attr.parse::<u64>().unwrap_or_else(|_| panic!("Failed to parse generator {}.", name))
+    });
+
+    match node.attribute("type") {
+        Some("sphereflake") => {
+            let radius = f64_attr(node, "radius", 1.0);
+            if radius <= 0.0 {
+                panic!("The sphereflake radius must be positive.");
+            }
+            generator::sphereflake(vec_attr(node, "center", "sphereflake"), radius, count("depth", 3) as u32, mat)
+        },
+        Some("menger") => generator::menger(vec_attr(node, "min", "menger sponge"),
+                                            vec_attr(node, "max", "menger sponge"),
+                                            count("depth", 3) as u32,
+                                            mat),
+        Some("triangles") => generator::triangle_soup(vec_attr(node, "min", "triangle soup"),
+                                                      vec_attr(node, "max", "triangle soup"),
+                                                      count("count", 10000),
+                                                      f64_attr(node, "size", 0.1),
+                                                      count("seed", 0),
+                                                      mat),
+        Some(other) => panic!("The generator type '{}' doesn't exists!", other),
+        None => panic!("Missing generator type!")
+    }
+}
+
+// Environment layers are added together; a sun layer also adds the light
+// that gives its direct lighting to diffuse surfaces
+fn environment_parser(node: &roxmltree::Node, lights: &mut Vec<Box<dyn Light>>, light_names: &mut Vec<Option<String>>) -> Environment {
+    let mut env = Environment::new();
+
+    for layer in node.children().filter(|n| n.has_tag_name("layer")) {
+        let visibility = Visibility {
+            camera: bool_attr(&layer, "camera", true),
+            diffuse: bool_attr(&layer, "diffuse", true),
+            specular: bool_attr(&layer, "specular", true)
+        };
+
+        match layer.attribute("type") {
+            Some("gradient") => env.push(Layer::Gradient {
+                horizon: color_attr(&layer, "horizon", Color::new(1.0, 1.0, 1.0)),
+                zenith: color_attr(&layer, "zenith", Color::new(0.5, 0.7, 1.0))
+            }, visibility),
+            Some("constant") => {
+                let color = color_attr(&layer, "color", Color::new(1.0, 1.0, 1.0)) * spectrum_attr(&layer, "environment");
+                env.push(Layer::Constant(color), visibility);
+            },
+            Some("hdri") => {
+                let filename = layer.attribute("filename").expect("Missing HDRI filename!");
+                env.push(Layer::Hdri {
+                    image: Arc::new(Image::load(filename)),
+                    intensity: f64_attr(&layer, "intensity", 1.0),
+                    rotate: f64_attr(&layer, "rotate", 0.0).to_radians()
+                }, visibility);
+            },
+            Some("studio") => env.push(Layer::Hdri {
+                image: Arc::new(environment::studio_hdri()),
+                intensity: f64_attr(&layer, "intensity", 1.0),
+                rotate: f64_attr(&layer, "rotate", 0.0).to_radians()
+            }, visibility),
+            Some("sun") => {
+                let direction = vec_attr(&layer, "direction", "sun");
+                let color = color_attr(&layer, "color", Color::new(1.0, 1.0, 1.0)) * spectrum_attr(&layer, "sun");
+                let irradiance = f64_attr(&layer, "irradiance", 3.0);
+                let sun = SunLight::new(direction, 0.5 * f64_attr(&layer, "angle", 0.53), irradiance * color);
+
+                // Diffuse surfaces get the sun from light sampling, so the disk
+                // itself is only shown to camera and specular rays
+                env.push(sun.layer(), Visibility { diffuse: false, ..visibility });
+                if visibility.diffuse {
+                    lights.push(Box::new(sun));
+                    light_names.push(layer.attribute("name").map(str::to_string));
+                }
+            },
+            Some(other) => panic!("The environment layer '{}' doesn't exists!", other),
+            None => panic!("Missing environment layer type!")
+        }
+    }
+
+    env
+}
+
+// Mask of the named lights, which must be declared before they are linked
+fn light_mask(names: &str, light_names: &[Option<String>]) -> LightMask {
+    let mut mask = 0;
+
+    for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let index = light_names.iter()
+            .position(|n| n.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("Unknown light '{}' in light linking!", name));
+        if index >= 64 {
+            panic!("Only the first 64 lights of a scene can be linked!");
+        }
+        mask |= 1 << index;
+    }
+
+    mask
+}
+
+// Attributes naming the files the parsers read, to keep in step with them
+const INPUT_FILE_ATTRIBUTES: [&str; 6] = ["filename", "heightmap", "ies", "image", "map", "plate"];
+
+// Attributes of the document naming files the scene reads; the filename of
+// the film is the image written
+fn input_files<'a>(doc: &'a Document<'a>) -> Vec<&'a roxmltree::Attribute<'a>> {
+    doc.descendants()
+        .filter(|n| n.is_element() && !n.has_tag_name("film"))
+        .flat_map(|n| n.attributes().iter())
+        .filter(|a| INPUT_FILE_ATTRIBUTES.contains(&a.name()))
+        .collect()
+}
+
+// Size of the film and the camera's aspect ratio, that of the pixels times
+// their shape. Films without a height follow the camera's aspect instead if
+// it has one. Shared with the scenes build.rs bakes.
+fn film_shape(film: (Option<u64>, Option<u64>), pixel_aspect: f64, camera_aspect: Option<f64>) -> ((u64, u64), f64) {
+    let film_width = film.0.unwrap_or(DEFAULT_FILM_SIZE.0);
+    let film_height = match (film.1, camera_aspect) {
+        (Some(height), Some(aspect)) => {
+            let film_aspect = film_width as f64 * pixel_aspect / height as f64;
+            if (aspect - film_aspect).abs() > 1e-3 * film_aspect {
+                eprintln!("Warning: the camera aspect {} doesn't match the {}x{} film, using the film's {:.4}", aspect, film_width, height, film_aspect);
+            }
+            height
+        },
+        (Some(height), None) => height,
+        (None, aspect) => ((film_width as f64 * pixel_aspect / aspect.unwrap_or(DEFAULT_ASPECT_RATIO)).round() as u64).max(1)
+    };
+    ((film_width, film_height), film_width as f64 * pixel_aspect / film_height as f64)
+}
+
+fn xml_parser(xml: &str) -> (String, Scene) {
+    let doc = Document::parse(xml).expect("Failed to parse XML");
+
+    // Chosen before the first mesh or group builds one
+    let accel = doc.descendants().find(|n| n.has_tag_name("accelerator")).map(|node| {
+        let name = node.attribute("type").expect("Missing accelerator type!");
+        AccelKind::from_name(name).unwrap_or_else(|| panic!("The accelerator type '{}' doesn't exists!", name))
+    });
+    AccelKind::set_for_scene(accel);
+
+    let mut img_name = String::new();
+
+    // Camera infos
+    let mut lookfrom = Point3::new(0.0, 0.0, 0.0);
+    let mut lookat = Point3::new(0.0, 0.0, 0.0);
+    let mut vup = Vec3::new(0.0, 0.0, 0.0);
+    let mut aperture = 0.0;
+    let mut shutter = (0.0, 1.0);
+    let mut camera_keys = Vec::new();
+    let mut camera_aspect = None;
+    let mut film = (None, None);
+    let mut flip = Flip::default();
+    let mut pixel_aspect = 1.0;
+    let mut integrator = IntegratorParams::default();
+
+    // World infos
+    let mut world = World::new();
+    let mut object_names = Vec::new();
+    let mut has_holdouts = false;
+    let mut clip_planes = Vec::new();
+    let mut defines = HashMap::new();
+
+    let mut lights: Vec<Box<dyn Light>> = Vec::new();
+    let mut light_names: Vec<Option<String>> = Vec::new();
+    let mut light_objects = Vec::new();
+    let mut environment = None;
+    let mut plate = None;
+
+    // Last material added
+    let mut last_mat : Arc<dyn Scatter> = Arc::new(Lambertian::new(Color::new(0.0, 0.0, 0.0)));
+
+    // Plugins are loaded first, so their types can be used anywhere
+    for node in doc.descendants().filter(|n| n.has_tag_name("plugin")) {
+        plugin::load(node.attribute("path").expect("Missing plugin path!"));
+    }
+
+    // Traversing XML tree; what a <define> or <group> holds is parsed with it
+    for node in doc.descendants() {
+        if node.is_element() && !node.ancestors().skip(1).any(|a| a.has_tag_name("define") || a.has_tag_name("group")) {
+            let tag_name = node.tag_name().name();
+
+            match tag_name {
+                "film" => {
+                    if let Some(value) = node.attribute("filename") {
+                        img_name = value.to_string();
+                    } else {
+                        println!("Missing output file name in XML, used default.ppm");
+                        img_name = "default.ppm".to_string();
+                    }
+
+                    // Parsing resolution, scaled by --scale
+                    let size = |name: &str| node.attribute(name).map(|attr| {
+                        let value: u64 = attr.parse()
+                            .unwrap_or_else(|_| panic!("Failed to parse film {}.", name));
+                        if value == 0 {
+                            panic!("The film {} can't be zero.", name);
+                        }
+                        value
+                    });
+                    film = (size("width"), size("height"));
+
+                    if let Some(value) = node.attribute("flip") {
+                        flip = Flip::from_name(value)
+                            .unwrap_or_else(|| panic!("The film flip '{}' doesn't exists!", value));
+                    }
+
+                    // Parsing pixel aspect, the width of a pixel over its
+                    // height, as a number or a ratio like "10/11"
+                    if let Some(attr) = node.attribute("pixel_aspect") {
+                        let number = |s: &str| s.trim().parse::<f64>().ok();
+                        let value = match attr.split_once('/') {
+                            Some((a, b)) => number(a).zip(number(b)).map(|(a, b)| a / b),
+                            None => number(attr)
+                        }.expect("Failed to parse film pixel aspect.");
+                        if !(value > 0.0 && value.is_finite()) {
+                            panic!("The film pixel aspect must be positive.");
+                        }
+                        pixel_aspect = value;
+                    }
+                },
+                "camera" => {
+                    // Parsing look-from
+                    if let Some(attr) = node.attribute("look_from") {
+                        let value = value_parser(attr);
+                        lookfrom = Point3::new(value.0, value.1, value.2);
+                    } else {
+                        panic!("Missing camera look from position!");
+                    }
+
+                    // Parsing look-at
+                    if let Some(attr) = node.attribute("look_at") {
+                        let value = value_parser(attr);
+                        lookat = Point3::new(value.0, value.1, value.2);
+                    } else {
+                        panic!("Missing camera look at position!");
+                    }
+
+                    // Parsing up 
+                    if let Some(attr) = node.attribute("up") {
+                        let value = value_parser(attr);
+                        vup = Point3::new(value.0, value.1, value.2);
+                    } else {
+                        panic!("Missing camera up position!");
+                    }
+
+                    // Parsing aperture 
+                    if let Some(attr) = node.attribute("aperture") {
+                        aperture = attr.parse()
+                            .expect("Failed to parse camera aperture.");
+                    } else {
+                        panic!("Missing camera aperture!");
+                    }
+
+                    // Parsing shutter interval
+                    shutter = (f64_attr(&node, "shutter_open", 0.0), f64_attr(&node, "shutter_close", 1.0));
+                    if shutter.1 < shutter.0 {
+                        panic!("The camera shutter can't close before it opens.");
+                    }
+
+                    // Parsing aspect ratio, which films of their own size
+                    // overrule
+                    if node.has_attribute("aspect") {
+                        let aspect = f64_attr(&node, "aspect", 0.0);
+                        if aspect <= 0.0 {
+                            panic!("The camera aspect must be positive.");
+                        }
+                        camera_aspect = Some(aspect);
+                    }
+
+                    // Parsing keyframes, which default to the values above
+                    camera_keys = node.children()
+                        .filter(|key| key.has_tag_name("key"))
+                        .map(|key| CameraKey {
+                            frame: key.attribute("frame")
+                                .expect("Missing camera key frame!")
+                                .parse()
+                                .expect("Failed to parse camera key frame."),
+                            look_from: key.attribute("look_from").map_or(lookfrom, |_| vec_attr(&key, "look_from", "camera key")),
+                            look_at: key.attribute("look_at").map_or(lookat, |_| vec_attr(&key, "look_at", "camera key")),
+                            up: key.attribute("up").map_or(vup, |_| vec_attr(&key, "up", "camera key")),
+                            aperture: f64_attr(&key, "aperture", aperture)
+                        })
+                        .collect();
+                },
+                // Materials nested in others or capping clip planes are parsed
+                // with their parent
+                "material" if !node.parent_element().is_some_and(|p| p.has_tag_name("material") || p.has_tag_name("clip")) => {
+                    last_mat = material_parser(&node, (lookfrom, lookat, vup));
+                },
+                // Objects of a CSG element are parsed with it
+                "object" | "csg" | "use" | "group" if !node.ancestors().skip(1).any(|a| a.has_tag_name("csg")) => {
+                    let mut new_obj = element_parser(&node, last_mat.clone(), (lookfrom, lookat, vup), &defines);
+
+                    // Parsing light linking
+                    if let Some(attr) = node.attribute("lights") {
+                        new_obj = Box::new(LightLinked::new(new_obj, light_mask(attr, &light_names)));
+                    }
+                    if let Some(attr) = node.attribute("exclude_lights") {
+                        new_obj = Box::new(LightLinked::new(new_obj, !light_mask(attr, &light_names)));
+                    }
+                    if bool_attr(&node, "holdout", false) {
+                        new_obj = Box::new(Holdout::new(new_obj));
+                        has_holdouts = true;
+                    }
+
+                    world.push(new_obj);
+                    object_names.push(node.attribute("name").map(str::to_string));
+
+                },
+                // Clips of an object are parsed with it
+                "clip" if !node.parent_element().is_some_and(|p| ["object", "csg", "use", "group"].contains(&p.tag_name().name())) => {
+                    let cap = node.children()
+                        .find(|n| n.has_tag_name("material"))
+                        .map(|n| material_parser(&n, (lookfrom, lookat, vup)));
+
+                    // Planes of the camera cut away everything nearer to it
+                    // than `distance`
+                    if node.parent_element().is_some_and(|p| p.has_tag_name("camera")) {
+                        let forward = (lookat - lookfrom).normalized();
+                        clip_planes.push(ClipPlane::new(lookfrom + f64_attr(&node, "distance", 1.0) * forward, (-1.0) * forward, cap));
+                    } else {
+                        clip_planes.push(clip_plane_parser(&node, cap));
+                    }
+                },
+                "define" => {
+                    let name = node.attribute("name").expect("Missing definition name!");
+                    let object = define_parser(&node, last_mat.clone(), (lookfrom, lookat, vup), &defines);
+                    defines.insert(name.to_string(), object);
+                },
+                "generator" => {
+                    for object in generator_parser(&node, last_mat.clone()) {
+                        world.push(object);
+                        object_names.push(None);
+                    }
+                },
+                "integrator" => integrator = integrator_parser(&node),
+                "environment" => {
+                    environment = Some(environment_parser(&node, &mut lights, &mut light_names));
+
+                    // Parsing background plate
+                    if let Some(attr) = node.attribute("plate") {
+                        plate = Some(Arc::new(Image::load(attr)));
+                    }
+                },
+                "light" => {
+                    let (light, shape) = light_parser(&node);
+                    lights.push(light);
+                    light_names.push(node.attribute("name").map(str::to_string));
+                    light_objects.resize(lights.len() - 1, None);
+                    light_objects.push(shape.map(|shape| {
+                        object_names.push(node.attribute("name").map(str::to_string));
+                        world.push(shape)
+                    }));
+                },
+                _ => { },
+            }
+        }
+    }
+
+    if !clip_planes.is_empty() {
+        let planes = Arc::new(clip_planes);
+        world.wrap(|object| Box::new(Clipped::new(object, planes.clone())));
+    }
+
+    let (film_size, aspect_ratio) = film_shape(film, pixel_aspect, camera_aspect);
+
+    let cam = Camera::new(
+        lookfrom,
+        lookat,
+        vup,
+        DEFAULT_VFOV,
+        aspect_ratio,
+        aperture,
+        DEFAULT_FOCUS_DIST,
+    ).with_shutter(shutter.0, shutter.1);
+
+    let mut scene = Scene::new(world, lights, cam);
+    scene.light_objects = light_objects;
+    if let Some(env) = environment {
+        scene.environment = env;
+    }
+    scene.plate = plate;
+    scene.film_size = film_size;
+    scene.flip = flip;
+    scene.pixel_aspect = pixel_aspect;
+    scene.camera_path = (!camera_keys.is_empty()).then(|| CameraPath::new(camera_keys, shutter, aspect_ratio));
+    scene.object_names = object_names;
+    scene.has_holdouts = has_holdouts;
+    scene.integrator = integrator;
+
+    (img_name, scene)
+}
+
+// "scene.ppm" -> "scene.0042.ppm"
+fn frame_filename(img_name: &str, frame: u64) -> String {
+    match img_name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}.{:04}.{}", stem, frame, ext),
+        None => format!("{}.{:04}", img_name, frame)
+    }
+}
+
+// Scene of an XML scene file's contents, for frontends using the library
+pub fn load_scene(xml: &str) -> Scene {
+    xml_parser(xml).1
+}
+
+fn read_xml(filename: &str) -> String {
+    let mut xml_file = File::open(filename).expect("Unable to open file.");
+    let mut xml_contents = String::new();
+    xml_file.read_to_string(&mut xml_contents).expect("Unable to read file.");
+    xml_contents
+}
+
+// Writes an EXR or PNG if the file name asks for one and a PPM otherwise.
+// EXRs hold the linear colors of the whole render, overscan included; the
+// other formats only the image inside it. All of them carry the metadata.
+// `coverage`, for scenes with holdouts, gives the samples of each pixel that
+// don't see one, which become the alpha of EXR and PNG images. PPM images
+// have none, the holdouts staying black.
+fn write_image(filename: &str, pixels: &[Color], coverage: Option<&[u64]>, settings: &RenderSettings, metadata: &Metadata) {
+    if filename.ends_with(".exr") {
+        write_exr(filename, pixels, coverage, settings.samples_per_pixel, settings, metadata);
+        return;
+    }
+
+    let (width, height) = settings.output_size();
+    let pixels = settings.output(pixels);
+    if filename.ends_with(".png") {
+        match coverage {
+            // PNG colors aren't premultiplied, so they are averaged over the
+            // samples that were kept
+            Some(coverage) => {
+                let spp = settings.samples_per_pixel as f64;
+                let rgba: Vec<u8> = pixels.iter().zip(settings.output(coverage)).flat_map(|(p, c)| {
+                    let [r, g, b] = p.to_rgb8(c.max(1));
+                    [r, g, b, (255.0 * c as f64 / spp).round() as u8]
+                }).collect();
+                png::write_rgba(filename, width as usize, height as usize, &rgba, metadata.entries())
+                    .expect("Filed to write");
+            },
+            None => {
+                let rgb: Vec<u8> = pixels.iter().flat_map(|p| p.to_rgb8(settings.samples_per_pixel)).collect();
+                png::write(filename, width as usize, height as usize, &rgb, metadata.entries())
+                    .expect("Filed to write");
+            }
+        }
+    } else {
+        let new_file = File::create(filename)
+            .expect("Failed to create file.");
+        write_ppm(&mut BufWriter::new(new_file), &pixels, settings, metadata);
+    }
+}
+
+// Linear RGB EXR of pixels summed over `samples` samples each, with an alpha
+// channel when the coverage is given
+fn write_exr(filename: &str, pixels: &[Color], coverage: Option<&[u64]>, samples: u64, settings: &RenderSettings, metadata: &Metadata) {
+    let n = samples as f64;
+    let mut exr = ExrImage::new(settings.image_width as usize, settings.image_height as usize);
+    exr.overscan = settings.overscan as usize;
+    exr.flip = settings.flip;
+    exr.pixel_aspect = settings.pixel_aspect;
+    exr.text = metadata.entries().to_vec();
+    exr.add_channel("R", pixels.iter().map(|p| (p.x() / n) as f32).collect());
+    exr.add_channel("G", pixels.iter().map(|p| (p.y() / n) as f32).collect());
+    exr.add_channel("B", pixels.iter().map(|p| (p.z() / n) as f32).collect());
+    if let Some(coverage) = coverage {
+        exr.add_channel("A", coverage.iter().map(|&c| (c as f64 / n) as f32).collect());
+    }
+    exr.write(filename).unwrap_or_else(|_| panic!("Failed to write {}.", filename));
+}
+
+// The even and odd samples as two EXRs next to the image, independent
+// estimates of the same frame before denoising
+fn write_half_buffers(img_name: &str, split: &[SplitColor], settings: &RenderSettings, metadata: &Metadata) {
+    let spp = settings.samples_per_pixel;
+    let even: Vec<Color> = split.iter().map(|p| p.even).collect();
+    let odd: Vec<Color> = split.iter().map(|p| p.odd).collect();
+
+    for (pass, pixels, samples) in [("even", even, spp.div_ceil(2)), ("odd", odd, spp / 2)] {
+        let filename = aov::pass_filename(img_name, pass);
+        write_exr(&filename, &pixels, None, samples, settings, metadata);
+        eprintln!("Wrote {} samples to {}", pass, filename);
+    }
+}
+
+// "scene.ppm" -> "scene.ev+2.ppm", for images made to be looked at; those
+// of HDR images are PNGs, for viewers that can't show them
+fn display_filename(img_name: &str, tag: &str) -> String {
+    let (stem, ext) = img_name.rsplit_once('.').unwrap_or((img_name, "ppm"));
+    let ext = if ext == "exr" { "png" } else { ext };
+    format!("{}.{}.{}", stem, tag, ext)
+}
+
+// The image again at every exposure offset, each stop doubling or halving
+// its linear colors before they are encoded and clipped
+fn write_brackets(img_name: &str, brackets: &[f64], pixels: &[Color], coverage: Option<&[u64]>, settings: &RenderSettings, metadata: &Metadata) {
+    for &ev in brackets {
+        let gain = 2.0_f64.powf(ev);
+        let exposed: Vec<Color> = pixels.iter().map(|&p| gain * p).collect();
+
+        let mut metadata = metadata.clone();
+        metadata.add("Exposure", format!("{:+} EV", ev));
+        let filename = display_filename(img_name, &format!("ev{:+}", ev));
+        write_image(&filename, &exposed, coverage, settings, &metadata);
+        eprintln!("Wrote {:+} EV to {}", ev, filename);
+    }
+}
+
+fn write_ppm(new_file: &mut impl Write, pixels: &[Color], settings: &RenderSettings, metadata: &Metadata) {
+    let (width, height) = settings.output_size();
+    writeln!(new_file, "P3").expect("Filed to write");
+    for (key, value) in metadata.entries() {
+        writeln!(new_file, "# {}: {}", key, value.replace('\n', " ")).expect("Filed to write");
+    }
+    writeln!(new_file, "{} {}", width, height).expect("Filed to write");
+    writeln!(new_file, "255").expect("Filed to write");
+
+    for pixel_color in pixels {
+        writeln!(new_file, "{}", pixel_color.format_color(settings.samples_per_pixel)).expect("Filed to write");
+    }
+}
+
+// Entry point of the rt binary, also for crates that register their own
+// types first, see registry.rs
+pub fn run() {
+    // Subcommands, otherwise the arguments are render options and a scene,
+    // which can also follow `render`
+    let mut skip = 1;
+    match std::env::args().nth(1).as_deref() {
+        Some("serve") => return server::serve(std::env::args().skip(2)),
+        Some("contact-sheet") => return contact::contact_sheet(std::env::args().skip(2)),
+        Some("matpreview") => return matpreview::matpreview(std::env::args().skip(2)),
+        Some("bake") => return bake::bake(std::env::args().skip(2)),
+        Some("measure") => return measure::measure(std::env::args().skip(2)),
+        Some("merge") => return bucket::merge(std::env::args().skip(2)),
+        Some("generate") => return generate::generate(std::env::args().skip(2)),
+        Some("tree") => return tree::tree(std::env::args().skip(2)),
+        Some("render") => skip = 2,
+        _ => { }
+    }
+
+    let args: Vec<String> = std::env::args().skip(skip).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        print!("{}", cli::usage());
+        return;
+    }
+    let opts = Options::parse_known(args.into_iter()).unwrap_or_else(|arg| {
+        eprint!("Unknown option '{}'.\n\n{}", arg, cli::usage());
+        std::process::exit(2);
+    });
+    opts.bvh.set();
+    if let Some(accel) = opts.accel {
+        accel.set();
+    }
+    if opts.allow_plugins {
+        plugin::allow();
+    }
+
+    let script = opts.script.as_deref().map(Script::load);
+
+    // What the images record of where they come from, see metadata.rs
+    let mut source = Metadata::new();
+
+    // Scripted scenes are first parsed for the frame --frame picks, or 0
+    let scripted_frame = opts.frame.unwrap_or(0);
+    let (xml_contents, (mut img_name, mut scene)) = if let Some(name) = &opts.builtin {
+        if script.is_some() {
+            panic!("Scripts need an XML scene, builtin scenes can't be scripted.");
+        }
+
+        if opts.accel_cache {
+            panic!("The acceleration cache is kept next to the scene file, builtin scenes can't use one.");
+        }
+
+        let builtin = builtin::builtin(name).unwrap_or_else(|| {
+            panic!("Unknown builtin scene '{}', expected one of: {}.", name, builtin::BUILTIN_NAMES.join(", "))
+        });
+        source.add("Builtin", name);
+        (String::new(), builtin)
+    } else {
+        // Reading XML scene 
+        let mut xml_name = opts.scene.clone().unwrap_or_default();
+        if xml_name.is_empty() {
+            print!("Please enter the name of the XML scene file: ");
+            std::io::stdout().flush().unwrap();
+
+            std::io::stdin()
+                .read_line(&mut xml_name)
+                .expect("Failed to read line");
+        }
+
+        let xml_name = xml_name.trim();
+        if opts.accel_cache {
+            accel_cache::open(&std::path::Path::new(xml_name).with_extension("accel"));
+        }
+
+        if xml_name.ends_with(".gltf") || xml_name.ends_with(".glb") {
+            // glTF files are imported instead of parsed
+            if script.is_some() {
+                panic!("Scripts need an XML scene, glTF scenes can't be scripted.");
+            }
+            source.add("Scene", xml_name);
+            (String::new(), gltf::load(xml_name))
+        } else {
+            let xml_contents = read_xml(xml_name);
+            source.add_file("Scene", xml_name, &xml_contents);
+            if let Some(path) = &opts.script {
+                source.add_file("Script", path, &read_xml(path));
+            }
+
+            // Parsing XML contents
+            let parsed = match &script {
+                Some(script) => script.scene(&xml_contents, scripted_frame),
+                None => xml_parser(&xml_contents)
+            };
+            (xml_contents, parsed)
+        }
+    };
+
+    // Structures built for the scene are kept for its next render
+    accel_cache::save();
+ 
+    // Animations render every frame of the camera path unless --frame picks
+    // one or --frames sets how many
+    let mut settings = opts.settings(&scene);
+    let frames: Vec<u64> = match (opts.frame, opts.frames, &scene.camera_path) {
+        (Some(frame), _, _) => vec![frame],
+        (None, Some(count), _) => (0..count).collect(),
+        (None, None, Some(path)) => path.frames().collect(),
+        (None, None, None) => vec![settings.frame]
+    };
+    let animated = scene.camera_path.is_some() || opts.frames.is_some() || script.is_some();
+    if opts.half_buffers && settings.samples_per_pixel < 2 {
+        panic!("Half buffers need at least 2 samples per pixel.");
+    }
+    if opts.ladder && opts.temporal {
+        panic!("--ladder can't be combined with --temporal.");
+    }
+    if opts.focus_stack.is_some() {
+        if opts.ladder || opts.temporal {
+            panic!("--focus-stack can't be combined with --ladder or --temporal.");
+        }
+        if scene.camera.aperture() == 0.0 {
+            panic!("A focus stack needs a camera aperture.");
+        }
+        if settings.samples_per_pixel < 2 {
+            panic!("Focus stacks need at least 2 samples per pixel.");
+        }
+    }
+    if opts.bucket.is_some() && (opts.ladder || opts.temporal || opts.deep || opts.half_buffers || !opts.aovs.is_empty() || opts.focus_stack.is_some() || !opts.brackets.is_empty() || opts.false_color) {
+        panic!("--bucket only renders the image, it can't be combined with --ladder, --temporal, --deep, --half-buffers, --aov, --focus-stack, --brackets or --false-color.");
+    }
+    let mut history = None;
+    let mut parsed_frame = script.is_some().then_some(scripted_frame);
+
+    for frame in frames {
+        settings.frame = frame;
+        if animated {
+            eprintln!("Frame {}", frame);
+        }
+
+        // Scripted scenes are parsed again with every frame's changes, which
+        // may rename its output too
+        if let Some(script) = &script {
+            if parsed_frame.take() != Some(frame) {
+                (img_name, scene) = script.scene(&xml_contents, frame);
+                accel_cache::save();
+            }
+        }
+        if opts.bucket.is_some() && !img_name.ends_with(".ppm") {
+            panic!("Buckets are written as PPM, the film filename must end with .ppm.");
+        }
+        if let Some(path) = &scene.camera_path {
+            scene.camera = path.camera(frame);
+        }
+
+        let frame_name = if animated { frame_filename(&img_name, frame) } else { img_name.clone() };
+        let frame_name = match opts.bucket {
+            Some(bucket) => bucket.filename(&frame_name),
+            None => frame_name
+        };
+
+        let start = Instant::now();
+        let split = if let Some(stack) = opts.focus_stack {
+            focus::render_focus_stack(&mut scene, &settings, stack, &frame_name, &source)
+        } else if opts.ladder {
+            ladder::render_ladder(&scene, &settings, &frame_name, &source)
+        } else {
+            render::render_split(&scene, &settings)
+        };
+        let samples: Vec<Color> = split.iter().map(|p| p.total()).collect();
+
+        let pixels = if opts.temporal {
+            let (pixels, next) = temporal::accumulate(&scene, &settings, &samples, history.as_ref());
+            history = Some(next);
+            pixels
+        } else {
+            samples
+        };
+        let pixels = if settings.denoise {
+            denoise::denoise(&pixels, settings.image_width, settings.image_height, settings.samples_per_pixel)
+        } else {
+            pixels
+        };
+
+        // Every output of the frame records the beauty render's time
+        let mut metadata = source.clone();
+        metadata.add_settings(&settings);
+        metadata.add("RenderTime", format!("{:.3} s", start.elapsed().as_secs_f64()));
+
+        let coverage = scene.has_holdouts.then(|| render::coverage(&split));
+        write_image(&frame_name, &pixels, coverage.as_deref(), &settings, &metadata);
+
+        if !opts.brackets.is_empty() {
+            write_brackets(&frame_name, &opts.brackets, &pixels, coverage.as_deref(), &settings, &metadata);
+        }
+
+        if opts.false_color {
+            falsecolor::write_false_color(&frame_name, &pixels, &settings, &metadata);
+        }
+
+        if opts.half_buffers {
+            write_half_buffers(&frame_name, &split, &settings, &metadata);
+        }
+
+        if !opts.aovs.is_empty() {
+            aov::render_aovs(&scene, &settings, &opts.aovs, opts.aov_pinhole, opts.z_range, opts.stereo, &frame_name, &metadata);
+        }
+
+        if opts.deep {
+            deep::render_deep(&scene, &settings, &frame_name, &metadata);
+        }
+    }
+
+    eprintln!("Done.");
+
+}
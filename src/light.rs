@@ -0,0 +1,483 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::ies::IesProfile;
+use super::sampler;
+use super::vec::{Color, Point3, Vec3};
+
+// Luminous efficacy used to convert photometric units to radiometric ones
+const LUMENS_PER_WATT: f64 = 683.0;
+
+#[derive(Clone, Copy)]
+pub enum Falloff {
+    // Physically based 1/d^2 attenuation
+    InverseSquare,
+    // No attenuation with distance, for stylized lighting
+    None
+}
+
+impl Falloff {
+    pub fn from_name(name: &str) -> Option<Falloff> {
+        match name {
+            "inverse_square" => Some(Falloff::InverseSquare),
+            "none" => Some(Falloff::None),
+            _ => None
+        }
+    }
+
+    fn attenuation(self, dist: f64) -> f64 {
+        match self {
+            Falloff::InverseSquare => 1.0 / (dist * dist),
+            Falloff::None => 1.0
+        }
+    }
+}
+
+// Quantity given for a light's `power` attribute
+#[derive(Clone, Copy)]
+pub enum Units {
+    // Radiant flux, for point, spot and area lights
+    Watt,
+    // Luminous flux, for point, spot and area lights
+    Lumen,
+    // Luminous intensity, for point and spot lights
+    Candela,
+    // Luminance (cd/m^2), for area lights
+    Nit
+}
+
+impl Units {
+    pub fn from_name(name: &str) -> Option<Units> {
+        match name {
+            "watt" | "W" => Some(Units::Watt),
+            "lumen" | "lm" => Some(Units::Lumen),
+            "candela" | "cd" => Some(Units::Candela),
+            "nit" | "cd/m2" => Some(Units::Nit),
+            _ => None
+        }
+    }
+
+    // Radiant intensity (W/sr) of a light emitting its flux into `solid_angle`
+    fn intensity(self, power: f64, solid_angle: f64) -> Option<f64> {
+        match self {
+            Units::Watt => Some(power / solid_angle),
+            Units::Lumen => Some(power / LUMENS_PER_WATT / solid_angle),
+            Units::Candela => Some(power / LUMENS_PER_WATT),
+            Units::Nit => None
+        }
+    }
+
+    // Radiance (W/m^2/sr) of a one-sided diffuse emitter of the given area
+    fn radiance(self, power: f64, area: f64) -> Option<f64> {
+        match self {
+            Units::Watt => Some(power / (PI * area)),
+            Units::Lumen => Some(power / LUMENS_PER_WATT / (PI * area)),
+            Units::Nit => Some(power / LUMENS_PER_WATT),
+            Units::Candela => None
+        }
+    }
+}
+
+// Light arriving at a shading point from one sample on a light
+pub struct LightSample {
+    // Unit direction from the shading point towards the light
+    pub wi: Vec3,
+    pub dist: f64,
+    // Irradiance at normal incidence, already divided by the sampling pdf
+    pub radiance: Color
+}
+
+pub trait Light : Send + Sync {
+    fn sample(&self, p: Point3) -> Option<LightSample>;
+}
+
+// IES distribution oriented in the world, attached to a point or spot light
+struct Photometry {
+    profile: Arc<IesProfile>,
+    nadir: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    // Converts profile candelas into a multiplier of the light's intensity
+    scale: f64
+}
+
+impl Photometry {
+    // rotate turns the profile's 0 degree horizontal plane about the nadir
+    fn new(profile: Arc<IesProfile>, nadir: Vec3, rotate: f64, scale: f64) -> Photometry {
+        let nadir = nadir.normalized();
+        let (t, b) = nadir.orthonormal_basis();
+        let (sin, cos) = rotate.to_radians().sin_cos();
+
+        Photometry {
+            profile,
+            nadir,
+            tangent: cos * t + sin * b,
+            bitangent: cos * b - sin * t,
+            scale
+        }
+    }
+
+    // Intensity multiplier towards the unit direction d leaving the light
+    fn factor(&self, d: Vec3) -> f64 {
+        let theta = d.dot(self.nadir).clamp(-1.0, 1.0).acos().to_degrees();
+        let phi = d.dot(self.bitangent).atan2(d.dot(self.tangent)).to_degrees();
+        self.scale * self.profile.candela(theta, phi)
+    }
+}
+
+// Applies an IES profile to a light intensity. With an absolute profile the
+// file's candelas set the intensity and the light's own color only tints
+// it; otherwise the profile is normalized to its peak and shapes the
+// intensity derived from the light's power.
+fn photometry(intensity: &mut Color, color: Color, profile: Arc<IesProfile>, nadir: Vec3, rotate: f64, absolute: bool) -> Photometry {
+    let scale = if absolute {
+        *intensity = color;
+        1.0 / LUMENS_PER_WATT
+    } else {
+        1.0 / profile.max_candela().max(1e-12)
+    };
+
+    Photometry::new(profile, nadir, rotate, scale)
+}
+
+pub struct PointLight {
+    position: Point3,
+    intensity: Color,
+    color: Color,
+    falloff: Falloff,
+    photometry: Option<Photometry>
+}
+
+impl PointLight {
+    pub fn new(position: Point3, color: Color, power: f64, units: Units, falloff: Falloff) -> PointLight {
+        let intensity = units.intensity(power, 4.0 * PI)
+            .expect("Point lights take watt, lumen or candela units.");
+
+        PointLight {
+            position,
+            intensity: intensity * color,
+            color,
+            falloff,
+            photometry: None
+        }
+    }
+
+    // Shapes the emission by an IES profile whose nadir points along `nadir`
+    pub fn with_ies(mut self, profile: Arc<IesProfile>, nadir: Vec3, rotate: f64, absolute: bool) -> PointLight {
+        self.photometry = Some(photometry(&mut self.intensity, self.color, profile, nadir, rotate, absolute));
+        self
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let dist = to_light.length();
+        let wi = to_light / dist;
+
+        let profile = self.photometry.as_ref().map_or(1.0, |ph| ph.factor((-1.0) * wi));
+
+        Some(LightSample {
+            wi,
+            dist,
+            radiance: profile * self.falloff.attenuation(dist) * self.intensity
+        })
+    }
+}
+
+pub struct SpotLight {
+    position: Point3,
+    direction: Vec3,
+    cos_inner: f64,
+    cos_outer: f64,
+    intensity: Color,
+    color: Color,
+    falloff: Falloff,
+    photometry: Option<Photometry>
+}
+
+impl SpotLight {
+    // Angles are cone half-angles in degrees; the beam fades out between them
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(position: Point3,
+               direction: Vec3,
+               inner_angle: f64,
+               outer_angle: f64,
+               color: Color,
+               power: f64,
+               units: Units,
+               falloff: Falloff) -> SpotLight {
+        let cos_inner = inner_angle.min(outer_angle).to_radians().cos();
+        let cos_outer = outer_angle.to_radians().cos();
+
+        // Flux is spread over the cone, counting the blend region as half
+        let solid_angle = 2.0 * PI * (1.0 - 0.5 * (cos_inner + cos_outer));
+        let intensity = units.intensity(power, solid_angle)
+            .expect("Spot lights take watt, lumen or candela units.");
+
+        SpotLight {
+            position,
+            direction: direction.normalized(),
+            cos_inner,
+            cos_outer,
+            intensity: intensity * color,
+            color,
+            falloff,
+            photometry: None
+        }
+    }
+
+    // Shapes the beam by an IES profile whose nadir is the spot direction
+    pub fn with_ies(mut self, profile: Arc<IesProfile>, rotate: f64, absolute: bool) -> SpotLight {
+        self.photometry = Some(photometry(&mut self.intensity, self.color, profile, self.direction, rotate, absolute));
+        self
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let dist = to_light.length();
+        let wi = to_light / dist;
+
+        let cos_theta = ((-1.0) * wi).dot(self.direction);
+        if cos_theta <= self.cos_outer {
+            return None;
+        }
+
+        let edge = if cos_theta >= self.cos_inner {
+            1.0
+        } else {
+            let x = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+            x * x * (3.0 - 2.0 * x)
+        };
+
+        let profile = self.photometry.as_ref().map_or(1.0, |ph| ph.factor((-1.0) * wi));
+
+        Some(LightSample {
+            wi,
+            dist,
+            radiance: edge * profile * self.falloff.attenuation(dist) * self.intensity
+        })
+    }
+}
+
+// How the radiance of an emitting surface varies with direction
+#[derive(Clone, Copy)]
+pub struct Emission {
+    // Emits from the back face as well, the flux being split between both
+    pub two_sided: bool,
+    // Exponent of the cosine to the normal the radiance is scaled by, 0 for
+    // a diffuse emitter; larger values aim the light like a softbox grid
+    pub spread: f64
+}
+
+impl Emission {
+    pub fn diffuse() -> Emission {
+        Emission {
+            two_sided: false,
+            spread: 0.0
+        }
+    }
+
+    // Scale of the radiance towards a direction at cos_theta to the normal,
+    // or to the back face's for negative values
+    pub fn factor(self, cos_theta: f64) -> f64 {
+        if cos_theta <= 0.0 && !self.two_sided {
+            return 0.0;
+        }
+
+        if self.spread > 0.0 {
+            cos_theta.abs().powf(self.spread)
+        } else {
+            1.0
+        }
+    }
+
+    // Radiance of this emitter relative to a one-sided diffuse one of the same
+    // flux, which emits pi * area per unit radiance against
+    // 2 pi / (spread + 2) * area per face
+    fn flux_scale(self) -> f64 {
+        let sides = if self.two_sided { 2.0 } else { 1.0 };
+        (self.spread + 2.0) / 2.0 / sides
+    }
+}
+
+// Emitting parallelogram; the matching geometry is a Quad with an Emissive
+// material of the same radiance and emission
+pub struct AreaLight {
+    corner: Point3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    area: f64,
+    radiance: Color,
+    falloff: Falloff,
+    units: Units,
+    emission: Emission
+}
+
+impl AreaLight {
+    pub fn new(corner: Point3, u: Vec3, v: Vec3, color: Color, power: f64, units: Units, falloff: Falloff) -> AreaLight {
+        let n = u.cross(v);
+        let area = n.length();
+        let radiance = units.radiance(power, area)
+            .expect("Area lights take watt, lumen or nit units.");
+
+        AreaLight {
+            corner,
+            u,
+            v,
+            normal: n / area,
+            area,
+            radiance: radiance * color,
+            falloff,
+            units,
+            emission: Emission::diffuse()
+        }
+    }
+
+    // Makes the light two-sided or directional. A power keeps its flux,
+    // while a luminance is the one seen along the normal.
+    pub fn with_emission(mut self, emission: Emission) -> AreaLight {
+        if !matches!(self.units, Units::Nit) {
+            self.radiance = emission.flux_scale() / self.emission.flux_scale() * self.radiance;
+        }
+        self.emission = emission;
+        self
+    }
+
+    pub fn radiance(&self) -> Color {
+        self.radiance
+    }
+
+    pub fn emission(&self) -> Emission {
+        self.emission
+    }
+}
+
+impl Light for AreaLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        let mut rng = sampler::rng();
+        let on_light = self.corner + rng.gen::<f64>() * self.u + rng.gen::<f64>() * self.v;
+
+        let to_light = on_light - p;
+        let dist = to_light.length();
+        let wi = to_light / dist;
+
+        // The front face is on the side of u x v
+        let cos_light = ((-1.0) * wi).dot(self.normal);
+        let factor = self.emission.factor(cos_light);
+        if factor <= 0.0 {
+            return None;
+        }
+
+        // Uniform area sampling: pdf = 1 / area, converted to solid angle by
+        // cos_light / d^2 (which the falloff model may replace)
+        Some(LightSample {
+            wi,
+            dist,
+            radiance: factor * cos_light.abs() * self.area * self.falloff.attenuation(dist) * self.radiance
+        })
+    }
+}
+
+// Diffuse emitting sphere, sampled over the cone of directions it subtends
+// rather than over its area, which wastes no samples on its far side and
+// keeps small bulbs nearly noise free. The matching geometry is a Sphere
+// with an Emissive material of the same radiance.
+pub struct SphereLight {
+    center: Point3,
+    radius: f64,
+    radiance: Color,
+    falloff: Falloff
+}
+
+impl SphereLight {
+    pub fn new(center: Point3, radius: f64, color: Color, power: f64, units: Units, falloff: Falloff) -> SphereLight {
+        let radiance = units.radiance(power, 4.0 * PI * radius * radius)
+            .expect("Sphere lights take watt, lumen or nit units.");
+
+        SphereLight {
+            center,
+            radius,
+            radiance: radiance * color,
+            falloff
+        }
+    }
+
+    pub fn radiance(&self) -> Color {
+        self.radiance
+    }
+}
+
+impl Light for SphereLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        let to_center = self.center - p;
+        let d2 = to_center.dot(to_center);
+        if d2 <= self.radius * self.radius {
+            // Points inside the bulb aren't lit by it
+            return None;
+        }
+
+        let d = d2.sqrt();
+        let w = to_center / d;
+        let sin2_max = self.radius * self.radius / d2;
+        let cos_max = (1.0 - sin2_max).max(0.0).sqrt();
+
+        // Uniform over the cone: pdf = 1 / (2 pi (1 - cos_max))
+        let mut rng = sampler::rng();
+        let cos_theta = 1.0 - rng.gen::<f64>() * (1.0 - cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+
+        let (t, b) = w.orthonormal_basis();
+        let wi = sin_theta * phi.cos() * t + sin_theta * phi.sin() * b + cos_theta * w;
+
+        // Nearest intersection with the sphere along wi
+        let dist = d * cos_theta - (self.radius * self.radius - d2 * sin_theta * sin_theta).max(0.0).sqrt();
+
+        // The cone already falls off with the inverse square of the distance,
+        // which other falloff models replace
+        let falloff = self.falloff.attenuation(d) * d2;
+
+        Some(LightSample {
+            wi,
+            dist,
+            radiance: 2.0 * PI * (1.0 - cos_max) * falloff * self.radiance
+        })
+    }
+}
+
+// Light moved by an offset, like the shape emitting it when a scene edit
+// moves that
+pub struct MovedLight {
+    light: Box<dyn Light>,
+    offset: Vec3
+}
+
+impl MovedLight {
+    pub fn new(light: Box<dyn Light>, offset: Vec3) -> MovedLight {
+        MovedLight {
+            light,
+            offset
+        }
+    }
+}
+
+impl Light for MovedLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        self.light.sample(p - self.offset)
+    }
+}
+
+// Light lighting nothing, in the place of one a scene edit removed
+pub struct NoLight;
+
+impl Light for NoLight {
+    fn sample(&self, _p: Point3) -> Option<LightSample> {
+        None
+    }
+}
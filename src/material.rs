@@ -1,48 +1,167 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
 use rand::{Rng};
 
 use super::vec::{Vec3, Color};
-use super::ray::Ray;
+use super::ray::{Ray, RayType};
+use super::displace::Displacement;
 use super::hit::HitRecord;
+use super::light::Emission;
+use super::microfacet::{Frame, Ggx};
+use super::sampler;
+use super::subsurface::Medium;
+use super::texture::{SolidColor, Texture};
+use super::waves::Waves;
 
 pub trait Scatter : Send + Sync {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)>;
+
+    // BRDF times cosine towards the unit direction wi, used to gather direct
+    // light. Materials that scatter only specularly can't be lit this way and
+    // return None.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _wi: Vec3) -> Option<Color> {
+        None
+    }
+
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    // Material standing in for this one at the hit, for combinators that
+    // pick one of their children per scattering event; see resolve
+    fn select(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<Arc<dyn Scatter>> {
+        None
+    }
+
+    // Scattering interior that rays refracted into the surface walk through,
+    // see subsurface.rs
+    fn medium(&self) -> Option<&Medium> {
+        None
+    }
+
+    // Type of the ray this material scattered off rec
+    fn ray_type(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> RayType {
+        lobe_type(rec, scattered, RayType::Diffuse)
+    }
+
+    // Whether rays pass through the surface unchanged, which shadow rays then
+    // do too instead of being blocked
+    fn transparent(&self, _r_in: &Ray, _rec: &HitRecord) -> bool {
+        false
+    }
+
+    // Displacement of the geometry made of this material, applied when the
+    // scene is loaded
+    fn displacement(&self) -> Option<&Displacement> {
+        None
+    }
+}
+
+// Transmission for a ray scattered through the surface, and the type of the
+// material's reflections otherwise
+pub fn lobe_type(rec: &HitRecord, scattered: &Ray, reflection: RayType) -> RayType {
+    if scattered.direction().dot(rec.normal) < 0.0 {
+        RayType::Transmission
+    } else {
+        reflection
+    }
+}
+
+// Replaces the material of rec by the one its combinators select, so the
+// light sampling and the scattering of a hit see the same child
+pub fn resolve(r_in: &Ray, rec: &mut HitRecord) {
+    while let Some(mat) = rec.mat.select(r_in, rec) {
+        rec.mat = mat;
+    }
+}
+
+// Base color of a texture at the hit, varied by the instance hit
+fn base_color(texture: &Arc<dyn Texture>, rec: &HitRecord) -> Color {
+    let color = texture.value(rec);
+    rec.variation.map_or(color, |v| v.color(color))
+}
+
+fn roughness(roughness: f64, rec: &HitRecord) -> f64 {
+    rec.variation.map_or(roughness, |v| v.roughness(roughness))
 }
 
 pub struct Lambertian {
-    albedo: Color
+    albedo: Arc<dyn Texture>
 }
 
 impl Lambertian {
     pub fn new(a: Color) -> Lambertian {
+        Lambertian::textured(Arc::new(SolidColor::new(a)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Lambertian {
         Lambertian {
-            albedo: a
+            albedo
         }
     }
 }
 
 impl Scatter for Lambertian {
-    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
         let mut scatter_direction = rec.normal + Vec3::random_in_unit_sphere().normalized();
         if scatter_direction.near_zero() {
             // Catch degenerate scatter direction
             scatter_direction = rec.normal;
         }
 
-        let scattered = Ray::new(rec.p, scatter_direction);
+        let scattered = Ray::with_time(rec.p, scatter_direction, r_in.time());
+
+        Some((base_color(&self.albedo, rec), scattered))
+    }
+
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, wi: Vec3) -> Option<Color> {
+        Some(rec.normal.dot(wi).max(0.0) / PI * base_color(&self.albedo, rec))
+    }
+}
+
+// Phase function of a medium scattering equally in every direction, see
+// volume.rs
+pub struct Isotropic {
+    albedo: Color
+}
+
+impl Isotropic {
+    pub fn new(a: Color) -> Isotropic {
+        Isotropic {
+            albedo: a
+        }
+    }
+}
 
+impl Scatter for Isotropic {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let scattered = Ray::with_time(rec.p, Vec3::random_in_unit_sphere().normalized(), r_in.time());
         Some((self.albedo, scattered))
     }
+
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _wi: Vec3) -> Option<Color> {
+        Some(self.albedo / (4.0 * PI))
+    }
+
+    fn ray_type(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> RayType {
+        RayType::Diffuse
+    }
 }
 
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     fuzz: f64
 }
 
 impl Metal {
     pub fn new(a: Color, f: f64) -> Metal {
+        Metal::textured(Arc::new(SolidColor::new(a)), f)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, f: f64) -> Metal {
         Metal {
-            albedo: a,
+            albedo,
             fuzz: f
         }
     }
@@ -51,14 +170,18 @@ impl Metal {
 impl Scatter for Metal {
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
         let reflected = r_in.direction().reflect(rec.normal).normalized();
-        let scattered = Ray::new(rec.p, reflected + self.fuzz * Vec3::random_in_unit_sphere());
+        let scattered = Ray::with_time(rec.p, reflected + roughness(self.fuzz, rec) * Vec3::random_in_unit_sphere(), r_in.time());
 
         if scattered.direction().dot(rec.normal) > 0.0 {
-            Some((self.albedo, scattered))
+            Some((base_color(&self.albedo, rec), scattered))
         } else {
             None
         }
     }
+
+    fn ray_type(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> RayType {
+        lobe_type(rec, scattered, RayType::Glossy)
+    }
 }
 
 pub struct Dielectric {
@@ -72,38 +195,517 @@ impl Dielectric {
         }
     }
 
-    fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+    pub fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
         // Use Schlick's approximation for reflectance
         let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
     }
-}
 
-impl Scatter for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
-        let refraction_ratio = if rec.front_face {
+    // Reflected or refracted unit direction, picked by the reflectance,
+    // about a normal facing the incoming unit direction
+    fn bend(&self, unit_direction: Vec3, normal: Vec3, front_face: bool) -> Vec3 {
+        let refraction_ratio = if front_face {
             1.0 / self.ir
         } else {
             self.ir
         };
 
-        let unit_direction = r_in.direction().normalized();
-
-        let cos_theta = ((-1.0) * unit_direction).dot(rec.normal).min(1.0);
+        let cos_theta = ((-1.0) * unit_direction).dot(normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
-        let mut rng = rand::thread_rng();
+        let mut rng = sampler::rng();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let will_reflect = rng.gen::<f64>() < Self::reflectance(cos_theta, refraction_ratio);
 
-        let direction = if cannot_refract || will_reflect {
+        if cannot_refract || will_reflect {
+            unit_direction.reflect(normal)
+        } else {
+            unit_direction.refract(normal, refraction_ratio)
+        }
+    }
+}
+
+impl Scatter for Dielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let direction = self.bend(r_in.direction().normalized(), rec.normal, rec.front_face);
+        let scattered = Ray::with_time(rec.p, direction, r_in.time());
+
+        Some((Color::new(1.0, 1.0, 1.0), scattered))
+    }
+
+    fn ray_type(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> RayType {
+        lobe_type(rec, scattered, RayType::Glossy)
+    }
+}
+
+// Dielectric sheet of negligible thickness, for window panes and bubbles.
+// Light refracted in at one face leaves the other at the angle it came in,
+// so transmitted rays go straight on, and the reflections bouncing between
+// the two faces add up to 2R / (1 + R) of a single face's reflectance R.
+pub struct ThinDielectric {
+    ir: f64
+}
+
+impl ThinDielectric {
+    pub fn new(index_of_refraction: f64) -> ThinDielectric {
+        ThinDielectric {
+            ir: index_of_refraction
+        }
+    }
+}
+
+impl Scatter for ThinDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let unit_direction = r_in.direction().normalized();
+        let cos_theta = ((-1.0) * unit_direction).dot(rec.normal).clamp(0.0, 1.0);
+
+        let r = Dielectric::reflectance(cos_theta, 1.0 / self.ir);
+        let reflectance = 2.0 * r / (1.0 + r);
+
+        let direction = if sampler::rng().gen::<f64>() < reflectance {
             unit_direction.reflect(rec.normal)
         } else {
-            unit_direction.refract(rec.normal, refraction_ratio)
+            unit_direction
         };
 
-        let scattered = Ray::new(rec.p, direction);
+        Some((Color::new(1.0, 1.0, 1.0), Ray::with_time(rec.p, direction, r_in.time())))
+    }
 
-        Some((Color::new(1.0, 1.0, 1.0), scattered))
+    fn ray_type(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> RayType {
+        lobe_type(rec, scattered, RayType::Glossy)
+    }
+}
+
+// Dielectric absorbing the light travelling through it, reds first, so deep
+// water turns blue-green. Absorption is applied where rays leave, so the
+// water must be a closed volume. Its faces looking up take the normals of
+// waves over them.
+pub struct Water {
+    surface: Dielectric,
+    absorption: Color,
+    waves: Option<Arc<Waves>>
+}
+
+impl Water {
+    pub fn new(index_of_refraction: f64, absorption: Color, waves: Option<Arc<Waves>>) -> Water {
+        Water {
+            surface: Dielectric::new(index_of_refraction),
+            absorption,
+            waves
+        }
+    }
+}
+
+impl Scatter for Water {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let unit_direction = r_in.direction().normalized();
+        let outward = if rec.front_face { rec.normal } else { (-1.0) * rec.normal };
+        let normal = match &self.waves {
+            Some(waves) if outward.y() > 0.5 => {
+                let n = waves.normal(rec.p.x(), rec.p.z());
+                if rec.front_face { n } else { (-1.0) * n }
+            },
+            _ => rec.normal
+        };
+
+        // Steep waves can bend a ray to the wrong side of the flat face it
+        // hit, where the face itself decides instead
+        let mut direction = self.surface.bend(unit_direction, normal, rec.front_face);
+        if (direction.dot(rec.geometric_normal) > 0.0) != (direction.dot(normal) > 0.0) {
+            direction = self.surface.bend(unit_direction, rec.geometric_normal, rec.front_face);
+        }
+
+        let attenuation = if rec.front_face {
+            Color::new(1.0, 1.0, 1.0)
+        } else {
+            let distance = rec.t * r_in.direction().length();
+            Color::new((-self.absorption.x() * distance).exp(), (-self.absorption.y() * distance).exp(), (-self.absorption.z() * distance).exp())
+        };
+
+        Some((attenuation, Ray::with_time(rec.p, direction, r_in.time())))
+    }
+
+    fn ray_type(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> RayType {
+        lobe_type(rec, scattered, RayType::Glossy)
+    }
+}
+
+// Smooth dielectric boundary over a scattering interior, for skin, wax and
+// marble; light enters, scatters around below the surface and leaves
+// somewhere else
+pub struct Subsurface {
+    surface: Dielectric,
+    medium: Medium
+}
+
+impl Subsurface {
+    pub fn new(index_of_refraction: f64, mean_free_path: Color, albedo: Color) -> Subsurface {
+        Subsurface {
+            surface: Dielectric::new(index_of_refraction),
+            medium: Medium::new(mean_free_path, albedo)
+        }
     }
-}
\ No newline at end of file
+}
+
+impl Scatter for Subsurface {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.surface.scatter(r_in, rec)
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        Some(&self.medium)
+    }
+
+    fn ray_type(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> RayType {
+        lobe_type(rec, scattered, RayType::Glossy)
+    }
+}
+
+// Glossy reflection whose color shifts with the viewing angle, the look of
+// thin films and structural color such as beetle shells, CDs and
+// pearlescent paint. The hue cycles `period` times between normal and
+// grazing incidence, blended over a Schlick reflectance of `albedo` by
+// `intensity`.
+pub struct Iridescent {
+    albedo: Color,
+    ggx: Ggx,
+    period: f64,
+    phase: f64,
+    intensity: f64
+}
+
+impl Iridescent {
+    pub fn new(albedo: Color, roughness: f64, period: f64, phase: f64, intensity: f64) -> Iridescent {
+        Iridescent {
+            albedo,
+            ggx: Ggx::new(roughness),
+            period,
+            phase,
+            intensity: intensity.clamp(0.0, 1.0)
+        }
+    }
+
+    // Reflectance for the cosine between the view and the microfacet normal
+    fn fresnel(&self, cosine: f64) -> Color {
+        let schlick = (1.0 - cosine.clamp(0.0, 1.0)).powi(5);
+        let base = self.albedo + schlick * (Color::new(1.0, 1.0, 1.0) - self.albedo);
+
+        // Channels a third of a cycle apart, sweeping around the hue circle
+        let x = self.period * (1.0 - cosine) + self.phase;
+        let hue = |offset: f64| 0.5 + 0.5 * (2.0 * PI * (x + offset)).cos();
+        let shifted = Color::new(hue(0.0), hue(2.0 / 3.0), hue(1.0 / 3.0));
+
+        (1.0 - self.intensity) * base + self.intensity * shifted
+    }
+}
+
+impl Scatter for Iridescent {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let wo = ((-1.0) * r_in.direction()).normalized();
+        let n = rec.normal;
+        let frame = Frame::new(n);
+        let wh = self.ggx.sample_half(&frame);
+        let wi = ((-1.0) * wo).reflect(wh);
+
+        let cos_o = n.dot(wo);
+        let cos_oh = wo.dot(wh);
+        if wi.dot(n) <= 0.0 || cos_o <= 0.0 || cos_oh <= 0.0 {
+            return None;
+        }
+
+        // f * cos / pdf with the half vector sampled by d(wh) * dot(n, wh)
+        let weight = self.ggx.g(&frame, wo, wi) * cos_oh / (cos_o * n.dot(wh));
+        Some((weight * self.fresnel(cos_oh), Ray::with_time(rec.p, wi, r_in.time())))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: Vec3) -> Option<Color> {
+        let wo = ((-1.0) * r_in.direction()).normalized();
+        let n = rec.normal;
+        let cos_o = n.dot(wo);
+        if cos_o <= 0.0 || n.dot(wi) <= 0.0 {
+            return Some(Color::new(0.0, 0.0, 0.0));
+        }
+
+        let frame = Frame::new(n);
+        let wh = (wo + wi).normalized();
+        let f = self.ggx.d(&frame, wh) * self.ggx.g(&frame, wo, wi) / (4.0 * cos_o);
+        Some(f * self.fresnel(wo.dot(wh)))
+    }
+
+    fn ray_type(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> RayType {
+        lobe_type(rec, scattered, RayType::Glossy)
+    }
+}
+
+// Glossy colored reflector whose GGX lobe can vary over the surface: the
+// roughness, the anisotropy stretching the highlight along the tangent, and
+// the rotation of the tangent around the normal in turns all come from
+// textures, averaged over their channels. The tangent starts out along the
+// part of `tangent` lying in the surface.
+pub struct Glossy {
+    albedo: Arc<dyn Texture>,
+    roughness: Arc<dyn Texture>,
+    anisotropy: Arc<dyn Texture>,
+    rotation: Arc<dyn Texture>,
+    tangent: Vec3
+}
+
+impl Glossy {
+    pub fn new(albedo: Arc<dyn Texture>,
+               roughness: Arc<dyn Texture>,
+               anisotropy: Arc<dyn Texture>,
+               rotation: Arc<dyn Texture>,
+               tangent: Vec3) -> Glossy {
+        Glossy {
+            albedo,
+            roughness,
+            anisotropy,
+            rotation,
+            tangent
+        }
+    }
+
+    // Distribution and shading frame at the hit
+    fn lobe(&self, rec: &HitRecord) -> (Ggx, Frame) {
+        let value = |texture: &Arc<dyn Texture>| {
+            let c = texture.value(rec);
+            (c.x() + c.y() + c.z()) / 3.0
+        };
+
+        let ggx = Ggx::anisotropic(roughness(value(&self.roughness), rec), value(&self.anisotropy));
+        let frame = Frame::with_tangent(rec.normal, self.tangent).rotated(2.0 * PI * value(&self.rotation));
+        (ggx, frame)
+    }
+
+    fn fresnel(&self, rec: &HitRecord, cosine: f64) -> Color {
+        let albedo = base_color(&self.albedo, rec);
+        albedo + (1.0 - cosine.clamp(0.0, 1.0)).powi(5) * (Color::new(1.0, 1.0, 1.0) - albedo)
+    }
+}
+
+impl Scatter for Glossy {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let (ggx, frame) = self.lobe(rec);
+        let wo = ((-1.0) * r_in.direction()).normalized();
+        let n = rec.normal;
+        let wh = ggx.sample_half(&frame);
+        let wi = ((-1.0) * wo).reflect(wh);
+
+        let cos_o = n.dot(wo);
+        let cos_oh = wo.dot(wh);
+        if wi.dot(n) <= 0.0 || cos_o <= 0.0 || cos_oh <= 0.0 {
+            return None;
+        }
+
+        let weight = ggx.g(&frame, wo, wi) * cos_oh / (cos_o * n.dot(wh));
+        Some((weight * self.fresnel(rec, cos_oh), Ray::with_time(rec.p, wi, r_in.time())))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: Vec3) -> Option<Color> {
+        let wo = ((-1.0) * r_in.direction()).normalized();
+        let n = rec.normal;
+        let cos_o = n.dot(wo);
+        if cos_o <= 0.0 || n.dot(wi) <= 0.0 {
+            return Some(Color::new(0.0, 0.0, 0.0));
+        }
+
+        let (ggx, frame) = self.lobe(rec);
+        let wh = (wo + wi).normalized();
+        let f = ggx.d(&frame, wh) * ggx.g(&frame, wo, wi) / (4.0 * cos_o);
+        Some(f * self.fresnel(rec, wo.dot(wh)))
+    }
+
+    fn ray_type(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> RayType {
+        lobe_type(rec, scattered, RayType::Glossy)
+    }
+}
+
+// Picks `b` with the probability given by the mask, averaged over its
+// channels, and `a` otherwise
+pub struct Mix {
+    a: Arc<dyn Scatter>,
+    b: Arc<dyn Scatter>,
+    mask: Arc<dyn Texture>
+}
+
+impl Mix {
+    pub fn new(a: Arc<dyn Scatter>, b: Arc<dyn Scatter>, mask: Arc<dyn Texture>) -> Mix {
+        Mix {
+            a,
+            b,
+            mask
+        }
+    }
+}
+
+impl Scatter for Mix {
+    // Only reached if the material wasn't resolved first, in which case the
+    // event is still sampled from a randomly selected child
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.select(r_in, rec)?.scatter(r_in, rec)
+    }
+
+    fn select(&self, _r_in: &Ray, rec: &HitRecord) -> Option<Arc<dyn Scatter>> {
+        let m = self.mask.value(rec);
+        let weight = ((m.x() + m.y() + m.z()) / 3.0).clamp(0.0, 1.0);
+
+        if sampler::rng().gen::<f64>() < weight {
+            Some(self.b.clone())
+        } else {
+            Some(self.a.clone())
+        }
+    }
+}
+
+// Picks `b` with the Fresnel reflectance of a dielectric of index `ir` at
+// the viewing angle and `a` otherwise, so `b` shows at glancing angles, e.g.
+// for sheen or a wet coat
+pub struct FresnelSwitch {
+    a: Arc<dyn Scatter>,
+    b: Arc<dyn Scatter>,
+    ir: f64
+}
+
+impl FresnelSwitch {
+    pub fn new(a: Arc<dyn Scatter>, b: Arc<dyn Scatter>, index_of_refraction: f64) -> FresnelSwitch {
+        FresnelSwitch {
+            a,
+            b,
+            ir: index_of_refraction
+        }
+    }
+}
+
+impl Scatter for FresnelSwitch {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.select(r_in, rec)?.scatter(r_in, rec)
+    }
+
+    fn select(&self, r_in: &Ray, rec: &HitRecord) -> Option<Arc<dyn Scatter>> {
+        let cosine = r_in.direction().normalized().dot(rec.normal).abs();
+
+        if sampler::rng().gen::<f64>() < Dielectric::reflectance(cosine, self.ir) {
+            Some(self.b.clone())
+        } else {
+            Some(self.a.clone())
+        }
+    }
+}
+
+// Picks `b` for rays of the given types and `a` for the others, e.g. to
+// hide an object from the camera or let shadow rays through glass
+pub struct RaySwitch {
+    a: Arc<dyn Scatter>,
+    b: Arc<dyn Scatter>,
+    types: Vec<RayType>
+}
+
+impl RaySwitch {
+    pub fn new(a: Arc<dyn Scatter>, b: Arc<dyn Scatter>, types: Vec<RayType>) -> RaySwitch {
+        RaySwitch {
+            a,
+            b,
+            types
+        }
+    }
+
+    fn pick(&self, r_in: &Ray) -> &Arc<dyn Scatter> {
+        if self.types.contains(&r_in.ray_type()) {
+            &self.b
+        } else {
+            &self.a
+        }
+    }
+}
+
+impl Scatter for RaySwitch {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.pick(r_in).scatter(r_in, rec)
+    }
+
+    fn select(&self, r_in: &Ray, _rec: &HitRecord) -> Option<Arc<dyn Scatter>> {
+        Some(self.pick(r_in).clone())
+    }
+
+    // Asked by shadow rays, which don't resolve the material first
+    fn transparent(&self, r_in: &Ray, rec: &HitRecord) -> bool {
+        self.pick(r_in).transparent(r_in, rec)
+    }
+}
+
+// Emits light from its front face, or both faces, and scatters none
+pub struct Emissive {
+    radiance: Color,
+    emission: Emission
+}
+
+impl Emissive {
+    pub fn new(radiance: Color, emission: Emission) -> Emissive {
+        Emissive {
+            radiance,
+            emission
+        }
+    }
+}
+
+impl Scatter for Emissive {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        // Cosine to the outward normal, negative on the back face
+        let cos_theta = r_in.direction().normalized().dot(rec.normal).abs();
+        let cos_theta = if rec.front_face { cos_theta } else { -cos_theta };
+
+        self.emission.factor(cos_theta) * self.radiance
+    }
+}
+
+// A material with the displacement of its <displacement> child, which
+// stands in for it at every hit once the geometry has been displaced
+pub struct Displaced {
+    material: Arc<dyn Scatter>,
+    displacement: Displacement
+}
+
+impl Displaced {
+    pub fn new(material: Arc<dyn Scatter>, displacement: Displacement) -> Displaced {
+        Displaced {
+            material,
+            displacement
+        }
+    }
+}
+
+impl Scatter for Displaced {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        self.material.scatter(r_in, rec)
+    }
+
+    fn select(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<Arc<dyn Scatter>> {
+        Some(self.material.clone())
+    }
+
+    fn displacement(&self) -> Option<&Displacement> {
+        Some(&self.displacement)
+    }
+}
+
+// Lets every ray through unchanged, keeping its type, as if the surface
+// wasn't there
+pub struct Transparent;
+
+impl Scatter for Transparent {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        Some((Color::new(1.0, 1.0, 1.0), Ray::with_time(rec.p, r_in.direction(), r_in.time())))
+    }
+
+    fn ray_type(&self, r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> RayType {
+        r_in.ray_type()
+    }
+
+    fn transparent(&self, _r_in: &Ray, _rec: &HitRecord) -> bool {
+        true
+    }
+}
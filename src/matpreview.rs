@@ -0,0 +1,80 @@
+use std::time::Instant;
+
+use roxmltree::Document;
+
+use super::cli::Options;
+use super::metadata::Metadata;
+use super::render;
+
+// Shader-ball setup: a unit ball resting on the gray ground, lit by the
+// studio environment and seen from a fixed camera
+const PREVIEW_SCENE: &str = r#"<RT>
+    <film filename="preview.png" />
+    <camera look_from="0 2.2 8.5" look_at="0 0.9 0" up="0 1 0" aperture="0" />
+    <environment>
+        <layer type="studio" />
+    </environment>
+    <world>
+        <material type="lambertian" color="0.5 0.5 0.5" />
+        <object type="plane" point="0 0 0" normal="0 1 0" />
+        MATERIALS
+        <object type="sphere" center="0 1 0" radius="1" />
+    </world>
+</RT>"#;
+
+// `rt matpreview material.xml [-o preview.png] [render options]`. The file
+// holds one or more <material> elements, the last of which is previewed.
+pub fn matpreview(args: impl Iterator<Item = String>) {
+    let mut output = None;
+    let mut render_args = vec!["--quality".to_string(), "medium".to_string()];
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        if arg == "-o" || arg == "--output" {
+            output = Some(args.next().unwrap_or_else(|| panic!("Missing value for {}.", arg)));
+        } else {
+            render_args.push(arg);
+        }
+    }
+
+    let opts = Options::parse(render_args.into_iter());
+    let path = opts.scene.clone().expect("Missing material file for matpreview.");
+    let xml = super::read_xml(&path);
+
+    // The materials may be listed without a root element, so the file is
+    // wrapped in one before looking for them
+    let body = match xml.trim_start().strip_prefix("<?xml") {
+        Some(rest) => rest.split_once("?>").map_or("", |(_, body)| body),
+        None => &xml
+    };
+    let wrapped = format!("<materials>{}</materials>", body);
+    let doc = Document::parse(&wrapped).expect("Failed to parse XML");
+
+    // Material elements are copied as written into the preview scene, along
+    // with the materials nested in them and the plugins they come from
+    let materials: Vec<&str> = doc.descendants()
+        .filter(|n| n.has_tag_name("plugin") || n.has_tag_name("material") && !n.parent_element().is_some_and(|p| p.has_tag_name("material")))
+        .map(|n| &wrapped[n.range()])
+        .collect();
+    if !materials.iter().any(|m| m.starts_with("<material")) {
+        panic!("No <material> found in {}.", path);
+    }
+
+    let (_, scene) = super::xml_parser(&PREVIEW_SCENE.replace("MATERIALS", &materials.join("\n")));
+
+    let output = output.unwrap_or_else(|| {
+        let stem = path.strip_suffix(".xml").unwrap_or(&path);
+        format!("{}.preview.png", stem)
+    });
+
+    let settings = opts.settings(&scene);
+    let start = Instant::now();
+    let pixels = render::render(&scene, &settings);
+
+    let mut metadata = Metadata::new();
+    metadata.add_file("Material", &path, &xml);
+    metadata.add_settings(&settings);
+    metadata.add("RenderTime", format!("{:.3} s", start.elapsed().as_secs_f64()));
+    super::write_image(&output, &pixels, None, &settings, &metadata);
+    eprintln!("Wrote preview to {}", output);
+}
@@ -0,0 +1,146 @@
+use std::ops::Mul;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+
+// Affine transform as a row-major 4x4 matrix acting on column vectors, so
+// in a * b, b applies first
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix {
+    pub m: [[f64; 4]; 4]
+}
+
+impl Matrix {
+    pub fn identity() -> Matrix {
+        Matrix::scale(Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(offset: Vec3) -> Matrix {
+        let mut matrix = Matrix::identity();
+        for row in 0..3 {
+            matrix.m[row][3] = offset[row];
+        }
+        matrix
+    }
+
+    pub fn scale(factors: Vec3) -> Matrix {
+        let mut m = [[0.0; 4]; 4];
+        for (k, row) in m.iter_mut().take(3).enumerate() {
+            row[k] = factors[k];
+        }
+        m[3][3] = 1.0;
+        Matrix { m }
+    }
+
+    // Counter-clockwise by `degrees` looking down the axis towards the origin
+    pub fn rotation(axis: Vec3, degrees: f64) -> Matrix {
+        let a = axis.normalized();
+        let (s, c) = degrees.to_radians().sin_cos();
+        let t = 1.0 - c;
+        let (x, y, z) = (a.x(), a.y(), a.z());
+
+        Matrix::from_linear([
+            [t * x * x + c, t * x * y - s * z, t * x * z + s * y],
+            [t * x * y + s * z, t * y * y + c, t * y * z - s * x],
+            [t * x * z - s * y, t * y * z + s * x, t * z * z + c]
+        ])
+    }
+
+    // Rotation by the unit quaternion x i + y j + z k + w
+    pub fn quaternion(x: f64, y: f64, z: f64, w: f64) -> Matrix {
+        Matrix::from_linear([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w)],
+            [2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w)],
+            [2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y)]
+        ])
+    }
+
+    fn from_linear(l: [[f64; 3]; 3]) -> Matrix {
+        let mut matrix = Matrix::identity();
+        for (row, values) in matrix.m.iter_mut().zip(l) {
+            row[..3].copy_from_slice(&values);
+        }
+        matrix
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.direction(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    pub fn direction(&self, v: Vec3) -> Vec3 {
+        let row = |r: usize| self.m[r][0] * v.x() + self.m[r][1] * v.y() + self.m[r][2] * v.z();
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    // Of the linear part; negative for transforms that mirror
+    pub fn determinant(&self) -> f64 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    // The transpose of the inverse of the linear part, which takes normals
+    // to the transformed surface's; None for singular transforms
+    pub fn normal_matrix(&self) -> Option<Matrix> {
+        let det = self.determinant();
+        if det == 0.0 {
+            return None;
+        }
+
+        // Cofactors over the determinant
+        let mut result = Matrix::identity();
+        for row in 0..3 {
+            for col in 0..3 {
+                let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+                let (c0, c1) = ((col + 1) % 3, (col + 2) % 3);
+                result.m[row][col] = (self.m[r0][c0] * self.m[r1][c1] - self.m[r0][c1] * self.m[r1][c0]) / det;
+            }
+        }
+        Some(result)
+    }
+
+    pub fn inverse(&self) -> Option<Matrix> {
+        let normal = self.normal_matrix()?;
+        let mut linear = Matrix::identity();
+        for row in 0..3 {
+            for col in 0..3 {
+                linear.m[row][col] = normal.m[col][row];
+            }
+        }
+
+        let offset = linear.direction(Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3]));
+        Some(Matrix::translation((-1.0) * offset) * linear)
+    }
+
+    // Box around the transformed corners of bbox
+    pub fn bounds(&self, bbox: &Aabb) -> Aabb {
+        let corner = |k: usize| Point3::new(
+            if k & 1 == 0 { bbox.min.x() } else { bbox.max.x() },
+            if k & 2 == 0 { bbox.min.y() } else { bbox.max.y() },
+            if k & 4 == 0 { bbox.min.z() } else { bbox.max.z() }
+        );
+
+        (0..8)
+            .map(|k| {
+                let p = self.point(corner(k));
+                Aabb::new(p, p)
+            })
+            .reduce(Aabb::surrounding)
+            .unwrap()
+    }
+}
+
+impl Mul for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, other: Matrix) -> Matrix {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (col, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[row][k] * other.m[k][col]).sum();
+            }
+        }
+        Matrix { m }
+    }
+}
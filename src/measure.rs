@@ -0,0 +1,56 @@
+use super::ray::Ray;
+use super::vec::Vec3;
+
+fn xml_vec(v: Vec3) -> String {
+    format!("{} {} {}", v.x(), v.y(), v.z())
+}
+
+// `rt measure scene.xml --from "x y z" --dir "x y z"` casts one ray and
+// prints what it hits first, one `key: value` line each, with vectors
+// written as in scene files. Misses print `miss` and exit with status 1.
+pub fn measure(args: impl Iterator<Item = String>) {
+    let mut path = None;
+    let mut from = None;
+    let mut dir = None;
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| panic!("Missing value for {}.", arg));
+        match arg.as_str() {
+            "--from" => from = Some(super::value_parser(&value())),
+            "--dir" => dir = Some(super::value_parser(&value())),
+            _ if arg.starts_with("--") => panic!("Unknown option '{}'.", arg),
+            _ => path = Some(arg)
+        }
+    }
+
+    let path = path.expect("Missing scene file to measure.");
+    let from = from.map(|v| Vec3::new(v.0, v.1, v.2)).expect("Missing --from position.");
+    let dir = dir.map(|v| Vec3::new(v.0, v.1, v.2)).expect("Missing --dir direction.");
+    if dir.near_zero() {
+        panic!("The --dir direction can't be zero.");
+    }
+
+    let (_, scene) = super::xml_parser(&super::read_xml(&path));
+
+    // Distances are measured along the unit direction
+    let hit = match scene.intersect(&Ray::new(from, dir.normalized())) {
+        Some(hit) => hit,
+        None => {
+            println!("miss");
+            std::process::exit(1);
+        }
+    };
+
+    match scene.object_names.get(hit.object).and_then(|n| n.as_deref()) {
+        Some(name) => println!("object: {} ({})", hit.object, name),
+        None => println!("object: {}", hit.object)
+    }
+    println!("distance: {}", hit.t);
+    println!("position: {}", xml_vec(hit.point));
+
+    // The outward normal, whichever side the ray came from
+    let normal = if hit.front_face { hit.normal } else { (-1.0) * hit.normal };
+    println!("normal: {}", xml_vec(normal));
+    println!("face: {}", if hit.front_face { "front" } else { "back" });
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::accel::{self, Accelerator};
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::ray::Ray;
+use super::triangle::Triangle;
+use super::vec::{Point3, Vec3};
+
+// Triangle of a mesh, by vertex index, with the indices of its vertex
+// normals if the file has them
+pub struct Face {
+    pub vertices: [usize; 3],
+    pub normals: Option<[usize; 3]>
+}
+
+// Geometry read from a mesh file, in the file's own space
+#[derive(Default)]
+pub struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub faces: Vec<Face>
+}
+
+impl MeshData {
+    // Vertex indices of every face, wound counter-clockwise seen from the
+    // side its vertex normals point to, if it has any, and the normals of
+    // those vertices in the same order
+    pub fn oriented_faces(&self) -> impl Iterator<Item = ([usize; 3], Option<[Vec3; 3]>)> + '_ {
+        self.faces.iter().map(|face| {
+            let [a, b, c] = face.vertices;
+            let [pa, pb, pc] = face.vertices.map(|v| self.positions[v]);
+            let normals = face.normals.map(|normals| normals.map(|n| self.normals[n]));
+
+            let facing = normals.is_none_or(|[na, nb, nc]| (pb - pa).cross(pc - pa).dot(na + nb + nc) >= 0.0);
+            if facing {
+                ([a, b, c], normals)
+            } else {
+                ([a, c, b], normals.map(|[na, nb, nc]| [na, nc, nb]))
+            }
+        })
+    }
+}
+
+// Triangle mesh with an accelerator of its own over its triangles
+pub struct Mesh {
+    triangles: Vec<Triangle>,
+    accel: Box<dyn Accelerator<Triangle>>
+}
+
+impl Mesh {
+    pub fn new(triangles: Vec<Triangle>) -> Mesh {
+        let accel = accel::new(&triangles);
+        Mesh {
+            triangles,
+            accel
+        }
+    }
+
+    // Triangles of data scaled per axis, then translated, wound as in
+    // oriented_faces, and shaded smoothly with the vertex normals of the
+    // faces that have them
+    pub fn transformed(data: &MeshData, translate: Vec3, scale: Vec3, m: Arc<dyn Scatter>) -> Mesh {
+        let place = |p: Point3| scale * p + translate;
+        // Normals scale by the inverse, staying perpendicular to the surface
+        let turn = |n: Vec3| Vec3::new(n.x() / scale.x(), n.y() / scale.y(), n.z() / scale.z());
+        // Mirroring by the scale reverses the winding of every face
+        let mirrored = scale.x() * scale.y() * scale.z() < 0.0;
+
+        let triangles = data.oriented_faces().map(|([a, b, c], normals)| {
+            let [a, b, c] = [a, b, c].map(|v| place(data.positions[v]));
+            let normals = normals.map(|normals| normals.map(turn));
+            let (triangle, normals) = if mirrored {
+                (Triangle::new(a, c, b, m.clone()), normals.map(|[na, nb, nc]| [na, nc, nb]))
+            } else {
+                (Triangle::new(a, b, c, m.clone()), normals)
+            };
+            match normals {
+                Some(normals) => triangle.with_normals(normals),
+                None => triangle
+            }
+        }).collect();
+
+        Mesh::new(triangles)
+    }
+}
+
+impl Hit for Mesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.accel.hit(&self.triangles, r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.accel.bounding_box()
+    }
+}
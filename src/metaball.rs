@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::sdf;
+
+// Steepest slope of the falloff (1 - x^2)^3, reached at x = 1 / sqrt(5)
+const MAX_SLOPE: f64 = 1.7173;
+
+// Point charge adding weight (1 - (r / radius)^2)^3 to the field at the
+// distance r < radius from its center; negative weights carve into the
+// others
+#[derive(Clone, Copy)]
+pub struct Charge {
+    pub center: Point3,
+    pub radius: f64,
+    pub weight: f64
+}
+
+// Blobby surface where the summed field of the charges reaches threshold,
+// inside where it is stronger. Nearby charges merge smoothly into each
+// other. The field changes by at most `slope` per scene unit, so it is
+// marched like a signed distance once divided by it.
+pub struct Metaballs {
+    charges: Vec<Charge>,
+    threshold: f64,
+    slope: f64,
+    bbox: Aabb,
+    mat: Arc<dyn Scatter>
+}
+
+impl Metaballs {
+    pub fn new(charges: Vec<Charge>, threshold: f64, m: Arc<dyn Scatter>) -> Metaballs {
+        let slope = charges.iter().map(|c| c.weight.abs() * MAX_SLOPE / c.radius).sum();
+
+        // Only positive charges reach the threshold on their own
+        let bbox = charges.iter()
+            .filter(|c| c.weight > 0.0)
+            .map(|c| {
+                let r = Vec3::new(c.radius, c.radius, c.radius);
+                Aabb::new(c.center - r, c.center + r)
+            })
+            .reduce(Aabb::surrounding)
+            .unwrap_or(Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, 0.0)));
+
+        Metaballs {
+            charges,
+            threshold,
+            slope,
+            bbox,
+            mat: m
+        }
+    }
+
+    fn field(&self, p: Point3) -> f64 {
+        self.charges.iter().map(|c| {
+            let x2 = (p - c.center).dot(p - c.center) / (c.radius * c.radius);
+            if x2 < 1.0 { c.weight * (1.0 - x2).powi(3) } else { 0.0 }
+        }).sum()
+    }
+
+    fn gradient(&self, p: Point3) -> Vec3 {
+        self.charges.iter().fold(Vec3::new(0.0, 0.0, 0.0), |g, c| {
+            let d = p - c.center;
+            let r2 = c.radius * c.radius;
+            let x2 = d.dot(d) / r2;
+            if x2 < 1.0 { g + (-6.0 * c.weight * (1.0 - x2).powi(2) / r2) * d } else { g }
+        })
+    }
+}
+
+impl Hit for Metaballs {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if self.slope == 0.0 {
+            return None;
+        }
+
+        let t = sdf::march(r, t_min, t_max, &self.bbox, |p| (self.threshold - self.field(p)) / self.slope)?;
+
+        // The field grows inwards
+        let g = self.gradient(r.at(t));
+        let outward_normal = if g.near_zero() { (-1.0) * r.direction().normalized() } else { (-1.0) * g.normalized() };
+        Some(HitRecord::new(r, t, outward_normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
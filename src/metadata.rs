@@ -0,0 +1,68 @@
+use super::render::{Integrator, RenderSettings};
+
+// Text entries describing how a render was made, written into every image of
+// it (PNG tEXt chunks, EXR string attributes, PPM comments) so an image can
+// be traced back to the exact scene and settings
+#[derive(Clone)]
+pub struct Metadata {
+    entries: Vec<(String, String)>
+}
+
+// 64-bit FNV-1a, enough to tell scene files apart
+pub fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+impl Metadata {
+    pub fn new() -> Metadata {
+        Metadata {
+            entries: vec![("Software".to_string(), format!("rt {}", env!("CARGO_PKG_VERSION")))]
+        }
+    }
+
+    pub fn add(&mut self, key: &str, value: impl ToString) {
+        self.entries.push((key.to_string(), value.to_string()));
+    }
+
+    // The file's name, followed by the hash of its contents under key + "Hash"
+    pub fn add_file(&mut self, key: &str, name: &str, contents: &str) {
+        self.add(key, name);
+        self.add(&format!("{}Hash", key), format!("{:016x}", hash(contents.as_bytes())));
+    }
+
+    // Everything of the settings that changes the image; the packet size
+    // and tile order only change how it is computed
+    pub fn add_settings(&mut self, settings: &RenderSettings) {
+        let (width, height) = settings.display_size();
+        self.add("Resolution", format!("{}x{}", width, height));
+        if settings.overscan > 0 {
+            self.add("Overscan", settings.overscan);
+        }
+        if let Some(bucket) = settings.bucket {
+            self.add("Bucket", bucket.name());
+        }
+        if !settings.flip.is_none() {
+            self.add("Orientation", settings.flip.orientation());
+        }
+        if settings.pixel_aspect != 1.0 {
+            self.add("PixelAspect", settings.pixel_aspect);
+        }
+        self.add("Integrator", settings.integrator.name());
+        self.add("Samples", settings.samples_per_pixel);
+        self.add("MaxDepth", settings.max_depth);
+        if let Some(start) = settings.rr_start {
+            self.add("RouletteStart", start);
+        }
+        if matches!(settings.integrator, Integrator::Ao | Integrator::Depth) {
+            self.add("Distance", settings.distance);
+        }
+        self.add("Clamp", settings.clamp);
+        self.add("Denoise", settings.denoise);
+        self.add("Seed", settings.seed);
+        self.add("Frame", settings.frame);
+    }
+
+    pub fn entries(&self) -> &[(String, String)] {
+        &self.entries
+    }
+}
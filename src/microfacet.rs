@@ -0,0 +1,122 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use super::sampler;
+use super::vec::Vec3;
+
+// Shading frame at a hit: the unit normal n and the tangents t and b, along
+// which anisotropic highlights are stretched
+pub struct Frame {
+    pub t: Vec3,
+    pub b: Vec3,
+    pub n: Vec3
+}
+
+impl Frame {
+    // Frame with arbitrary tangents, for isotropic distributions
+    pub fn new(n: Vec3) -> Frame {
+        let (t, b) = n.orthonormal_basis();
+        Frame { t, b, n }
+    }
+
+    // Frame with t along the part of `tangent` lying in the surface, or
+    // arbitrary tangents where it is normal to it
+    pub fn with_tangent(n: Vec3, tangent: Vec3) -> Frame {
+        let t = tangent - tangent.dot(n) * n;
+        if t.near_zero() {
+            return Frame::new(n);
+        }
+
+        let t = t.normalized();
+        Frame { t, b: n.cross(t), n }
+    }
+
+    // The same frame with its tangents turned by `angle` radians around n
+    pub fn rotated(self, angle: f64) -> Frame {
+        let (sin, cos) = angle.sin_cos();
+        Frame {
+            t: cos * self.t + sin * self.b,
+            b: cos * self.b - sin * self.t,
+            n: self.n
+        }
+    }
+
+    fn local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.t), v.dot(self.b), v.dot(self.n))
+    }
+}
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals around the
+// shading normal, with Smith's uncorrelated shadowing-masking. Directions are
+// unit vectors pointing away from the surface. Anisotropic distributions
+// have a different width along the two tangents of the frame.
+pub struct Ggx {
+    alpha_x: f64,
+    alpha_y: f64
+}
+
+impl Ggx {
+    // Perceptual roughness in [0, 1], squared as usual so that it varies the
+    // highlight evenly; a minimum keeps the distribution finite
+    pub fn new(roughness: f64) -> Ggx {
+        Ggx::anisotropic(roughness, 0.0)
+    }
+
+    // Highlight stretched along the frame's tangent t by anisotropy in
+    // [0, 1], with the same mapping as the Disney principled BRDF
+    pub fn anisotropic(roughness: f64, anisotropy: f64) -> Ggx {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropy.clamp(0.0, 1.0)).sqrt();
+
+        Ggx {
+            alpha_x: (alpha / aspect).max(1e-3),
+            alpha_y: (alpha * aspect).max(1e-3)
+        }
+    }
+
+    // Density of microfacet normals wh
+    pub fn d(&self, frame: &Frame, wh: Vec3) -> f64 {
+        let h = frame.local(wh);
+        if h.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let x = h.x() / self.alpha_x;
+        let y = h.y() / self.alpha_y;
+        let denom = x * x + y * y + h.z() * h.z();
+        1.0 / (PI * self.alpha_x * self.alpha_y * denom * denom)
+    }
+
+    fn g1(&self, frame: &Frame, v: Vec3) -> f64 {
+        let v = frame.local(v);
+        if v.z() <= 0.0 {
+            return 0.0;
+        }
+
+        let x = self.alpha_x * v.x();
+        let y = self.alpha_y * v.y();
+        let tan2 = (x * x + y * y) / (v.z() * v.z());
+        2.0 / (1.0 + (1.0 + tan2).sqrt())
+    }
+
+    // Fraction of the microfacets seen from wo that are also seen from wi
+    pub fn g(&self, frame: &Frame, wo: Vec3, wi: Vec3) -> f64 {
+        self.g1(frame, wo) * self.g1(frame, wi)
+    }
+
+    // Microfacet normal sampled proportionally to d(wh) * dot(n, wh), from
+    // its slopes, which are those of the isotropic distribution stretched
+    // by the widths along the tangents
+    pub fn sample_half(&self, frame: &Frame) -> Vec3 {
+        let mut rng = sampler::rng();
+        let u1: f64 = rng.gen();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+
+        let slope = (u1 / (1.0 - u1)).sqrt();
+        let x = self.alpha_x * slope * phi.cos();
+        let y = self.alpha_y * slope * phi.sin();
+
+        (x * frame.t + y * frame.b + frame.n).normalized()
+    }
+}
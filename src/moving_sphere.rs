@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Sphere moving in a straight line from center0 at time0 to center1 at
+// time1, and standing still at either end outside that interval
+pub struct MovingSphere {
+    center0: Point3,
+    center1: Point3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    mat: Arc<dyn Scatter>
+}
+
+impl MovingSphere {
+    pub fn new(center0: Point3, center1: Point3, time0: f64, time1: f64, radius: f64, m: Arc<dyn Scatter>) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            mat: m
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Point3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+
+        let t = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0 + t * (self.center1 - self.center0)
+    }
+}
+
+impl Hit for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let center = self.center(r.time());
+        let oc = r.origin() - center;
+        let a = r.direction().length().powi(2);
+        let half_b = oc.dot(r.direction());
+        let c = oc.length().powi(2) - self.radius.powi(2);
+
+        let discriminant = half_b.powi(2) - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let sqrtd = discriminant.sqrt();
+        let mut root = (-half_b - sqrtd) / a;
+        if root < t_min || t_max < root {
+            root = (-half_b + sqrtd) / a;
+            if root < t_min || t_max < root {
+                return None;
+            }
+        }
+
+        let outward_normal = (r.at(root) - center) / self.radius;
+        Some(HitRecord::new(r, root, outward_normal, self.mat.clone()))
+    }
+
+    // Everywhere the sphere passes through on its way
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        let start = Aabb::new(self.center0 - r, self.center0 + r);
+        let end = Aabb::new(self.center1 - r, self.center1 + r);
+        Some(start.surrounding(end))
+    }
+}
@@ -0,0 +1,102 @@
+use super::mesh::{Face, MeshData};
+use super::vec::{Point3, Vec3};
+
+// Wavefront OBJ reader for the geometry of a mesh: vertices, vertex normals
+// and faces, which are split into triangles fanning out from their first
+// corner. Texture coordinates, groups and materials are skipped.
+pub fn load(filename: &str) -> MeshData {
+    let contents = std::fs::read_to_string(filename)
+        .unwrap_or_else(|_| panic!("Unable to read OBJ file {}.", filename));
+    let mut data = MeshData::default();
+
+    for (number, line) in contents.lines().enumerate() {
+        let fail = || -> ! { panic!("Failed to parse OBJ file {} at line {}.", filename, number + 1) };
+
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let v = coordinates(&mut tokens).unwrap_or_else(|| fail());
+                data.positions.push(Point3::new(v[0], v[1], v[2]));
+            },
+            Some("vn") => {
+                let n = coordinates(&mut tokens).unwrap_or_else(|| fail());
+                data.normals.push(Vec3::new(n[0], n[1], n[2]));
+            },
+            Some("f") => {
+                let corners: Vec<(usize, Option<usize>)> = tokens
+                    .map(|corner| parse_corner(corner, &data).unwrap_or_else(|| fail()))
+                    .collect();
+                if corners.len() < 3 {
+                    fail();
+                }
+
+                for k in 1..corners.len() - 1 {
+                    let [a, b, c] = [corners[0], corners[k], corners[k + 1]];
+                    let normals = match (a.1, b.1, c.1) {
+                        (Some(na), Some(nb), Some(nc)) => Some([na, nb, nc]),
+                        _ => None
+                    };
+                    data.faces.push(Face { vertices: [a.0, b.0, c.0], normals });
+                }
+            },
+            _ => { }
+        }
+    }
+
+    if data.faces.is_empty() {
+        panic!("No faces found in OBJ file {}.", filename);
+    }
+    data
+}
+
+fn coordinates<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Option<[f64; 3]> {
+    let mut v = [0.0; 3];
+    for x in v.iter_mut() {
+        *x = tokens.next()?.parse().ok()?;
+    }
+
+    Some(v)
+}
+
+// A face corner `v`, `v/vt`, `v/vt/vn` or `v//vn`, as zero based vertex and
+// normal indices; negative indices count back from the last one read
+fn parse_corner(corner: &str, data: &MeshData) -> Option<(usize, Option<usize>)> {
+    let mut parts = corner.split('/');
+    let vertex = index(parts.next()?, data.positions.len())?;
+    let normal = match parts.nth(1) {
+        Some(n) if !n.is_empty() => Some(index(n, data.normals.len())?),
+        _ => None
+    };
+
+    Some((vertex, normal))
+}
+
+fn index(token: &str, count: usize) -> Option<usize> {
+    let i: i64 = token.parse().ok()?;
+    let index = if i < 0 { count as i64 + i } else { i - 1 };
+
+    (0..count as i64).contains(&index).then_some(index as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn loads_faces_with_normals() {
+        let data = load(&fixtures::path("square.obj"));
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.normals.len(), 1);
+
+        // Fanned out from the first corner, the last one counted back
+        let faces: Vec<_> = data.faces.iter().map(|f| (f.vertices, f.normals)).collect();
+        assert_eq!(faces, [([0, 1, 2], Some([0; 3])), ([0, 2, 3], Some([0; 3]))]);
+    }
+
+    #[test]
+    #[should_panic(expected = "at line 2")]
+    fn rejects_faces_of_missing_vertices() {
+        load(&fixtures::write("missing.obj", b"v 0 0 0\nf 1 2 3\n"));
+    }
+}
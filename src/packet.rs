@@ -0,0 +1,94 @@
+use super::aabb::Aabb;
+use super::ray::Ray;
+use super::vec::{Point3, Vec3};
+
+// Widest packet supported; narrower packets leave the remaining lanes unused
+pub const MAX_LANES: usize = 8;
+
+// A bundle of rays stored lane by lane, so per-lane arithmetic can be
+// auto-vectorized by the compiler
+pub struct RayPacket {
+    pub lanes: usize,
+    pub ox: [f64; MAX_LANES],
+    pub oy: [f64; MAX_LANES],
+    pub oz: [f64; MAX_LANES],
+    pub dx: [f64; MAX_LANES],
+    pub dy: [f64; MAX_LANES],
+    pub dz: [f64; MAX_LANES],
+    pub time: [f64; MAX_LANES]
+}
+
+impl RayPacket {
+    pub fn new(rays: &[Ray]) -> RayPacket {
+        assert!(rays.len() <= MAX_LANES, "Ray packets hold at most {} rays.", MAX_LANES);
+
+        let mut p = RayPacket {
+            lanes: rays.len(),
+            ox: [0.0; MAX_LANES],
+            oy: [0.0; MAX_LANES],
+            oz: [0.0; MAX_LANES],
+            dx: [0.0; MAX_LANES],
+            dy: [0.0; MAX_LANES],
+            dz: [0.0; MAX_LANES],
+            time: [0.0; MAX_LANES]
+        };
+
+        for (k, r) in rays.iter().enumerate() {
+            let o = r.origin();
+            let d = r.direction();
+            p.ox[k] = o.x();
+            p.oy[k] = o.y();
+            p.oz[k] = o.z();
+            p.dx[k] = d.x();
+            p.dy[k] = d.y();
+            p.dz[k] = d.z();
+            p.time[k] = r.time();
+        }
+
+        p
+    }
+
+    pub fn ray(&self, k: usize) -> Ray {
+        Ray::with_time(Point3::new(self.ox[k], self.oy[k], self.oz[k]),
+                       Vec3::new(self.dx[k], self.dy[k], self.dz[k]),
+                       self.time[k])
+    }
+
+    // Closest-hit distances start at t_max for used lanes and -inf for unused
+    // ones, so unused lanes never report a hit
+    pub fn initial_t_max(&self, t_max: f64) -> [f64; MAX_LANES] {
+        let mut t = [f64::NEG_INFINITY; MAX_LANES];
+        for v in t.iter_mut().take(self.lanes) {
+            *v = t_max;
+        }
+        t
+    }
+
+    // Reciprocals of the lanes' directions along each axis, for first_hit_box
+    pub fn inv_dirs(&self) -> [[f64; MAX_LANES]; 3] {
+        [self.dx, self.dy, self.dz].map(|d| d.map(|v| 1.0 / v))
+    }
+
+    // First lane from `from` on crossing the box between t_min and its t_max:
+    // the slab test of Aabb::interval run on N lanes at once, at least the
+    // used ones, with selects for branches so it compiles to vector
+    // instructions
+    pub fn first_hit_box<const N: usize>(&self, inv: &[[f64; MAX_LANES]; 3], bbox: &Aabb, t_min: f64, t_max: &[f64; MAX_LANES], from: usize) -> Option<usize> {
+        let mut t0 = [t_min; N];
+        let mut t1 = [f64::NEG_INFINITY; N];
+        t1[..self.lanes].copy_from_slice(&t_max[..self.lanes]);
+
+        for (axis, origin) in [&self.ox, &self.oy, &self.oz].into_iter().enumerate() {
+            let (min, max) = (bbox.min[axis], bbox.max[axis]);
+            for k in 0..N {
+                let near = (min - origin[k]) * inv[axis][k];
+                let far = (max - origin[k]) * inv[axis][k];
+                let (near, far) = if near <= far { (near, far) } else { (far, near) };
+                t0[k] = if near > t0[k] { near } else { t0[k] };
+                t1[k] = if far < t1[k] { far } else { t1[k] };
+            }
+        }
+
+        (from..N).find(|&k| t0[k] <= t1[k])
+    }
+}
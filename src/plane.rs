@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Infinite plane through point, facing the side its normal points to. It
+// has no bounding box, so it is tested against every ray
+pub struct Plane {
+    normal: Vec3,
+    d: f64,
+    mat: Arc<dyn Scatter>
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, m: Arc<dyn Scatter>) -> Plane {
+        let normal = normal.normalized();
+
+        Plane {
+            normal,
+            d: normal.dot(point),
+            mat: m
+        }
+    }
+}
+
+impl Hit for Plane {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = self.normal.dot(r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(r.origin())) / denom;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        Some(HitRecord::new(r, t, self.normal, self.mat.clone()))
+    }
+}
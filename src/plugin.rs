@@ -0,0 +1,429 @@
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::ray::Ray;
+use super::registry;
+use super::sampler;
+use super::texture::Texture;
+use super::vec::{Color, Vec3};
+
+// Runtime plugins: shared libraries adding object, material and texture
+// types to scene files through a C ABI, so they can be built and shipped
+// apart from the renderer. A plugin exports
+//
+//     bool rt_plugin_init(const RtHost *host);
+//
+// which checks host->version and registers its factories with the host.
+// include/rt_plugin.h declares the same types for C. Plugins stay loaded
+// until the renderer exits, and their callbacks are called from every
+// render thread at once.
+//
+// Loading a plugin runs its code with the renderer's rights, so scenes can
+// only load them once allowed, by --allow-plugins or `allow`.
+
+pub const ABI_VERSION: u32 = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct RtVec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64
+}
+
+impl From<Vec3> for RtVec3 {
+    fn from(v: Vec3) -> RtVec3 {
+        RtVec3 { x: v.x(), y: v.y(), z: v.z() }
+    }
+}
+
+impl From<RtVec3> for Vec3 {
+    fn from(v: RtVec3) -> Vec3 {
+        Vec3::new(v.x, v.y, v.z)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct RtRay {
+    pub origin: RtVec3,
+    pub direction: RtVec3
+}
+
+impl From<&Ray> for RtRay {
+    fn from(r: &Ray) -> RtRay {
+        RtRay { origin: r.origin().into(), direction: r.direction().into() }
+    }
+}
+
+// Shapes fill in t and the outward normal; materials and textures get the
+// point, the normal facing the ray and whether the ray hit the front
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct RtHit {
+    pub t: f64,
+    pub point: RtVec3,
+    pub normal: RtVec3,
+    pub front_face: bool
+}
+
+impl From<&HitRecord> for RtHit {
+    fn from(rec: &HitRecord) -> RtHit {
+        RtHit { t: rec.t, point: rec.p.into(), normal: rec.normal.into(), front_face: rec.front_face }
+    }
+}
+
+// Attributes of the scene element a factory builds from
+#[repr(C)]
+pub struct RtAttributes {
+    pub names: *const *const c_char,
+    pub values: *const *const c_char,
+    pub count: usize
+}
+
+#[repr(C)]
+pub struct RtShape {
+    pub data: *mut c_void,
+    pub hit: extern "C" fn(data: *mut c_void, ray: *const RtRay, t_min: f64, t_max: f64, out: *mut RtHit) -> bool,
+    // Optional; unbounded shapes are tested against every ray
+    pub bounds: Option<extern "C" fn(data: *mut c_void, min: *mut RtVec3, max: *mut RtVec3) -> bool>,
+    pub destroy: Option<extern "C" fn(data: *mut c_void)>
+}
+
+#[repr(C)]
+pub struct RtMaterial {
+    pub data: *mut c_void,
+    pub scatter: extern "C" fn(data: *mut c_void, ray: *const RtRay, hit: *const RtHit, attenuation: *mut RtVec3, scattered: *mut RtRay) -> bool,
+    // Optional BRDF times cosine towards wi for direct lighting; materials
+    // without it are lit by their scattered rays only
+    pub eval: Option<extern "C" fn(data: *mut c_void, ray: *const RtRay, hit: *const RtHit, wi: *const RtVec3, out: *mut RtVec3) -> bool>,
+    // Optional radiance leaving the surface, which lights the others through
+    // the rays they scatter onto it
+    pub emitted: Option<extern "C" fn(data: *mut c_void, ray: *const RtRay, hit: *const RtHit, out: *mut RtVec3)>,
+    pub destroy: Option<extern "C" fn(data: *mut c_void)>
+}
+
+#[repr(C)]
+pub struct RtTexture {
+    pub data: *mut c_void,
+    pub value: extern "C" fn(data: *mut c_void, hit: *const RtHit, out: *mut RtVec3),
+    pub destroy: Option<extern "C" fn(data: *mut c_void)>
+}
+
+pub type RtShapeFactory = extern "C" fn(attributes: *const RtAttributes, out: *mut RtShape) -> bool;
+pub type RtMaterialFactory = extern "C" fn(attributes: *const RtAttributes, out: *mut RtMaterial) -> bool;
+pub type RtTextureFactory = extern "C" fn(attributes: *const RtAttributes, out: *mut RtTexture) -> bool;
+
+#[repr(C)]
+pub struct RtHost {
+    pub version: u32,
+    // Uniform number in [0, 1) from the sample's random stream, so plugin
+    // materials stay deterministic like the built-in ones
+    pub random: extern "C" fn() -> f64,
+    pub register_shape: extern "C" fn(name: *const c_char, factory: RtShapeFactory),
+    pub register_material: extern "C" fn(name: *const c_char, factory: RtMaterialFactory),
+    pub register_texture: extern "C" fn(name: *const c_char, factory: RtTextureFactory)
+}
+
+type InitFn = unsafe extern "C" fn(host: *const RtHost) -> bool;
+
+// Paths of the plugins already loaded, which aren't loaded again when a
+// scene is parsed once more
+static LOADED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+static ALLOWED: AtomicBool = AtomicBool::new(false);
+
+// Lets the scenes parsed from now on load plugins
+pub fn allow() {
+    ALLOWED.store(true, Ordering::Relaxed);
+}
+
+// Loads the plugin at path and registers its types
+pub fn load(path: &str) {
+    if !ALLOWED.load(Ordering::Relaxed) {
+        panic!("The scene loads the plugin {}, which needs --allow-plugins.", path);
+    }
+
+    let mut loaded = LOADED.lock().unwrap();
+    if loaded.iter().any(|p| p == path) {
+        return;
+    }
+
+    let init: InitFn = unsafe { dl::symbol(path, "rt_plugin_init") };
+    let host = RtHost {
+        version: ABI_VERSION,
+        random: host_random,
+        register_shape: host_register_shape,
+        register_material: host_register_material,
+        register_texture: host_register_texture
+    };
+    if !unsafe { init(&host) } {
+        panic!("Failed to initialize the plugin {}.", path);
+    }
+
+    loaded.push(path.to_string());
+}
+
+extern "C" fn host_random() -> f64 {
+    sampler::rng().gen()
+}
+
+fn type_name(name: *const c_char) -> String {
+    unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned()
+}
+
+// Calls a factory with the attributes of node, panicking if it fails
+fn build<T>(node: &roxmltree::Node, kind: &str, factory: extern "C" fn(*const RtAttributes, *mut T) -> bool) -> T {
+    let names: Vec<CString> = node.attributes().iter().map(|a| CString::new(a.name()).unwrap()).collect();
+    let values: Vec<CString> = node.attributes().iter().map(|a| CString::new(a.value()).unwrap()).collect();
+    let name_ptrs: Vec<*const c_char> = names.iter().map(|s| s.as_ptr()).collect();
+    let value_ptrs: Vec<*const c_char> = values.iter().map(|s| s.as_ptr()).collect();
+
+    let attributes = RtAttributes { names: name_ptrs.as_ptr(), values: value_ptrs.as_ptr(), count: names.len() };
+    let mut out = std::mem::MaybeUninit::<T>::uninit();
+    if !factory(&attributes, out.as_mut_ptr()) {
+        panic!("The plugin failed to create the {} '{}'.", kind, node.attribute("type").unwrap_or(""));
+    }
+
+    // SAFETY: the ABI requires a factory returning true to have written
+    // every field of out, with non-null callbacks where they aren't
+    // optional. Nothing can check that here: a plugin breaking it is
+    // undefined behavior, like any other bug in native code it runs.
+    unsafe { out.assume_init() }
+}
+
+extern "C" fn host_register_shape(name: *const c_char, factory: RtShapeFactory) {
+    registry::register_shape(&type_name(name), move |node, mat| {
+        Box::new(PluginShape { raw: build(node, "object", factory), mat })
+    });
+}
+
+extern "C" fn host_register_material(name: *const c_char, factory: RtMaterialFactory) {
+    registry::register_material(&type_name(name), move |node| {
+        Arc::new(PluginMaterial { raw: build(node, "material", factory) })
+    });
+}
+
+extern "C" fn host_register_texture(name: *const c_char, factory: RtTextureFactory) {
+    registry::register_texture(&type_name(name), move |node| {
+        Arc::new(PluginTexture { raw: build(node, "texture", factory) })
+    });
+}
+
+struct PluginShape {
+    raw: RtShape,
+    mat: Arc<dyn Scatter>
+}
+
+struct PluginMaterial {
+    raw: RtMaterial
+}
+
+struct PluginTexture {
+    raw: RtTexture
+}
+
+// SAFETY: the ABI's contract for every object a factory creates, which the
+// plugin must uphold and the host can't check:
+// - its callbacks can be called from several threads at once with the same
+//   data, so data is only read by them or synchronized by the plugin;
+// - data stays valid until destroy, called once from any thread when the
+//   scene is dropped, and never after it;
+// - the pointers given to a callback are only valid during the call.
+unsafe impl Send for PluginShape { }
+unsafe impl Sync for PluginShape { }
+unsafe impl Send for PluginMaterial { }
+unsafe impl Sync for PluginMaterial { }
+unsafe impl Send for PluginTexture { }
+unsafe impl Sync for PluginTexture { }
+
+impl Drop for PluginShape {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.raw.destroy {
+            destroy(self.raw.data);
+        }
+    }
+}
+
+impl Drop for PluginMaterial {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.raw.destroy {
+            destroy(self.raw.data);
+        }
+    }
+}
+
+impl Drop for PluginTexture {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.raw.destroy {
+            destroy(self.raw.data);
+        }
+    }
+}
+
+impl Hit for PluginShape {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut out = RtHit::default();
+        if !(self.raw.hit)(self.raw.data, &r.into(), t_min, t_max, &mut out) {
+            return None;
+        }
+
+        Some(HitRecord::new(r, out.t, Vec3::from(out.normal).normalized(), self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bounds = self.raw.bounds?;
+        let (mut min, mut max) = (RtVec3::default(), RtVec3::default());
+        if bounds(self.raw.data, &mut min, &mut max) {
+            Some(Aabb::new(min.into(), max.into()))
+        } else {
+            None
+        }
+    }
+}
+
+impl Scatter for PluginMaterial {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Color, Ray)> {
+        let (mut attenuation, mut scattered) = (RtVec3::default(), RtRay::default());
+        if !(self.raw.scatter)(self.raw.data, &r_in.into(), &rec.into(), &mut attenuation, &mut scattered) {
+            return None;
+        }
+
+        Some((attenuation.into(), Ray::with_time(scattered.origin.into(), scattered.direction.into(), r_in.time())))
+    }
+
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: Vec3) -> Option<Color> {
+        let eval = self.raw.eval?;
+        let mut out = RtVec3::default();
+        if eval(self.raw.data, &r_in.into(), &rec.into(), &wi.into(), &mut out) {
+            Some(out.into())
+        } else {
+            None
+        }
+    }
+
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        let mut out = RtVec3::default();
+        if let Some(emitted) = self.raw.emitted {
+            emitted(self.raw.data, &r_in.into(), &rec.into(), &mut out);
+        }
+
+        out.into()
+    }
+}
+
+impl Texture for PluginTexture {
+    fn value(&self, rec: &HitRecord) -> Color {
+        let mut out = RtVec3::default();
+        (self.raw.value)(self.raw.data, &rec.into(), &mut out);
+
+        out.into()
+    }
+}
+
+#[cfg(unix)]
+mod dl {
+    use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+    const RTLD_NOW: c_int = 2;
+
+    #[link(name = "dl")]
+    extern "C" {
+        fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlerror() -> *mut c_char;
+    }
+
+    fn error() -> String {
+        let message = unsafe { dlerror() };
+        if message.is_null() {
+            "unknown error".to_string()
+        } else {
+            unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+        }
+    }
+
+    // Opens the library at path, never to close it, and looks up the
+    // function `name`, which must have the type T
+    pub unsafe fn symbol<T: Copy>(path: &str, name: &str) -> T {
+        let filename = CString::new(path).expect("Failed to parse plugin path.");
+        let handle = dlopen(filename.as_ptr(), RTLD_NOW);
+        if handle.is_null() {
+            panic!("Failed to load the plugin {}: {}", path, error());
+        }
+
+        let symbol = CString::new(name).unwrap();
+        let address = dlsym(handle, symbol.as_ptr());
+        if address.is_null() {
+            panic!("The plugin {} doesn't export {}: {}", path, name, error());
+        }
+
+        std::mem::transmute_copy(&address)
+    }
+}
+
+#[cfg(not(unix))]
+mod dl {
+    pub unsafe fn symbol<T: Copy>(path: &str, _name: &str) -> T {
+        panic!("Failed to load the plugin {}: plugins are only supported on Unix.", path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+    use crate::cli::Options;
+    use crate::ray::RayKind;
+    use crate::render;
+    use crate::vec::Point3;
+
+    extern "C" fn glow_scatter(_data: *mut c_void, _ray: *const RtRay, _hit: *const RtHit, _attenuation: *mut RtVec3, _scattered: *mut RtRay) -> bool {
+        false
+    }
+
+    extern "C" fn glow_emitted(_data: *mut c_void, _ray: *const RtRay, _hit: *const RtHit, out: *mut RtVec3) {
+        unsafe { *out = RtVec3 { x: 4.0, y: 4.0, z: 4.0 } };
+    }
+
+    // Material glowing the same everywhere, built through the ABI like the
+    // materials of a loaded plugin
+    extern "C" fn glow(_attributes: *const RtAttributes, out: *mut RtMaterial) -> bool {
+        let material = RtMaterial { data: ptr::null_mut(), scatter: glow_scatter, eval: None, emitted: Some(glow_emitted), destroy: None };
+        unsafe { out.write(material) };
+        true
+    }
+
+    // Diffuse ground under a glowing ceiling, with no lights and a black sky
+    const XML: &str = r#"<RT>
+        <camera look_from="0 1 3" look_at="0 0 0" up="0 1 0" aperture="0"/>
+        <environment><layer type="constant" color="0 0 0"/></environment>
+        <material type="lambertian" color="0.5 0.5 0.5"/>
+        <object type="plane" point="0 0 0" normal="0 1 0"/>
+        <material type="test_glow"/>
+        <object type="quad" corner="-5 2 -5" u="10 0 0" v="0 0 10"/>
+    </RT>"#;
+
+    #[test]
+    fn emissive_materials_light_diffuse_surfaces() {
+        host_register_material(c"test_glow".as_ptr(), glow);
+        let (_, scene) = crate::xml_parser(XML);
+        let args = ["--spp", "1"].map(str::to_string);
+        let settings = Options::parse(args.into_iter()).settings(&scene);
+
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for _ in 0..200 {
+            sum += render::trace(&r, &scene, &settings, settings.max_depth, RayKind::Camera);
+        }
+        // Most of the sky above the ground is the ceiling
+        assert!(sum.y() / 200.0 > 0.5, "The ceiling doesn't light the ground: {:?}", sum / 200.0);
+    }
+}
@@ -0,0 +1,259 @@
+use super::mesh::{Face, MeshData};
+use super::vec::{Point3, Vec3};
+
+// Stanford PLY reader, ASCII or binary of either byte order, for the
+// vertices, vertex normals and faces of a mesh. Faces are split into
+// triangles fanning out from their first corner like OBJ faces, and other
+// elements and properties, such as colors, are skipped.
+pub fn load(filename: &str) -> MeshData {
+    let bytes = std::fs::read(filename)
+        .unwrap_or_else(|_| panic!("Unable to read PLY file {}.", filename));
+
+    let data = parse(&bytes).unwrap_or_else(|| panic!("Failed to parse PLY file {}.", filename));
+    if data.faces.is_empty() {
+        panic!("No faces found in PLY file {}.", filename);
+    }
+    data
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8
+        }
+    }
+}
+
+enum Property {
+    Scalar(Scalar, String),
+    // Count type, item type and name
+    List(Scalar, Scalar, String)
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>
+}
+
+// Values of the body in the order the header lays them out
+enum Body<'a> {
+    Ascii(std::str::SplitWhitespace<'a>),
+    Binary { bytes: &'a [u8], pos: usize, big_endian: bool }
+}
+
+impl Body<'_> {
+    fn next(&mut self, ty: Scalar) -> Option<f64> {
+        match self {
+            Body::Ascii(tokens) => tokens.next()?.parse().ok(),
+            Body::Binary { bytes, pos, big_endian } => {
+                let mut raw = [0u8; 8];
+                let size = ty.size();
+                raw[..size].copy_from_slice(bytes.get(*pos..*pos + size)?);
+                *pos += size;
+                if *big_endian {
+                    raw[..size].reverse();
+                }
+
+                let value = match ty {
+                    Scalar::I8 => raw[0] as i8 as f64,
+                    Scalar::U8 => raw[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    Scalar::U32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    Scalar::F32 => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    Scalar::F64 => f64::from_le_bytes(raw)
+                };
+                Some(value)
+            }
+        }
+    }
+}
+
+fn parse(bytes: &[u8]) -> Option<MeshData> {
+    let end = bytes.windows(10).position(|w| w == b"end_header")?;
+    let body_start = end + bytes[end..].iter().position(|&b| b == b'\n')? + 1;
+    let header = std::str::from_utf8(&bytes[..end]).ok()?;
+
+    let mut lines = header.lines();
+    if lines.next()?.trim() != "ply" {
+        return None;
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", name, _] => format = Some(*name),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().ok()?,
+                properties: Vec::new()
+            }),
+            ["property", "list", count, item, name] => elements.last_mut()?.properties.push(
+                Property::List(Scalar::from_name(count)?, Scalar::from_name(item)?, name.to_string())
+            ),
+            ["property", ty, name] => elements.last_mut()?.properties.push(
+                Property::Scalar(Scalar::from_name(ty)?, name.to_string())
+            ),
+            _ => { }
+        }
+    }
+
+    let mut body = match format? {
+        "ascii" => Body::Ascii(std::str::from_utf8(&bytes[body_start..]).ok()?.split_whitespace()),
+        "binary_little_endian" => Body::Binary { bytes: &bytes[body_start..], pos: 0, big_endian: false },
+        "binary_big_endian" => Body::Binary { bytes: &bytes[body_start..], pos: 0, big_endian: true },
+        _ => return None
+    };
+
+    let mut data = MeshData::default();
+    let mut has_normals = false;
+    for element in &elements {
+        let has = |name: &str| element.properties.iter().any(|p| matches!(p, Property::Scalar(_, n) if n == name));
+        let vertices = element.name == "vertex";
+        if vertices {
+            has_normals = has("nx") && has("ny") && has("nz");
+        }
+
+        for _ in 0..element.count {
+            let mut position = [0.0; 3];
+            let mut normal = [0.0; 3];
+            for property in &element.properties {
+                match property {
+                    Property::Scalar(ty, name) => {
+                        let value = body.next(*ty)?;
+                        if vertices {
+                            match name.as_str() {
+                                "x" => position[0] = value,
+                                "y" => position[1] = value,
+                                "z" => position[2] = value,
+                                "nx" => normal[0] = value,
+                                "ny" => normal[1] = value,
+                                "nz" => normal[2] = value,
+                                _ => { }
+                            }
+                        }
+                    },
+                    Property::List(count, item, name) => {
+                        let count = body.next(*count)? as usize;
+                        let values = (0..count).map(|_| body.next(*item)).collect::<Option<Vec<f64>>>()?;
+
+                        let indices = element.name == "face" && (name == "vertex_indices" || name == "vertex_index");
+                        if indices && count >= 3 {
+                            if values.iter().any(|&v| v < 0.0) {
+                                return None;
+                            }
+                            for k in 1..count - 1 {
+                                let corners = [values[0], values[k], values[k + 1]].map(|v| v as usize);
+                                data.faces.push(Face { vertices: corners, normals: None });
+                            }
+                        }
+                    }
+                }
+            }
+
+            if vertices {
+                data.positions.push(Point3::new(position[0], position[1], position[2]));
+                if has_normals {
+                    data.normals.push(Vec3::new(normal[0], normal[1], normal[2]));
+                }
+            }
+        }
+    }
+
+    // The normals are per vertex, so every corner uses its vertex's
+    let count = data.positions.len();
+    for face in data.faces.iter_mut() {
+        if face.vertices.iter().any(|&v| v >= count) {
+            return None;
+        }
+        if has_normals {
+            face.normals = Some(face.vertices);
+        }
+    }
+
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn square_header(format: &str) -> String {
+        format!("ply\nformat {} 1.0\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+                 element face 1\nproperty list uchar int vertex_indices\nend_header\n", format)
+    }
+
+    #[test]
+    fn loads_ascii() {
+        let data = load(&fixtures::path("square.ply"));
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.positions[2], Point3::new(1.0, 1.0, 0.0));
+        assert_eq!(data.normals, vec![Vec3::new(0.0, 0.0, 1.0); 4]);
+
+        let faces: Vec<_> = data.faces.iter().map(|f| (f.vertices, f.normals)).collect();
+        assert_eq!(faces, [([0, 1, 2], Some([0, 1, 2])), ([0, 2, 3], Some([0, 2, 3]))]);
+    }
+
+    #[test]
+    fn loads_binary_of_either_byte_order() {
+        let corners = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        for (format, big_endian) in [("binary_little_endian", false), ("binary_big_endian", true)] {
+            let mut bytes = square_header(format).into_bytes();
+            let float = |x: f32| if big_endian { x.to_be_bytes() } else { x.to_le_bytes() };
+            let int = |i: i32| if big_endian { i.to_be_bytes() } else { i.to_le_bytes() };
+            for corner in corners {
+                corner.iter().for_each(|&x| bytes.extend_from_slice(&float(x)));
+            }
+            bytes.push(4);
+            (0..4).for_each(|i| bytes.extend_from_slice(&int(i)));
+
+            let data = parse(&bytes).unwrap_or_else(|| panic!("Failed to parse {}.", format));
+            assert_eq!(data.positions[2], Point3::new(1.0, 1.0, 0.0));
+            assert!(data.normals.is_empty());
+            assert_eq!(data.faces.len(), 2);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_bodies_and_bad_indices() {
+        let mut bytes = square_header("ascii").into_bytes();
+        bytes.extend_from_slice(b"0 0 0\n1 0 0\n1 1 0\n0 1 0\n4 0 1 2");
+        assert!(parse(&bytes).is_none());
+        bytes.extend_from_slice(b" 4\n");
+        assert!(parse(&bytes).is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+
+// 8-bit RGB and RGBA PNG encoder. Image data is stored in uncompressed deflate
+// blocks, which every decoder reads, trading file size for not needing a
+// compressor.
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+// Zlib stream of stored blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(65535).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(if blocks.peek().is_none() { 1 } else { 0 });
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+// `rgb` holds three bytes per pixel, top row first. Each keyword and text
+// pair of `text` is stored in a tEXt chunk, which only holds Latin-1, so
+// other characters are replaced with '?'. An "Orientation" entry from 1 to
+// 8 is also stored as the EXIF orientation of the image, in an eXIf chunk,
+// and a "PixelAspect" one as the pixel shape of a pHYs chunk.
+pub fn encode(width: usize, height: usize, rgb: &[u8], text: &[(String, String)]) -> Vec<u8> {
+    encode_pixels(width, height, 3, rgb, text)
+}
+
+// Same as encode with `rgba` holding four bytes per pixel, the last one the
+// alpha that the color isn't premultiplied by
+pub fn encode_rgba(width: usize, height: usize, rgba: &[u8], text: &[(String, String)]) -> Vec<u8> {
+    encode_pixels(width, height, 4, rgba, text)
+}
+
+// Big-endian TIFF header and a single IFD holding the orientation tag
+fn exif_orientation(orientation: u16) -> Vec<u8> {
+    let mut exif = b"MM\0\x2a".to_vec();
+    exif.extend_from_slice(&8u32.to_be_bytes());
+    exif.extend_from_slice(&1u16.to_be_bytes());
+    // Tag 0x0112, SHORT, one value padded to four bytes
+    exif.extend_from_slice(&0x0112u16.to_be_bytes());
+    exif.extend_from_slice(&3u16.to_be_bytes());
+    exif.extend_from_slice(&1u32.to_be_bytes());
+    exif.extend_from_slice(&orientation.to_be_bytes());
+    exif.extend_from_slice(&[0, 0]);
+    // No next IFD
+    exif.extend_from_slice(&0u32.to_be_bytes());
+    exif
+}
+
+fn encode_pixels(width: usize, height: usize, channels: usize, data: &[u8], text: &[(String, String)]) -> Vec<u8> {
+    assert_eq!(data.len(), width * height * channels, "PNG image has the wrong size");
+
+    let mut out = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // 8 bits per channel, truecolor with or without alpha, deflate, adaptive
+    // filtering, no interlace
+    let color_type = if channels == 4 { 6 } else { 2 };
+    header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+    chunk(&mut out, b"IHDR", &header);
+
+    for (keyword, value) in text {
+        let latin1 = |s: &str| s.chars().map(|c| if (c as u32) < 256 { c as u8 } else { b'?' }).collect::<Vec<u8>>();
+        let mut data = latin1(keyword);
+        data.push(0);
+        data.extend(latin1(value));
+        chunk(&mut out, b"tEXt", &data);
+    }
+
+    let orientation = text.iter()
+        .find(|(keyword, _)| keyword == "Orientation")
+        .and_then(|(_, value)| value.parse::<u16>().ok())
+        .filter(|o| (1..=8).contains(o));
+    if let Some(orientation) = orientation {
+        chunk(&mut out, b"eXIf", &exif_orientation(orientation));
+    }
+
+    // Pixels per unit along x and y, of no unit, so only their ratio counts
+    let pixel_aspect = text.iter()
+        .find(|(keyword, _)| keyword == "PixelAspect")
+        .and_then(|(_, value)| value.parse::<f64>().ok())
+        .filter(|&a| a > 0.0 && a.is_finite());
+    if let Some(aspect) = pixel_aspect {
+        let (x, y) = if aspect >= 1.0 { (100000.0 / aspect, 100000.0) } else { (100000.0, 100000.0 * aspect) };
+        let mut phys = Vec::with_capacity(9);
+        phys.extend_from_slice(&(x.round().max(1.0) as u32).to_be_bytes());
+        phys.extend_from_slice(&(y.round().max(1.0) as u32).to_be_bytes());
+        phys.push(0);
+        chunk(&mut out, b"pHYs", &phys);
+    }
+
+    // Every row starts with filter type 0 (none)
+    let mut raw = Vec::with_capacity(height * (width * channels + 1));
+    for row in data.chunks(width * channels) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+    chunk(&mut out, b"IEND", &[]);
+
+    out
+}
+
+pub fn write(path: &str, width: usize, height: usize, rgb: &[u8], text: &[(String, String)]) -> std::io::Result<()> {
+    fs::write(path, encode(width, height, rgb, text))
+}
+
+pub fn write_rgba(path: &str, width: usize, height: usize, rgba: &[u8], text: &[(String, String)]) -> std::io::Result<()> {
+    fs::write(path, encode_rgba(width, height, rgba, text))
+}
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Vec3, Point3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Parallelogram spanned by the edges u and v from corner q
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    normal: Vec3,
+    d: f64,
+    mat: Arc<dyn Scatter>
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, m: Arc<dyn Scatter>) -> Quad {
+        let n = u.cross(v);
+        let normal = n.normalized();
+
+        Quad {
+            q,
+            u,
+            v,
+            w: n / n.dot(n),
+            normal,
+            d: normal.dot(q),
+            mat: m
+        }
+    }
+}
+
+impl Hit for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denom = self.normal.dot(r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(r.origin())) / denom;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        // Planar coordinates of the hit point along u and v
+        let planar = r.at(t) - self.q;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(HitRecord::new(r, t, self.normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let far = self.q + self.u + self.v;
+        let diagonal = Aabb::new(self.q, far).surrounding(Aabb::new(self.q + self.u, self.q + self.v));
+        Some(diagonal.padded(1e-4))
+    }
+
+    // u and v run along the edges of the same name
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        Some(HitRecord::facing(self.q + u * self.u + v * self.v, self.normal, self.mat.clone()))
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Quadric surface, the points x of x^T Q x = 0 for a symmetric 4x4 matrix Q
+// and x = (x, y, z, 1), whose inside is where x^T Q x < 0. Ellipsoids,
+// paraboloids, hyperboloids, cones and cylinders are all quadrics; the
+// unbounded ones stop at the sides of a box.
+pub struct Quadric {
+    q: [[f64; 4]; 4],
+    bounds: Aabb,
+    mat: Arc<dyn Scatter>
+}
+
+impl Quadric {
+    // The symmetric part of q, which is all x^T Q x depends on
+    pub fn new(q: [[f64; 4]; 4], bounds: Aabb, m: Arc<dyn Scatter>) -> Quadric {
+        let mut symmetric = [[0.0; 4]; 4];
+        for (i, row) in symmetric.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = 0.5 * (q[i][j] + q[j][i]);
+            }
+        }
+
+        Quadric { q: symmetric, bounds, mat: m }
+    }
+
+    // ((x - cx) / rx)^2 + ((y - cy) / ry)^2 + ((z - cz) / rz)^2 = 1
+    pub fn ellipsoid(center: Point3, radii: Vec3, m: Arc<dyn Scatter>) -> Quadric {
+        let mut q = [[0.0; 4]; 4];
+        q[3][3] = -1.0;
+        for k in 0..3 {
+            let inv = 1.0 / (radii[k] * radii[k]);
+            q[k][k] = inv;
+            q[k][3] = -center[k] * inv;
+            q[3][k] = -center[k] * inv;
+            q[3][3] += center[k] * center[k] * inv;
+        }
+
+        let extent = Vec3::new(radii.x().abs(), radii.y().abs(), radii.z().abs());
+        Quadric::new(q, Aabb::new(center - extent, center + extent), m)
+    }
+
+    // a^T Q b for homogeneous a and b
+    fn form(&self, a: [f64; 4], b: [f64; 4]) -> f64 {
+        (0..4).map(|i| a[i] * (0..4).map(|j| self.q[i][j] * b[j]).sum::<f64>()).sum()
+    }
+
+    fn inside_bounds(&self, p: Point3) -> bool {
+        let slack = 1e-9 * self.bounds.extent().length();
+        (0..3).all(|k| p[k] >= self.bounds.min[k] - slack && p[k] <= self.bounds.max[k] + slack)
+    }
+}
+
+impl Hit for Quadric {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin(), r.direction());
+        let o = [o.x(), o.y(), o.z(), 1.0];
+        let d = [d.x(), d.y(), d.z(), 0.0];
+
+        // (o + t d)^T Q (o + t d) = a t^2 + 2 half_b t + c
+        let a = self.form(d, d);
+        let half_b = self.form(d, o);
+        let c = self.form(o, o);
+
+        let roots = if a.abs() < 1e-12 {
+            // Rays along a paraboloid's axis or a cylinder's sides cross the
+            // surface at most once
+            if half_b.abs() < 1e-12 { vec![] } else { vec![-c / (2.0 * half_b)] }
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let sqrtd = discriminant.sqrt();
+            let (t0, t1) = ((-half_b - sqrtd) / a, (-half_b + sqrtd) / a);
+            vec![t0.min(t1), t0.max(t1)]
+        };
+
+        let t = roots.into_iter()
+            .find(|&t| t >= t_min && t <= t_max && self.inside_bounds(r.at(t)))?;
+
+        // The gradient of x^T Q x, which points out of the inside. It only
+        // vanishes at singular points like the apex of a cone, where the
+        // surface is taken to face the ray.
+        let p = r.at(t);
+        let x = [p.x(), p.y(), p.z(), 1.0];
+        let row = |i: usize| (0..4).map(|j| self.q[i][j] * x[j]).sum::<f64>();
+        let gradient = Vec3::new(row(0), row(1), row(2));
+        let outward_normal = if gradient.near_zero() {
+            (-1.0) * r.direction().normalized()
+        } else {
+            gradient.normalized()
+        };
+
+        Some(HitRecord::new(r, t, outward_normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds.padded(1e-4))
+    }
+}
@@ -2,17 +2,32 @@ use super::vec::{Vec3, Point3};
 
 pub struct Ray {
     orig: Point3,
-    dir: Vec3
+    dir: Vec3,
+    // Instant within the shutter interval the ray is traced at, which moving
+    // objects are placed at
+    tm: f64,
+    ty: RayType
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Ray {
+        Ray::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point3, direction: Vec3, time: f64) -> Ray {
         Ray {
             orig: origin,
-            dir: direction
+            dir: direction,
+            tm: time,
+            ty: RayType::Camera
         }
     }
 
+    pub fn with_type(mut self, ty: RayType) -> Ray {
+        self.ty = ty;
+        self
+    }
+
     pub fn origin(&self) -> Point3 {
         self.orig
     }
@@ -21,7 +36,51 @@ impl Ray {
         self.dir
     }
 
+    pub fn time(&self) -> f64 {
+        self.tm
+    }
+
+    pub fn ray_type(&self) -> RayType {
+        self.ty
+    }
+
     pub fn at(&self, t: f64) -> Point3 {
         self.orig + t * self.dir
     }
-}
\ No newline at end of file
+}
+
+// How a ray was generated, which decides what parts of the environment and
+// which emitters it sees
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RayKind {
+    Camera,
+    // Bounced off a material whose direct lighting was sampled
+    Diffuse,
+    // Bounced off a reflecting or refracting material
+    Specular
+}
+
+// What a ray was traced for, which materials can respond to: camera rays are
+// the first ones of a path, shadow rays test the visibility of lights, and
+// scattered rays are typed by how the material sent them on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RayType {
+    Camera,
+    Shadow,
+    Diffuse,
+    Glossy,
+    Transmission
+}
+
+impl RayType {
+    pub fn from_name(name: &str) -> Option<RayType> {
+        match name {
+            "camera" => Some(RayType::Camera),
+            "shadow" => Some(RayType::Shadow),
+            "diffuse" => Some(RayType::Diffuse),
+            "glossy" => Some(RayType::Glossy),
+            "transmission" => Some(RayType::Transmission),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::sync::{Arc, RwLock};
+
+use super::hit::Hit;
+use super::material::Scatter;
+use super::texture::Texture;
+
+// Extension point for crates embedding the renderer: material, object and
+// texture types registered here can be used in scene files like the built-in ones.
+// The XML parser looks a type up here when it isn't one of its own, so
+// registered types can't replace a built-in type of the same name.
+//
+//     rt::registry::register_material("checker", |node| Arc::new(Checker::parse(node)));
+//     rt::registry::register_shape("gear", |node, mat| Box::new(Gear::parse(node, mat)));
+//     rt::run();
+//
+// Plugins loaded at runtime register their types here too, see plugin.rs.
+
+// Builds the material of a <material type="..."> element
+pub type MaterialParser = dyn Fn(&roxmltree::Node) -> Arc<dyn Scatter> + Send + Sync;
+
+// Builds the object of an <object type="..."> element, given the last
+// material defined before it
+pub type ShapeParser = dyn Fn(&roxmltree::Node, Arc<dyn Scatter>) -> Box<dyn Hit> + Send + Sync;
+
+// Builds the texture of a <texture type="..."> element
+pub type TextureParser = dyn Fn(&roxmltree::Node) -> Arc<dyn Texture> + Send + Sync;
+
+static MATERIALS: RwLock<Vec<(String, Arc<MaterialParser>)>> = RwLock::new(Vec::new());
+static SHAPES: RwLock<Vec<(String, Arc<ShapeParser>)>> = RwLock::new(Vec::new());
+static TEXTURES: RwLock<Vec<(String, Arc<TextureParser>)>> = RwLock::new(Vec::new());
+
+fn register<T: ?Sized>(table: &RwLock<Vec<(String, Arc<T>)>>, name: &str, parser: Arc<T>) {
+    let mut table = table.write().unwrap();
+    match table.iter_mut().find(|(n, _)| n == name) {
+        Some(entry) => entry.1 = parser,
+        None => table.push((name.to_string(), parser))
+    }
+}
+
+fn lookup<T: ?Sized>(table: &RwLock<Vec<(String, Arc<T>)>>, name: &str) -> Option<Arc<T>> {
+    table.read().unwrap().iter().find(|(n, _)| n == name).map(|(_, parser)| parser.clone())
+}
+
+// Registering a name again replaces its parser
+pub fn register_material(name: &str, parser: impl Fn(&roxmltree::Node) -> Arc<dyn Scatter> + Send + Sync + 'static) {
+    register(&MATERIALS, name, Arc::new(parser) as Arc<MaterialParser>);
+}
+
+pub fn register_shape(name: &str, parser: impl Fn(&roxmltree::Node, Arc<dyn Scatter>) -> Box<dyn Hit> + Send + Sync + 'static) {
+    register(&SHAPES, name, Arc::new(parser) as Arc<ShapeParser>);
+}
+
+pub fn register_texture(name: &str, parser: impl Fn(&roxmltree::Node) -> Arc<dyn Texture> + Send + Sync + 'static) {
+    register(&TEXTURES, name, Arc::new(parser) as Arc<TextureParser>);
+}
+
+pub fn material(name: &str) -> Option<Arc<MaterialParser>> {
+    lookup(&MATERIALS, name)
+}
+
+pub fn shape(name: &str) -> Option<Arc<ShapeParser>> {
+    lookup(&SHAPES, name)
+}
+
+pub fn texture(name: &str) -> Option<Arc<TextureParser>> {
+    lookup(&TEXTURES, name)
+}
@@ -0,0 +1,660 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rand::prelude::*;
+
+use super::bucket::Bucket;
+use super::camera::Camera;
+use super::denoise;
+use super::hit::{Hit, HitRecord};
+use super::material;
+use super::packet::{RayPacket, MAX_LANES};
+use super::ray::{Ray, RayKind, RayType};
+use super::sampler;
+use super::scene::Scene;
+use super::subsurface;
+use super::tiles::{self, TileOrder};
+use super::vec::{Color, Vec3};
+use super::wavefront;
+
+// Most transparent surfaces a shadow ray passes through before it is taken
+// to be blocked
+const MAX_TRANSPARENT: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Integrator {
+    // One recursive path per camera sample
+    Path,
+    // Batched per-bounce queues, see wavefront.rs
+    Wavefront,
+    // Emission and sampled lights at the first hit, plus whatever one
+    // scattered ray sees directly: a quick preview with no indirect light
+    Direct,
+    // Debug views of the first hit: ambient occlusion within `distance`,
+    // normals mapped to colors, and depth up to `distance` mapped to gray
+    Ao,
+    Normals,
+    Depth
+}
+
+impl Integrator {
+    pub fn from_name(name: &str) -> Option<Integrator> {
+        match name {
+            "path" => Some(Integrator::Path),
+            "wavefront" => Some(Integrator::Wavefront),
+            "direct" => Some(Integrator::Direct),
+            "ao" => Some(Integrator::Ao),
+            "normals" => Some(Integrator::Normals),
+            "depth" => Some(Integrator::Depth),
+            _ => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Integrator::Path => "path",
+            Integrator::Wavefront => "wavefront",
+            Integrator::Direct => "direct",
+            Integrator::Ao => "ao",
+            Integrator::Normals => "normals",
+            Integrator::Depth => "depth"
+        }
+    }
+
+    fn default_distance(self) -> f64 {
+        match self {
+            Integrator::Depth => 20.0,
+            _ => 1.0
+        }
+    }
+}
+
+// Integrator set up by a scene's <integrator> element; command line flags
+// take precedence over each value
+#[derive(Clone, Copy, Default)]
+pub struct IntegratorParams {
+    pub integrator: Option<Integrator>,
+    pub max_depth: Option<u64>,
+    // Bounces after which paths are terminated at random, None to never
+    pub rr_start: Option<u64>,
+    pub distance: Option<f64>
+}
+
+impl IntegratorParams {
+    pub fn distance(&self, integrator: Integrator) -> f64 {
+        self.distance.unwrap_or(integrator.default_distance())
+    }
+}
+
+// Mirroring of the written image, for tools that expect rows or columns in
+// the opposite order
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flip {
+    pub horizontal: bool,
+    pub vertical: bool
+}
+
+impl Flip {
+    pub fn from_name(name: &str) -> Option<Flip> {
+        match name {
+            "none" => Some(Flip { horizontal: false, vertical: false }),
+            "horizontal" => Some(Flip { horizontal: true, vertical: false }),
+            "vertical" => Some(Flip { horizontal: false, vertical: true }),
+            "both" => Some(Flip { horizontal: true, vertical: true }),
+            _ => None
+        }
+    }
+
+    pub fn is_none(self) -> bool {
+        !self.horizontal && !self.vertical
+    }
+
+    // The EXIF orientation of the stored pixels, which tells viewers how to
+    // undo the flip: 2 mirrored left to right, 3 turned half a turn, 4
+    // mirrored top to bottom
+    pub fn orientation(self) -> u16 {
+        match (self.horizontal, self.vertical) {
+            (false, false) => 1,
+            (true, false) => 2,
+            (true, true) => 3,
+            (false, true) => 4
+        }
+    }
+
+    // Rows of `width` pixels, top row first, mirrored
+    pub fn apply<T: Copy>(self, pixels: &[T], width: usize) -> Vec<T> {
+        let height = pixels.len() / width.max(1);
+        (0..pixels.len()).map(|n| {
+            let (i, j) = (n % width, n / width);
+            let i = if self.horizontal { width - 1 - i } else { i };
+            let j = if self.vertical { height - 1 - j } else { j };
+            pixels[j * width + i]
+        }).collect()
+    }
+}
+
+#[derive(Clone)]
+pub struct RenderSettings {
+    // Size of the rendered buffer, including the overscan border
+    pub image_width: u64,
+    pub image_height: u64,
+    // Pixels rendered beyond every edge of the image, for filters that need
+    // the surroundings of the edge pixels
+    pub overscan: u64,
+    // Part of the image rendered and written, all of it when None
+    pub bucket: Option<Bucket>,
+    // Mirroring of every image written, see Flip
+    pub flip: Flip,
+    // Width of a pixel over its height, which the camera already allows
+    // for; only written into the images for viewers to stretch them
+    pub pixel_aspect: f64,
+    pub samples_per_pixel: u64,
+    // Index of the first of them, for passes adding to earlier ones
+    pub first_sample: u64,
+    pub max_depth: u64,
+    // Largest allowed per-sample channel value, 0 to disable
+    pub clamp: f64,
+    pub denoise: bool,
+    pub integrator: Integrator,
+    // Bounces after which paths are terminated at random, see roulette
+    pub rr_start: Option<u64>,
+    // Range of the AO and depth debug integrators
+    pub distance: f64,
+    // Camera rays traced together per packet, 0 or 1 for single rays
+    pub packet_size: usize,
+    // Order the image is rendered in, see tiles.rs
+    pub tile_order: TileOrder,
+    // Select the random sample streams, see sampler.rs
+    pub seed: u64,
+    pub frame: u64,
+    // Counts the scanlines finished so far, for callers following the render
+    pub progress: Option<Arc<AtomicU64>>
+}
+
+impl RenderSettings {
+    // Size of the image without the overscan border
+    pub fn display_size(&self) -> (u64, u64) {
+        (self.image_width - 2 * self.overscan, self.image_height - 2 * self.overscan)
+    }
+
+    // Film coordinates of the point (x, y) within pixel (i, j), with j
+    // counted from the bottom row; the overscan border lies outside [0, 1]
+    pub fn film_coords(&self, i: u64, j: u64, x: f64, y: f64) -> (f64, f64) {
+        let (width, height) = self.display_size();
+        let n = self.overscan as f64;
+        ((i as f64 - n + x) / ((width - 1) as f64), (j as f64 - n + y) / ((height - 1) as f64))
+    }
+
+    // Pixel position at film coordinates (s, t), the inverse of film_coords
+    // for the pixel center
+    pub fn pixel_coords(&self, s: f64, t: f64) -> (f64, f64) {
+        let (width, height) = self.display_size();
+        let n = self.overscan as f64;
+        (s * ((width - 1) as f64) + n - 0.5, t * ((height - 1) as f64) + n - 0.5)
+    }
+
+    // Size of the written image, the bucket's if there is one
+    pub fn output_size(&self) -> (u64, u64) {
+        match self.bucket {
+            Some(bucket) => (bucket.width, bucket.height),
+            None => self.display_size()
+        }
+    }
+
+    // Pixels of the written image, without the overscan border and only the
+    // bucket's if there is one, top row first
+    pub fn crop<T: Copy>(&self, pixels: &[T]) -> Vec<T> {
+        let (width, height) = self.output_size();
+        let (x, y) = self.bucket.map_or((0, 0), |b| (b.x, b.y));
+        let n = self.overscan as usize;
+        pixels.chunks(self.image_width as usize)
+            .skip(n + y as usize)
+            .take(height as usize)
+            .flat_map(|row| row[n + x as usize..n + (x + width) as usize].iter().copied())
+            .collect()
+    }
+
+    // Cropped pixels of the written image, flipped, top row first
+    pub fn output<T: Copy>(&self, pixels: &[T]) -> Vec<T> {
+        let (width, _) = self.output_size();
+        self.flip.apply(&self.crop(pixels), width as usize)
+    }
+
+    pub fn finished_scanlines(&self, count: u64) {
+        if let Some(progress) = &self.progress {
+            progress.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+}
+
+// Sums of the even and odd numbered samples of a pixel, two independent
+// estimates of it from the same render, and the number of samples whose
+// camera ray doesn't hit a holdout first; divided by the samples per pixel,
+// the alpha of the image
+#[derive(Clone, Copy)]
+pub struct SplitColor {
+    pub even: Color,
+    pub odd: Color,
+    pub coverage: u64
+}
+
+impl Default for SplitColor {
+    fn default() -> SplitColor {
+        SplitColor { even: Color::new(0.0, 0.0, 0.0), odd: Color::new(0.0, 0.0, 0.0), coverage: 0 }
+    }
+}
+
+impl SplitColor {
+    pub fn add(&mut self, sample: u64, c: Color, covered: bool) {
+        if sample.is_multiple_of(2) {
+            self.even += c;
+        } else {
+            self.odd += c;
+        }
+        if covered {
+            self.coverage += 1;
+        }
+    }
+
+    pub fn total(self) -> Color {
+        self.even + self.odd
+    }
+}
+
+// Suppresses fireflies by limiting how much a single sample can contribute
+pub fn clamp_sample(c: Color, max: f64) -> Color {
+    if max > 0.0 {
+        Color::new(c.x().min(max), c.y().min(max), c.z().min(max))
+    } else {
+        c
+    }
+}
+
+pub fn ray_color(r: &Ray, scene: &Scene, settings: &RenderSettings) -> Color {
+    trace(r, scene, settings, settings.max_depth, RayKind::Camera)
+}
+
+pub fn trace(r: &Ray, scene: &Scene, settings: &RenderSettings, depth: u64, kind: RayKind) -> Color {
+    if depth == 0 {
+        // If we've exceeded the ray bounce limit, no more light is gathered
+        return Color::new(0.0, 0.0, 0.0);
+    }
+
+    shade(r, scene.world.hit(r, 0.001, f64::INFINITY), scene, settings, depth, kind)
+}
+
+// Russian roulette: past rr_start bounces, a path continues with the
+// probability of its bounce's largest attenuation and is reweighted to stay
+// unbiased. Returns the weight of a continuing path, or None to stop it.
+pub fn roulette(settings: &RenderSettings, bounce: u64, attenuation: Color) -> Option<f64> {
+    match settings.rr_start {
+        Some(start) if bounce >= start => {
+            let survival = attenuation.x().max(attenuation.y()).max(attenuation.z()).clamp(0.05, 0.95);
+            if sampler::rng().gen::<f64>() < survival {
+                Some(1.0 / survival)
+            } else {
+                None
+            }
+        },
+        _ => Some(1.0)
+    }
+}
+
+// The shapes of lights are skipped by rays leaving a diffuse bounce, whose
+// direct lighting was already gathered from them. Other emitters, like
+// glowing materials and the air of an atmosphere, aren't sampled, so every
+// ray counts them.
+pub fn counts_emission(kind: RayKind, rec: &HitRecord, scene: &Scene) -> bool {
+    kind != RayKind::Diffuse || !scene.is_light_shape(rec.object_id)
+}
+
+// Kind of the ray continuing a path, depending on whether the vertex it
+// leaves had its direct lighting sampled; rays passing through transparent
+// surfaces keep theirs
+pub fn bounce_kind(r: &Ray, rec: &HitRecord, kind: RayKind, light_sampled: bool) -> RayKind {
+    if rec.mat.transparent(r, rec) {
+        kind
+    } else if light_sampled {
+        RayKind::Diffuse
+    } else {
+        RayKind::Specular
+    }
+}
+
+// Contribution of a ray whose closest hit has already been found
+pub fn shade(r: &Ray, hit: Option<HitRecord>, scene: &Scene, settings: &RenderSettings, depth: u64, kind: RayKind) -> Color {
+    if let Some(mut rec) = hit {
+        if rec.holdout && kind == RayKind::Camera {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        material::resolve(r, &mut rec);
+
+        let mut color = if counts_emission(kind, &rec, scene) {
+            rec.mat.emitted(r, &rec)
+        } else {
+            Color::new(0.0, 0.0, 0.0)
+        };
+
+        let direct = direct_light(r, &rec, scene);
+        let light_sampled = direct.is_some();
+        color += direct.unwrap_or(Color::new(0.0, 0.0, 0.0));
+
+        if let Some((attenuation, scattered)) = scatter(r, &rec, scene) {
+            if let Some(weight) = roulette(settings, settings.max_depth - depth, attenuation) {
+                color += weight * attenuation * trace(&scattered, scene, settings, depth - 1, bounce_kind(r, &rec, kind, light_sampled));
+            }
+        }
+
+        color
+    } else {
+        scene.background(r, kind)
+    }
+}
+
+// The material's scattered ray, typed by the material, and walked through
+// its interior first when it enters a subsurface material, leaving it
+// diffusely
+pub fn scatter(r: &Ray, rec: &HitRecord, scene: &Scene) -> Option<(Color, Ray)> {
+    let (attenuation, scattered) = rec.mat.scatter(r, rec)?;
+    let ty = rec.mat.ray_type(r, rec, &scattered);
+    let scattered = scattered.with_type(ty);
+
+    match rec.mat.medium() {
+        Some(medium) if rec.front_face && scattered.direction().dot(rec.geometric_normal) < 0.0 => {
+            let (throughput, exit) = subsurface::walk(&scattered, medium, scene)?;
+            Some((attenuation * throughput, exit.with_type(RayType::Diffuse)))
+        },
+        _ => Some((attenuation, scattered))
+    }
+}
+
+// Returns for each light the unoccluded contribution with its shadow ray and
+// distance, or None if the material can't be lit directly
+pub fn light_samples(r: &Ray, rec: &HitRecord, scene: &Scene) -> Option<Vec<(Color, Ray, f64)>> {
+    // Only materials with a BRDF to evaluate are lit directly
+    rec.mat.eval(r, rec, rec.normal)?;
+
+    let mut samples = Vec::with_capacity(scene.lights.len());
+    for (index, light) in scene.lights.iter().enumerate() {
+        if !rec.lit_by(index) {
+            continue;
+        }
+
+        if let Some(ls) = light.sample(rec.p) {
+            if let Some(f) = rec.mat.eval(r, rec, ls.wi) {
+                let contribution = f * ls.radiance;
+                if !contribution.near_zero() {
+                    // Stop just short of the light so its own geometry doesn't occlude it
+                    samples.push((contribution, Ray::with_time(rec.p, ls.wi, r.time()).with_type(RayType::Shadow), ls.dist * (1.0 - 1e-4)));
+                }
+            }
+        }
+    }
+
+    Some(samples)
+}
+
+// Next-event estimation: one shadow-tested sample from every light
+pub fn direct_light(r: &Ray, rec: &HitRecord, scene: &Scene) -> Option<Color> {
+    let samples = light_samples(r, rec, scene)?;
+
+    let mut total = Color::new(0.0, 0.0, 0.0);
+    for (contribution, shadow, t_max) in samples {
+        if !occluded(&shadow, t_max, scene) {
+            total += contribution;
+        }
+    }
+
+    Some(total)
+}
+
+// Whether the shadow ray is blocked before t_max by a surface that isn't
+// transparent to it
+pub fn occluded(shadow: &Ray, t_max: f64, scene: &Scene) -> bool {
+    let mut t_min = 0.001;
+    for _ in 0..MAX_TRANSPARENT {
+        match scene.world.hit(shadow, t_min, t_max) {
+            Some(rec) if rec.mat.transparent(shadow, &rec) => t_min = rec.t + 0.001,
+            Some(_) => return true,
+            None => return false
+        }
+    }
+
+    true
+}
+
+// Seeds the random numbers of sample `sample` of pixel (i, j), with j
+// counted from the bottom row
+pub fn start_sample(settings: &RenderSettings, i: u64, j: u64, sample: u64) {
+    let n = settings.overscan;
+    let (width, height) = settings.display_size();
+    let pixel = if (n..n + width).contains(&i) && (n..n + height).contains(&j) {
+        (height - 1 - (j - n)) * width + (i - n)
+    } else {
+        // Border pixels are numbered after the image's, which keep the
+        // streams they have in a render without overscan
+        width * height + (settings.image_height - 1 - j) * settings.image_width + i
+    };
+    sampler::start_sample(settings.seed, settings.frame, pixel, sample);
+}
+
+// Camera ray through pixel (i, j), with j counted from the bottom row
+pub fn sample_ray(cam: &Camera, settings: &RenderSettings, i: u64, j: u64) -> Ray {
+    let mut rng = sampler::rng();
+    let random_u: f64 = rng.gen();
+    let random_v: f64 = rng.gen();
+
+    let (u, v) = settings.film_coords(i, j, random_u, random_v);
+    cam.get_ray(u, v)
+}
+
+// Returns the summed (not averaged) samples of every pixel, top row first
+pub fn render(scene: &Scene, settings: &RenderSettings) -> Vec<Color> {
+    let pixels = render_samples(scene, settings);
+
+    if settings.denoise {
+        denoise::denoise(&pixels, settings.image_width, settings.image_height, settings.samples_per_pixel)
+    } else {
+        pixels
+    }
+}
+
+// Same as render, without denoising
+pub fn render_samples(scene: &Scene, settings: &RenderSettings) -> Vec<Color> {
+    render_split(scene, settings).into_iter().map(SplitColor::total).collect()
+}
+
+// Coverage of every pixel, see SplitColor
+pub fn coverage(split: &[SplitColor]) -> Vec<u64> {
+    split.iter().map(|p| p.coverage).collect()
+}
+
+// Same as render_samples, keeping the even and odd samples of every pixel
+// apart
+pub fn render_split(scene: &Scene, settings: &RenderSettings) -> Vec<SplitColor> {
+    match settings.integrator {
+        Integrator::Wavefront => wavefront::render(scene, settings),
+        _ => render_path(scene, settings)
+    }
+}
+
+// Sample of the direct integrator. Specular bounces are followed up to the
+// first surface lit by light sampling, which ends the path with one more
+// ray for the environment it sees; nothing that ray hits is lit. Also tells
+// whether the camera ray misses the holdouts.
+fn direct_color(mut ray: Ray, scene: &Scene, settings: &RenderSettings) -> (Color, bool) {
+    let mut kind = RayKind::Camera;
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut color = Color::new(0.0, 0.0, 0.0);
+    let mut covered = true;
+
+    for _ in 0..settings.max_depth {
+        let mut rec = match scene.world.hit(&ray, 0.001, f64::INFINITY) {
+            Some(rec) => rec,
+            None => {
+                color += throughput * scene.background(&ray, kind);
+                break;
+            }
+        };
+        if rec.holdout && kind == RayKind::Camera {
+            covered = false;
+            break;
+        }
+        material::resolve(&ray, &mut rec);
+
+        if counts_emission(kind, &rec, scene) {
+            color += throughput * rec.mat.emitted(&ray, &rec);
+        }
+        let direct = direct_light(&ray, &rec, scene);
+        let light_sampled = direct.is_some();
+        color += throughput * direct.unwrap_or(Color::new(0.0, 0.0, 0.0));
+
+        let (attenuation, scattered) = match scatter(&ray, &rec, scene) {
+            Some(bounce) => bounce,
+            None => break
+        };
+        throughput *= attenuation;
+        let last = light_sampled && !rec.mat.transparent(&ray, &rec);
+        kind = bounce_kind(&ray, &rec, kind, light_sampled);
+        ray = scattered;
+
+        if last {
+            if scene.world.hit(&ray, 0.001, f64::INFINITY).is_none() {
+                color += throughput * scene.background(&ray, kind);
+            }
+            break;
+        }
+    }
+
+    (clamp_sample(color, settings.clamp), covered)
+}
+
+// Sample of the path integrator, and whether its camera ray misses the
+// holdouts
+fn path_color(r: &Ray, scene: &Scene, settings: &RenderSettings) -> (Color, bool) {
+    let hit = scene.world.hit(r, 0.001, f64::INFINITY);
+    let covered = !hit.as_ref().is_some_and(|rec| rec.holdout);
+    if settings.max_depth == 0 {
+        return (Color::new(0.0, 0.0, 0.0), covered);
+    }
+
+    (clamp_sample(shade(r, hit, scene, settings, settings.max_depth, RayKind::Camera), settings.clamp), covered)
+}
+
+// Sample of the debug integrators, squared so that the gamma 2 encoding of
+// the output writes the values themselves
+fn debug_color(r: &Ray, scene: &Scene, settings: &RenderSettings) -> (Color, bool) {
+    let rec = match scene.world.hit(r, 0.001, f64::INFINITY) {
+        Some(rec) => rec,
+        None => return (Color::new(0.0, 0.0, 0.0), true)
+    };
+
+    let value = match settings.integrator {
+        Integrator::Ao => {
+            let dir = (rec.normal + Vec3::random_in_unit_sphere().normalized()).normalized();
+            let open = dir.near_zero() || scene.world.hit(&Ray::with_time(rec.p, dir, r.time()), 0.001, settings.distance).is_none();
+            let ao = if open { 1.0 } else { 0.0 };
+            Color::new(ao, ao, ao)
+        },
+        Integrator::Normals => {
+            let n = if rec.front_face { rec.normal } else { (-1.0) * rec.normal };
+            0.5 * (n + Color::new(1.0, 1.0, 1.0))
+        },
+        _ => {
+            let d = (rec.t * r.direction().length() / settings.distance).min(1.0);
+            Color::new(d, d, d)
+        }
+    };
+
+    (value * value, !rec.holdout)
+}
+
+fn render_path(scene: &Scene, settings: &RenderSettings) -> Vec<SplitColor> {
+    tiles::render_tiles(scene, settings, |i, j| {
+        if settings.packet_size > 1 && settings.max_depth > 0 && settings.integrator == Integrator::Path {
+            return trace_pixel_packets(scene, settings, i, j);
+        }
+
+        let mut pixel_color = SplitColor::default();
+        for s in settings.first_sample..settings.first_sample + settings.samples_per_pixel {
+            start_sample(settings, i, j, s);
+            let r = sample_ray(&scene.camera, settings, i, j);
+            let (color, covered) = match settings.integrator {
+                Integrator::Path => path_color(&r, scene, settings),
+                Integrator::Direct => direct_color(r, scene, settings),
+                _ => debug_color(&r, scene, settings)
+            };
+            pixel_color.add(s, color, covered);
+        }
+
+        pixel_color
+    })
+}
+
+// The samples of one pixel are nearly parallel rays, so their primary hits
+// are found a packet at a time; the incoherent bounces are traced one by one
+fn trace_pixel_packets(scene: &Scene, settings: &RenderSettings, i: u64, j: u64) -> SplitColor {
+    let lanes = settings.packet_size.min(MAX_LANES);
+    let mut pixel_color = SplitColor::default();
+    let mut remaining = settings.samples_per_pixel as usize;
+    let mut sample = settings.first_sample;
+
+    while remaining > 0 {
+        let n = lanes.min(remaining);
+
+        // Each sample resumes its own random stream once its hit is known
+        let mut states = Vec::with_capacity(n);
+        let rays: Vec<Ray> = (0..n)
+            .map(|_| {
+                start_sample(settings, i, j, sample);
+                sample += 1;
+                let r = sample_ray(&scene.camera, settings, i, j);
+                states.push(sampler::state());
+                r
+            })
+            .collect();
+
+        let packet = RayPacket::new(&rays);
+        let mut t_max = packet.initial_t_max(f64::INFINITY);
+        let mut recs: Vec<Option<HitRecord>> = (0..n).map(|_| None).collect();
+        scene.world.hit_packet(&packet, 0.001, &mut t_max, &mut recs);
+
+        let first = sample - n as u64;
+        for (k, ((r, rec), state)) in rays.iter().zip(recs).zip(states).enumerate() {
+            sampler::set_state(state);
+            let covered = !rec.as_ref().is_some_and(|rec| rec.holdout);
+            pixel_color.add(first + k as u64, clamp_sample(shade(r, rec, scene, settings, settings.max_depth, RayKind::Camera), settings.clamp), covered);
+        }
+
+        remaining -= n;
+    }
+
+    pixel_color
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::Options;
+    use crate::render;
+
+    // A holdout ball in the middle of the frame, with the corners clear
+    const XML: &str = r#"<RT>
+        <film filename="holdout.ppm" width="8" height="8"/>
+        <camera look_from="0 0 10" look_at="0 0 0" up="0 1 0" aperture="0"/>
+        <material type="lambertian" color="0.5 0.5 0.5"/>
+        <object type="sphere" center="0 0 0" radius="0.8" holdout="true"/>
+    </RT>"#;
+
+    #[test]
+    fn beauty_passes_count_holdout_coverage() {
+        let (_, scene) = crate::xml_parser(XML);
+        for options in [&["--packet", "1"][..], &["--packet", "4"], &["--integrator", "direct"], &["--integrator", "wavefront"], &["--integrator", "normals"]] {
+            let args = ["--spp", "8", "--no-denoise"].iter().chain(options).map(|s| s.to_string());
+            let settings = Options::parse(args).settings(&scene);
+            let coverage = render::coverage(&render::render_split(&scene, &settings));
+
+            assert_eq!((coverage[0], coverage[63]), (8, 8), "Corners hit the holdout with {:?}", options);
+            assert_eq!((coverage[27], coverage[36]), (0, 0), "The holdout is seen through with {:?}", options);
+        }
+    }
+}
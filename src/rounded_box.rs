@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Axis-aligned box between the corners min and max with its edges and
+// corners rounded off by `radius`, the points within radius of the box
+// shrunk by it. Its surface is made of exact pieces that don't overlap: the
+// flat part of each face, a quarter cylinder along each edge and an eighth
+// of a sphere at each corner. The nearest hit on any piece is the hit, so
+// rays leaving the box find their way out like those entering it.
+pub struct RoundedBox {
+    // The shrunk box the rounding is swept around
+    inner: Aabb,
+    radius: f64,
+    mat: Arc<dyn Scatter>
+}
+
+impl RoundedBox {
+    pub fn new(a: Point3, b: Point3, radius: f64, m: Arc<dyn Scatter>) -> RoundedBox {
+        let outer = Aabb::new(a, b);
+        let r = Vec3::new(radius, radius, radius);
+        RoundedBox {
+            inner: Aabb { min: outer.min + r, max: outer.max - r },
+            radius,
+            mat: m
+        }
+    }
+
+    fn within(&self, p: Point3, axis: usize) -> bool {
+        p[axis] >= self.inner.min[axis] && p[axis] <= self.inner.max[axis]
+    }
+
+    // Whether p lies beyond the inner box's min or max along `axis`, where
+    // the pieces rounded around that side face
+    fn outward(&self, p: Point3, axis: usize, max: bool) -> bool {
+        if max { p[axis] >= self.inner.max[axis] } else { p[axis] <= self.inner.min[axis] }
+    }
+}
+
+impl Hit for RoundedBox {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (o, d) = (r.origin(), r.direction());
+        let r2 = self.radius * self.radius;
+
+        let mut closest: Option<(f64, Vec3)> = None;
+        let mut consider = |t: f64, normal: Vec3| {
+            if t >= t_min && t <= t_max && closest.is_none_or(|(c, _)| t < c) {
+                closest = Some((t, normal));
+            }
+        };
+
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+            // Flat parts of the two faces across the axis
+            if d[axis] != 0.0 {
+                for (plane, side) in [(self.inner.min[axis] - self.radius, -1.0), (self.inner.max[axis] + self.radius, 1.0)] {
+                    let t = (plane - o[axis]) / d[axis];
+                    let p = r.at(t);
+                    if self.within(p, u) && self.within(p, v) {
+                        let mut normal = Vec3::new(0.0, 0.0, 0.0);
+                        normal[axis] = side;
+                        consider(t, normal);
+                    }
+                }
+            }
+
+            // Quarter cylinders along the four edges parallel to the axis
+            let a = d[u] * d[u] + d[v] * d[v];
+            if a == 0.0 {
+                continue;
+            }
+            for (cu, max_u) in [(self.inner.min[u], false), (self.inner.max[u], true)] {
+                for (cv, max_v) in [(self.inner.min[v], false), (self.inner.max[v], true)] {
+                    let (ou, ov) = (o[u] - cu, o[v] - cv);
+                    let half_b = ou * d[u] + ov * d[v];
+                    let discriminant = half_b * half_b - a * (ou * ou + ov * ov - r2);
+                    if discriminant < 0.0 {
+                        continue;
+                    }
+
+                    let sqrtd = discriminant.sqrt();
+                    for t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                        let p = r.at(t);
+                        if self.within(p, axis) && self.outward(p, u, max_u) && self.outward(p, v, max_v) {
+                            let mut normal = Vec3::new(0.0, 0.0, 0.0);
+                            normal[u] = (p[u] - cu) / self.radius;
+                            normal[v] = (p[v] - cv) / self.radius;
+                            consider(t, normal);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Eighths of spheres at the corners
+        let a = d.dot(d);
+        for k in 0..8 {
+            let max = |axis: usize| k & (1 << axis) != 0;
+            let pick = |axis: usize| if max(axis) { self.inner.max[axis] } else { self.inner.min[axis] };
+            let corner = Point3::new(pick(0), pick(1), pick(2));
+            let oc = o - corner;
+            let half_b = oc.dot(d);
+            let discriminant = half_b * half_b - a * (oc.dot(oc) - r2);
+            if discriminant < 0.0 {
+                continue;
+            }
+
+            let sqrtd = discriminant.sqrt();
+            for t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                let p = r.at(t);
+                if (0..3).all(|axis| self.outward(p, axis, max(axis))) {
+                    consider(t, (p - corner) / self.radius);
+                }
+            }
+        }
+
+        let (t, outward_normal) = closest?;
+        Some(HitRecord::new(r, t, outward_normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.inner.min - r, self.inner.max + r))
+    }
+}
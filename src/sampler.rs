@@ -0,0 +1,109 @@
+use std::cell::Cell;
+
+use rand::{Error, RngCore};
+
+// Random numbers for rendering come from a PCG32 generator per thread that
+// is reseeded at the start of every camera sample, so each sample's stream
+// depends only on (seed, frame, pixel, sample index) and not on which
+// thread, tile or machine renders it
+
+const MULTIPLIER: u64 = 6364136223846793005;
+const INCREMENT: u64 = 1442695040888963407;
+
+thread_local! {
+    static STATE: Cell<u64> = const { Cell::new(0x853c49e6748fea9b) };
+    static KEY: Cell<u64> = const { Cell::new(0) };
+}
+
+// 64-bit finalizer spreading every input bit over the whole output
+pub fn hash(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+pub fn start_sample(seed: u64, frame: u64, pixel: u64, sample: u64) {
+    let key = hash(hash(hash(hash(seed) ^ frame) ^ pixel) ^ sample);
+    KEY.with(|k| k.set(key));
+    set_state(key);
+}
+
+// Point of the shutter interval the current camera sample is taken at, in
+// [0, 1). It is hashed from the sample's key instead of drawn from the
+// generator, which leaves the streams of scenes without motion as they were.
+pub fn shutter_time() -> f64 {
+    let key = KEY.with(|k| k.get());
+    (hash(key ^ 0x9e3779b97f4a7c15) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// The generator state can be saved and restored for paths that are traced
+// in several steps, interleaved with other paths on the same thread
+pub fn state() -> u64 {
+    STATE.with(|s| s.get())
+}
+
+pub fn set_state(state: u64) {
+    STATE.with(|s| s.set(state));
+}
+
+// Handle to the current thread's generator
+pub struct SampleRng;
+
+pub fn rng() -> SampleRng {
+    SampleRng
+}
+
+impl RngCore for SampleRng {
+    fn next_u32(&mut self) -> u32 {
+        STATE.with(|s| {
+            let old = s.get();
+            s.set(old.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT));
+
+            // XSH RR output permutation
+            let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+            xorshifted.rotate_right((old >> 59) as u32)
+        })
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | (self.next_u32() as u64)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builtin;
+    use crate::cli::Options;
+    use crate::render;
+
+    // Renders of the cover at 120x80, several tiles of 32 by 32, are the same
+    // in any tile order and with any number of threads
+    #[test]
+    fn renders_are_deterministic() {
+        let (_, scene) = builtin::builtin("cover").unwrap();
+        let render = |order: &str, threads: usize| {
+            let args = ["--spp", "2", "--depth", "8", "--scale", "0.1", "--no-denoise", "--tile-order", order];
+            let settings = Options::parse(args.map(str::to_string).into_iter()).settings(&scene);
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| render::render_samples(&scene, &settings))
+        };
+
+        let reference = render("scanline", 1);
+        assert_eq!(reference.len(), 120 * 80);
+        for (order, threads) in [("scanline", 4), ("spiral", 1), ("spiral", 4), ("variance", 4)] {
+            assert!(render(order, threads) == reference, "The {} order on {} threads changes the image", order, threads);
+        }
+    }
+}
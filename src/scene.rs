@@ -0,0 +1,293 @@
+use std::sync::Arc;
+
+use super::animation::CameraPath;
+use super::camera::Camera;
+use super::environment::Environment;
+use super::hit::{Hit, World};
+use super::image::Image;
+use super::light::{Light, MovedLight, NoLight};
+use super::material::Scatter;
+use super::ray::{Ray, RayKind};
+use super::render::{Flip, IntegratorParams};
+use super::vec::{Color, Point3, Vec3};
+
+// Offset from the end points of ray queries, keeping surfaces from
+// shadowing themselves, as with the rays of the renderer
+const QUERY_EPSILON: f64 = 0.001;
+
+// Width and height of films whose scene doesn't give them
+pub const DEFAULT_FILM_SIZE: (u64, u64) = (1200, 800);
+
+// First surface along a query ray
+pub struct HitInfo {
+    pub t: f64,
+    pub point: Point3,
+    // Normal facing the ray
+    pub normal: Vec3,
+    pub front_face: bool,
+    // Index of the hit object in the world
+    pub object: usize
+}
+
+pub struct Scene {
+    pub world: World,
+    // Lights sampled directly at every diffuse hit
+    pub lights: Vec<Box<dyn Light>>,
+    // World index of the shape emitting each light, for the lights that
+    // have one
+    pub light_objects: Vec<Option<usize>>,
+    pub environment: Environment,
+    // Photograph shown behind the scene to camera rays instead of the
+    // environment, which still lights it
+    pub plate: Option<Arc<Image>>,
+    pub camera: Camera,
+    // Width and height of the film at full resolution, before --scale; the
+    // camera has the same aspect ratio once stretched by the pixel aspect
+    pub film_size: (u64, u64),
+    // Mirroring of the written images
+    pub flip: Flip,
+    // Width of the film's pixels over their height
+    pub pixel_aspect: f64,
+    // Camera keyframes of an animated scene
+    pub camera_path: Option<CameraPath>,
+    // Names given to the objects of the world, by index
+    pub object_names: Vec<Option<String>>,
+    // Whether some object is a holdout, which gives images an alpha channel
+    pub has_holdouts: bool,
+    pub integrator: IntegratorParams
+}
+
+impl Scene {
+    // Builds the accelerator of the world
+    pub fn new(mut world: World, lights: Vec<Box<dyn Light>>, camera: Camera) -> Scene {
+        world.build_accel();
+
+        Scene {
+            world,
+            lights,
+            light_objects: Vec::new(),
+            environment: Environment::default(),
+            plate: None,
+            camera,
+            film_size: DEFAULT_FILM_SIZE,
+            flip: Flip::default(),
+            pixel_aspect: 1.0,
+            camera_path: None,
+            object_names: Vec::new(),
+            has_holdouts: false,
+            integrator: IntegratorParams::default()
+        }
+    }
+
+    // Index of the object with the given name, or of the given index
+    pub fn object_id(&self, name: &str) -> Option<usize> {
+        self.object_names.iter()
+            .position(|n| n.as_deref() == Some(name))
+            .or_else(|| name.parse().ok())
+            .filter(|&index| self.world.get(index).is_some())
+    }
+
+    // Object with the given name, or at the given index in the world
+    pub fn object(&self, name: &str) -> Option<&dyn Hit> {
+        self.world.get(self.object_id(name)?)
+    }
+
+    // Ray queries, for tools picking objects or testing visibility in a
+    // loaded scene without rendering it
+
+    pub fn intersect(&self, r: &Ray) -> Option<HitInfo> {
+        self.world.hit(r, QUERY_EPSILON, f64::INFINITY).map(|rec| HitInfo {
+            t: rec.t,
+            point: rec.p,
+            normal: rec.normal,
+            front_face: rec.front_face,
+            object: rec.object_id
+        })
+    }
+
+    // Whether an object lies between the points a and b
+    pub fn occluded(&self, a: Point3, b: Point3) -> bool {
+        let distance = (b - a).length();
+        if distance <= 2.0 * QUERY_EPSILON {
+            return false;
+        }
+
+        let r = Ray::new(a, (b - a) / distance);
+        self.world.hit(&r, QUERY_EPSILON, distance - QUERY_EPSILON).is_some()
+    }
+
+    // Editing, for frontends changing a loaded scene between render passes.
+    // Scenes are Send + Sync, so frontends share one as Arc<RwLock<Scene>>:
+    // passes render under the read lock and edits wait for the write lock.
+    // Object indices stay valid across edits, and removed ones aren't
+    // reused; edits of an index without an object return false. Lights
+    // follow the shapes emitting them: moving a shape moves its light, and
+    // removing it or changing its material turns the light off. Lights keep
+    // their places, which light linking refers to them by.
+
+    // Adds an object to the world and returns its index
+    pub fn add_object(&mut self, object: Box<dyn Hit>, name: Option<&str>) -> usize {
+        let index = self.world.push(object);
+        self.object_names.resize(index, None);
+        self.object_names.push(name.map(str::to_string));
+
+        index
+    }
+
+    pub fn remove_object(&mut self, index: usize) -> bool {
+        if let Some(name) = self.object_names.get_mut(index) {
+            *name = None;
+        }
+
+        if !self.world.remove(index) {
+            return false;
+        }
+        self.remove_light(index);
+        true
+    }
+
+    pub fn move_object(&mut self, index: usize, offset: Vec3) -> bool {
+        if !self.world.translate(index, offset) {
+            return false;
+        }
+        if let Some(k) = self.light_of(index) {
+            let light = std::mem::replace(&mut self.lights[k], Box::new(NoLight));
+            self.lights[k] = Box::new(MovedLight::new(light, offset));
+        }
+        true
+    }
+
+    // The light of an emissive object goes with its material. A new
+    // material that glows still lights the scene, through the rays
+    // scattered onto it rather than by light sampling, so it is noisier.
+    pub fn set_material(&mut self, index: usize, mat: Arc<dyn Scatter>) -> bool {
+        if !self.world.set_material(index, mat) {
+            return false;
+        }
+        self.remove_light(index);
+        true
+    }
+
+    // Index in lights of the light the object at index emits
+    fn light_of(&self, index: usize) -> Option<usize> {
+        self.light_objects.iter().position(|&object| object == Some(index))
+            .filter(|&k| k < self.lights.len())
+    }
+
+    fn remove_light(&mut self, index: usize) {
+        if let Some(k) = self.light_of(index) {
+            self.lights[k] = Box::new(NoLight);
+            self.light_objects[k] = None;
+        }
+    }
+
+    // Whether the object at index is the shape of one of the lights
+    pub fn is_light_shape(&self, index: usize) -> bool {
+        self.light_objects.contains(&Some(index))
+    }
+
+    // Radiance seen by a ray that escapes the scene
+    pub fn background(&self, r: &Ray, kind: RayKind) -> Color {
+        if kind == RayKind::Camera {
+            if let Some(plate) = &self.plate {
+                if let Some((s, t)) = self.camera.film_coords(r) {
+                    return plate.sample(s.clamp(0.0, 1.0), 1.0 - t);
+                }
+            }
+        }
+
+        self.environment.radiance(r, kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Options;
+    use crate::light::Emission;
+    use crate::material::{Emissive, Lambertian};
+    use crate::ray::RayKind;
+
+    // Two lights, the top one facing down and the bottom one facing up,
+    // each linked to one of the spheres
+    const XML: &str = r#"<RT>
+        <camera look_from="0 0 5" look_at="0 0 0" up="0 1 0" aperture="0"/>
+        <material type="lambertian" color="0.5 0.5 0.5"/>
+        <light type="area" name="top" corner="-1 4 -1" u="2 0 0" v="0 0 2" power="100"/>
+        <light type="area" name="bottom" corner="-1 -4 -1" u="0 0 2" v="2 0 0" power="100"/>
+        <object type="sphere" center="-2 0 0" radius="1" lights="bottom"/>
+        <object type="sphere" center="2 0 0" radius="1" lights="top"/>
+    </RT>"#;
+
+    // Height of the point sampled on the light at k, seen from the origin
+    fn light_height(scene: &Scene, k: usize) -> f64 {
+        let sample = scene.lights[k].sample(Point3::new(0.0, 0.0, 0.0)).unwrap();
+        sample.dist * sample.wi.y()
+    }
+
+    // Number of lights reaching the bottom of the sphere at x
+    fn lights_below(scene: &Scene, x: f64) -> usize {
+        let r = Ray::new(Point3::new(x, -3.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let rec = scene.world.hit(&r, QUERY_EPSILON, f64::INFINITY).unwrap();
+        crate::render::light_samples(&r, &rec, scene).unwrap().len()
+    }
+
+    #[test]
+    fn lights_follow_their_shapes() {
+        let (_, mut scene) = crate::xml_parser(XML);
+        assert_eq!(scene.light_objects, [Some(0), Some(1)]);
+        assert_eq!((lights_below(&scene, -2.0), lights_below(&scene, 2.0)), (1, 0));
+
+        assert!(scene.move_object(0, Vec3::new(0.0, 2.0, 0.0)));
+        assert!((light_height(&scene, 0) - 6.0).abs() < 1e-9);
+        assert!((light_height(&scene, 1) + 4.0).abs() < 1e-9);
+
+        // The bottom light stays the one the first sphere links to
+        assert!(scene.remove_object(0));
+        assert_eq!(scene.light_objects, [None, Some(1)]);
+        assert!(scene.lights[0].sample(Point3::new(0.0, 0.0, 0.0)).is_none());
+        assert!((light_height(&scene, 1) + 4.0).abs() < 1e-9);
+        assert_eq!((lights_below(&scene, -2.0), lights_below(&scene, 2.0)), (1, 0));
+
+        assert!(scene.set_material(1, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))));
+        assert_eq!(scene.light_objects, [None, None]);
+        assert_eq!(lights_below(&scene, -2.0), 0);
+
+        // Objects without a light leave them be
+        assert!(scene.move_object(2, Vec3::new(0.0, 0.0, 1.0)));
+        assert!(!scene.remove_object(0));
+    }
+
+    // Average light reaching the middle of a diffuse floor, lit by a ceiling
+    // light under a black sky
+    fn floor_light(scene: &Scene) -> f64 {
+        let settings = Options::parse(["--spp", "1"].map(str::to_string).into_iter()).settings(scene);
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        (0..400).map(|_| crate::render::trace(&r, scene, &settings, settings.max_depth, RayKind::Camera).y()).sum::<f64>() / 400.0
+    }
+
+    #[test]
+    fn glowing_materials_keep_lighting() {
+        let xml = r#"<RT>
+            <camera look_from="0 1 3" look_at="0 0 0" up="0 1 0" aperture="0"/>
+            <environment><layer type="constant" color="0 0 0"/></environment>
+            <material type="lambertian" color="0.5 0.5 0.5"/>
+            <object type="plane" point="0 0 0" normal="0 1 0"/>
+            <light type="area" corner="-2 2 -2" u="4 0 0" v="0 0 4" power="200"/>
+        </RT>"#;
+        let (_, mut scene) = crate::xml_parser(xml);
+        let radiance = floor_light(&scene);
+        assert!(radiance > 0.0);
+
+        // Sampled or reached by scattered rays, the same glow gives the
+        // same light
+        let glow = Emissive::new(Color::new(1.0, 1.0, 1.0) * (200.0 / (16.0 * std::f64::consts::PI)), Emission::diffuse());
+        assert!(scene.set_material(1, Arc::new(glow)));
+        assert!(!scene.is_light_shape(1));
+        let scattered = floor_light(&scene);
+        assert!((scattered - radiance).abs() < 0.2 * radiance, "The glow gives {} instead of {}.", scattered, radiance);
+
+        assert!(scene.set_material(1, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))));
+        assert_eq!(floor_light(&scene), 0.0);
+    }
+}
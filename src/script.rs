@@ -0,0 +1,569 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fs;
+
+use roxmltree::{Document, Node};
+
+// Per-frame scene scripts. A script is a list of statements, one per line
+// or separated by `;`, evaluated for every frame with the variable `frame`
+// set:
+//
+//     let t = frame / 24
+//     object("ball").center = [4 * cos(t), 1, 4 * sin(t)]
+//     material("glass").refrect_idx = 1.3 + 0.2 * sin(t)
+//     camera.aperture = 0.1
+//
+// Assignments override an attribute of the scene's XML elements before the
+// frame is parsed: `camera`, `film` and `environment` name the element by
+// its tag, and `object("name")`, `material("name")` and `light("name")` the
+// element of that tag with the given `name` attribute.
+
+#[derive(Clone)]
+enum Value {
+    Number(f64),
+    Vector(Vec<f64>),
+    Text(String)
+}
+
+impl Value {
+    fn number(&self, line: usize) -> f64 {
+        match self {
+            Value::Number(x) => *x,
+            _ => error(line, "expected a number")
+        }
+    }
+
+    // Attribute text, vectors being written like "1 2 3"
+    fn attribute(&self) -> String {
+        match self {
+            Value::Number(x) => x.to_string(),
+            Value::Vector(v) => v.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" "),
+            Value::Text(s) => s.clone()
+        }
+    }
+}
+
+enum Expr {
+    Literal(Value),
+    Variable(String),
+    List(Vec<Expr>),
+    Negate(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>)
+}
+
+enum Statement {
+    Let(String, Expr),
+    Set {
+        tag: String,
+        name: Option<String>,
+        attribute: String,
+        value: Expr
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Text(String),
+    Symbol(char),
+    End
+}
+
+// Attribute value set by a script for one frame
+pub struct Override {
+    pub tag: String,
+    pub name: Option<String>,
+    pub attribute: String,
+    pub value: String
+}
+
+pub struct Script {
+    // Statements with their line numbers
+    statements: Vec<(usize, Statement)>
+}
+
+fn error(line: usize, message: &str) -> ! {
+    panic!("Script error on line {}: {}.", line, message)
+}
+
+fn tokenize(line_text: &str, line: usize) -> Vec<Token> {
+    let chars: Vec<char> = line_text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c == '#' || (c == '/' && chars.get(pos + 1) == Some(&'/')) {
+            break;
+        } else if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_digit() || (c == '.' && chars.get(pos + 1).is_some_and(|d| d.is_ascii_digit())) {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                pos += 1;
+            }
+            // Exponent, e.g. 1e-3
+            if pos < chars.len() && (chars[pos] == 'e' || chars[pos] == 'E') {
+                pos += 1;
+                if pos < chars.len() && (chars[pos] == '-' || chars[pos] == '+') {
+                    pos += 1;
+                }
+                while pos < chars.len() && chars[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+            }
+            let text: String = chars[start..pos].iter().collect();
+            let value = text.parse().unwrap_or_else(|_| error(line, &format!("invalid number '{}'", text)));
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(Token::Ident(chars[start..pos].iter().collect()));
+        } else if c == '"' {
+            let start = pos + 1;
+            pos = start;
+            while pos < chars.len() && chars[pos] != '"' {
+                pos += 1;
+            }
+            if pos == chars.len() {
+                error(line, "unterminated string");
+            }
+            tokens.push(Token::Text(chars[start..pos].iter().collect()));
+            pos += 1;
+        } else if "()[],.=+-*/%;".contains(c) {
+            tokens.push(Token::Symbol(c));
+            pos += 1;
+        } else {
+            error(line, &format!("unexpected character '{}'", c));
+        }
+    }
+
+    tokens.push(Token::End);
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    line: usize
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn accept(&mut self, symbol: char) -> bool {
+        if *self.peek() == Token::Symbol(symbol) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) {
+        if !self.accept(symbol) {
+            error(self.line, &format!("expected '{}'", symbol));
+        }
+    }
+
+    fn ident(&mut self) -> String {
+        match self.next() {
+            Token::Ident(name) => name,
+            _ => error(self.line, "expected a name")
+        }
+    }
+
+    fn statement(&mut self) -> Statement {
+        let first = self.ident();
+        if first == "let" {
+            let name = self.ident();
+            self.expect('=');
+            return Statement::Let(name, self.expr());
+        }
+
+        let name = match first.as_str() {
+            "camera" | "film" | "environment" => None,
+            "object" | "material" | "light" => {
+                self.expect('(');
+                let name = match self.next() {
+                    Token::Text(name) => name,
+                    _ => error(self.line, &format!("expected the quoted name of the {}", first))
+                };
+                self.expect(')');
+                Some(name)
+            },
+            _ => error(self.line, &format!("unknown target '{}'", first))
+        };
+
+        self.expect('.');
+        let attribute = self.ident();
+        self.expect('=');
+
+        Statement::Set {
+            tag: first,
+            name,
+            attribute,
+            value: self.expr()
+        }
+    }
+
+    fn expr(&mut self) -> Expr {
+        let mut lhs = self.term();
+        loop {
+            let op = match self.peek() {
+                Token::Symbol(c) if *c == '+' || *c == '-' => *c,
+                _ => return lhs
+            };
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()));
+        }
+    }
+
+    fn term(&mut self) -> Expr {
+        let mut lhs = self.unary();
+        loop {
+            let op = match self.peek() {
+                Token::Symbol(c) if *c == '*' || *c == '/' || *c == '%' => *c,
+                _ => return lhs
+            };
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()));
+        }
+    }
+
+    fn unary(&mut self) -> Expr {
+        if self.accept('-') {
+            Expr::Negate(Box::new(self.unary()))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Expr {
+        match self.next() {
+            Token::Number(x) => Expr::Literal(Value::Number(x)),
+            Token::Text(s) => Expr::Literal(Value::Text(s)),
+            Token::Ident(name) => {
+                if self.accept('(') {
+                    Expr::Call(name, self.list(')'))
+                } else {
+                    Expr::Variable(name)
+                }
+            },
+            Token::Symbol('(') => {
+                let e = self.expr();
+                self.expect(')');
+                e
+            },
+            Token::Symbol('[') => Expr::List(self.list(']')),
+            _ => error(self.line, "expected a value")
+        }
+    }
+
+    // Comma separated expressions up to the closing symbol
+    fn list(&mut self, close: char) -> Vec<Expr> {
+        let mut items = Vec::new();
+        if self.accept(close) {
+            return items;
+        }
+        loop {
+            items.push(self.expr());
+            if self.accept(close) {
+                return items;
+            }
+            self.expect(',');
+        }
+    }
+}
+
+fn binary(op: char, a: Value, b: Value, line: usize) -> Value {
+    let apply = |x: f64, y: f64| match op {
+        '+' => x + y,
+        '-' => x - y,
+        '*' => x * y,
+        '/' => x / y,
+        _ => x.rem_euclid(y)
+    };
+
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => Value::Number(apply(x, y)),
+        (Value::Vector(v), Value::Number(y)) => Value::Vector(v.iter().map(|&x| apply(x, y)).collect()),
+        (Value::Number(x), Value::Vector(v)) => Value::Vector(v.iter().map(|&y| apply(x, y)).collect()),
+        (Value::Vector(u), Value::Vector(v)) if u.len() == v.len() => {
+            Value::Vector(u.iter().zip(&v).map(|(&x, &y)| apply(x, y)).collect())
+        },
+        (Value::Text(s), Value::Text(t)) if op == '+' => Value::Text(s + &t),
+        _ => error(line, &format!("invalid operands for '{}'", op))
+    }
+}
+
+fn call(name: &str, args: Vec<Value>, line: usize) -> Value {
+    let n = |k: usize| -> f64 {
+        args.get(k).unwrap_or_else(|| error(line, &format!("missing argument to {}", name))).number(line)
+    };
+    let arity = |count: usize| {
+        if args.len() != count {
+            error(line, &format!("{} takes {} argument(s)", name, count));
+        }
+    };
+
+    let x = match name {
+        "sin" | "cos" | "tan" | "asin" | "acos" | "atan" | "sqrt" | "abs" | "floor" | "ceil" | "exp" | "ln" | "radians" => {
+            arity(1);
+            let a = n(0);
+            match name {
+                "sin" => a.sin(),
+                "cos" => a.cos(),
+                "tan" => a.tan(),
+                "asin" => a.asin(),
+                "acos" => a.acos(),
+                "atan" => a.atan(),
+                "sqrt" => a.sqrt(),
+                "abs" => a.abs(),
+                "floor" => a.floor(),
+                "ceil" => a.ceil(),
+                "exp" => a.exp(),
+                "ln" => a.ln(),
+                _ => a.to_radians()
+            }
+        },
+        "atan2" | "min" | "max" | "pow" => {
+            arity(2);
+            match name {
+                "atan2" => n(0).atan2(n(1)),
+                "min" => n(0).min(n(1)),
+                "max" => n(0).max(n(1)),
+                _ => n(0).powf(n(1))
+            }
+        },
+        "clamp" => {
+            arity(3);
+            let (min, max) = (n(1), n(2));
+            if min.is_nan() || max.is_nan() {
+                error(line, "the bounds of clamp can't be NaN");
+            }
+            if min > max {
+                error(line, &format!("the lower bound {} of clamp is above its upper bound {}", min, max));
+            }
+            n(0).clamp(min, max)
+        },
+        // Linear interpolation from a to b, also between vectors
+        "mix" => {
+            arity(3);
+            let t = n(2);
+            let a = binary('*', args[0].clone(), Value::Number(1.0 - t), line);
+            let b = binary('*', args[1].clone(), Value::Number(t), line);
+            return binary('+', a, b, line);
+        },
+        _ => error(line, &format!("unknown function '{}'", name))
+    };
+
+    Value::Number(x)
+}
+
+fn eval(e: &Expr, vars: &HashMap<String, Value>, line: usize) -> Value {
+    match e {
+        Expr::Literal(v) => v.clone(),
+        Expr::Variable(name) => vars.get(name)
+            .cloned()
+            .unwrap_or_else(|| error(line, &format!("unknown variable '{}'", name))),
+        Expr::List(items) => Value::Vector(items.iter().map(|item| eval(item, vars, line).number(line)).collect()),
+        Expr::Negate(a) => binary('*', eval(a, vars, line), Value::Number(-1.0), line),
+        Expr::Binary(op, a, b) => binary(*op, eval(a, vars, line), eval(b, vars, line), line),
+        Expr::Call(name, args) => call(name, args.iter().map(|a| eval(a, vars, line)).collect(), line)
+    }
+}
+
+impl Script {
+    pub fn load(path: &str) -> Script {
+        let source = fs::read_to_string(path).unwrap_or_else(|_| panic!("Unable to read script {}.", path));
+        Script::parse(&source)
+    }
+
+    pub fn parse(source: &str) -> Script {
+        let mut statements = Vec::new();
+
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let mut parser = Parser {
+                tokens: tokenize(text, line),
+                pos: 0,
+                line
+            };
+
+            while *parser.peek() != Token::End {
+                if parser.accept(';') {
+                    continue;
+                }
+                statements.push((line, parser.statement()));
+                if *parser.peek() != Token::End {
+                    parser.expect(';');
+                }
+            }
+        }
+
+        Script {
+            statements
+        }
+    }
+
+    // Attribute overrides for the given frame, in the order they were set
+    pub fn run(&self, frame: u64) -> Vec<Override> {
+        let mut vars = HashMap::new();
+        vars.insert("frame".to_string(), Value::Number(frame as f64));
+        vars.insert("pi".to_string(), Value::Number(PI));
+
+        let mut overrides = Vec::new();
+        for (line, statement) in &self.statements {
+            match statement {
+                Statement::Let(name, e) => {
+                    let value = eval(e, &vars, *line);
+                    vars.insert(name.clone(), value);
+                },
+                Statement::Set { tag, name, attribute, value } => overrides.push(Override {
+                    tag: tag.clone(),
+                    name: name.clone(),
+                    attribute: attribute.clone(),
+                    value: eval(value, &vars, *line).attribute()
+                })
+            }
+        }
+
+        overrides
+    }
+
+    // The output name and scene of a frame, parsed once with its overrides
+    pub fn scene(&self, xml: &str, frame: u64) -> (String, crate::Scene) {
+        crate::xml_parser(&apply(xml, &self.run(frame)))
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn write_node(node: Node, overrides: &[Override], used: &mut [bool], out: &mut String) {
+    if node.is_text() {
+        out.push_str(&escape(node.text().unwrap_or("")));
+        return;
+    }
+    if !node.is_element() {
+        return;
+    }
+
+    let tag = node.tag_name().name();
+    let mut attributes: Vec<(String, String)> = node.attributes()
+        .iter()
+        .map(|a| (a.name().to_string(), a.value().to_string()))
+        .collect();
+
+    for (k, o) in overrides.iter().enumerate() {
+        if o.tag != tag || (o.name.is_some() && o.name.as_deref() != node.attribute("name")) {
+            continue;
+        }
+        used[k] = true;
+
+        match attributes.iter_mut().find(|(name, _)| *name == o.attribute) {
+            Some(attribute) => attribute.1 = o.value.clone(),
+            None => attributes.push((o.attribute.clone(), o.value.clone()))
+        }
+    }
+
+    out.push('<');
+    out.push_str(tag);
+    for (name, value) in &attributes {
+        out.push_str(&format!(" {}=\"{}\"", name, escape(value)));
+    }
+    out.push('>');
+    for child in node.children() {
+        write_node(child, overrides, used, out);
+    }
+    out.push_str(&format!("</{}>", tag));
+}
+
+// Scene XML with the overrides applied
+pub fn apply(xml: &str, overrides: &[Override]) -> String {
+    let doc = Document::parse(xml).expect("Failed to parse XML");
+    let mut used = vec![false; overrides.len()];
+    let mut out = String::new();
+
+    write_node(doc.root_element(), overrides, &mut used, &mut out);
+
+    if let Some(k) = used.iter().position(|u| !u) {
+        let o = &overrides[k];
+        match &o.name {
+            Some(name) => panic!("Script sets {} of {} '{}', which isn't in the scene.", o.attribute, o.tag, name),
+            None => panic!("Script sets {} of the {}, which isn't in the scene.", o.attribute, o.tag)
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(source: &str, frame: u64) -> Vec<(String, Option<String>, String, String)> {
+        Script::parse(source).run(frame).into_iter().map(|o| (o.tag, o.name, o.attribute, o.value)).collect()
+    }
+
+    #[test]
+    fn evaluates_assignments_per_frame() {
+        let source = "let t = frame / 2; let r = max(1, t)\n\
+                      object(\"ball\").center = [r, -t, 2 * (1 + 1)]\n\
+                      camera.aperture = clamp(t, 0, 1); film.filename = \"out.png\"";
+        assert_eq!(values(source, 4), [
+            ("object".to_string(), Some("ball".to_string()), "center".to_string(), "2 -2 4".to_string()),
+            ("camera".to_string(), None, "aperture".to_string(), "1".to_string()),
+            ("film".to_string(), None, "filename".to_string(), "out.png".to_string())
+        ]);
+        assert_eq!(values("light(\"key\").power = mix(10, 20, frame / 4)", 1)[0].3, "12.5");
+    }
+
+    #[test]
+    fn applies_overrides_to_named_elements() {
+        let xml = r#"<scene><camera aperture="0"/><object name="ball" center="0 0 0"/><object center="1 1 1"/></scene>"#;
+        let out = apply(xml, &Script::parse("camera.aperture = 0.5; object(\"ball\").center = [1, 2, 3]").run(0));
+        let doc = Document::parse(&out).unwrap();
+        let attr = |tag: &str, k: usize, name: &str| {
+            doc.descendants().filter(|n| n.has_tag_name(tag)).nth(k).and_then(|n| n.attribute(name)).map(str::to_string)
+        };
+        assert_eq!(attr("camera", 0, "aperture").as_deref(), Some("0.5"));
+        assert_eq!(attr("object", 0, "center").as_deref(), Some("1 2 3"));
+        assert_eq!(attr("object", 1, "center").as_deref(), Some("1 1 1"));
+    }
+
+    #[test]
+    fn frames_take_their_scripted_filename() {
+        let xml = r#"<scene><film filename="plain.ppm" width="8" height="8"/></scene>"#;
+        let script = Script::parse("film.filename = \"scripted.ppm\"");
+        assert_eq!(script.scene(xml, 3).0, "scripted.ppm");
+    }
+
+    #[test]
+    #[should_panic(expected = "Script error on line 2: unknown variable 'x'")]
+    fn reports_errors_with_their_line() {
+        Script::parse("let a = 1\ncamera.aperture = x").run(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Script error on line 3: the lower bound 2 of clamp is above its upper bound 1")]
+    fn rejects_clamps_of_reversed_bounds() {
+        Script::parse("let a = 1\n\nlet b = clamp(a, 2, 1)").run(0);
+    }
+}
@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Most steps of a march before the ray is taken to miss
+const MAX_STEPS: usize = 512;
+
+// Shortest step of a march, so rays close to the surface still cross it
+const MIN_STEP: f64 = 1e-4;
+
+// Offset of the samples estimating the gradient
+const GRADIENT_STEP: f64 = 1e-5;
+
+// Signed distance function: negative inside, positive outside, and never
+// more than the distance to the surface, so marching by it can't skip past
+// the surface
+pub enum SdfShape {
+    Sphere { center: Point3, radius: f64 },
+    // Box of the given half extents around its center, with its edges and
+    // corners rounded off by `rounding`
+    Box { center: Point3, half: Vec3, rounding: f64 },
+    // Ring lying flat around the y axis
+    Torus { center: Point3, major_radius: f64, minor_radius: f64 },
+    // Points within radius of the segment from a to b
+    Capsule { a: Point3, b: Point3, radius: f64 },
+    // Combinations blending into each other over `smooth`, sharp when 0
+    Union(Box<SdfShape>, Box<SdfShape>, f64),
+    Intersection(Box<SdfShape>, Box<SdfShape>, f64),
+    Difference(Box<SdfShape>, Box<SdfShape>, f64)
+}
+
+// Polynomial smooth minimum, the plain one for k = 0
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b + h * (a - b) - k * h * (1.0 - h)
+}
+
+impl SdfShape {
+    pub fn distance(&self, p: Point3) -> f64 {
+        match self {
+            SdfShape::Sphere { center, radius } => (p - *center).length() - radius,
+            SdfShape::Box { center, half, rounding } => {
+                let d = p - *center;
+                let q = Vec3::new(d.x().abs() - half.x() + rounding, d.y().abs() - half.y() + rounding, d.z().abs() - half.z() + rounding);
+                let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).length();
+                outside + q.x().max(q.y()).max(q.z()).min(0.0) - rounding
+            },
+            SdfShape::Torus { center, major_radius, minor_radius } => {
+                let d = p - *center;
+                let ring = (d.x() * d.x() + d.z() * d.z()).sqrt() - major_radius;
+                (ring * ring + d.y() * d.y()).sqrt() - minor_radius
+            },
+            SdfShape::Capsule { a, b, radius } => {
+                let pa = p - *a;
+                let ba = *b - *a;
+                let h = if ba.near_zero() { 0.0 } else { (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0) };
+                (pa - h * ba).length() - radius
+            },
+            SdfShape::Union(a, b, k) => smooth_min(a.distance(p), b.distance(p), *k),
+            SdfShape::Intersection(a, b, k) => -smooth_min(-a.distance(p), -b.distance(p), *k),
+            SdfShape::Difference(a, b, k) => -smooth_min(-a.distance(p), b.distance(p), *k)
+        }
+    }
+
+    pub fn bounding_box(&self) -> Aabb {
+        match self {
+            SdfShape::Sphere { center, radius } => {
+                let r = Vec3::new(*radius, *radius, *radius);
+                Aabb::new(*center - r, *center + r)
+            },
+            SdfShape::Box { center, half, .. } => Aabb::new(*center - *half, *center + *half),
+            SdfShape::Torus { center, major_radius, minor_radius } => {
+                let e = Vec3::new(major_radius + minor_radius, *minor_radius, major_radius + minor_radius);
+                Aabb::new(*center - e, *center + e)
+            },
+            SdfShape::Capsule { a, b, radius } => {
+                let r = Vec3::new(*radius, *radius, *radius);
+                Aabb::new(*a - r, *a + r).surrounding(Aabb::new(*b - r, *b + r))
+            },
+            // A smooth union fills in at most a quarter of the blend width
+            // between its shapes
+            SdfShape::Union(a, b, k) => {
+                let pad = Vec3::new(*k, *k, *k) / 4.0;
+                let bbox = a.bounding_box().surrounding(b.bounding_box());
+                Aabb::new(bbox.min - pad, bbox.max + pad)
+            },
+            SdfShape::Intersection(a, b, _) => a.bounding_box().overlap(b.bounding_box()),
+            SdfShape::Difference(a, _, _) => a.bounding_box()
+        }
+    }
+}
+
+// Surface where an SdfShape is zero, found by sphere tracing: the ray
+// advances by the distance to the shape, which can't take it through the
+// surface, until the sign of the distance changes, and the crossing is then
+// refined by bisection. Normals are the distance's gradient.
+pub struct Sdf {
+    shape: SdfShape,
+    bbox: Aabb,
+    mat: Arc<dyn Scatter>
+}
+
+impl Sdf {
+    pub fn new(shape: SdfShape, m: Arc<dyn Scatter>) -> Sdf {
+        let bbox = shape.bounding_box().padded(1e-3);
+        Sdf {
+            shape,
+            bbox,
+            mat: m
+        }
+    }
+
+    // Central differences between the four corners of a tetrahedron
+    fn normal(&self, p: Point3) -> Vec3 {
+        let corners = [Vec3::new(1.0, -1.0, -1.0), Vec3::new(-1.0, -1.0, 1.0), Vec3::new(-1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)];
+        let gradient = corners.iter().fold(Vec3::new(0.0, 0.0, 0.0), |g, &k| g + self.shape.distance(p + GRADIENT_STEP * k) * k);
+        if gradient.near_zero() {
+            return Vec3::new(0.0, 1.0, 0.0);
+        }
+        gradient.normalized()
+    }
+}
+
+// Range of distances along the unit direction d from o inside the box
+fn clip(bbox: &Aabb, o: Point3, d: Vec3) -> Option<(f64, f64)> {
+    let (mut near, mut far) = (f64::NEG_INFINITY, f64::INFINITY);
+    for axis in 0..3 {
+        if d[axis] == 0.0 {
+            if o[axis] < bbox.min[axis] || o[axis] > bbox.max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let t0 = (bbox.min[axis] - o[axis]) / d[axis];
+        let t1 = (bbox.max[axis] - o[axis]) / d[axis];
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+
+    if near <= far { Some((near, far)) } else { None }
+}
+
+// Ray parameter in [t_min, t_max] of the first crossing of the zero set of
+// `distance` within bbox. The function must be negative inside and never
+// more than the distance to its zero set, which any function whose gradient
+// is at most 1 long is.
+pub fn march(r: &Ray, t_min: f64, t_max: f64, bbox: &Aabb, distance: impl Fn(Point3) -> f64) -> Option<f64> {
+    let length = r.direction().length();
+    let d = r.direction() / length;
+    let o = r.origin();
+
+    // Marched in scene units along the unit direction
+    let (near, far) = clip(bbox, o, d)?;
+    let mut s = near.max(t_min * length);
+    let end = far.min(t_max * length);
+    if s > end {
+        return None;
+    }
+
+    let mut dist = distance(o + s * d);
+    let inside = dist < 0.0;
+
+    for _ in 0..MAX_STEPS {
+        if s >= end {
+            return None;
+        }
+        let next = (s + dist.abs().max(MIN_STEP)).min(end);
+        let next_dist = distance(o + next * d);
+        if (next_dist < 0.0) != inside {
+            // Bisection between the last point on the starting side and the
+            // first one past the surface
+            let (mut lo, mut hi) = (s, next);
+            for _ in 0..32 {
+                let mid = 0.5 * (lo + hi);
+                if (distance(o + mid * d) < 0.0) == inside {
+                    lo = mid;
+                } else {
+                    hi = mid;
+                }
+            }
+            return Some(hi / length);
+        }
+
+        s = next;
+        dist = next_dist;
+    }
+
+    None
+}
+
+impl Hit for Sdf {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = march(r, t_min, t_max, &self.bbox, |p| self.shape.distance(p))?;
+        let outward_normal = self.normal(r.at(t));
+        Some(HitRecord::new(r, t, outward_normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use roxmltree::Document;
+
+use super::builtin;
+use super::cli::Options;
+use super::render::{self, RenderSettings};
+use super::vec::Color;
+
+// Headless render server. Jobs are rendered one at a time, in the order
+// they were submitted, by a single worker thread:
+//
+//     POST /render?quality=draft     scene XML as the body, returns the job id
+//     POST /render?builtin=cover     builtin scene, no body
+//     GET  /jobs                     status of every job
+//     GET  /jobs/<id>                status and progress of one job
+//     GET  /jobs/<id>/image          the finished image as a binary PPM
+//
+// Query parameters are the command line options without their dashes, e.g.
+// `?spp=64&scale=0.5&no-denoise`.
+//
+// Submitted scenes can't load plugins, and only read files inside the
+// directory given by --assets. Their samples per pixel and scale are bounded
+// by --max-spp and --max-scale. Images are dropped once fetched, or after
+// IMAGE_TTL if they never are.
+
+// Largest request body read, in bytes
+const MAX_BODY: usize = 16 << 20;
+
+const DEFAULT_MAX_SPP: u64 = 1024;
+const DEFAULT_MAX_SCALE: f64 = 2.0;
+
+const IMAGE_TTL: Duration = Duration::from_secs(60 * 60);
+
+enum Status {
+    Queued,
+    Rendering,
+    // The image and when it was finished
+    Done(Vec<u8>, Instant),
+    Failed(String),
+    // Done, with the image fetched or dropped
+    Expired
+}
+
+struct Job {
+    status: Status,
+    // Scanlines finished and in total
+    progress: Arc<AtomicU64>,
+    height: u64
+}
+
+struct Request {
+    id: u64,
+    opts: Options,
+    xml: String
+}
+
+type Jobs = Arc<Mutex<Vec<Job>>>;
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>
+}
+
+impl Response {
+    fn json(status: &'static str, body: String) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            body: body.into_bytes()
+        }
+    }
+
+    fn error(status: &'static str, message: &str) -> Response {
+        Response::json(status, format!("{{\"error\": \"{}\"}}", json_escape(message)))
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out
+}
+
+fn job_json(id: usize, job: &Job) -> String {
+    let finished = job.progress.load(Ordering::Relaxed);
+    let fraction = if job.height > 0 { (finished as f64 / job.height as f64).min(1.0) } else { 0.0 };
+
+    let (status, progress, error) = match &job.status {
+        Status::Queued => ("queued", 0.0, None),
+        Status::Rendering => ("rendering", fraction, None),
+        Status::Done(..) => ("done", 1.0, None),
+        Status::Failed(message) => ("failed", fraction, Some(message)),
+        Status::Expired => ("expired", 1.0, None)
+    };
+
+    match error {
+        Some(message) => format!("{{\"id\": {}, \"status\": \"{}\", \"progress\": {:.3}, \"error\": \"{}\"}}",
+                                 id, status, progress, json_escape(message)),
+        None => format!("{{\"id\": {}, \"status\": \"{}\", \"progress\": {:.3}}}", id, status, progress)
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "render failed".to_string()
+    }
+}
+
+// Binary PPM of a finished render
+fn encode(pixels: &[Color], settings: &RenderSettings) -> Vec<u8> {
+    let (width, height) = settings.output_size();
+    let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for pixel in settings.output(pixels) {
+        out.extend_from_slice(&pixel.to_rgb8(settings.samples_per_pixel));
+    }
+    out
+}
+
+fn render_job(request: &Request, jobs: &Jobs) -> Result<Vec<u8>, String> {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let (_, mut scene) = match &request.opts.builtin {
+            Some(name) => builtin::builtin(name).ok_or_else(|| format!("Unknown builtin scene '{}'.", name))?,
+            None => super::xml_parser(&request.xml)
+        };
+
+        let mut settings = request.opts.settings(&scene);
+        if let Some(path) = &scene.camera_path {
+            scene.camera = path.camera(settings.frame);
+        }
+
+        {
+            let mut jobs = jobs.lock().unwrap();
+            let job = &mut jobs[request.id as usize];
+            job.status = Status::Rendering;
+            job.height = settings.image_height;
+            settings.progress = Some(job.progress.clone());
+        }
+
+        let pixels = render::render(&scene, &settings);
+        Ok(encode(&pixels, &settings))
+    }));
+
+    result.unwrap_or_else(|payload| Err(panic_message(payload)))
+}
+
+fn worker(requests: Receiver<Request>, jobs: Jobs) {
+    for request in requests {
+        let status = match render_job(&request, &jobs) {
+            Ok(image) => Status::Done(image, Instant::now()),
+            Err(message) => Status::Failed(message)
+        };
+        eprintln!("Job {} finished.", request.id);
+        jobs.lock().unwrap()[request.id as usize].status = status;
+    }
+}
+
+// Turns `?spp=64&no-denoise` into `--spp 64 --no-denoise`
+fn query_args(query: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        args.push(format!("--{}", key));
+        if !value.is_empty() {
+            args.push(value.replace("%2C", ",").replace("%2c", ","));
+        }
+    }
+    args
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('"', "&quot;")
+}
+
+// The scene with the files it reads resolved in the assets directory, or
+// why it can't be rendered here
+fn sandbox(xml: &str, assets: Option<&Path>) -> Result<String, String> {
+    let doc = Document::parse(xml).map_err(|e| format!("Failed to parse XML: {}", e))?;
+    if doc.descendants().any(|n| n.has_tag_name("plugin")) {
+        return Err("Scenes sent to the server can't load plugins.".to_string());
+    }
+
+    let mut out = String::with_capacity(xml.len());
+    let mut end = 0;
+    for attr in super::input_files(&doc) {
+        let Some(assets) = assets else {
+            return Err(format!("The scene reads the file '{}', start the server with --assets to allow files.", attr.value()));
+        };
+
+        // Relative paths going down from the directory, and only reaching
+        // into it through links
+        let relative = Path::new(attr.value());
+        let path = assets.join(relative);
+        let inside = relative.components().all(|c| matches!(c, Component::Normal(_)))
+            && path.canonicalize().map(|p| p.starts_with(assets)).unwrap_or(false);
+        if !inside {
+            return Err(format!("The file '{}' isn't in the assets directory.", attr.value()));
+        }
+
+        let range = attr.value_range();
+        out.push_str(&xml[end..range.start]);
+        out.push_str(&xml_escape(&path.to_string_lossy()));
+        end = range.end;
+    }
+    out.push_str(&xml[end..]);
+    Ok(out)
+}
+
+fn submit(query: &str, body: Vec<u8>, server: &Server) -> Response {
+    let args = query_args(query);
+    let opts = match panic::catch_unwind(|| Options::parse(args.into_iter())) {
+        Ok(opts) => opts,
+        Err(payload) => return Response::error("400 Bad Request", &panic_message(payload))
+    };
+    if opts.samples_per_pixel() > server.max_spp {
+        let message = format!("Jobs can't ask for more than {} samples per pixel.", server.max_spp);
+        return Response::error("400 Bad Request", &message);
+    }
+    if opts.image_scale() > server.max_scale {
+        let message = format!("Jobs can't scale the film by more than {}.", server.max_scale);
+        return Response::error("400 Bad Request", &message);
+    }
+
+    let xml = match String::from_utf8(body) {
+        Ok(xml) => xml,
+        Err(_) => return Response::error("400 Bad Request", "The scene must be UTF-8 XML.")
+    };
+    if opts.builtin.is_none() && xml.trim().is_empty() {
+        return Response::error("400 Bad Request", "Missing scene XML in the request body.");
+    }
+    let xml = match opts.builtin {
+        Some(_) => xml,
+        None => match sandbox(&xml, server.assets.as_deref()) {
+            Ok(xml) => xml,
+            Err(message) => return Response::error("403 Forbidden", &message)
+        }
+    };
+
+    let id = {
+        let mut jobs = server.jobs.lock().unwrap();
+        jobs.push(Job {
+            status: Status::Queued,
+            progress: Arc::new(AtomicU64::new(0)),
+            height: 0
+        });
+        jobs.len() - 1
+    };
+
+    server.queue.send(Request { id: id as u64, opts, xml }).expect("Render worker stopped.");
+    Response::json("202 Accepted", format!("{{\"id\": {}}}", id))
+}
+
+// Drops the images left unfetched for longer than IMAGE_TTL
+fn expire(jobs: &mut [Job]) {
+    for job in jobs {
+        if matches!(job.status, Status::Done(_, finished) if finished.elapsed() > IMAGE_TTL) {
+            job.status = Status::Expired;
+        }
+    }
+}
+
+fn route(method: &str, target: &str, body: Vec<u8>, server: &Server) -> Response {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    expire(&mut server.jobs.lock().unwrap());
+
+    match (method, parts.as_slice()) {
+        ("POST", ["render"]) => submit(query, body, server),
+        ("GET", ["jobs"]) => {
+            let jobs = server.jobs.lock().unwrap();
+            let list: Vec<String> = jobs.iter().enumerate().map(|(id, job)| job_json(id, job)).collect();
+            Response::json("200 OK", format!("[{}]", list.join(", ")))
+        },
+        ("GET", ["jobs", id]) | ("GET", ["jobs", id, "image"]) => {
+            let mut jobs = server.jobs.lock().unwrap();
+            let (id, job) = match id.parse::<usize>().ok().and_then(|id| jobs.get_mut(id).map(|job| (id, job))) {
+                Some(job) => job,
+                None => return Response::error("404 Not Found", "No such job.")
+            };
+
+            if parts.len() == 2 {
+                return Response::json("200 OK", job_json(id, job));
+            }
+
+            if let Status::Done(..) = job.status {
+                let Status::Done(image, _) = std::mem::replace(&mut job.status, Status::Expired) else {
+                    unreachable!()
+                };
+                return Response {
+                    status: "200 OK",
+                    content_type: "image/x-portable-pixmap",
+                    body: image
+                };
+            }
+            match &job.status {
+                Status::Failed(message) => Response::error("409 Conflict", message),
+                Status::Expired => Response::error("410 Gone", "The image was already fetched or has expired."),
+                _ => Response::error("409 Conflict", "The job hasn't finished yet.")
+            }
+        },
+        ("GET", _) | ("POST", _) => Response::error("404 Not Found", "Unknown endpoint."),
+        _ => Response::error("405 Method Not Allowed", "Only GET and POST are supported.")
+    }
+}
+
+fn respond(mut stream: TcpStream, response: Response) -> std::io::Result<()> {
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+           response.status, response.content_type, response.body.len())?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+struct HttpRequest {
+    method: String,
+    target: String,
+    body: Vec<u8>
+}
+
+// The request read from a connection, or the response refusing it
+fn read_request(reader: &mut impl BufRead) -> std::io::Result<Result<HttpRequest, Response>> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let target = parts.next().unwrap_or("/").to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+    if length > MAX_BODY {
+        let message = format!("Request bodies can't be over {} bytes.", MAX_BODY);
+        return Ok(Err(Response::error("413 Payload Too Large", &message)));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Ok(HttpRequest { method, target, body }))
+}
+
+fn handle(stream: TcpStream, server: &Server) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut reader)? {
+        Ok(request) => route(&request.method, &request.target, request.body, server),
+        Err(response) => response
+    };
+    respond(stream, response)
+}
+
+// What the connections share
+struct Server {
+    jobs: Jobs,
+    queue: Sender<Request>,
+    // Directory the files read by submitted scenes must be in, None if they
+    // can't read any
+    assets: Option<PathBuf>,
+    max_spp: u64,
+    max_scale: f64
+}
+
+// `rt serve [--port 8080] [--bind 127.0.0.1] [--assets <dir>] [--max-spp 1024] [--max-scale 2]`
+pub fn serve(mut args: impl Iterator<Item = String>) {
+    let mut port = 8080;
+    let mut bind = "127.0.0.1".to_string();
+    let mut assets = None;
+    let mut max_spp = DEFAULT_MAX_SPP;
+    let mut max_scale = DEFAULT_MAX_SCALE;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => {
+                port = args.next()
+                    .and_then(|p| p.parse().ok())
+                    .expect("Failed to parse value for --port.");
+            },
+            "--bind" => bind = args.next().expect("Missing value for --bind."),
+            "--assets" => {
+                let dir = args.next().expect("Missing value for --assets.");
+                assets = Some(Path::new(&dir).canonicalize()
+                    .unwrap_or_else(|_| panic!("The assets directory '{}' doesn't exists!", dir)));
+            },
+            "--max-spp" => {
+                max_spp = args.next()
+                    .and_then(|n| n.parse().ok())
+                    .expect("Failed to parse value for --max-spp.");
+            },
+            "--max-scale" => {
+                max_scale = args.next()
+                    .and_then(|s| s.parse().ok())
+                    .expect("Failed to parse value for --max-scale.");
+            },
+            _ => panic!("Unknown option '{}' for serve.", arg)
+        }
+    }
+
+    let listener = TcpListener::bind((bind.as_str(), port))
+        .unwrap_or_else(|_| panic!("Unable to listen on {}:{}.", bind, port));
+    eprintln!("Listening on {}:{}", bind, port);
+
+    let jobs: Jobs = Arc::new(Mutex::new(Vec::new()));
+    let (queue, requests) = mpsc::channel();
+    {
+        let jobs = jobs.clone();
+        thread::spawn(move || worker(requests, jobs));
+    }
+    let server = Arc::new(Server { jobs, queue, assets, max_spp, max_scale });
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue
+        };
+
+        let server = server.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &server) {
+                eprintln!("Request failed: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(assets: Option<PathBuf>) -> (Server, Receiver<Request>) {
+        let (queue, requests) = mpsc::channel();
+        let server = Server {
+            jobs: Arc::new(Mutex::new(Vec::new())),
+            queue,
+            assets,
+            max_spp: DEFAULT_MAX_SPP,
+            max_scale: DEFAULT_MAX_SCALE
+        };
+        (server, requests)
+    }
+
+    #[test]
+    fn reads_requests() {
+        let raw = b"POST /render?spp=4 HTTP/1.1\r\nHost: localhost\r\nCONTENT-LENGTH: 5\r\n\r\n<RT/>trailing";
+        let Ok(Ok(request)) = read_request(&mut &raw[..]) else {
+            panic!("The request wasn't read.");
+        };
+        assert_eq!((request.method.as_str(), request.target.as_str()), ("POST", "/render?spp=4"));
+        assert_eq!(request.body, b"<RT/>");
+
+        let raw = format!("POST /render HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY + 1);
+        match read_request(&mut raw.as_bytes()) {
+            Ok(Err(response)) => assert_eq!(response.status, "413 Payload Too Large"),
+            _ => panic!("An oversized body was accepted.")
+        }
+
+        assert_eq!(query_args("spp=64&no-denoise&&aov=depth%2Cnormal"), ["--spp", "64", "--no-denoise", "--aov", "depth,normal"]);
+    }
+
+    #[test]
+    fn refuses_jobs_over_the_limits() {
+        let (server, _requests) = server(None);
+        let status = |query: &str| submit(query, Vec::new(), &server).status;
+
+        assert_eq!(status("builtin=cover&spp=1025"), "400 Bad Request");
+        assert_eq!(status("builtin=cover&scale=2.5"), "400 Bad Request");
+        assert_eq!(status("builtin=cover&spp=1024&scale=2"), "202 Accepted");
+        assert_eq!(status("builtin=cover&quality=final"), "202 Accepted");
+        assert_eq!(status("builtin=cover&spp=lots"), "400 Bad Request");
+        assert_eq!(server.jobs.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn sandboxes_submitted_scenes() {
+        let mesh = |filename: &str| format!(r#"<RT><object type="mesh" filename="{}"/></RT>"#, filename);
+        let assets = Path::new(&crate::fixtures::path("")).canonicalize().unwrap();
+
+        let plugin = sandbox(r#"<RT><plugin filename="libglow.so"/></RT>"#, Some(&assets)).unwrap_err();
+        assert!(plugin.contains("can't load plugins"), "{}", plugin);
+        let no_assets = sandbox(&mesh("square.obj"), None).unwrap_err();
+        assert!(no_assets.contains("--assets"), "{}", no_assets);
+
+        let inside = sandbox(&mesh("square.obj"), Some(&assets)).unwrap();
+        assert!(inside.contains(&assets.join("square.obj").to_string_lossy().into_owned()), "{}", inside);
+        for outside in ["../fixtures/square.obj", "/etc/passwd", "./square.obj", "missing.obj"] {
+            assert!(sandbox(&mesh(outside), Some(&assets)).is_err(), "'{}' was let through", outside);
+        }
+
+        let (server, _requests) = server(Some(assets));
+        assert_eq!(submit("", mesh("../../Cargo.toml").into_bytes(), &server).status, "403 Forbidden");
+    }
+}
@@ -0,0 +1,93 @@
+// Emission spectra for lights. The renderer itself works in linear sRGB, so
+// a spectrum is reduced to the color a camera with the CIE 1931 observer's
+// response would see, at unit luminance. D65 is the sRGB white point and
+// comes out as white.
+//
+// Also included by build.rs, so this file can't depend on the rest of the
+// crate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Spectrum {
+    D65,
+    // Illuminant E, the same power at every wavelength
+    Equal,
+    // Planck's law at a temperature in kelvins
+    Blackbody(f64)
+}
+
+// CIE standard illuminant D65 from 380 to 780 nm in 10 nm steps
+const D65: [f64; 41] = [
+    49.98, 54.65, 82.75, 91.49, 93.43, 86.68, 104.86, 117.01, 117.81, 114.86,
+    115.92, 108.81, 109.35, 107.80, 104.79, 107.69, 104.41, 104.05, 100.00, 96.33,
+    95.79, 88.69, 90.01, 89.60, 87.70, 83.29, 83.70, 80.03, 80.21, 82.28,
+    78.28, 69.72, 71.61, 74.35, 61.60, 69.89, 75.09, 63.59, 46.42, 66.81,
+    63.38
+];
+
+impl Spectrum {
+    // From a spectrum attribute, with the temperature blackbodies need
+    pub fn from_name(name: &str, temperature: Option<f64>) -> Option<Spectrum> {
+        match name {
+            "d65" | "D65" => Some(Spectrum::D65),
+            "e" | "E" | "equal" => Some(Spectrum::Equal),
+            "blackbody" => temperature.map(Spectrum::Blackbody),
+            _ => None
+        }
+    }
+
+    // Relative power at a wavelength in nanometres
+    fn power(&self, wavelength: f64) -> f64 {
+        match *self {
+            Spectrum::D65 => {
+                let x = (wavelength - 380.0) / 10.0;
+                let k = (x.floor() as usize).min(D65.len() - 2);
+                let f = x - k as f64;
+                D65[k] * (1.0 - f) + D65[k + 1] * f
+            },
+            Spectrum::Equal => 1.0,
+            Spectrum::Blackbody(kelvin) => {
+                const H: f64 = 6.62607015e-34;
+                const C: f64 = 2.99792458e8;
+                const K: f64 = 1.380649e-23;
+
+                let l = wavelength * 1e-9;
+                2.0 * H * C * C / (l.powi(5) * ((H * C / (l * K * kelvin)).exp() - 1.0))
+            }
+        }
+    }
+
+    // Linear sRGB at unit luminance. Colors outside the sRGB gamut, like
+    // the reds of cool blackbodies, lose their negative components.
+    pub fn rgb(&self) -> [f64; 3] {
+        let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+        for nm in 380..=780 {
+            let wavelength = nm as f64;
+            let power = self.power(wavelength);
+            let [cx, cy, cz] = observer(wavelength);
+            x += power * cx;
+            y += power * cy;
+            z += power * cz;
+        }
+        let (x, z) = (x / y, z / y);
+
+        [
+            (3.2406 * x - 1.5372 - 0.4986 * z).max(0.0),
+            (-0.9689 * x + 1.8758 + 0.0415 * z).max(0.0),
+            (0.0557 * x - 0.2040 + 1.0570 * z).max(0.0)
+        ]
+    }
+}
+
+// The CIE 1931 2 degree color matching functions, from the piecewise
+// Gaussian fit of Wyman, Sloan and Shirley (2013)
+fn observer(wavelength: f64) -> [f64; 3] {
+    let g = |mean: f64, below: f64, above: f64| {
+        let t = (wavelength - mean) / if wavelength < mean { below } else { above };
+        (-0.5 * t * t).exp()
+    };
+
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8)
+    ]
+}
@@ -1,9 +1,19 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
-use super::vec::{Vec3, Point3};
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
 use super::ray::Ray;
 use super::hit::{Hit, HitRecord};
 use super::material::Scatter;
+use super::packet::{RayPacket, MAX_LANES};
+
+// Texture coordinates of the unit normal n, those surface_at takes to it
+pub fn uv(n: Vec3) -> (f64, f64) {
+    let u = n.z().atan2(-n.x()).rem_euclid(2.0 * PI) / (2.0 * PI);
+    let v = (-n.y()).clamp(-1.0, 1.0).acos() / PI;
+    (u, v)
+}
 
 pub struct Sphere {
     center: Point3,
@@ -19,6 +29,11 @@ impl Sphere {
             mat: m
         }
     }
+
+    fn record(&self, r: &Ray, root: f64) -> HitRecord {
+        let outward_normal = (r.at(root) - self.center) / self.radius;
+        HitRecord::new(r, root, outward_normal, self.mat.clone())
+    }
 }
 
 impl Hit for Sphere {
@@ -43,17 +58,55 @@ impl Hit for Sphere {
             }
         }
 
-        let mut rec = HitRecord {
-            t: root,
-            p: r.at(root),
-            mat: self.mat.clone(),
-            normal: Vec3::new(0.0, 0.0, 0.0),
-            front_face: false
-        };
+        Some(self.record(r, root))
+    }
 
-        let outward_normal = (rec.p - self.center) / self.radius;
-        rec.set_face_normal(r, outward_normal);
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - r, self.center + r))
+    }
+
+    // u goes around from -x, v up from the south pole
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let phi = 2.0 * PI * u;
+        let theta = PI * v;
+        let n = Vec3::new(-phi.cos() * theta.sin(), -theta.cos(), phi.sin() * theta.sin());
+
+        Some(HitRecord::facing(self.center + self.radius * n, n, self.mat.clone()))
+    }
 
-        Some(rec)
+    fn hit_packet(&self, p: &RayPacket, t_min: f64, t_max: &mut [f64; MAX_LANES], recs: &mut [Option<HitRecord>]) {
+        // Same quadratic as hit, evaluated for all lanes at once
+        let mut roots = [f64::NAN; MAX_LANES];
+
+        for k in 0..MAX_LANES {
+            let ocx = p.ox[k] - self.center.x();
+            let ocy = p.oy[k] - self.center.y();
+            let ocz = p.oz[k] - self.center.z();
+
+            let a = p.dx[k] * p.dx[k] + p.dy[k] * p.dy[k] + p.dz[k] * p.dz[k];
+            let half_b = ocx * p.dx[k] + ocy * p.dy[k] + ocz * p.dz[k];
+            let c = ocx * ocx + ocy * ocy + ocz * ocz - self.radius * self.radius;
+
+            // A negative discriminant yields NaN roots, which fail every test below
+            let sqrtd = (half_b * half_b - a * c).sqrt();
+            let near = (-half_b - sqrtd) / a;
+            let far = (-half_b + sqrtd) / a;
+
+            roots[k] = if near >= t_min && near <= t_max[k] {
+                near
+            } else if far >= t_min && far <= t_max[k] {
+                far
+            } else {
+                f64::NAN
+            };
+        }
+
+        for k in 0..p.lanes {
+            if !roots[k].is_nan() {
+                t_max[k] = roots[k];
+                recs[k] = Some(self.record(&p.ray(k), roots[k]));
+            }
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,150 @@
+use super::mesh::{Face, MeshData};
+use super::vec::{Point3, Vec3};
+
+// STL reader, ASCII or binary. Every facet is a triangle of its own, with no
+// vertices shared between them; its normal, where the file gives one,
+// decides which side is the front, and the counter-clockwise order of the
+// corners does otherwise.
+pub fn load(filename: &str) -> MeshData {
+    let bytes = std::fs::read(filename)
+        .unwrap_or_else(|_| panic!("Unable to read STL file {}.", filename));
+
+    // Binary files can start with "solid" too, so only those that parse as
+    // ASCII are read as such. Binary ones hold at least the facets their
+    // header counts, and some exporters pad them past those.
+    let count = (bytes.len() >= 84).then(|| u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize);
+    let binary = count.filter(|&count| bytes.len() - 84 >= 50 * count);
+    let facets = match (parse_ascii(&bytes), binary) {
+        (Some(facets), binary) if !facets.is_empty() || binary.is_none() => Some(facets),
+        (_, Some(count)) => Some(parse_binary(&bytes, count)),
+        (_, None) => None
+    };
+
+    let facets = facets.unwrap_or_else(|| panic!("Failed to parse STL file {}.", filename));
+    if facets.is_empty() {
+        panic!("No facets found in STL file {}.", filename);
+    }
+
+    let mut data = MeshData::default();
+    for (normal, corners) in facets {
+        let first = data.positions.len();
+        data.positions.extend(corners);
+
+        let vertices = [first, first + 1, first + 2];
+        let normals = (!normal.near_zero()).then(|| {
+            data.normals.push(normal);
+            [data.normals.len() - 1; 3]
+        });
+        data.faces.push(Face { vertices, normals });
+    }
+    data
+}
+
+type Facet = (Vec3, [Point3; 3]);
+
+fn parse_binary(bytes: &[u8], count: usize) -> Vec<Facet> {
+    let float = |pos: usize| f32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]]) as f64;
+    let vector = |pos: usize| Vec3::new(float(pos), float(pos + 4), float(pos + 8));
+
+    // 12 floats and 2 bytes of attributes per facet
+    (0..count).map(|k| {
+        let pos = 84 + 50 * k;
+        (vector(pos), [vector(pos + 12), vector(pos + 24), vector(pos + 36)])
+    }).collect()
+}
+
+// solid name
+//   facet normal nx ny nz
+//     outer loop
+//       vertex x y z (three times)
+//     endloop
+//   endfacet
+// endsolid name
+fn parse_ascii(bytes: &[u8]) -> Option<Vec<Facet>> {
+    let text = std::str::from_utf8(bytes).ok()?;
+    let mut tokens = text.split_whitespace();
+    if tokens.next()? != "solid" {
+        return None;
+    }
+
+    let vector = |tokens: &mut std::str::SplitWhitespace| -> Option<Vec3> {
+        let mut v = [0.0; 3];
+        for x in v.iter_mut() {
+            *x = tokens.next()?.parse().ok()?;
+        }
+        Some(Vec3::new(v[0], v[1], v[2]))
+    };
+
+    let mut facets = Vec::new();
+    let mut normal = Vec3::new(0.0, 0.0, 0.0);
+    let mut corners = Vec::with_capacity(3);
+    while let Some(token) = tokens.next() {
+        match token {
+            "normal" => normal = vector(&mut tokens)?,
+            "vertex" => corners.push(vector(&mut tokens)?),
+            "endfacet" => {
+                if corners.len() != 3 {
+                    return None;
+                }
+                facets.push((normal, [corners[0], corners[1], corners[2]]));
+                normal = Vec3::new(0.0, 0.0, 0.0);
+                corners.clear();
+            },
+            _ => { }
+        }
+    }
+    Some(facets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    // Binary STL of the two facets of the unit square
+    fn binary_square() -> Vec<u8> {
+        let mut bytes = vec![0; 80];
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        for corners in [[[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]], [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]] {
+            for v in [[0.0, 0.0, 1.0]].iter().chain(corners.iter()) {
+                v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes()));
+            }
+            bytes.extend_from_slice(&[0, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn loads_ascii() {
+        let data = load(&fixtures::path("square.stl"));
+        assert_eq!(data.positions.len(), 6);
+        assert_eq!(data.positions[4], Point3::new(1.0, 1.0, 0.0));
+        assert_eq!(data.normals, vec![Vec3::new(0.0, 0.0, 1.0); 2]);
+        assert_eq!(data.faces[1].vertices, [3, 4, 5]);
+    }
+
+    #[test]
+    fn loads_binary_starting_with_solid() {
+        let mut bytes = binary_square();
+        bytes[..5].copy_from_slice(b"solid");
+        let data = load(&fixtures::write("solid.stl", &bytes));
+        assert_eq!(data.faces.len(), 2);
+        assert_eq!(data.positions[5], Point3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn loads_padded_binary() {
+        let mut bytes = binary_square();
+        bytes[..5].copy_from_slice(b"solid");
+        bytes.extend_from_slice(&[0; 30]);
+        let data = load(&fixtures::write("padded.stl", &bytes));
+        assert_eq!(data.faces.len(), 2);
+        assert_eq!(data.positions[2], Point3::new(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to parse STL file")]
+    fn rejects_facets_without_three_corners() {
+        load(&fixtures::write("corners.stl", b"solid s\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nendloop\nendfacet\nendsolid s\n"));
+    }
+}
@@ -0,0 +1,82 @@
+use rand::Rng;
+
+use super::hit::Hit;
+use super::material;
+use super::ray::Ray;
+use super::sampler;
+use super::scene::Scene;
+use super::vec::{Color, Vec3};
+
+// Walks longer than this are dropped, which darkens only very thick,
+// barely absorbing media
+const MAX_STEPS: usize = 256;
+
+// Homogeneous scattering interior of a subsurface material
+pub struct Medium {
+    // Extinction coefficient per channel, the inverse of the mean free path
+    pub sigma_t: Color,
+    // Fraction of the extinction that is scattering rather than absorption
+    pub albedo: Color
+}
+
+impl Medium {
+    pub fn new(mean_free_path: Color, albedo: Color) -> Medium {
+        Medium {
+            sigma_t: Color::new(1.0 / mean_free_path.x(), 1.0 / mean_free_path.y(), 1.0 / mean_free_path.z()),
+            albedo
+        }
+    }
+
+    fn transmittance(&self, distance: f64) -> Color {
+        Color::new((-self.sigma_t.x() * distance).exp(),
+                   (-self.sigma_t.y() * distance).exp(),
+                   (-self.sigma_t.z() * distance).exp())
+    }
+}
+
+fn average(c: Color) -> f64 {
+    (c.x() + c.y() + c.z()) / 3.0
+}
+
+// Random walk through the medium from r, which has just entered the object,
+// to where it leaves through the boundary. Distances are sampled in a
+// randomly chosen channel and weighted by the average over the channels,
+// so each channel keeps its own mean free path. Returns the throughput of
+// the walk and the outgoing ray, or None if the walk was lost.
+pub fn walk(r: &Ray, medium: &Medium, scene: &Scene) -> Option<(Color, Ray)> {
+    let mut rng = sampler::rng();
+    let mut ray = Ray::with_time(r.origin(), r.direction().normalized(), r.time()).with_type(r.ray_type());
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+
+    for _ in 0..MAX_STEPS {
+        // Open geometry lets the walk escape into nothing
+        let mut rec = scene.world.hit(&ray, 0.001, f64::INFINITY)?;
+        material::resolve(&ray, &mut rec);
+
+        let channel = rng.gen_range(0..3);
+        let distance = -(1.0 - rng.gen::<f64>()).ln() / medium.sigma_t[channel];
+
+        if distance < rec.t {
+            let tr = medium.transmittance(distance);
+            throughput *= medium.albedo * medium.sigma_t * tr / average(medium.sigma_t * tr);
+
+            // Isotropic phase function
+            ray = Ray::with_time(ray.at(distance), Vec3::random_in_unit_sphere().normalized(), ray.time()).with_type(r.ray_type());
+        } else {
+            let tr = medium.transmittance(rec.t);
+            throughput *= tr / average(tr);
+
+            // The boundary reflects back inside or refracts out
+            let (attenuation, next) = rec.mat.scatter(&ray, &rec)?;
+            throughput *= attenuation;
+
+            let outward = if rec.front_face { rec.normal } else { (-1.0) * rec.normal };
+            if next.direction().dot(outward) > 0.0 {
+                return Some((throughput, next));
+            }
+            ray = Ray::with_time(next.origin(), next.direction().normalized(), next.time()).with_type(r.ray_type());
+        }
+    }
+
+    None
+}
@@ -0,0 +1,101 @@
+use rayon::prelude::*;
+
+use super::aov::{self, PrimaryHit};
+use super::camera::Camera;
+use super::render::RenderSettings;
+use super::scene::Scene;
+use super::vec::Color;
+
+// History is reused for at most this many times the frame's own samples
+const HISTORY_LIMIT: f64 = 8.0;
+// Reprojected surfaces must match within this fraction of their depth
+const POSITION_TOLERANCE: f64 = 0.01;
+
+// Accumulated result of the previous frames
+pub struct History {
+    camera: Camera,
+    // Average color and number of samples behind it, per pixel
+    color: Vec<Color>,
+    weight: Vec<f64>,
+    hits: Vec<Option<PrimaryHit>>
+}
+
+// Average color and sample count the history holds for the surface seen
+// through a pixel, looked up bilinearly where the previous camera saw it.
+// Neighbours that saw another object, or another part of it, are rejected
+// so disoccluded pixels start over.
+fn reproject(history: &History, settings: &RenderSettings, hit: &PrimaryHit) -> Option<(Color, f64)> {
+    let width = settings.image_width as i64;
+    let height = settings.image_height as i64;
+
+    let (s, t) = history.camera.project(hit.p)?;
+    let (x, y) = settings.pixel_coords(s, t);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let mut color = Color::new(0.0, 0.0, 0.0);
+    let mut weight = 0.0;
+    let mut total = 0.0;
+    for (dx, dy, w) in [(0, 0, (1.0 - fx) * (1.0 - fy)), (1, 0, fx * (1.0 - fy)), (0, 1, (1.0 - fx) * fy), (1, 1, fx * fy)] {
+        let i = x0 as i64 + dx;
+        let j = y0 as i64 + dy;
+        if i < 0 || j < 0 || i >= width || j >= height || w <= 0.0 {
+            continue;
+        }
+
+        let m = ((height - 1 - j) * width + i) as usize;
+        let matches = history.hits[m].as_ref().is_some_and(|prev| {
+            prev.object_id == hit.object_id && (prev.p - hit.p).length() < POSITION_TOLERANCE * hit.depth
+        });
+
+        if matches {
+            color += w * history.color[m];
+            weight += w * history.weight[m];
+            total += w;
+        }
+    }
+
+    if total > 0.0 {
+        Some((color / total, weight / total))
+    } else {
+        None
+    }
+}
+
+// Blends the samples of a frame with what the previous frames saw of the same
+// surfaces and returns the sums over settings.samples_per_pixel samples, like
+// a raw render, along with the history for the next frame. Only valid for
+// static scenes: objects are assumed to stay where the history saw them, and
+// view-dependent shading such as reflections lags behind the camera.
+pub fn accumulate(scene: &Scene, settings: &RenderSettings, pixels: &[Color], previous: Option<&History>) -> (Vec<Color>, History) {
+    let spp = settings.samples_per_pixel as f64;
+    let hits = aov::primary_hits(scene, settings, &scene.camera.pinhole());
+
+    let blended: Vec<(Color, f64)> = (0..pixels.len())
+        .into_par_iter()
+        .map(|n| {
+            let reused = match (previous, &hits[n]) {
+                (Some(history), Some(hit)) => reproject(history, settings, hit),
+                _ => None
+            };
+
+            match reused {
+                Some((color, weight)) => {
+                    let weight = weight.min(HISTORY_LIMIT * spp);
+                    ((pixels[n] + weight * color) / (spp + weight), spp + weight)
+                },
+                None => (pixels[n] / spp, spp)
+            }
+        })
+        .collect();
+
+    let sums = blended.iter().map(|&(color, _)| spp * color).collect();
+    let history = History {
+        camera: scene.camera.pinhole(),
+        color: blended.iter().map(|&(color, _)| color).collect(),
+        weight: blended.iter().map(|&(_, weight)| weight).collect(),
+        hits
+    };
+
+    (sums, history)
+}
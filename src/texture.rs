@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use super::camera::Camera;
+use super::hit::HitRecord;
+use super::image::Image;
+use super::sphere;
+use super::vec::{Color, Point3};
+
+pub trait Texture : Send + Sync {
+    fn value(&self, rec: &HitRecord) -> Color;
+}
+
+pub struct SolidColor {
+    color: Color
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor {
+            color
+        }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _rec: &HitRecord) -> Color {
+        self.color
+    }
+}
+
+// Latitude-longitude image wrapped around a center, its top row over +y, in
+// the texture coordinates of spheres; for maps of planets
+pub struct LatLong {
+    image: Arc<Image>,
+    center: Point3
+}
+
+impl LatLong {
+    pub fn new(image: Arc<Image>, center: Point3) -> LatLong {
+        LatLong {
+            image,
+            center
+        }
+    }
+}
+
+impl Texture for LatLong {
+    fn value(&self, rec: &HitRecord) -> Color {
+        let (u, v) = sphere::uv((rec.p - self.center).normalized());
+        self.image.sample(u, 1.0 - v)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Blend {
+    // The image replaces the texture below it
+    Over,
+    // The image darkens the texture below it, e.g. for dirt maps
+    Multiply
+}
+
+// An image cast onto the surface from a projector, like a slide projector
+// or a decal, over the texture below. Points outside the projector's frustum
+// or facing away from it keep the texture below.
+pub struct Projected {
+    below: Arc<dyn Texture>,
+    image: Arc<Image>,
+    projector: Camera,
+    opacity: f64,
+    blend: Blend
+}
+
+impl Projected {
+    pub fn new(below: Arc<dyn Texture>, image: Arc<Image>, projector: Camera, opacity: f64, blend: Blend) -> Projected {
+        Projected {
+            below,
+            image,
+            projector,
+            opacity: opacity.clamp(0.0, 1.0),
+            blend
+        }
+    }
+}
+
+impl Texture for Projected {
+    fn value(&self, rec: &HitRecord) -> Color {
+        let below = self.below.value(rec);
+        if rec.normal.dot(rec.p - self.projector.origin()) >= 0.0 {
+            return below;
+        }
+
+        let (s, t) = match self.projector.project(rec.p) {
+            Some((s, t)) if (0.0..=1.0).contains(&s) && (0.0..=1.0).contains(&t) => (s, t),
+            _ => return below
+        };
+
+        // Film coordinates run up from the bottom, images down from the top
+        let color = self.image.sample(s, 1.0 - t);
+        let projected = match self.blend {
+            Blend::Over => color,
+            Blend::Multiply => color * below
+        };
+        (1.0 - self.opacity) * below + self.opacity * projected
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use rayon::prelude::*;
+
+use super::render::{self, RenderSettings};
+use super::scene::Scene;
+
+// Width and height of the tiles handed to the render threads
+const TILE_SIZE: u64 = 32;
+
+// Samples per probed pixel of the variance pre-pass
+const PROBE_SAMPLES: u64 = 4;
+
+// Pixels probed per tile side by the variance pre-pass
+const PROBES: u64 = 4;
+
+// Order the render threads take the tiles in, which is the order the image
+// fills in; the result is the same in every order
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TileOrder {
+    // Rows of tiles from the top
+    Scanline,
+    // Rings of tiles around the center tile, from the middle out
+    Spiral,
+    // The noisiest tiles of a short pre-pass first
+    Variance
+}
+
+impl TileOrder {
+    pub fn from_name(name: &str) -> Option<TileOrder> {
+        match name {
+            "scanline" => Some(TileOrder::Scanline),
+            "spiral" => Some(TileOrder::Spiral),
+            "variance" => Some(TileOrder::Variance),
+            _ => None
+        }
+    }
+}
+
+// Pixel rectangle [x0, x1) x [y0, y1), rows counted from the top
+#[derive(Clone, Copy)]
+struct Tile {
+    x0: u64,
+    y0: u64,
+    x1: u64,
+    y1: u64
+}
+
+// The image's tiles, or their parts inside the bucket; buckets can't be
+// combined with overscan, so they are in buffer pixels as well
+fn tiles(settings: &RenderSettings) -> Vec<Tile> {
+    let (bx0, by0, bx1, by1) = match settings.bucket {
+        Some(b) => (b.x, b.y, b.x + b.width, b.y + b.height),
+        None => (0, 0, settings.image_width, settings.image_height)
+    };
+
+    let mut tiles = Vec::new();
+    for y0 in (0..settings.image_height).step_by(TILE_SIZE as usize) {
+        for x0 in (0..settings.image_width).step_by(TILE_SIZE as usize) {
+            let tile = Tile {
+                x0: x0.max(bx0),
+                y0: y0.max(by0),
+                x1: (x0 + TILE_SIZE).min(settings.image_width).min(bx1),
+                y1: (y0 + TILE_SIZE).min(settings.image_height).min(by1)
+            };
+            if tile.x0 < tile.x1 && tile.y0 < tile.y1 {
+                tiles.push(tile);
+            }
+        }
+    }
+
+    tiles
+}
+
+// Luminance variance over a few pixels of the tile, sampled from streams
+// past the ones of the render itself
+fn tile_variance(tile: &Tile, scene: &Scene, settings: &RenderSettings) -> f64 {
+    let (mut sum, mut sum_sq, mut count) = (0.0, 0.0, 0.0);
+
+    for py in 0..PROBES {
+        for px in 0..PROBES {
+            let i = tile.x0 + (tile.x1 - tile.x0) * (2 * px + 1) / (2 * PROBES);
+            let j = settings.image_height - 1 - (tile.y0 + (tile.y1 - tile.y0) * (2 * py + 1) / (2 * PROBES));
+
+            for s in 0..PROBE_SAMPLES {
+                render::start_sample(settings, i, j, settings.first_sample + settings.samples_per_pixel + s);
+                let c = render::ray_color(&render::sample_ray(&scene.camera, settings, i, j), scene, settings);
+                let c = render::clamp_sample(c, settings.clamp);
+                let y = 0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z();
+                sum += y;
+                sum_sq += y * y;
+                count += 1.0;
+            }
+        }
+    }
+
+    let mean = sum / count;
+    let variance = sum_sq / count - mean * mean;
+
+    // Relative to the brightness, the way noise is seen
+    variance / (mean * mean + 1e-3)
+}
+
+fn ordered_tiles(scene: &Scene, settings: &RenderSettings) -> Vec<Tile> {
+    let mut tiles = tiles(settings);
+
+    match settings.tile_order {
+        TileOrder::Scanline => { },
+        TileOrder::Spiral => {
+            // Ring and angle of the tile center around the image center, in
+            // tile units
+            let key = |t: &Tile| {
+                let dx = ((t.x0 + t.x1) as f64 - settings.image_width as f64) / (2 * TILE_SIZE) as f64;
+                let dy = ((t.y0 + t.y1) as f64 - settings.image_height as f64) / (2 * TILE_SIZE) as f64;
+                ((dx.abs().max(dy.abs()) + 0.5).floor(), dy.atan2(dx))
+            };
+            tiles.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        },
+        TileOrder::Variance => {
+            let variances: Vec<f64> = tiles.par_iter().map(|t| tile_variance(t, scene, settings)).collect();
+            let mut order: Vec<usize> = (0..tiles.len()).collect();
+            order.sort_by(|&a, &b| variances[b].total_cmp(&variances[a]));
+            tiles = order.into_iter().map(|k| tiles[k]).collect();
+        }
+    }
+
+    tiles
+}
+
+// Value of every pixel, top row first, from pixel(i, j) with j counted from
+// the bottom row. Each render thread takes the next tile of the order and
+// renders it on its own. Pixels outside the bucket are left at the default.
+pub fn render_tiles<T: Copy + Send + Default>(scene: &Scene, settings: &RenderSettings, pixel: impl Fn(u64, u64) -> T + Sync) -> Vec<T> {
+    let width = settings.image_width;
+    let height = settings.image_height;
+    let tiles = ordered_tiles(scene, settings);
+
+    let pixels = Mutex::new(vec![None; (width * height) as usize]);
+    let next = AtomicUsize::new(0);
+    let finished = AtomicU64::new(0);
+
+    rayon::scope(|s| {
+        for _ in 0..rayon::current_num_threads() {
+            s.spawn(|_| {
+                while let Some(tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let mut colors = Vec::with_capacity(((tile.x1 - tile.x0) * (tile.y1 - tile.y0)) as usize);
+                    for y in tile.y0..tile.y1 {
+                        for x in tile.x0..tile.x1 {
+                            colors.push(pixel(x, height - 1 - y));
+                        }
+                    }
+
+                    let mut pixels = pixels.lock().unwrap();
+                    let mut k = 0;
+                    for y in tile.y0..tile.y1 {
+                        for x in tile.x0..tile.x1 {
+                            pixels[(y * width + x) as usize] = Some(colors[k]);
+                            k += 1;
+                        }
+                    }
+
+                    // Progress is counted in scanlines' worth of pixels
+                    let count = colors.len() as u64;
+                    let before = finished.fetch_add(count, Ordering::Relaxed);
+                    let scanlines = (before + count) / width - before / width;
+                    if scanlines > 0 {
+                        eprintln!("Scanlines remaining: {}", height - (before + count) / width);
+                        settings.finished_scanlines(scanlines);
+                    }
+                }
+            });
+        }
+    });
+
+    // Every tile has been rendered once all threads are done
+    pixels.into_inner().unwrap().into_iter().map(Option::unwrap_or_default).collect()
+}
+
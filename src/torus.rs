@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Ring around the axis through center: the points minor_radius away from
+// the circle of major_radius in the plane the axis is normal to
+pub struct Torus {
+    center: Point3,
+    axis: Vec3,
+    u: Vec3,
+    w: Vec3,
+    major_radius: f64,
+    minor_radius: f64,
+    mat: Arc<dyn Scatter>
+}
+
+// Largest real root of x^3 + a x^2 + b x + c
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+
+    let mut x = if r * r < q * q * q {
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        -2.0 * q.sqrt() * (theta / 3.0).cos() - a / 3.0
+    } else {
+        let big = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let small = if big != 0.0 { q / big } else { 0.0 };
+        big + small - a / 3.0
+    };
+
+    // Newton steps make up for the cancellation in the closed forms
+    for _ in 0..2 {
+        let f = ((x + a) * x + b) * x + c;
+        let df = (3.0 * x + 2.0 * a) * x + b;
+        if df != 0.0 {
+            x -= f / df;
+        }
+    }
+    x
+}
+
+// Real roots of t^4 + b t^3 + c t^2 + d t + e, by Ferrari's method: the
+// depressed quartic is split into two quadratics with a root of its
+// resolvent cubic
+fn quartic_roots(b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    let p = c - 3.0 * b * b / 8.0;
+    let q = d - b * c / 2.0 + b * b * b / 8.0;
+    let r = e - b * d / 4.0 + b * b * c / 16.0 - 3.0 * b * b * b * b / 256.0;
+
+    let mut roots = Vec::with_capacity(4);
+    let mut quadratic = |linear: f64, constant: f64| {
+        let discriminant = linear * linear - 4.0 * constant;
+        if discriminant >= 0.0 {
+            let sqrtd = discriminant.sqrt();
+            roots.push((-linear - sqrtd) / 2.0);
+            roots.push((-linear + sqrtd) / 2.0);
+        }
+    };
+
+    let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+    if m <= 1e-12 {
+        // Biquadratic, a quadratic in y^2
+        let discriminant = p * p - 4.0 * r;
+        if discriminant >= 0.0 {
+            for y2 in [(-p - discriminant.sqrt()) / 2.0, (-p + discriminant.sqrt()) / 2.0] {
+                if y2 >= 0.0 {
+                    roots.push(-y2.sqrt());
+                    roots.push(y2.sqrt());
+                }
+            }
+        }
+    } else {
+        let s = (2.0 * m).sqrt();
+        quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+        quadratic(s, p / 2.0 + m - q / (2.0 * s));
+    }
+
+    // Back from the depressed quartic, polished on the original one
+    roots.iter().map(|&y| {
+        let mut t = y - b / 4.0;
+        for _ in 0..2 {
+            let f = (((t + b) * t + c) * t + d) * t + e;
+            let df = ((4.0 * t + 3.0 * b) * t + 2.0 * c) * t + d;
+            if df != 0.0 {
+                t -= f / df;
+            }
+        }
+        t
+    }).collect()
+}
+
+impl Torus {
+    pub fn new(center: Point3, axis: Vec3, major_radius: f64, minor_radius: f64, m: Arc<dyn Scatter>) -> Torus {
+        let axis = axis.normalized();
+        let (u, w) = axis.orthonormal_basis();
+
+        Torus {
+            center,
+            axis,
+            u,
+            w,
+            major_radius,
+            minor_radius,
+            mat: m
+        }
+    }
+
+    // Coordinates of v in the frame of the torus, with the axis as y
+    fn local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.u), v.dot(self.axis), v.dot(self.w))
+    }
+}
+
+impl Hit for Torus {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let length = r.direction().length();
+        let d = self.local(r.direction() / length);
+        let o = self.local(r.origin() - self.center);
+
+        // The quartic is solved from where the ray enters the bounding
+        // sphere, in units of the major radius, which keeps its coefficients
+        // small however far away the ray starts
+        let bound = self.major_radius + self.minor_radius;
+        let half_b = o.dot(d);
+        let discriminant = half_b * half_b - (o.dot(o) - bound * bound);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let s0 = -half_b - discriminant.sqrt();
+        if (-half_b + discriminant.sqrt()) / length < t_min || s0 / length > t_max {
+            return None;
+        }
+
+        let scale = self.major_radius;
+        let o = (o + s0 * d) / scale;
+        let minor = self.minor_radius / scale;
+
+        // (|p|^2 - (1 + minor^2))^2 + 4 (p_y^2 - minor^2) = 0 along p = o + s d
+        let f = o.dot(d);
+        let g = o.dot(o) - (1.0 + minor * minor);
+        let roots = quartic_roots(
+            4.0 * f,
+            4.0 * f * f + 2.0 * g + 4.0 * d.y() * d.y(),
+            4.0 * f * g + 8.0 * o.y() * d.y(),
+            g * g + 4.0 * (o.y() * o.y() - minor * minor)
+        );
+
+        let t = roots.into_iter()
+            .map(|s| (s0 + s * scale) / length)
+            .filter(|&t| t >= t_min && t <= t_max)
+            .min_by(f64::total_cmp)?;
+
+        // The normal points away from the nearest point of the center circle
+        let p = self.local(r.at(t) - self.center);
+        let radial = Vec3::new(p.x(), 0.0, p.z());
+        let ring = if radial.near_zero() { Vec3::new(0.0, 0.0, 0.0) } else { self.major_radius * radial.normalized() };
+        let n = (p - ring).normalized();
+        let outward_normal = n.x() * self.u + n.y() * self.axis + n.z() * self.w;
+
+        Some(HitRecord::new(r, t, outward_normal, self.mat.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // The center circle reaches major_radius * sin of the angle to each
+        // axis, and the tube minor_radius beyond it
+        let a = self.axis;
+        let extent = |c: f64| self.major_radius * (1.0 - c * c).max(0.0).sqrt() + self.minor_radius;
+        let e = Vec3::new(extent(a.x()), extent(a.y()), extent(a.z()));
+        Some(Aabb::new(self.center - e, self.center + e).padded(1e-4))
+    }
+}
@@ -0,0 +1,137 @@
+use super::aabb::Aabb;
+use super::hit::{Hit, HitRecord};
+use super::matrix::Matrix;
+use super::ray::Ray;
+use super::vec::Vec3;
+
+// Object moved by an offset, by moving the rays the other way instead
+pub struct Translate {
+    object: Box<dyn Hit>,
+    offset: Vec3
+}
+
+impl Translate {
+    pub fn new(object: Box<dyn Hit>, offset: Vec3) -> Translate {
+        Translate { object, offset }
+    }
+}
+
+impl Hit for Translate {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let moved = Ray::with_time(r.origin() - self.offset, r.direction(), r.time()).with_type(r.ray_type());
+        let mut rec = self.object.hit(&moved, t_min, t_max)?;
+        rec.p += self.offset;
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bbox = self.object.bounding_box()?;
+        Some(Aabb::new(bbox.min + self.offset, bbox.max + self.offset))
+    }
+
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let mut rec = self.object.surface_at(u, v)?;
+        rec.p += self.offset;
+        Some(rec)
+    }
+}
+
+// Object turned about an axis through the origin, counter-clockwise by
+// `degrees` looking down the axis; the book's RotateY is the y axis case
+pub struct Rotate {
+    object: Box<dyn Hit>,
+    rotation: Matrix,
+    inverse: Matrix
+}
+
+impl Rotate {
+    pub fn new(object: Box<dyn Hit>, axis: Vec3, degrees: f64) -> Rotate {
+        Rotate {
+            object,
+            rotation: Matrix::rotation(axis, degrees),
+            inverse: Matrix::rotation(axis, -degrees)
+        }
+    }
+}
+
+impl Hit for Rotate {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let turned = Ray::with_time(self.inverse.direction(r.origin()), self.inverse.direction(r.direction()), r.time())
+            .with_type(r.ray_type());
+        let mut rec = self.object.hit(&turned, t_min, t_max)?;
+
+        // Rotations keep lengths and angles, so normals turn like points and
+        // stay on the same side of the ray
+        rec.p = self.rotation.direction(rec.p);
+        rec.normal = self.rotation.direction(rec.normal);
+        rec.geometric_normal = self.rotation.direction(rec.geometric_normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.rotation.bounds(&self.object.bounding_box()?))
+    }
+
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let mut rec = self.object.surface_at(u, v)?;
+        rec.p = self.rotation.direction(rec.p);
+        rec.normal = self.rotation.direction(rec.normal);
+        rec.geometric_normal = self.rotation.direction(rec.geometric_normal);
+        Some(rec)
+    }
+}
+
+// Object placed by any affine transform, uneven scales and mirroring
+// included. Rays are taken into the object's space and their hits brought
+// back, keeping the ray parameter t; normals go through the inverse
+// transpose, so they stay perpendicular to the stretched surface.
+pub struct Transform<H: Hit = Box<dyn Hit>> {
+    object: H,
+    to_world: Matrix,
+    to_object: Matrix,
+    normals: Matrix
+}
+
+impl<H: Hit> Transform<H> {
+    pub fn new(object: H, transform: Matrix) -> Transform<H> {
+        Transform {
+            object,
+            to_world: transform,
+            to_object: transform.inverse().expect("The transform can't be singular."),
+            normals: transform.normal_matrix().expect("The transform can't be singular.")
+        }
+    }
+
+    fn to_world(&self, mut rec: HitRecord, r: &Ray) -> HitRecord {
+        let side = if rec.front_face { 1.0 } else { -1.0 };
+        let (outward, shading) = (side * rec.geometric_normal, side * rec.normal);
+        rec.p = self.to_world.point(rec.p);
+        rec.set_face_normal(r, self.normals.direction(outward).normalized());
+        rec.set_shading_normal(self.normals.direction(shading).normalized());
+        rec
+    }
+}
+
+impl<H: Hit> Hit for Transform<H> {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let local = Ray::with_time(self.to_object.point(r.origin()), self.to_object.direction(r.direction()), r.time())
+            .with_type(r.ray_type());
+        let rec = self.object.hit(&local, t_min, t_max)?;
+        Some(self.to_world(rec, r))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.to_world.bounds(&self.object.bounding_box()?))
+    }
+
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        // Seen head-on, so the normal stays on the front
+        let mut rec = self.object.surface_at(u, v)?;
+        let side = if rec.front_face { 1.0 } else { -1.0 };
+        rec.p = self.to_world.point(rec.p);
+        rec.normal = self.normals.direction(side * rec.normal).normalized();
+        rec.geometric_normal = self.normals.direction(side * rec.geometric_normal).normalized();
+        rec.front_face = true;
+        Some(rec)
+    }
+}
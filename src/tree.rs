@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use roxmltree::{Document, Node};
+
+use super::material::Lambertian;
+use super::scene::Scene;
+use super::vec::{Color, Vec3};
+
+// What objects before the first material are made of
+const DEFAULT_MATERIAL: &str = "default black lambertian";
+
+fn xml_vec(v: Vec3) -> String {
+    format!("{} {} {}", v.x(), v.y(), v.z())
+}
+
+// The attributes of an element as written, but for those in `skip`
+fn attributes(node: Node, skip: &[&str]) -> String {
+    node.attributes().iter()
+        .filter(|a| !skip.contains(&a.name()))
+        .map(|a| format!(" {}=\"{}\"", a.name(), a.value()))
+        .collect()
+}
+
+// Type and name of an element, `type "name"`
+fn label(node: Node) -> String {
+    let kind = node.attribute("type").unwrap_or(node.tag_name().name());
+    match node.attribute("name") {
+        Some(name) => format!("{} \"{}\"", kind, name),
+        None => kind.to_string()
+    }
+}
+
+// Walks the scene the way xml_parser does, numbering the world's objects and
+// lights as it would and following the material each scope is left with
+struct Printer<'a> {
+    scene: &'a Scene,
+    materials: usize,
+    last_material: String,
+    objects: usize,
+    lights: usize,
+    // Objects held by each definition
+    defines: HashMap<String, usize>
+}
+
+impl Printer<'_> {
+    fn line(depth: usize, text: String) {
+        println!("{}{}", "  ".repeat(depth), text);
+    }
+
+    // Bounds of world object `index` after every transform
+    fn bounds(&self, index: usize) -> String {
+        match self.scene.world.get(index).and_then(|object| object.bounding_box()) {
+            Some(bbox) => format!(", bounds ({}) to ({})", xml_vec(bbox.min), xml_vec(bbox.max)),
+            None => ", unbounded".to_string()
+        }
+    }
+
+    // Elements nested in others, like the textures of a material or the
+    // keys of a camera, with their attributes alone
+    fn details(node: Node, depth: usize) {
+        for child in node.children().filter(|n| n.is_element()) {
+            Printer::line(depth, format!("{}{}", child.tag_name().name(), attributes(child, &[])));
+            Printer::details(child, depth + 1);
+        }
+    }
+
+    fn material(&mut self, node: Node, depth: usize) -> String {
+        self.materials += 1;
+        let name = format!("material {} ({})", self.materials, label(node));
+        Printer::line(depth, format!("{}{}", name, attributes(node, &["type", "name"])));
+        Printer::details(node, depth + 1);
+        self.last_material = name.clone();
+        name
+    }
+
+    // Elements outside any <define> or <group>, whose materials carry over
+    // to every object after them, even from inside a <csg>
+    fn top_level(&mut self, node: Node, depth: usize, mat: &mut String) {
+        match node.tag_name().name() {
+            "material" => *mat = self.material(node, depth),
+            "object" | "csg" | "use" | "group" => {
+                let index = self.objects;
+                self.objects += 1;
+                let before = self.materials;
+                self.element(node, depth, Some(index), mat);
+                // The last material anywhere inside a <csg> carries over
+                if node.has_tag_name("csg") && self.materials > before {
+                    *mat = self.last_material.clone();
+                }
+            },
+            "define" => {
+                let name = node.attribute("name").unwrap_or("");
+                let count = self.children(node, depth, mat.clone());
+                self.defines.insert(name.to_string(), count);
+            },
+            "generator" => {
+                let count = self.generator(node, depth, mat, true);
+                self.objects += count;
+            },
+            "light" => {
+                let shape = matches!(node.attribute("type"), Some("area") | Some("sphere"));
+                let mut text = format!("light {} ({}){}", self.lights, label(node), attributes(node, &["type", "name"]));
+                if shape {
+                    text += &format!(", emitter object {}{}", self.objects, self.bounds(self.objects));
+                    self.objects += 1;
+                }
+                self.lights += 1;
+                Printer::line(depth, text);
+                Printer::details(node, depth + 1);
+            },
+            "environment" => {
+                Printer::line(depth, format!("environment{}", attributes(node, &[])));
+                for layer in node.children().filter(|n| n.is_element()) {
+                    let mut text = format!("layer ({}){}", label(layer), attributes(layer, &["type", "name"]));
+                    // Suns light the scene like lights of their own
+                    if layer.attribute("type") == Some("sun") {
+                        text += &format!(", light {}", self.lights);
+                        self.lights += 1;
+                    }
+                    Printer::line(depth + 1, text);
+                    Printer::details(layer, depth + 2);
+                }
+            },
+            // Caps of clip planes are parsed with them
+            "clip" => {
+                Printer::line(depth, format!("clip{}", attributes(node, &[])));
+                Printer::details(node, depth + 1);
+            },
+            tag => {
+                Printer::line(depth, format!("{}{}", tag, attributes(node, &[])));
+                for child in node.children().filter(|n| n.is_element()) {
+                    self.top_level(child, depth + 1, mat);
+                }
+            }
+        }
+    }
+
+    // An <object>, <csg>, <use> or <group>, world object `index` if it is
+    // at the top level
+    fn element(&mut self, node: Node, depth: usize, index: Option<usize>, mat: &str) {
+        let number = index.map_or(String::new(), |i| format!(" {}", i));
+        let bounds = index.map_or(String::new(), |i| self.bounds(i));
+        let tag = node.tag_name().name();
+        match tag {
+            "object" => {
+                let mut text = format!("object{} ({}){}, {}", number, label(node), attributes(node, &["type", "name"]), mat);
+                if node.has_attribute("density") {
+                    text += ", filled with a medium";
+                }
+                Printer::line(depth, text + &bounds);
+                Printer::details(node, depth + 1);
+            },
+            "use" => {
+                let name = node.attribute("ref").unwrap_or("");
+                let target = match self.defines.get(name) {
+                    Some(&count) => format!("define \"{}\" of {} object{}", name, count, if count == 1 { "" } else { "s" }),
+                    None => format!("undefined \"{}\"", name)
+                };
+                Printer::line(depth, format!("use{}{}, {}{}", number, attributes(node, &["ref"]), target, bounds));
+            },
+            "csg" => {
+                Printer::line(depth, format!("csg{}{}{}", number, attributes(node, &[]), bounds));
+                let mut mat = mat.to_string();
+                for child in node.children().filter(|n| n.is_element()) {
+                    match child.tag_name().name() {
+                        "material" => mat = self.material(child, depth + 1),
+                        _ => self.element(child, depth + 1, None, &mat)
+                    }
+                }
+            },
+            _ => {
+                Printer::line(depth, format!("{}{}{}{}", tag, number, attributes(node, &[]), bounds));
+                self.children(node, depth, mat.to_string());
+            }
+        }
+    }
+
+    // Children of a <define> or <group>, made of the material mat until one
+    // of their own, returning how many objects they make
+    fn children(&mut self, node: Node, depth: usize, mut mat: String) -> usize {
+        if node.has_tag_name("define") {
+            Printer::line(depth, format!("define \"{}\"", node.attribute("name").unwrap_or("")));
+        }
+
+        let mut count = 0;
+        for child in node.children().filter(|n| n.is_element()) {
+            match child.tag_name().name() {
+                "material" => mat = self.material(child, depth + 1),
+                "generator" => count += self.generator(child, depth + 1, &mat, false),
+                _ => {
+                    self.element(child, depth + 1, None, &mat);
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    // Prints a generator, and the range of world objects it makes if it is
+    // at the top level, returning how many
+    fn generator(&self, node: Node, depth: usize, mat: &str, top: bool) -> usize {
+        let count = super::generator_parser(&node, Arc::new(Lambertian::new(Color::new(0.0, 0.0, 0.0)))).len();
+        let objects = if top && count > 0 {
+            format!(", objects {} to {}", self.objects, self.objects + count - 1)
+        } else {
+            format!(", {} objects", count)
+        };
+        Printer::line(depth, format!("generator ({}){}, {}{}", label(node), attributes(node, &["type", "name"]), mat, objects));
+        count
+    }
+}
+
+// `rt tree scene.xml` prints the elements of a scene as an indented tree,
+// with the number every object and light gets in the world, those of
+// `rt measure` and scripts, the material each object ends up made of and
+// the world bounds of top-level objects after their transforms
+pub fn tree(args: impl Iterator<Item = String>) {
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            _ if arg.starts_with("--") => panic!("Unknown option '{}'.", arg),
+            _ => path = Some(arg)
+        }
+    }
+
+    let path = path.expect("Missing scene file to print.");
+    let xml = super::read_xml(&path);
+
+    // Parsed first, so broken scenes fail the way they do when rendered
+    let (_, scene) = super::xml_parser(&xml);
+    let doc = Document::parse(&xml).expect("Failed to parse XML");
+
+    let mut printer = Printer { scene: &scene, materials: 0, last_material: String::new(), objects: 0, lights: 0, defines: HashMap::new() };
+    let mut mat = DEFAULT_MATERIAL.to_string();
+    printer.top_level(doc.root_element(), 0, &mut mat);
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use super::aabb::Aabb;
+use super::vec::{Point3, Vec3};
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+
+// Triangle with corners v0, v1 and v2; the winding v0 -> v1 -> v2 is
+// counter-clockwise seen from the front. With vertex normals it shades
+// smoothly, their interpolation at the hit being its shading normal.
+pub struct Triangle {
+    v0: Point3,
+    v1: Point3,
+    v2: Point3,
+    normal: Vec3,
+    vertex_normals: Option<[Vec3; 3]>,
+    mat: Arc<dyn Scatter>
+}
+
+impl Triangle {
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, m: Arc<dyn Scatter>) -> Triangle {
+        Triangle {
+            v0,
+            v1,
+            v2,
+            normal: (v1 - v0).cross(v2 - v0).normalized(),
+            vertex_normals: None,
+            mat: m
+        }
+    }
+
+    // Normals at v0, v1 and v2, pointing to the front
+    pub fn with_normals(self, normals: [Vec3; 3]) -> Triangle {
+        Triangle {
+            vertex_normals: Some(normals.map(|n| n.normalized())),
+            ..self
+        }
+    }
+
+    fn shading_normal(&self, u: f64, v: f64) -> Option<Vec3> {
+        let [n0, n1, n2] = self.vertex_normals?;
+        let n = (1.0 - u - v) * n0 + u * n1 + v * n2;
+        (!n.near_zero()).then(|| n.normalized())
+    }
+}
+
+// Watertight ray-triangle test of Woop, Benthin and Wald, returning t and the
+// barycentric coordinates towards b and c. The corners are moved into a
+// space where the ray runs along +z from the origin and the edge functions
+// are evaluated there from the corners alone, so two triangles sharing an
+// edge compute the same value for it with opposite signs, and a ray through
+// the edge or a shared corner can't slip between them.
+pub fn intersect(r: &Ray, a: Point3, b: Point3, c: Point3, t_min: f64, t_max: f64) -> Option<(f64, f64, f64)> {
+    let d = r.direction();
+
+    // The largest axis of the direction becomes z, the other two keep the
+    // winding of the triangle
+    let kz = if d.x().abs() > d.y().abs() && d.x().abs() > d.z().abs() {
+        0
+    } else if d.y().abs() > d.z().abs() {
+        1
+    } else {
+        2
+    };
+    let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    if d[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    let sx = d[kx] / d[kz];
+    let sy = d[ky] / d[kz];
+    let sz = 1.0 / d[kz];
+
+    let (a, b, c) = (a - r.origin(), b - r.origin(), c - r.origin());
+    let (ax, ay) = (a[kx] - sx * a[kz], a[ky] - sy * a[kz]);
+    let (bx, by) = (b[kx] - sx * b[kz], b[ky] - sy * b[kz]);
+    let (cx, cy) = (c[kx] - sx * c[kz], c[ky] - sy * c[kz]);
+
+    // Twice the signed areas of the triangles the ray makes with each edge,
+    // the weights of the opposite corners
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t = (u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz]) / det;
+    if t < t_min || t_max < t {
+        return None;
+    }
+    Some((t, v / det, w / det))
+}
+
+impl Hit for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t, u, v) = intersect(r, self.v0, self.v1, self.v2, t_min, t_max)?;
+
+        let mut rec = HitRecord::new(r, t, self.normal, self.mat.clone());
+        if let Some(n) = self.shading_normal(u, v) {
+            rec.set_shading_normal(n);
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let bbox = Aabb::new(self.v0, self.v1).surrounding(Aabb::new(self.v0, self.v2));
+        Some(bbox.padded(1e-4))
+    }
+
+    // Barycentric coordinates, u towards v1 and v towards v2; the other half
+    // of the unit square folds back onto the triangle
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let (u, v) = if u + v > 1.0 { (1.0 - u, 1.0 - v) } else { (u, v) };
+        let mut rec = HitRecord::facing(self.v0 + u * (self.v1 - self.v0) + v * (self.v2 - self.v0), self.normal, self.mat.clone());
+        if let Some(n) = self.shading_normal(u, v) {
+            rec.set_shading_normal(n);
+        }
+        Some(rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    // Whether a ray hits either half of the quad a, b, c, d, split along
+    // its diagonal a-c
+    fn hits_quad(r: &Ray, [a, b, c, d]: [Point3; 4]) -> bool {
+        intersect(r, a, b, c, 0.001, f64::INFINITY).is_some() || intersect(r, a, c, d, 0.001, f64::INFINITY).is_some()
+    }
+
+    #[test]
+    fn rays_through_shared_edges_and_corners_hit() {
+        // Rays through the diagonal and the corners both halves share of the
+        // unit square, going straight down or aslant with directions floating
+        // point holds exactly, land exactly on them
+        let square = [Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0), Point3::new(0.0, 1.0, 0.0)];
+        for k in 0..=8 {
+            let s = k as f64 / 8.0;
+            for dir in [Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.25, -0.5, -1.0), Vec3::new(-1.0, 0.5, -0.25)] {
+                let r = Ray::new(Point3::new(s, s, 0.0) - 2.0 * dir, dir);
+                assert!(hits_quad(&r, square), "The ray {:?} slips through the square at {}", dir, s);
+            }
+        }
+
+        // Rays from anywhere through the shared edge of a tilted flat quad, as
+        // near to it as floating point gets
+        let (a, b, c) = (Point3::new(-1.3, 0.2, 0.7), Point3::new(0.9, -0.4, 1.1), Point3::new(1.7, 1.9, -0.6));
+        let quad = [a, b, c, a + (c - b)];
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..2000 {
+            let target = quad[0] + rng.gen_range(0.01..0.99) * (quad[2] - quad[0]);
+            let origin = Point3::new(rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0), rng.gen_range(-5.0..5.0));
+            let r = Ray::new(origin, target - origin);
+            assert!(hits_quad(&r, quad), "The ray from {:?} slips through the quad at {:?}", origin, target);
+        }
+    }
+}
@@ -3,7 +3,9 @@ use std::fmt;
 use std::fmt::Display;
 use rand::prelude::*;
 
-#[derive(Clone, Copy)]
+use super::sampler;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Vec3 {
     e: [f64; 3]
 }
@@ -19,7 +21,7 @@ impl Vec3 {
     }
 
     pub fn random(r: Range<f64>) -> Vec3 {
-        let mut rng = rand::thread_rng();
+        let mut rng = sampler::rng();
         
         Vec3 {
             e: [rng.gen_range(r.clone()), rng.gen_range(r.clone()), rng.gen_range(r.clone())]
@@ -46,7 +48,7 @@ impl Vec3 {
     }
 
     pub fn random_in_unit_disk() -> Vec3 {
-        let mut rng = rand::thread_rng();
+        let mut rng = sampler::rng();
 
         loop {
             let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
@@ -97,6 +99,17 @@ impl Vec3 {
         r_out_perp + r_out_parallel
     }
 
+    // Two unit vectors completing self (which must be normalized) to an
+    // orthonormal basis (Duff et al., "Building an Orthonormal Basis, Revisited")
+    pub fn orthonormal_basis(self) -> (Vec3, Vec3) {
+        let sign = 1.0_f64.copysign(self[2]);
+        let a = -1.0 / (sign + self[2]);
+        let b = self[0] * self[1] * a;
+
+        (Vec3::new(1.0 + sign * self[0] * self[0] * a, sign * b, -sign * self[0]),
+         Vec3::new(b, sign + self[1] * self[1] * a, -self[1]))
+    }
+
     pub fn normalized(self) -> Vec3 {
         self / self.length()
     }
@@ -106,10 +119,15 @@ impl Vec3 {
         self[0].abs() < EPS && self[1].abs() < EPS && self[2].abs() < EPS
     }
 
+    // 8-bit gamma 2 encoding of the average of summed samples
+    pub fn to_rgb8(self, samples_per_pixel: u64) -> [u8; 3] {
+        let encode = |c: f64| (256.0 * (c / (samples_per_pixel as f64)).sqrt().clamp(0.0, 0.999)) as u8;
+
+        [encode(self[0]), encode(self[1]), encode(self[2])]
+    }
+
     pub fn format_color(self, samples_per_pixel: u64) -> String {
-        let ir = (256.0 * (self[0] / (samples_per_pixel as f64)).sqrt().clamp(0.0, 0.999)) as u64;
-        let ig = (256.0 * (self[1] / (samples_per_pixel as f64)).sqrt().clamp(0.0, 0.999)) as u64;
-        let ib = (256.0 * (self[2] / (samples_per_pixel as f64)).sqrt().clamp(0.0, 0.999)) as u64;
+        let [ir, ig, ib] = self.to_rgb8(samples_per_pixel);
 
         format!("{} {} {}", ir, ig, ib)
     }
@@ -146,7 +164,7 @@ impl Add for Vec3 {
 }
 
 impl AddAssign for Vec3 {
-    fn add_assign(&mut self, other: Vec3) -> () {
+    fn add_assign(&mut self, other: Vec3) {
         *self = Vec3 {
             e: [self[0] + other[0], self[1] + other[1], self[2] + other[2]]
         };
@@ -164,7 +182,7 @@ impl Sub for Vec3 {
 }
 
 impl SubAssign for Vec3 {
-    fn sub_assign(&mut self, other: Vec3) -> () {
+    fn sub_assign(&mut self, other: Vec3) {
         *self = Vec3 {
             e: [self[0] - other[0], self[1] - other[1], self[2] - other[2]]
         };
@@ -182,7 +200,7 @@ impl Mul<f64> for Vec3 {
 }
 
 impl MulAssign<f64> for Vec3 {
-    fn mul_assign(&mut self, other: f64) -> () {
+    fn mul_assign(&mut self, other: f64) {
         *self = Vec3 {
             e: [self[0] * other, self[1] * other, self[2] * other]
         };
@@ -200,7 +218,7 @@ impl Mul<Vec3> for Vec3 {
 }
 
 impl MulAssign<Vec3> for Vec3 {
-    fn mul_assign(&mut self, other: Vec3) -> () {
+    fn mul_assign(&mut self, other: Vec3) {
         *self = Vec3 {
             e: [self[0] * other[0], self[1] * other[1], self[2] * other[2]]
         };
@@ -228,7 +246,7 @@ impl Div<f64> for Vec3 {
 }
 
 impl DivAssign<f64> for Vec3 {
-    fn div_assign(&mut self, other: f64) -> () {
+    fn div_assign(&mut self, other: f64) {
         *self = Vec3 {
             e: [self[0] / other, self[1] / other, self[2] / other]
         };
@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use super::aabb::Aabb;
+use super::ray::Ray;
+use super::hit::{Hit, HitRecord};
+use super::material::Scatter;
+use super::sampler;
+
+// Uniform participating medium, like smoke or mist, filling a closed
+// object. Rays inside are scattered by `phase` after an exponentially
+// distributed distance, so they go through with probability
+// exp(-density * length); its surface itself is invisible.
+pub struct ConstantMedium {
+    boundary: Box<dyn Hit>,
+    density: f64,
+    phase: Arc<dyn Scatter>
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Box<dyn Hit>, density: f64, phase: Arc<dyn Scatter>) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            density,
+            phase
+        }
+    }
+}
+
+impl Hit for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // Where the ray's line enters and leaves the boundary, which is
+        // assumed convex
+        let enter = self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hit(r, enter.t + 1e-4, f64::INFINITY)?;
+
+        let start = enter.t.max(t_min);
+        let end = exit.t.min(t_max);
+        if start >= end {
+            return None;
+        }
+
+        let speed = r.direction().length();
+        let distance = -sampler::rng().gen::<f64>().ln() / self.density;
+        if distance > (end - start) * speed {
+            return None;
+        }
+
+        // Normal and side mean nothing inside a volume; the normal faces the
+        // ray so shading treats the point as lit from anywhere
+        let t = start + distance / speed;
+        Some(HitRecord::new(r, t, (-1.0) * r.direction().normalized(), self.phase.clone()))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
@@ -0,0 +1,267 @@
+use rayon::prelude::*;
+
+use super::hit::{Hit, HitRecord};
+use super::material;
+use super::ray::{Ray, RayKind, RayType};
+use super::render::{bounce_kind, clamp_sample, counts_emission, light_samples, occluded, roulette, sample_ray, scatter, start_sample, RenderSettings, SplitColor};
+use super::sampler;
+use super::scene::Scene;
+use super::vec::{Color, Point3, Vec3};
+
+// Upper bound on the number of paths in flight per batch
+const BATCH_SIZE: u64 = 1 << 18;
+
+// Live paths stored as a structure of arrays, one entry per path
+struct PathQueue {
+    origin: Vec<Point3>,
+    direction: Vec<Vec3>,
+    time: Vec<f64>,
+    ray_type: Vec<RayType>,
+    throughput: Vec<Color>,
+    kind: Vec<RayKind>,
+    // Where the path's light is added, see render
+    slot: Vec<usize>,
+    // Random generator state of each path, saved between stages
+    rng: Vec<u64>
+}
+
+impl PathQueue {
+    fn with_capacity(n: usize) -> PathQueue {
+        PathQueue {
+            origin: Vec::with_capacity(n),
+            direction: Vec::with_capacity(n),
+            time: Vec::with_capacity(n),
+            ray_type: Vec::with_capacity(n),
+            throughput: Vec::with_capacity(n),
+            kind: Vec::with_capacity(n),
+            slot: Vec::with_capacity(n),
+            rng: Vec::with_capacity(n)
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.slot.len()
+    }
+
+    fn push(&mut self, r: &Ray, throughput: Color, kind: RayKind, slot: usize, rng: u64) {
+        self.origin.push(r.origin());
+        self.direction.push(r.direction());
+        self.time.push(r.time());
+        self.ray_type.push(r.ray_type());
+        self.throughput.push(throughput);
+        self.kind.push(kind);
+        self.slot.push(slot);
+        self.rng.push(rng);
+    }
+
+    fn ray(&self, k: usize) -> Ray {
+        Ray::with_time(self.origin[k], self.direction[k], self.time[k]).with_type(self.ray_type[k])
+    }
+}
+
+// Shadow rays waiting for their occlusion test
+struct ShadowQueue {
+    origin: Vec<Point3>,
+    direction: Vec<Vec3>,
+    time: Vec<f64>,
+    t_max: Vec<f64>,
+    contribution: Vec<Color>,
+    slot: Vec<usize>
+}
+
+impl ShadowQueue {
+    fn new() -> ShadowQueue {
+        ShadowQueue {
+            origin: Vec::new(),
+            direction: Vec::new(),
+            time: Vec::new(),
+            t_max: Vec::new(),
+            contribution: Vec::new(),
+            slot: Vec::new()
+        }
+    }
+
+    fn push(&mut self, r: &Ray, t_max: f64, contribution: Color, slot: usize) {
+        self.origin.push(r.origin());
+        self.direction.push(r.direction());
+        self.time.push(r.time());
+        self.t_max.push(t_max);
+        self.contribution.push(contribution);
+        self.slot.push(slot);
+    }
+}
+
+// Outcome of shading one path vertex, weighted by the path throughput
+struct Shaded {
+    emitted: Color,
+    shadows: Vec<(Color, Ray, f64)>,
+    // Throughput, ray and kind of the continued path
+    next: Option<(Color, Ray, RayKind)>,
+    rng: u64
+}
+
+// Same estimator as render::ray_color, but every bounce of a whole batch of
+// paths is processed stage by stage: generate, intersect, shade, and test
+// the shadow rays of the direct lighting. Light is summed into two slots per
+// pixel, 2 * pixel for the even samples and the next one for the odd ones.
+pub fn render(scene: &Scene, settings: &RenderSettings) -> Vec<SplitColor> {
+    let width = settings.image_width;
+    let height = settings.image_height;
+    let mut slots = vec![Color::new(0.0, 0.0, 0.0); (2 * width * height) as usize];
+    let mut coverage = vec![settings.samples_per_pixel; (width * height) as usize];
+
+    let paths_per_row = (width * settings.samples_per_pixel).max(1);
+    let rows_per_batch = (BATCH_SIZE / paths_per_row).max(1);
+
+    let mut row = 0;
+    while row < height {
+        let rows = rows_per_batch.min(height - row);
+        eprintln!("Scanlines remaining: {}", height - row);
+
+        let mut queue = generate(scene, settings, row, rows);
+
+        for bounce in 0..settings.max_depth {
+            if queue.len() == 0 {
+                break;
+            }
+
+            let hits = intersect(scene, &queue);
+            if bounce == 0 {
+                for (k, hit) in hits.iter().enumerate() {
+                    if hit.as_ref().is_some_and(|rec| rec.holdout) {
+                        coverage[queue.slot[k] / 2] -= 1;
+                    }
+                }
+            }
+            let shaded = shade(scene, settings, bounce, &queue, hits);
+
+            let mut next = PathQueue::with_capacity(queue.len());
+            let mut shadows = ShadowQueue::new();
+            for (k, result) in shaded.into_iter().enumerate() {
+                let slot = queue.slot[k];
+
+                // Clamping applies to each contribution of a path separately
+                slots[slot] += clamp_sample(result.emitted, settings.clamp);
+                for (contribution, r, t_max) in result.shadows {
+                    shadows.push(&r, t_max, contribution, slot);
+                }
+                if let Some((throughput, r, kind)) = result.next {
+                    next.push(&r, throughput, kind, slot, result.rng);
+                }
+            }
+
+            for (k, visible) in occlusion(scene, &shadows).into_iter().enumerate() {
+                if visible {
+                    slots[shadows.slot[k]] += clamp_sample(shadows.contribution[k], settings.clamp);
+                }
+            }
+
+            queue = next;
+        }
+
+        // Paths still alive after max_depth bounces gather no more light
+        row += rows;
+        settings.finished_scanlines(rows);
+    }
+
+    slots.chunks(2).zip(coverage).map(|(pair, coverage)| SplitColor { even: pair[0], odd: pair[1], coverage }).collect()
+}
+
+// Output rows are stored top row first, so output row `row` is scanline
+// height - 1 - row of the camera
+fn generate(scene: &Scene, settings: &RenderSettings, row: u64, rows: u64) -> PathQueue {
+    let width = settings.image_width;
+    let spp = settings.samples_per_pixel;
+
+    let rays: Vec<(Ray, usize, u64)> = (0..rows * width * spp)
+        .into_par_iter()
+        .map(|n| {
+            let pixel = row * width + n / spp;
+            let i = pixel % width;
+            let j = settings.image_height - 1 - pixel / width;
+            let sample = settings.first_sample + n % spp;
+            start_sample(settings, i, j, sample);
+            let r = sample_ray(&scene.camera, settings, i, j);
+            (r, (2 * pixel + sample % 2) as usize, sampler::state())
+        })
+        .collect();
+
+    let mut queue = PathQueue::with_capacity(rays.len());
+    for (r, slot, rng) in rays {
+        queue.push(&r, Color::new(1.0, 1.0, 1.0), RayKind::Camera, slot, rng);
+    }
+
+    queue
+}
+
+fn intersect(scene: &Scene, queue: &PathQueue) -> Vec<Option<HitRecord>> {
+    (0..queue.len())
+        .into_par_iter()
+        .map(|k| scene.world.hit(&queue.ray(k), 0.001, f64::INFINITY))
+        .collect()
+}
+
+fn shade(scene: &Scene, settings: &RenderSettings, bounce: u64, queue: &PathQueue, hits: Vec<Option<HitRecord>>) -> Vec<Shaded> {
+    hits.into_par_iter()
+        .enumerate()
+        .map(|(k, hit)| {
+            let r = queue.ray(k);
+            let throughput = queue.throughput[k];
+            sampler::set_state(queue.rng[k]);
+
+            let rec = match hit {
+                Some(rec) if rec.holdout && queue.kind[k] == RayKind::Camera => return Shaded {
+                    emitted: Color::new(0.0, 0.0, 0.0),
+                    shadows: Vec::new(),
+                    next: None,
+                    rng: queue.rng[k]
+                },
+                Some(mut rec) => {
+                    material::resolve(&r, &mut rec);
+                    rec
+                },
+                None => return Shaded {
+                    emitted: throughput * scene.background(&r, queue.kind[k]),
+                    shadows: Vec::new(),
+                    next: None,
+                    rng: queue.rng[k]
+                }
+            };
+
+            let emitted = if counts_emission(queue.kind[k], &rec, scene) {
+                throughput * rec.mat.emitted(&r, &rec)
+            } else {
+                Color::new(0.0, 0.0, 0.0)
+            };
+
+            let samples = light_samples(&r, &rec, scene);
+            let light_sampled = samples.is_some();
+            let shadows = samples.unwrap_or_default()
+                .into_iter()
+                .map(|(contribution, shadow, t_max)| (throughput * contribution, shadow, t_max))
+                .collect();
+
+            let next = scatter(&r, &rec, scene).and_then(|(attenuation, scattered)| {
+                roulette(settings, bounce, attenuation)
+                    .map(|weight| (weight * throughput * attenuation, scattered, bounce_kind(&r, &rec, queue.kind[k], light_sampled)))
+            });
+
+            Shaded {
+                emitted,
+                shadows,
+                next,
+                rng: sampler::state()
+            }
+        })
+        .collect()
+}
+
+fn occlusion(scene: &Scene, shadows: &ShadowQueue) -> Vec<bool> {
+    (0..shadows.slot.len())
+        .into_par_iter()
+        .map(|k| {
+            let r = Ray::with_time(shadows.origin[k], shadows.direction[k], shadows.time[k]).with_type(RayType::Shadow);
+            !occluded(&r, shadows.t_max[k], scene)
+        })
+        .collect()
+}
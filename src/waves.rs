@@ -0,0 +1,104 @@
+use std::f64::consts::PI;
+
+use super::sampler;
+use super::vec::Vec3;
+
+// One Gerstner wave, travelling along a unit direction in the xz plane
+struct Wave {
+    dx: f64,
+    dz: f64,
+    // Wave number, 2 pi over the wavelength
+    k: f64,
+    amplitude: f64,
+    phase: f64
+}
+
+// Water surface over the xz plane as a sum of Gerstner waves: crests of
+// `count` wavelengths, from `scale` down to a tenth of it, spread around
+// the wind direction. Their height is in proportion to their length, the
+// longest being `amplitude` high, and `choppiness` from 0 (sine waves) to 1
+// pulls the water towards the crests, sharpening them.
+pub struct Waves {
+    waves: Vec<Wave>,
+    choppiness: f64
+}
+
+impl Waves {
+    pub fn new(count: u32, scale: f64, amplitude: f64, choppiness: f64, wind: Vec3, seed: u64) -> Waves {
+        let random = |k: u32, salt: u64| {
+            let h = sampler::hash(sampler::hash(seed ^ salt) ^ k as u64);
+            (h >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let heading = wind.z().atan2(wind.x());
+
+        let waves = (0..count).map(|k| {
+            let fraction = if count > 1 { k as f64 / (count - 1) as f64 } else { 0.0 };
+            let wavelength = scale * 0.1_f64.powf(fraction);
+            let angle = heading + (random(k, 1) - 0.5) * PI * 2.0 / 3.0;
+            Wave {
+                dx: angle.cos(),
+                dz: angle.sin(),
+                k: 2.0 * PI / wavelength,
+                amplitude: amplitude * wavelength / scale,
+                phase: 2.0 * PI * random(k, 2)
+            }
+        }).collect();
+
+        Waves {
+            waves,
+            choppiness: choppiness.clamp(0.0, 1.0)
+        }
+    }
+
+    // How far the crests are pulled in along a wave, at most what keeps the
+    // sum of the waves from folding over
+    fn steepness(&self, wave: &Wave) -> f64 {
+        self.choppiness / (wave.k * wave.amplitude * self.waves.len() as f64)
+    }
+
+    // Where the water at rest over (x0, z0) moves to
+    fn displaced(&self, x0: f64, z0: f64) -> Vec3 {
+        let mut p = Vec3::new(x0, 0.0, z0);
+        for w in &self.waves {
+            let (s, c) = (w.k * (w.dx * x0 + w.dz * z0) + w.phase).sin_cos();
+            let q = self.steepness(w) * w.amplitude;
+            p += Vec3::new(q * w.dx * c, w.amplitude * s, q * w.dz * c);
+        }
+        p
+    }
+
+    // The water at rest that ends up over (x, z), found by fixed point
+    // iteration, which converges since the waves don't fold over
+    fn rest(&self, x: f64, z: f64) -> (f64, f64) {
+        let (mut x0, mut z0) = (x, z);
+        for _ in 0..4 {
+            let p = self.displaced(x0, z0);
+            x0 += x - p.x();
+            z0 += z - p.z();
+        }
+        (x0, z0)
+    }
+
+    // Height of the surface over (x, z)
+    pub fn height(&self, x: f64, z: f64) -> f64 {
+        let (x0, z0) = self.rest(x, z);
+        self.displaced(x0, z0).y()
+    }
+
+    // Upward unit normal of the surface over (x, z)
+    pub fn normal(&self, x: f64, z: f64) -> Vec3 {
+        let (x0, z0) = self.rest(x, z);
+        let mut n = Vec3::new(0.0, 1.0, 0.0);
+        for w in &self.waves {
+            let (s, c) = (w.k * (w.dx * x0 + w.dz * z0) + w.phase).sin_cos();
+            let wa = w.k * w.amplitude;
+            n += Vec3::new(-w.dx * wa * c, -self.steepness(w) * wa * s, -w.dz * wa * c);
+        }
+        n.normalized()
+    }
+
+    // Sum of the amplitudes, the most the surface rises or falls
+    pub fn extent(&self) -> f64 {
+        self.waves.iter().map(|w| w.amplitude).sum()
+    }
+}